@@ -0,0 +1,263 @@
+//! Inventory analytics computed from recorded sales and purchases.
+//!
+//! Stock levels are only stored as a current figure, so the stock held at the
+//! start and end of a period is reconstructed by walking sales and purchases
+//! backwards from today. Manual quantity edits are not part of that history.
+
+use crate::export::{Cell, Table};
+use crate::timezone::format_date;
+
+const SECONDS_PER_DAY: f64 = 86_400.0;
+
+/// Share of revenue covered by class A items, and by A and B together.
+pub const ABC_A_SHARE: f64 = 0.80;
+pub const ABC_B_SHARE: f64 = 0.95;
+
+/// Half-open interval `[from, to)` of Unix timestamps; `None` leaves that side open.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct DateRange {
+    pub from: Option<i64>,
+    pub to: Option<i64>,
+}
+
+impl DateRange {
+    pub fn new(from: Option<i64>, to: Option<i64>) -> Self {
+        DateRange { from, to }
+    }
+
+    /// The end of the range, or `now` when it is open-ended.
+    pub fn end(&self, now: i64) -> i64 {
+        self.to.unwrap_or(now)
+    }
+
+    pub fn describe(&self) -> String {
+        match (self.from, self.to) {
+            (None, None) => "all time".to_string(),
+            (Some(from), None) => format!("since {}", format_date(from)),
+            (None, Some(to)) => format!("before {}", format_date(to)),
+            (Some(from), Some(to)) => format!("{} to {}", format_date(from), format_date(to)),
+        }
+    }
+}
+
+/// Sales and purchase totals for one product, relative to a date range.
+#[derive(Debug, Clone, Default)]
+pub struct ProductActivity {
+    pub product_id: String,
+    pub name: String,
+    pub created_at: i64,
+    /// Stock on hand today, in the product's stock unit.
+    pub quantity: f64,
+    pub price: f64,
+    /// Weighted average purchase price in the base currency, if the product was ever purchased.
+    pub unit_cost: Option<f64>,
+    pub units_sold: f64,
+    pub revenue: f64,
+    pub units_purchased: f64,
+    /// Movements after the range ended, used to rebuild the closing stock.
+    pub units_sold_after: f64,
+    pub units_purchased_after: f64,
+    /// Most recent sale before the end of the range.
+    pub last_sale_at: Option<i64>,
+}
+
+impl ProductActivity {
+    pub fn closing_stock(&self) -> f64 {
+        self.quantity + self.units_sold_after - self.units_purchased_after
+    }
+
+    pub fn opening_stock(&self) -> f64 {
+        self.closing_stock() + self.units_sold - self.units_purchased
+    }
+
+    /// Days the product was on the books within the range, at least one.
+    pub fn days_in_range(&self, range: &DateRange, now: i64) -> f64 {
+        let start = range.from.map_or(self.created_at, |from| from.max(self.created_at));
+        ((range.end(now) - start) as f64 / SECONDS_PER_DAY).max(1.0)
+    }
+
+    /// Revenue less the base-currency cost of the units sold; `None` if it was never purchased.
+    pub fn gross_profit(&self) -> Option<f64> {
+        self.unit_cost.map(|cost| self.revenue - self.units_sold * cost)
+    }
+
+    /// Value of the stock on hand at cost, or at list price if it was never purchased.
+    pub fn stock_value(&self) -> f64 {
+        self.quantity * self.unit_cost.unwrap_or(self.price)
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct TurnoverRow {
+    pub name: String,
+    pub units_sold: f64,
+    pub average_stock: f64,
+    /// Units sold divided by average stock; `None` when no stock was held.
+    pub turnover: Option<f64>,
+    pub on_hand: f64,
+    /// Days the current stock lasts at the range's sales rate; `None` without sales.
+    pub days_of_supply: Option<f64>,
+}
+
+#[derive(Debug, Clone)]
+pub struct DeadStockRow {
+    pub name: String,
+    pub on_hand: f64,
+    pub last_sale_at: Option<i64>,
+    pub tied_up_value: f64,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AbcClass {
+    A,
+    B,
+    C,
+}
+
+impl AbcClass {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            AbcClass::A => "A",
+            AbcClass::B => "B",
+            AbcClass::C => "C",
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct AbcRow {
+    pub name: String,
+    pub revenue: f64,
+    pub gross_profit: Option<f64>,
+    pub share: f64,
+    pub cumulative_share: f64,
+    pub class: AbcClass,
+}
+
+/// Turnover and days of supply per product, fastest movers first.
+pub fn turnover(activity: &[ProductActivity], range: &DateRange, now: i64) -> Vec<TurnoverRow> {
+    let mut rows: Vec<TurnoverRow> = activity
+        .iter()
+        .map(|product| {
+            let average_stock = (product.opening_stock() + product.closing_stock()) / 2.0;
+            let daily_rate = product.units_sold / product.days_in_range(range, now);
+            TurnoverRow {
+                name: product.name.clone(),
+                units_sold: product.units_sold,
+                average_stock,
+                turnover: (average_stock > 0.0).then(|| product.units_sold / average_stock),
+                on_hand: product.quantity,
+                days_of_supply: (daily_rate > 0.0).then(|| product.quantity / daily_rate),
+            }
+        })
+        .collect();
+
+    rows.sort_by(|a, b| b.turnover.unwrap_or(0.0).total_cmp(&a.turnover.unwrap_or(0.0)));
+    rows
+}
+
+/// Products with stock on hand and no sale in the `days` before `as_of`, largest tied-up value first.
+pub fn dead_stock(activity: &[ProductActivity], as_of: i64, days: i64) -> Vec<DeadStockRow> {
+    let cutoff = as_of - days * SECONDS_PER_DAY as i64;
+    let mut rows: Vec<DeadStockRow> = activity
+        .iter()
+        .filter(|product| product.quantity > 0.0)
+        .filter(|product| product.last_sale_at.map_or(product.created_at < cutoff, |at| at < cutoff))
+        .map(|product| DeadStockRow {
+            name: product.name.clone(),
+            on_hand: product.quantity,
+            last_sale_at: product.last_sale_at,
+            tied_up_value: product.stock_value(),
+        })
+        .collect();
+
+    rows.sort_by(|a, b| b.tied_up_value.total_cmp(&a.tied_up_value));
+    rows
+}
+
+/// Ranks products by revenue and classifies them: A items make up the first
+/// 80% of revenue, B items the next 15% and C items the rest.
+pub fn abc_classification(activity: &[ProductActivity]) -> Vec<AbcRow> {
+    let mut sorted: Vec<&ProductActivity> = activity.iter().collect();
+    sorted.sort_by(|a, b| b.revenue.total_cmp(&a.revenue));
+    let total: f64 = sorted.iter().map(|product| product.revenue).sum();
+
+    let mut cumulative = 0.0;
+    sorted
+        .into_iter()
+        .map(|product| {
+            let share = if total > 0.0 { product.revenue / total } else { 0.0 };
+            // Classify by the share reached before this item so the top seller is always A
+            let class = if product.revenue <= 0.0 {
+                AbcClass::C
+            } else if cumulative < ABC_A_SHARE {
+                AbcClass::A
+            } else if cumulative < ABC_B_SHARE {
+                AbcClass::B
+            } else {
+                AbcClass::C
+            };
+            cumulative += share;
+            AbcRow {
+                name: product.name.clone(),
+                revenue: product.revenue,
+                gross_profit: product.gross_profit(),
+                share,
+                cumulative_share: cumulative,
+                class,
+            }
+        })
+        .collect()
+}
+
+pub fn turnover_table(rows: &[TurnoverRow], range: &DateRange) -> Table {
+    let mut table = Table::new(
+        format!("Turnover {}", range.describe()),
+        &["Product", "Units Sold", "Average Stock", "Turnover", "On Hand", "Days of Supply"],
+    );
+    for row in rows {
+        table.push(vec![
+            Cell::text(&row.name),
+            Cell::Number(row.units_sold),
+            Cell::Number(row.average_stock),
+            Cell::optional(row.turnover),
+            Cell::Number(row.on_hand),
+            Cell::optional(row.days_of_supply),
+        ]);
+    }
+    table
+}
+
+pub fn dead_stock_table(rows: &[DeadStockRow], days: i64) -> Table {
+    let mut table = Table::new(
+        format!("Dead stock {} days", days),
+        &["Product", "On Hand", "Last Sale", "Tied-up Value"],
+    );
+    for row in rows {
+        table.push(vec![
+            Cell::text(&row.name),
+            Cell::Number(row.on_hand),
+            Cell::text(row.last_sale_at.map_or_else(|| "never".to_string(), format_date)),
+            Cell::Number(row.tied_up_value),
+        ]);
+    }
+    table
+}
+
+pub fn abc_table(rows: &[AbcRow], range: &DateRange) -> Table {
+    let mut table = Table::new(
+        format!("ABC analysis {}", range.describe()),
+        &["Product", "Revenue", "Gross Profit", "Share %", "Cumulative %", "Class"],
+    );
+    for row in rows {
+        table.push(vec![
+            Cell::text(&row.name),
+            Cell::Number(row.revenue),
+            Cell::optional(row.gross_profit),
+            Cell::Number(row.share * 100.0),
+            Cell::Number(row.cumulative_share * 100.0),
+            Cell::text(row.class.as_str()),
+        ]);
+    }
+    table
+}
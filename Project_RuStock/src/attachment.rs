@@ -0,0 +1,222 @@
+//! Images and documents attached to products and purchases.
+//!
+//! Content is addressed by its SHA-256 hash, so a file attached to several
+//! records is stored once. By default it is kept in the database as a blob and
+//! travels with every backup. With `RUSTOCK_ATTACHMENT_STORAGE=files` new content
+//! is written under a managed directory (`RUSTOCK_ATTACHMENT_DIR`) instead, which
+//! then has to be backed up alongside the database. Files larger than
+//! `RUSTOCK_ATTACHMENT_MAX_BYTES` are refused.
+
+use crate::config::env_or;
+use crate::error::{Error, Result};
+use crate::export::{Cell, Table};
+use crate::product::Product;
+use chrono::Utc;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::path::{Path, PathBuf};
+use uuid::Uuid;
+
+/// Largest attachment accepted unless `RUSTOCK_ATTACHMENT_MAX_BYTES` says otherwise.
+pub const DEFAULT_MAX_BYTES: u64 = 10 * 1024 * 1024;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum OwnerKind {
+    Product,
+    /// A purchase, typically with the supplier's invoice attached.
+    Purchase,
+}
+
+impl OwnerKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            OwnerKind::Product => "product",
+            OwnerKind::Purchase => "purchase",
+        }
+    }
+
+    pub fn parse(value: &str) -> Option<Self> {
+        match value {
+            "product" => Some(OwnerKind::Product),
+            "purchase" => Some(OwnerKind::Purchase),
+            _ => None,
+        }
+    }
+}
+
+/// Where newly attached content is stored.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StorageKind {
+    /// As a blob in the database.
+    Database,
+    /// As a file under the attachment directory.
+    Files,
+}
+
+impl StorageKind {
+    pub fn parse(value: &str) -> Option<Self> {
+        match value.trim().to_lowercase().as_str() {
+            "database" | "db" | "blob" => Some(StorageKind::Database),
+            "files" | "file" => Some(StorageKind::Files),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct AttachmentConfig {
+    pub storage: StorageKind,
+    pub dir: PathBuf,
+    pub max_bytes: u64,
+}
+
+impl Default for AttachmentConfig {
+    fn default() -> Self {
+        AttachmentConfig {
+            storage: StorageKind::Database,
+            dir: PathBuf::from("attachments"),
+            max_bytes: DEFAULT_MAX_BYTES,
+        }
+    }
+}
+
+impl AttachmentConfig {
+    pub fn from_env() -> Self {
+        let defaults = AttachmentConfig::default();
+        AttachmentConfig {
+            storage: StorageKind::parse(&env_or("RUSTOCK_ATTACHMENT_STORAGE", String::new()))
+                .unwrap_or(defaults.storage),
+            dir: env_or("RUSTOCK_ATTACHMENT_DIR", defaults.dir),
+            max_bytes: env_or("RUSTOCK_ATTACHMENT_MAX_BYTES", defaults.max_bytes),
+        }
+    }
+
+    /// Where content with `hash` is written under file storage, fanned out by
+    /// the first two hex digits so no directory grows too large.
+    pub fn path_for(&self, hash: &str) -> PathBuf {
+        self.dir.join(&hash[..2]).join(hash)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Attachment {
+    pub id: String,
+    pub owner_kind: OwnerKind,
+    pub owner_id: String,
+    /// Name of the file as attached, without its directory.
+    pub file_name: String,
+    pub content_type: String,
+    pub size: u64,
+    /// Hex SHA-256 of the content.
+    pub hash: String,
+    pub created_at: i64,
+}
+
+impl Attachment {
+    pub fn new(owner_kind: OwnerKind, owner_id: String, file_name: &str, data: &[u8]) -> Self {
+        let file_name = base_name(file_name);
+        Attachment {
+            id: Uuid::new_v4().to_string(),
+            owner_kind,
+            owner_id,
+            content_type: content_type(&file_name).to_string(),
+            file_name,
+            size: data.len() as u64,
+            hash: content_hash(data),
+            created_at: Utc::now().timestamp(),
+        }
+    }
+
+    pub fn is_image(&self) -> bool {
+        self.content_type.starts_with("image/")
+    }
+
+    pub fn validate(&self, config: &AttachmentConfig) -> Result<()> {
+        if self.file_name.is_empty() {
+            return Err(Error::validation("Attachment needs a file name"));
+        }
+        if self.size == 0 {
+            return Err(Error::validation("Attachment is empty"));
+        }
+        if self.size > config.max_bytes {
+            return Err(Error::validation(format!(
+                "{} is {}, over the {} limit",
+                self.file_name,
+                format_size(self.size),
+                format_size(config.max_bytes)
+            )));
+        }
+        Ok(())
+    }
+}
+
+pub fn content_hash(data: &[u8]) -> String {
+    Sha256::digest(data).iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+/// MIME type guessed from the file extension.
+pub fn content_type(file_name: &str) -> &'static str {
+    let extension = Path::new(file_name)
+        .extension()
+        .map(|ext| ext.to_string_lossy().to_lowercase())
+        .unwrap_or_default();
+    match extension.as_str() {
+        "png" => "image/png",
+        "jpg" | "jpeg" => "image/jpeg",
+        "gif" => "image/gif",
+        "webp" => "image/webp",
+        "svg" => "image/svg+xml",
+        "pdf" => "application/pdf",
+        "txt" => "text/plain",
+        "csv" => "text/csv",
+        "html" | "htm" => "text/html",
+        "doc" => "application/msword",
+        "docx" => "application/vnd.openxmlformats-officedocument.wordprocessingml.document",
+        "xlsx" => "application/vnd.openxmlformats-officedocument.spreadsheetml.sheet",
+        _ => "application/octet-stream",
+    }
+}
+
+fn base_name(file_name: &str) -> String {
+    Path::new(file_name.trim())
+        .file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_default()
+}
+
+/// Sizes in B, KB or MB with one decimal.
+pub fn format_size(bytes: u64) -> String {
+    match bytes {
+        0..=1023 => format!("{} B", bytes),
+        1024..=1_048_575 => format!("{:.1} KB", bytes as f64 / 1024.0),
+        _ => format!("{:.1} MB", bytes as f64 / 1_048_576.0),
+    }
+}
+
+/// The product catalogue with each product's attachments, for export.
+pub fn catalogue_table(products: &[(Product, Vec<Attachment>)]) -> Table {
+    let mut table = Table::new(
+        "Product catalogue",
+        &["ID", "Name", "SKU", "Description", "Tags", "Price", "Stock", "Unit", "Attachments"],
+    );
+    for (product, attachments) in products {
+        table.push(vec![
+            Cell::text(&product.id),
+            Cell::text(&product.name),
+            Cell::text(product.sku.as_deref().unwrap_or_default()),
+            Cell::text(&product.description),
+            Cell::text(product.tags.join(", ")),
+            Cell::Number(product.price),
+            Cell::Number(product.quantity),
+            Cell::text(&product.unit),
+            Cell::text(
+                attachments
+                    .iter()
+                    .map(|a| format!("{} ({})", a.file_name, &a.hash[..12]))
+                    .collect::<Vec<_>>()
+                    .join("; "),
+            ),
+        ]);
+    }
+    table
+}
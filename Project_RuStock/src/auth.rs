@@ -1,13 +1,138 @@
 use chrono::Utc;
+use pbkdf2::pbkdf2_hmac;
+use rand::rngs::OsRng;
+use rand::seq::SliceRandom;
+use rand::RngCore;
+use sha2::Sha256;
+use crate::config::env_or;
+use crate::error::{Error, Result};
 
 #[derive(Debug, Clone)]
 pub struct Manager {
     pub id: String,
     pub username: String,
+    /// The salted hash from [`hash_password`] once the manager is stored. A manager built
+    /// with [`Manager::new`] holds the plain password until it is added, so it can be
+    /// checked against the password policy.
     pub password: String,
     pub full_name: String,
     pub created_at: i64,
     pub is_active: bool,
+    pub failed_attempts: u32,
+    pub locked_until: Option<i64>,
+    pub must_change_password: bool,
+    /// Set when a manager with audit history is removed; the row stays so shifts and
+    /// voids still name who did them, but the account can never log in again.
+    pub removed_at: Option<i64>,
+}
+
+#[derive(Debug, Clone)]
+pub struct PasswordPolicy {
+    pub min_length: usize,
+    pub require_uppercase: bool,
+    pub require_lowercase: bool,
+    pub require_digit: bool,
+    pub require_symbol: bool,
+}
+
+impl Default for PasswordPolicy {
+    fn default() -> Self {
+        PasswordPolicy {
+            min_length: 8,
+            require_uppercase: true,
+            require_lowercase: true,
+            require_digit: true,
+            require_symbol: false,
+        }
+    }
+}
+
+impl PasswordPolicy {
+    pub fn check(&self, password: &str) -> Result<()> {
+        if password.trim().is_empty() {
+            return Err(Error::validation("Password cannot be empty"));
+        }
+        if password.chars().count() < self.min_length {
+            return Err(Error::validation(format!("Password must be at least {} characters", self.min_length)));
+        }
+        if self.require_uppercase && !password.chars().any(|c| c.is_uppercase()) {
+            return Err(Error::validation("Password must contain an uppercase letter"));
+        }
+        if self.require_lowercase && !password.chars().any(|c| c.is_lowercase()) {
+            return Err(Error::validation("Password must contain a lowercase letter"));
+        }
+        if self.require_digit && !password.chars().any(|c| c.is_ascii_digit()) {
+            return Err(Error::validation("Password must contain a digit"));
+        }
+        if self.require_symbol && password.chars().all(|c| c.is_alphanumeric()) {
+            return Err(Error::validation("Password must contain a symbol"));
+        }
+        Ok(())
+    }
+
+    pub fn describe(&self) -> String {
+        let mut rules = vec![format!("at least {} characters", self.min_length)];
+        if self.require_uppercase {
+            rules.push("an uppercase letter".to_string());
+        }
+        if self.require_lowercase {
+            rules.push("a lowercase letter".to_string());
+        }
+        if self.require_digit {
+            rules.push("a digit".to_string());
+        }
+        if self.require_symbol {
+            rules.push("a symbol".to_string());
+        }
+        rules.join(", ")
+    }
+}
+
+/// Login and session limits. Every value can be overridden with a `RUSTOCK_*` environment variable.
+#[derive(Debug, Clone)]
+pub struct SecurityPolicy {
+    pub max_failed_attempts: u32,
+    pub lockout_minutes: i64,
+    pub idle_timeout_minutes: i64,
+    pub password: PasswordPolicy,
+}
+
+impl Default for SecurityPolicy {
+    fn default() -> Self {
+        SecurityPolicy {
+            max_failed_attempts: 5,
+            lockout_minutes: 15,
+            idle_timeout_minutes: 10,
+            password: PasswordPolicy::default(),
+        }
+    }
+}
+
+impl SecurityPolicy {
+    pub fn from_env() -> Self {
+        let defaults = SecurityPolicy::default();
+        SecurityPolicy {
+            max_failed_attempts: env_or("RUSTOCK_MAX_LOGIN_ATTEMPTS", defaults.max_failed_attempts),
+            lockout_minutes: env_or("RUSTOCK_LOCKOUT_MINUTES", defaults.lockout_minutes),
+            idle_timeout_minutes: env_or("RUSTOCK_SESSION_TIMEOUT_MINUTES", defaults.idle_timeout_minutes),
+            password: PasswordPolicy {
+                min_length: env_or("RUSTOCK_PASSWORD_MIN_LENGTH", defaults.password.min_length),
+                require_uppercase: env_or("RUSTOCK_PASSWORD_REQUIRE_UPPERCASE", defaults.password.require_uppercase),
+                require_lowercase: env_or("RUSTOCK_PASSWORD_REQUIRE_LOWERCASE", defaults.password.require_lowercase),
+                require_digit: env_or("RUSTOCK_PASSWORD_REQUIRE_DIGIT", defaults.password.require_digit),
+                require_symbol: env_or("RUSTOCK_PASSWORD_REQUIRE_SYMBOL", defaults.password.require_symbol),
+            },
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum LoginOutcome {
+    Success(Manager),
+    /// The username is unknown, the password is wrong or the account is inactive.
+    /// The cases are not told apart so that usernames cannot be probed.
+    InvalidCredentials,
+    LockedOut { until: i64 },
 }
 
 impl Manager {
@@ -19,49 +144,156 @@ impl Manager {
             full_name,
             created_at: Utc::now().timestamp(),
             is_active: true,
+            failed_attempts: 0,
+            locked_until: None,
+            must_change_password: false,
+            removed_at: None,
         }
     }
 
-    pub fn validate(&self) -> Result<(), String> {
-        if self.username.trim().is_empty() {
-            return Err("Username cannot be empty".to_string());
-        }
-        
-        if self.username.len() < 3 {
-            return Err("Username must be at least 3 characters".to_string());
-        }
-        
-        if self.password.trim().is_empty() {
-            return Err("Password cannot be empty".to_string());
-        }
-        
-        if self.password.len() < 4 {
-            return Err("Password must be at least 4 characters".to_string());
-        }
-        
-        if self.full_name.trim().is_empty() {
-            return Err("Full name cannot be empty".to_string());
-        }
-        
-        Ok(())
+    /// The account seeded into an empty store. Its well-known password must be replaced on first login.
+    pub fn default_admin() -> Self {
+        let mut admin = Manager::new(
+            "admin".to_string(),
+            "admin123".to_string(),
+            "System Administrator".to_string()
+        );
+        admin.must_change_password = true;
+        admin
+    }
+
+    pub fn is_locked(&self, now: i64) -> bool {
+        self.locked_until.is_some_and(|until| until > now)
+    }
+
+    pub fn is_removed(&self) -> bool {
+        self.removed_at.is_some()
+    }
+
+    pub fn validate(&self, policy: &PasswordPolicy) -> Result<()> {
+        validate_profile(&self.username, &self.full_name)?;
+        policy.check(&self.password)
+    }
+}
+
+/// Checks the username and full name a manager is created or renamed with.
+pub fn validate_profile(username: &str, full_name: &str) -> Result<()> {
+    if username.trim().is_empty() {
+        return Err(Error::validation("Username cannot be empty"));
+    }
+
+    if username.len() < 3 {
+        return Err(Error::validation("Username must be at least 3 characters"));
+    }
+
+    if full_name.trim().is_empty() {
+        return Err(Error::validation("Full name cannot be empty"));
+    }
+
+    Ok(())
+}
+
+/// What happened to a manager account that was removed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ManagerRemoval {
+    /// The manager never opened a shift or approved a void, so the account was deleted.
+    Deleted,
+    /// The manager has audit history, so the account was deactivated and marked
+    /// removed instead, after handing any open shifts to a successor.
+    Retired { reassigned_shifts: usize },
+}
+
+const TEMPORARY_LOWERCASE: &[u8] = b"abcdefghjkmnpqrstuvwxyz";
+const TEMPORARY_UPPERCASE: &[u8] = b"ABCDEFGHJKMNPQRSTUVWXYZ";
+const TEMPORARY_DIGITS: &[u8] = b"23456789";
+const TEMPORARY_SYMBOLS: &[u8] = b"!#$%&*+-=?@";
+
+/// A random one-time password that satisfies `policy`, for an administrator to hand
+/// to a manager who forgot theirs. Look-alike characters such as `0`/`O` and `1`/`l`
+/// are left out so it can be read aloud or copied from a screen.
+pub fn temporary_password(policy: &PasswordPolicy) -> String {
+    let mut required = Vec::new();
+    if policy.require_uppercase {
+        required.push(TEMPORARY_UPPERCASE);
+    }
+    if policy.require_lowercase {
+        required.push(TEMPORARY_LOWERCASE);
+    }
+    if policy.require_digit {
+        required.push(TEMPORARY_DIGITS);
+    }
+    if policy.require_symbol {
+        required.push(TEMPORARY_SYMBOLS);
+    }
+    let all = [TEMPORARY_UPPERCASE, TEMPORARY_LOWERCASE, TEMPORARY_DIGITS].concat();
+
+    let length = policy.min_length.max(12);
+    let mut rng = OsRng;
+    let mut password: Vec<u8> = required.iter().filter_map(|set| set.choose(&mut rng).copied()).collect();
+    while password.len() < length {
+        password.extend(all.choose(&mut rng));
+    }
+    // Move the required characters away from the front
+    password.shuffle(&mut rng);
+    String::from_utf8(password).expect("temporary password characters are ASCII")
+}
+
+const PASSWORD_SCHEME: &str = "pbkdf2-sha256";
+const PASSWORD_ITERATIONS: u32 = 600_000;
+const PASSWORD_SALT_BYTES: usize = 16;
+const PASSWORD_HASH_BYTES: usize = 32;
+
+/// Hashes a password for storage as `pbkdf2-sha256$<iterations>$<salt>$<hash>`, with a
+/// fresh random salt. The iteration count is stored so it can be raised later without
+/// invalidating existing passwords.
+pub fn hash_password(password: &str) -> String {
+    let mut salt = [0u8; PASSWORD_SALT_BYTES];
+    OsRng.fill_bytes(&mut salt);
+    let mut hash = [0u8; PASSWORD_HASH_BYTES];
+    pbkdf2_hmac::<Sha256>(password.as_bytes(), &salt, PASSWORD_ITERATIONS, &mut hash);
+    format!("{}${}${}${}", PASSWORD_SCHEME, PASSWORD_ITERATIONS, to_hex(&salt), to_hex(&hash))
+}
+
+/// Whether `password` matches a hash made by [`hash_password`]. Anything else stored,
+/// including a plain-text password, never matches.
+pub fn verify_password(stored: &str, password: &str) -> bool {
+    let parts: Vec<&str> = stored.split('$').collect();
+    let [PASSWORD_SCHEME, iterations, salt, expected] = parts[..] else {
+        return false;
+    };
+    let (Ok(iterations), Some(salt), Some(expected)) = (iterations.parse(), from_hex(salt), from_hex(expected)) else {
+        return false;
+    };
+    let mut hash = vec![0u8; expected.len()];
+    pbkdf2_hmac::<Sha256>(password.as_bytes(), &salt, iterations, &mut hash);
+    // Compare every byte so the time taken does not reveal how much of the hash matched
+    !expected.is_empty() && hash.iter().zip(&expected).fold(0, |diff, (a, b)| diff | (a ^ b)) == 0
+}
+
+/// Whether a stored password is already hashed, as opposed to a plain-text password
+/// left by an older version.
+pub fn is_password_hash(stored: &str) -> bool {
+    stored.starts_with(&format!("{}$", PASSWORD_SCHEME))
+}
+
+fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+fn from_hex(text: &str) -> Option<Vec<u8>> {
+    if !text.len().is_multiple_of(2) {
+        return None;
     }
+    (0..text.len())
+        .step_by(2)
+        .map(|i| text.get(i..i + 2).and_then(|pair| u8::from_str_radix(pair, 16).ok()))
+        .collect()
 }
 
 pub struct AuthService;
 
 impl AuthService {
-    pub fn authenticate(username: &str, password: &str, managers: &[Manager]) -> Option<Manager> {
-        managers
-            .iter()
-            .find(|manager| {
-                manager.username == username 
-                    && manager.password == password 
-                    && manager.is_active
-            })
-            .cloned()
-    }
-    
     pub fn is_valid_credentials(username: &str, password: &str) -> bool {
         !username.trim().is_empty() && !password.trim().is_empty()
     }
-} 
\ No newline at end of file
+}
@@ -0,0 +1,107 @@
+use crate::db::Database;
+use crate::error::{Error, Result};
+use chrono::Local;
+use flate2::read::GzDecoder;
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use std::fs::{self, File};
+use std::io;
+use std::path::{Path, PathBuf};
+
+pub const BACKUP_DIR: &str = "backups";
+
+const COMPRESSED_EXTENSION: &str = "gz";
+
+pub fn backup_file_name(compress: bool) -> String {
+    let stamp = Local::now().format("%Y%m%d-%H%M%S");
+    if compress {
+        format!("rustock-{}.db.{}", stamp, COMPRESSED_EXTENSION)
+    } else {
+        format!("rustock-{}.db", stamp)
+    }
+}
+
+/// Writes a timestamped snapshot of the live database into `dir` and returns its path.
+pub fn create_backup(db: &Database, dir: &Path, compress: bool) -> Result<PathBuf> {
+    fs::create_dir_all(dir)?;
+    let target = dir.join(backup_file_name(compress));
+
+    if !compress {
+        db.backup_to(&target)?;
+        return Ok(target);
+    }
+
+    let snapshot = target.with_extension("tmp");
+    let result = db.backup_to(&snapshot).and_then(|()| gzip_file(&snapshot, &target).map_err(Error::from));
+    remove_scratch(&snapshot);
+    if result.is_err() {
+        remove_scratch(&target);
+    }
+    result.map(|_| target)
+}
+
+/// Restores the live database from a snapshot produced by `create_backup`.
+pub fn restore_backup(db: &mut Database, source: &Path) -> Result<()> {
+    if !source.exists() {
+        return Err(Error::not_found("Backup file", source.display().to_string()));
+    }
+
+    if !is_compressed(source) {
+        db.restore_from(source)?;
+        return Ok(());
+    }
+
+    let snapshot = source.with_extension("restore.tmp");
+    let result = gunzip_file(source, &snapshot).map_err(Error::from).and_then(|()| db.restore_from(&snapshot));
+    remove_scratch(&snapshot);
+    result
+}
+
+/// Lists snapshots in `dir`, newest first.
+pub fn list_backups(dir: &Path) -> io::Result<Vec<PathBuf>> {
+    if !dir.exists() {
+        return Ok(Vec::new());
+    }
+
+    let mut backups: Vec<PathBuf> = fs::read_dir(dir)?
+        .filter_map(|entry| entry.ok().map(|e| e.path()))
+        .filter(|path| {
+            path.file_name()
+                .and_then(|name| name.to_str())
+                .is_some_and(|name| {
+                    name.starts_with("rustock-")
+                        && (name.ends_with(".db") || name.ends_with(".db.gz"))
+                })
+        })
+        .collect();
+
+    // Timestamps in the file names sort lexically
+    backups.sort();
+    backups.reverse();
+    Ok(backups)
+}
+
+/// Removes an intermediate file. A leftover file must not hide the outcome of
+/// the backup or restore itself, so failing to remove it is ignored.
+fn remove_scratch(path: &Path) {
+    let _ = fs::remove_file(path);
+}
+
+fn is_compressed(path: &Path) -> bool {
+    path.extension().and_then(|ext| ext.to_str()) == Some(COMPRESSED_EXTENSION)
+}
+
+fn gzip_file(source: &Path, dest: &Path) -> io::Result<()> {
+    let mut input = File::open(source)?;
+    let mut encoder = GzEncoder::new(File::create(dest)?, Compression::default());
+    io::copy(&mut input, &mut encoder)?;
+    encoder.finish()?;
+    Ok(())
+}
+
+fn gunzip_file(source: &Path, dest: &Path) -> io::Result<()> {
+    let mut decoder = GzDecoder::new(File::open(source)?);
+    let mut output = File::create(dest)?;
+    io::copy(&mut decoder, &mut output)?;
+    Ok(())
+}
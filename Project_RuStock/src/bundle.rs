@@ -0,0 +1,66 @@
+use crate::error::{Error, Result};
+use crate::product::Product;
+use crate::unit;
+use serde::{Deserialize, Serialize};
+
+/// One line of a bundle's bill of materials: `quantity` of `component_id`, in the
+/// component's stock unit, goes into every bundle.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct BundleComponent {
+    pub bundle_id: String,
+    pub component_id: String,
+    pub quantity: f64,
+}
+
+impl BundleComponent {
+    pub fn new(bundle_id: String, component_id: String, quantity: f64) -> Self {
+        Self {
+            bundle_id,
+            component_id,
+            quantity,
+        }
+    }
+
+    /// Bundles are one level deep: a bundle cannot contain itself or another bundle,
+    /// and a product used as a component cannot become a bundle.
+    pub fn validate(&self, component: &Product, component_is_bundle: bool, bundle_is_component: bool) -> Result<()> {
+        if self.bundle_id == self.component_id {
+            return Err(Error::validation("A bundle cannot contain itself"));
+        }
+        if component_is_bundle {
+            return Err(Error::validation(format!("'{}' is a bundle and cannot be a component", component.name)));
+        }
+        if bundle_is_component {
+            return Err(Error::validation("A product used as a component cannot become a bundle"));
+        }
+        if !self.quantity.is_finite() || self.quantity <= 0.0 {
+            return Err(Error::validation("Component quantity must be positive"));
+        }
+        unit::check_quantity(&component.unit, self.quantity)
+    }
+}
+
+/// Whole bundles that can be assembled from `component_stock`, given as the
+/// stock of each component in the same order as `components`.
+pub fn buildable(components: &[BundleComponent], component_stock: &[f64]) -> f64 {
+    if components.is_empty() {
+        return 0.0;
+    }
+    components
+        .iter()
+        .zip(component_stock)
+        .map(|(component, stock)| unit::round_quantity(stock / component.quantity).floor().max(0.0))
+        .fold(f64::INFINITY, f64::min)
+}
+
+/// Splits a sale of `quantity` bundles into the part taken from assembled stock
+/// and the stock units of each component needed to build the rest.
+pub fn allocate(bundle_stock: f64, components: &[BundleComponent], quantity: f64) -> (f64, Vec<f64>) {
+    let from_stock = bundle_stock.max(0.0).min(quantity);
+    let to_build = quantity - from_stock;
+    let needed = components
+        .iter()
+        .map(|component| unit::round_quantity(component.quantity * to_build))
+        .collect();
+    (from_stock, needed)
+}
@@ -0,0 +1,7 @@
+use std::env;
+use std::str::FromStr;
+
+/// Reads `key` from the environment, falling back to `default` when unset or unparsable.
+pub fn env_or<T: FromStr>(key: &str, default: T) -> T {
+    env::var(key).ok().and_then(|value| value.trim().parse().ok()).unwrap_or(default)
+}
@@ -0,0 +1,83 @@
+use crate::error::{Error, Result};
+use crate::settings;
+use serde::{Deserialize, Serialize};
+
+/// Currency sales are made in and all costs are converted to, unless the store settings say otherwise.
+pub const DEFAULT_BASE_CURRENCY: &str = "USD";
+
+/// How many units of the base currency one unit of `currency` was worth from `effective_from` on.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct ExchangeRate {
+    pub currency: String,
+    pub rate: f64,
+    pub effective_from: i64,
+}
+
+impl ExchangeRate {
+    pub fn new(currency: &str, rate: f64, effective_from: i64) -> Self {
+        Self {
+            currency: currency.trim().to_uppercase(),
+            rate,
+            effective_from,
+        }
+    }
+
+    pub fn validate(&self) -> Result<()> {
+        normalize(&self.currency)?;
+        if self.currency == base_currency() {
+            return Err(Error::validation(format!("{} is the base currency", self.currency)));
+        }
+        if !self.rate.is_finite() || self.rate <= 0.0 {
+            return Err(Error::validation("Exchange rate must be positive"));
+        }
+        Ok(())
+    }
+}
+
+pub fn base_currency() -> String {
+    settings::current().currency
+}
+
+/// Upper-cases a three-letter ISO 4217 code such as `eur`.
+pub fn normalize(code: &str) -> Result<String> {
+    let code = code.trim().to_uppercase();
+    if code.len() != 3 || !code.chars().all(|c| c.is_ascii_alphabetic()) {
+        return Err(Error::validation(format!("'{}' is not a three-letter currency code", code)));
+    }
+    Ok(code)
+}
+
+/// Printed in front of amounts; currencies without a well-known sign use their code.
+pub fn symbol(code: &str) -> String {
+    match code {
+        "USD" => "$".to_string(),
+        "EUR" => "€".to_string(),
+        "GBP" => "£".to_string(),
+        "JPY" | "CNY" => "¥".to_string(),
+        "INR" => "₹".to_string(),
+        _ => format!("{} ", code),
+    }
+}
+
+/// Formats `amount` with the currency's sign. Base-currency amounts use the
+/// configured decimal places, others two.
+pub fn format_amount(amount: f64, code: &str) -> String {
+    let settings = settings::current();
+    let places = if code == settings.currency { settings.decimal_places as usize } else { 2 };
+    let sign = if amount < 0.0 { "-" } else { "" };
+    format!("{}{}{:.*}", sign, symbol(code), places, amount.abs())
+}
+
+/// An amount in the base currency.
+pub fn format_base(amount: f64) -> String {
+    format_amount(amount, &base_currency())
+}
+
+/// The rate in force at `at`: the latest one for `currency` taking effect on or before it.
+pub fn rate_on(rates: &[ExchangeRate], currency: &str, at: i64) -> Option<f64> {
+    rates
+        .iter()
+        .filter(|r| r.currency == currency && r.effective_from <= at)
+        .max_by_key(|r| r.effective_from)
+        .map(|r| r.rate)
+}
@@ -1,22 +1,144 @@
-use rusqlite::{params, Connection, Result, OptionalExtension};
-use crate::product::Product;
+use rusqlite::{params, params_from_iter, Connection, OptionalExtension, TransactionBehavior};
+use rusqlite::types::Value;
+use rusqlite::backup::Backup;
+use crate::analytics::{DateRange, ProductActivity};
+use crate::attachment::{self, Attachment, AttachmentConfig, OwnerKind, StorageKind};
+use crate::forecast::DemandSeries;
+use crate::journal::Transaction;
+use crate::loyalty::{Customer, CustomerSale, CustomerSummary, LoyaltyProgram};
+use crate::seed::DemoData;
+use crate::product::{self, Product};
 use crate::sale::{Sale, SaleItem};
+use crate::search;
+use crate::settings::{self, Settings};
 use crate::purchase::Purchase;
-use crate::auth::Manager;
+use crate::auth::{self, validate_profile, Manager, ManagerRemoval};
+use crate::bundle::{self, BundleComponent};
+use crate::notify::{Delivery, EventKind};
+use crate::order::{CustomerOrder, OrderKind, OrderStatus};
+use crate::pos::{self, Payment, PaymentMethod, ReportKind, Settlement, Shift, ShiftReport, TenderLine};
+use crate::currency::{self, ExchangeRate};
+use crate::pricing::{self, PriceChange, PricePeriod, ScheduleKind, ScheduleStatus, ScheduledPrice};
+use crate::repository::Repository;
+use crate::unit::{self, ProductUnit};
+use crate::void::{Operation, OperationKind, VoidRecord};
+use crate::error::{Error, Result};
+use crate::config::env_or;
+use crate::timezone::{self, Tz};
 use chrono::Utc;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+pub const DB_PATH: &str = "rustock.db";
+
+/// How long a statement waits for another terminal's write lock before failing.
+/// Override with `RUSTOCK_BUSY_TIMEOUT_MS`.
+pub const DEFAULT_BUSY_TIMEOUT_MS: u64 = 5000;
+
+// Pages copied per step of the online backup API before yielding to other connections
+const BACKUP_PAGES_PER_STEP: std::os::raw::c_int = 100;
 
 pub struct Database {
     conn: Connection,
+    passphrase: Option<String>,
 }
 
 impl Database {
-    pub fn new() -> Result<Database> {
-        let conn = Connection::open("rustock.db")?;
-        let db = Database { conn };
+    pub fn new(passphrase: Option<&str>) -> Result<Database> {
+        Self::open(DB_PATH, passphrase)
+    }
+
+    pub fn open<P: AsRef<Path>>(path: P, passphrase: Option<&str>) -> Result<Database> {
+        let conn = open_connection(path.as_ref(), passphrase)?;
+        let db = Database {
+            conn,
+            passphrase: passphrase.map(str::to_string),
+        };
         db.init_db()?;
         Ok(db)
     }
 
+    pub fn is_encrypted(&self) -> bool {
+        self.passphrase.is_some()
+    }
+
+    /// Opens another connection to the same database file, for use on another thread.
+    pub fn reopen(&self) -> Result<Database> {
+        match self.conn.path().filter(|path| !path.is_empty()) {
+            Some(path) => Self::open(path, self.passphrase.as_deref()),
+            None => Err(Error::validation("An in-memory database cannot be opened again")),
+        }
+    }
+
+    /// Copies a consistent snapshot of the live database to `dest` using SQLite's
+    /// online backup API. The snapshot is keyed with the same passphrase as this database.
+    pub fn backup_to(&self, dest: &Path) -> Result<()> {
+        let mut dst = open_connection(dest, self.passphrase.as_deref())?;
+        let backup = Backup::new(&self.conn, &mut dst)?;
+        backup.run_to_completion(BACKUP_PAGES_PER_STEP, Duration::from_millis(25), None)?;
+        Ok(())
+    }
+
+    /// Replaces the contents of the live database with the snapshot stored at `src`.
+    pub fn restore_from(&mut self, src: &Path) -> Result<()> {
+        let src_conn = open_connection(src, self.passphrase.as_deref())?;
+        {
+            let backup = Backup::new(&src_conn, &mut self.conn)?;
+            backup.run_to_completion(BACKUP_PAGES_PER_STEP, Duration::from_millis(25), None)?;
+        }
+        self.init_db()
+    }
+
+    /// Writes an encrypted copy of the database to `dest`, keyed with `passphrase`.
+    pub fn export_encrypted(&self, dest: &Path, passphrase: &str) -> Result<()> {
+        ensure_cipher_support(&self.conn)?;
+        self.conn.execute(
+            "ATTACH DATABASE ?1 AS encrypted KEY ?2",
+            params![dest.to_string_lossy(), passphrase],
+        )?;
+        self.conn.query_row("SELECT sqlcipher_export('encrypted')", [], |_| Ok(()))?;
+        self.conn.execute("DETACH DATABASE encrypted", [])?;
+        Ok(())
+    }
+
+    /// Closes the database and moves the file at `replacement` over it, as when the
+    /// encrypt command swaps in the encrypted copy. The write-ahead log is checkpointed
+    /// and emptied first, and its sidecar files are deleted afterwards, so no page of
+    /// the old database can be replayed into the new one.
+    pub fn replace_with(self, replacement: &Path) -> Result<()> {
+        let path = match self.conn.path().filter(|path| !path.is_empty()) {
+            Some(path) => PathBuf::from(path),
+            None => return Err(Error::validation("An in-memory database has no file to replace")),
+        };
+        let busy: i64 = self.conn.query_row("PRAGMA wal_checkpoint(TRUNCATE)", [], |row| row.get(0))?;
+        if busy != 0 {
+            return Err(Error::conflict("The database is in use by another terminal; close it and try again"));
+        }
+        self.conn.close().map_err(|(_, e)| e)?;
+
+        std::fs::rename(replacement, &path)?;
+        for suffix in ["-wal", "-shm"] {
+            let mut sidecar = path.clone().into_os_string();
+            sidecar.push(suffix);
+            match std::fs::remove_file(&sidecar) {
+                Err(e) if e.kind() != std::io::ErrorKind::NotFound => return Err(e.into()),
+                _ => {}
+            }
+        }
+        Ok(())
+    }
+
+    /// Changes the passphrase of an already encrypted database in place.
+    pub fn change_passphrase(&mut self, new_passphrase: &str) -> Result<()> {
+        if self.passphrase.is_none() {
+            return Err(Error::validation("Database is not encrypted; use the encrypt command first"));
+        }
+        self.conn.pragma_update(None, "rekey", new_passphrase)?;
+        self.passphrase = Some(new_passphrase.to_string());
+        Ok(())
+    }
+
     fn init_db(&self) -> Result<()> {
         self.conn.execute("PRAGMA foreign_keys = ON", [])?;
 
@@ -26,38 +148,254 @@ impl Database {
                 name TEXT NOT NULL,
                 description TEXT,
                 price REAL NOT NULL,
-                quantity INTEGER NOT NULL,
+                quantity REAL NOT NULL,
+                unit TEXT NOT NULL DEFAULT 'each',
                 created_at INTEGER NOT NULL,
-                updated_at INTEGER NOT NULL
+                updated_at INTEGER NOT NULL,
+                archived_at INTEGER,
+                version INTEGER NOT NULL DEFAULT 0,
+                sku TEXT,
+                tags TEXT NOT NULL DEFAULT ''
             )",
             [],
         )?;
+        self.add_column_if_missing("products", "unit", "TEXT NOT NULL DEFAULT 'each'")?;
+        self.add_column_if_missing("products", "archived_at", "INTEGER")?;
+        self.add_column_if_missing("products", "version", "INTEGER NOT NULL DEFAULT 0")?;
+        self.add_column_if_missing("products", "sku", "TEXT")?;
+        self.add_column_if_missing("products", "tags", "TEXT NOT NULL DEFAULT ''")?;
+        self.conn.execute(
+            "CREATE UNIQUE INDEX IF NOT EXISTS idx_products_sku ON products(sku COLLATE NOCASE) WHERE sku IS NOT NULL",
+            [],
+        )?;
+
+        self.conn.execute_batch(
+            "CREATE VIRTUAL TABLE IF NOT EXISTS product_search USING fts5(
+                product_id UNINDEXED, name, description, sku, tags
+             );
+             CREATE VIRTUAL TABLE IF NOT EXISTS product_search_vocab USING fts5vocab(product_search, row);",
+        )?;
+        // Products stored before the index existed, or by an older build, are indexed from scratch
+        let indexed: i64 = self.conn.query_row("SELECT COUNT(*) FROM product_search", [], |row| row.get(0))?;
+        let products: i64 = self.conn.query_row("SELECT COUNT(*) FROM products", [], |row| row.get(0))?;
+        if indexed != products {
+            self.conn.execute_batch(
+                "DELETE FROM product_search;
+                 INSERT INTO product_search (product_id, name, description, sku, tags)
+                 SELECT id, name, COALESCE(description, ''), COALESCE(sku, ''), tags FROM products;",
+            )?;
+        }
 
         self.conn.execute(
             "CREATE TABLE IF NOT EXISTS sales (
                 id INTEGER PRIMARY KEY AUTOINCREMENT,
+                sale_id TEXT REFERENCES sale_headers(id),
                 product_id TEXT NOT NULL,
-                quantity INTEGER NOT NULL,
+                quantity REAL NOT NULL,
+                unit TEXT,
+                unit_quantity REAL,
+                unit_price REAL,
                 total_price REAL NOT NULL,
-                sale_date TEXT NOT NULL,
-                FOREIGN KEY(product_id) REFERENCES products(id) ON DELETE CASCADE
+                sold_at INTEGER NOT NULL,
+                FOREIGN KEY(product_id) REFERENCES products(id)
+            )",
+            [],
+        )?;
+
+        self.conn.execute(
+            "CREATE TABLE IF NOT EXISTS customers (
+                id TEXT PRIMARY KEY,
+                name TEXT NOT NULL,
+                phone TEXT UNIQUE,
+                email TEXT,
+                created_at INTEGER NOT NULL
+            )",
+            [],
+        )?;
+
+        self.conn.execute(
+            "CREATE TABLE IF NOT EXISTS sale_headers (
+                id TEXT PRIMARY KEY,
+                sale_number INTEGER NOT NULL UNIQUE,
+                total_amount REAL NOT NULL,
+                created_at INTEGER NOT NULL,
+                shift_id TEXT,
+                change_given REAL NOT NULL DEFAULT 0,
+                customer_id TEXT REFERENCES customers(id),
+                discount REAL NOT NULL DEFAULT 0
+            )",
+            [],
+        )?;
+
+        // Sale rows recorded before sale headers existed become single-item sales
+        self.add_column_if_missing("sales", "sale_id", "TEXT REFERENCES sale_headers(id)")?;
+        self.add_column_if_missing("sales", "unit_price", "REAL")?;
+        self.add_column_if_missing("sales", "unit", "TEXT")?;
+        self.add_column_if_missing("sales", "unit_quantity", "REAL")?;
+        self.migrate_sale_dates()?;
+        self.conn.execute(
+            "INSERT INTO sale_headers (id, sale_number, total_amount, created_at)
+             SELECT 'legacy-' || id,
+                    (SELECT COALESCE(MAX(sale_number), 0) FROM sale_headers) + ROW_NUMBER() OVER (ORDER BY id),
+                    total_price,
+                    sold_at
+             FROM sales WHERE sale_id IS NULL",
+            [],
+        )?;
+        self.conn.execute(
+            "UPDATE sales SET sale_id = 'legacy-' || id, unit_price = total_price / quantity
+             WHERE sale_id IS NULL",
+            [],
+        )?;
+
+        self.conn.execute(
+            "CREATE TABLE IF NOT EXISTS customer_orders (
+                id TEXT PRIMARY KEY,
+                order_number INTEGER NOT NULL UNIQUE,
+                kind TEXT NOT NULL,
+                customer TEXT NOT NULL,
+                product_id TEXT NOT NULL,
+                quantity REAL NOT NULL,
+                allocated REAL NOT NULL DEFAULT 0,
+                unit_price REAL NOT NULL,
+                paid REAL NOT NULL DEFAULT 0,
+                status TEXT NOT NULL DEFAULT 'open',
+                created_at INTEGER NOT NULL,
+                closed_at INTEGER,
+                sale_number INTEGER,
+                FOREIGN KEY(product_id) REFERENCES products(id)
+            )",
+            [],
+        )?;
+
+        self.conn.execute(
+            "CREATE TABLE IF NOT EXISTS order_deposits (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                order_id TEXT NOT NULL,
+                amount REAL NOT NULL,
+                paid_at INTEGER NOT NULL,
+                FOREIGN KEY(order_id) REFERENCES customer_orders(id)
+            )",
+            [],
+        )?;
+
+        self.conn.execute(
+            "CREATE TABLE IF NOT EXISTS shifts (
+                id TEXT PRIMARY KEY,
+                manager_id TEXT NOT NULL,
+                opened_at INTEGER NOT NULL,
+                opening_float REAL NOT NULL,
+                closed_at INTEGER,
+                FOREIGN KEY(manager_id) REFERENCES managers(id)
+            )",
+            [],
+        )?;
+
+        // Expected and counted takings per payment method, stored when a shift is closed
+        self.conn.execute(
+            "CREATE TABLE IF NOT EXISTS shift_counts (
+                shift_id TEXT NOT NULL,
+                method TEXT NOT NULL,
+                expected REAL NOT NULL,
+                counted REAL NOT NULL,
+                PRIMARY KEY (shift_id, method),
+                FOREIGN KEY(shift_id) REFERENCES shifts(id)
+            )",
+            [],
+        )?;
+
+        // `amount` is the part of the tendered money kept against the sale
+        self.conn.execute(
+            "CREATE TABLE IF NOT EXISTS sale_payments (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                sale_id TEXT NOT NULL,
+                method TEXT NOT NULL,
+                tendered REAL NOT NULL,
+                amount REAL NOT NULL,
+                FOREIGN KEY(sale_id) REFERENCES sale_headers(id)
             )",
             [],
         )?;
 
+        // Sales rung up outside a shift have no payment details
+        self.add_column_if_missing("sale_headers", "shift_id", "TEXT REFERENCES shifts(id)")?;
+        self.add_column_if_missing("sale_headers", "change_given", "REAL NOT NULL DEFAULT 0")?;
+        // Sales made before the loyalty program belong to no customer
+        self.add_column_if_missing("sale_headers", "customer_id", "TEXT REFERENCES customers(id)")?;
+        self.add_column_if_missing("sale_headers", "discount", "REAL NOT NULL DEFAULT 0")?;
+
         self.conn.execute(
             "CREATE TABLE IF NOT EXISTS purchases (
                 id TEXT PRIMARY KEY,
                 product_id TEXT NOT NULL,
-                quantity INTEGER NOT NULL,
+                quantity REAL NOT NULL,
+                unit TEXT,
+                unit_quantity REAL,
                 purchase_price REAL NOT NULL,
                 total_cost REAL NOT NULL,
                 purchase_date INTEGER NOT NULL,
+                currency TEXT,
+                exchange_rate REAL NOT NULL DEFAULT 1,
+                FOREIGN KEY(product_id) REFERENCES products(id)
+            )",
+            [],
+        )?;
+
+        // Sales and purchases store `quantity` in the product's stock unit and the
+        // quantity as entered in `unit_quantity` when another unit was used
+        self.add_column_if_missing("purchases", "unit", "TEXT")?;
+        self.add_column_if_missing("purchases", "unit_quantity", "REAL")?;
+        // Purchases without a currency were made in the base currency
+        self.add_column_if_missing("purchases", "currency", "TEXT")?;
+        self.add_column_if_missing("purchases", "exchange_rate", "REAL NOT NULL DEFAULT 1")?;
+
+        self.conn.execute(
+            "CREATE TABLE IF NOT EXISTS exchange_rates (
+                currency TEXT NOT NULL,
+                effective_from INTEGER NOT NULL,
+                rate REAL NOT NULL,
+                PRIMARY KEY (currency, effective_from)
+            )",
+            [],
+        )?;
+
+        self.conn.execute(
+            "CREATE TABLE IF NOT EXISTS product_units (
+                product_id TEXT NOT NULL,
+                unit TEXT NOT NULL,
+                factor REAL NOT NULL,
+                PRIMARY KEY (product_id, unit),
                 FOREIGN KEY(product_id) REFERENCES products(id) ON DELETE CASCADE
             )",
             [],
         )?;
 
+        self.conn.execute(
+            "CREATE TABLE IF NOT EXISTS bundle_components (
+                bundle_id TEXT NOT NULL,
+                component_id TEXT NOT NULL,
+                quantity REAL NOT NULL,
+                PRIMARY KEY (bundle_id, component_id),
+                FOREIGN KEY(bundle_id) REFERENCES products(id) ON DELETE CASCADE,
+                FOREIGN KEY(component_id) REFERENCES products(id)
+            )",
+            [],
+        )?;
+
+        self.conn.execute(
+            "CREATE TABLE IF NOT EXISTS notification_log (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                event TEXT NOT NULL,
+                subject TEXT NOT NULL,
+                sink TEXT NOT NULL,
+                attempts INTEGER NOT NULL,
+                delivered INTEGER NOT NULL,
+                error TEXT,
+                created_at INTEGER NOT NULL
+            )",
+            [],
+        )?;
+
         self.conn.execute(
             "CREATE TABLE IF NOT EXISTS managers (
                 id TEXT PRIMARY KEY,
@@ -65,80 +403,283 @@ impl Database {
                 password TEXT NOT NULL,
                 full_name TEXT NOT NULL,
                 created_at INTEGER NOT NULL,
-                is_active INTEGER NOT NULL DEFAULT 1
+                is_active INTEGER NOT NULL DEFAULT 1,
+                failed_attempts INTEGER NOT NULL DEFAULT 0,
+                locked_until INTEGER,
+                must_change_password INTEGER NOT NULL DEFAULT 0,
+                removed_at INTEGER
+            )",
+            [],
+        )?;
+
+        // Databases created before login lockout existed lack these columns
+        self.add_column_if_missing("managers", "failed_attempts", "INTEGER NOT NULL DEFAULT 0")?;
+        self.add_column_if_missing("managers", "locked_until", "INTEGER")?;
+        if self.add_column_if_missing("managers", "must_change_password", "INTEGER NOT NULL DEFAULT 0")? {
+            self.conn.execute(
+                "UPDATE managers SET must_change_password = 1
+                 WHERE username = 'admin' AND password = 'admin123'",
+                [],
+            )?;
+        }
+        self.add_column_if_missing("managers", "removed_at", "INTEGER")?;
+        self.hash_plaintext_passwords()?;
+
+        self.conn.execute(
+            "CREATE TABLE IF NOT EXISTS price_history (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                product_id TEXT NOT NULL,
+                old_price REAL,
+                new_price REAL NOT NULL,
+                changed_at INTEGER NOT NULL,
+                reason TEXT NOT NULL,
+                FOREIGN KEY(product_id) REFERENCES products(id) ON DELETE CASCADE
+            )",
+            [],
+        )?;
+
+        self.conn.execute(
+            "CREATE TABLE IF NOT EXISTS scheduled_prices (
+                id TEXT PRIMARY KEY,
+                product_id TEXT NOT NULL,
+                kind TEXT NOT NULL,
+                price REAL NOT NULL,
+                starts_at INTEGER NOT NULL,
+                ends_at INTEGER,
+                previous_price REAL,
+                status TEXT NOT NULL DEFAULT 'pending',
+                created_at INTEGER NOT NULL,
+                FOREIGN KEY(product_id) REFERENCES products(id) ON DELETE CASCADE
+            )",
+            [],
+        )?;
+
+        self.conn.execute(
+            "CREATE TABLE IF NOT EXISTS attachment_contents (
+                hash TEXT PRIMARY KEY,
+                size INTEGER NOT NULL,
+                data BLOB,
+                path TEXT,
+                created_at INTEGER NOT NULL
+            )",
+            [],
+        )?;
+
+        self.conn.execute(
+            "CREATE TABLE IF NOT EXISTS attachments (
+                id TEXT PRIMARY KEY,
+                owner_kind TEXT NOT NULL,
+                owner_id TEXT NOT NULL,
+                file_name TEXT NOT NULL,
+                content_type TEXT NOT NULL,
+                hash TEXT NOT NULL,
+                size INTEGER NOT NULL,
+                created_at INTEGER NOT NULL,
+                FOREIGN KEY(hash) REFERENCES attachment_contents(hash)
+            )",
+            [],
+        )?;
+        self.conn.execute(
+            "CREATE INDEX IF NOT EXISTS idx_attachments_owner ON attachments(owner_kind, owner_id)",
+            [],
+        )?;
+
+        self.conn.execute(
+            "CREATE TABLE IF NOT EXISTS settings (
+                key TEXT PRIMARY KEY,
+                value TEXT NOT NULL,
+                updated_at INTEGER NOT NULL
+            )",
+            [],
+        )?;
+
+        // Goods brought back against a sale; `quantity` is in the product's stock unit
+        // and `unit_cost` is the average purchase cost when the goods were sold.
+        // Refunds paid at the till record the shift and tender they came out of
+        self.conn.execute(
+            "CREATE TABLE IF NOT EXISTS sale_returns (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                sale_id TEXT NOT NULL,
+                product_id TEXT NOT NULL,
+                quantity REAL NOT NULL,
+                refund REAL NOT NULL,
+                unit_cost REAL,
+                reason TEXT NOT NULL,
+                returned_at INTEGER NOT NULL,
+                shift_id TEXT REFERENCES shifts(id),
+                method TEXT,
+                FOREIGN KEY(sale_id) REFERENCES sale_headers(id),
+                FOREIGN KEY(product_id) REFERENCES products(id)
+            )",
+            [],
+        )?;
+        self.add_column_if_missing("sale_returns", "shift_id", "TEXT REFERENCES shifts(id)")?;
+        self.add_column_if_missing("sale_returns", "method", "TEXT")?;
+
+        // Stock corrections outside sales and purchases; `quantity` is the signed change
+        self.conn.execute(
+            "CREATE TABLE IF NOT EXISTS stock_adjustments (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                product_id TEXT NOT NULL,
+                quantity REAL NOT NULL,
+                unit_cost REAL,
+                reason TEXT NOT NULL,
+                adjusted_at INTEGER NOT NULL,
+                FOREIGN KEY(product_id) REFERENCES products(id)
+            )",
+            [],
+        )?;
+
+        // Voided operations and the manager who approved each; the compensating
+        // returns, purchase or adjustment are stored with the other records
+        self.conn.execute(
+            "CREATE TABLE IF NOT EXISTS voids (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                kind TEXT NOT NULL,
+                reference TEXT NOT NULL,
+                description TEXT NOT NULL,
+                compensation TEXT,
+                refund REAL NOT NULL DEFAULT 0,
+                manager_id TEXT NOT NULL,
+                reason TEXT NOT NULL,
+                voided_at INTEGER NOT NULL,
+                UNIQUE(kind, reference),
+                FOREIGN KEY(manager_id) REFERENCES managers(id)
+            )",
+            [],
+        )?;
+
+        // Points ledger: earned and redeemed per sale, and reversed by returns
+        self.conn.execute(
+            "CREATE TABLE IF NOT EXISTS loyalty_points (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                customer_id TEXT NOT NULL,
+                sale_id TEXT NOT NULL,
+                kind TEXT NOT NULL,
+                points INTEGER NOT NULL,
+                created_at INTEGER NOT NULL,
+                FOREIGN KEY(customer_id) REFERENCES customers(id),
+                FOREIGN KEY(sale_id) REFERENCES sale_headers(id)
             )",
             [],
         )?;
 
+        // Lookups by product and by date stay fast with hundreds of thousands of sales;
+        // the product index also covers the per-product sales totals of the reports
+        self.conn.execute_batch(
+            "CREATE INDEX IF NOT EXISTS idx_sales_product_date ON sales(product_id, sold_at, quantity, total_price);
+             CREATE INDEX IF NOT EXISTS idx_sales_date ON sales(sold_at);
+             CREATE INDEX IF NOT EXISTS idx_sales_sale ON sales(sale_id);
+             CREATE INDEX IF NOT EXISTS idx_sale_headers_date ON sale_headers(created_at);
+             CREATE INDEX IF NOT EXISTS idx_sale_payments_sale ON sale_payments(sale_id);
+             CREATE INDEX IF NOT EXISTS idx_sale_returns_sale ON sale_returns(sale_id, product_id);
+             CREATE INDEX IF NOT EXISTS idx_purchases_product_date ON purchases(product_id, purchase_date);
+             CREATE INDEX IF NOT EXISTS idx_purchases_date ON purchases(purchase_date);
+             CREATE INDEX IF NOT EXISTS idx_stock_adjustments_product ON stock_adjustments(product_id, adjusted_at);
+             CREATE INDEX IF NOT EXISTS idx_customer_orders_product ON customer_orders(product_id, status);
+             CREATE INDEX IF NOT EXISTS idx_price_history_product ON price_history(product_id, changed_at);
+             CREATE INDEX IF NOT EXISTS idx_sale_headers_customer ON sale_headers(customer_id);
+             CREATE INDEX IF NOT EXISTS idx_loyalty_points_customer ON loyalty_points(customer_id, sale_id);",
+        )?;
+
+        // Products created before price history existed start with their current price
+        self.conn.execute(
+            "INSERT INTO price_history (product_id, old_price, new_price, changed_at, reason)
+             SELECT id, NULL, price, created_at, ?1 FROM products
+             WHERE id NOT IN (SELECT product_id FROM price_history)",
+            [pricing::REASON_INITIAL],
+        )?;
+
         // Create default admin manager if no managers exist
         self.create_default_admin_if_needed()?;
 
         Ok(())
     }
 
-    pub fn add_product(&self, product: &Product) -> Result<()> {
-        self.conn.execute(
-            "INSERT INTO products (id, name, description, price, quantity, created_at, updated_at)
-             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
-            params![
-                product.id,
-                product.name,
-                product.description,
-                product.price,
-                product.quantity,
-                product.created_at,
-                product.updated_at
-            ],
-        )?;
+    /// Passwords used to be stored in plain text. Replaces any left with their hash.
+    fn hash_plaintext_passwords(&self) -> Result<()> {
+        let plaintext: Vec<(String, String)> = {
+            let mut stmt = self.conn.prepare("SELECT id, password FROM managers")?;
+            let rows = stmt.query_map([], |row| Ok((row.get(0)?, row.get(1)?)))?;
+            rows.collect::<rusqlite::Result<Vec<(String, String)>>>()?
+                .into_iter()
+                .filter(|(_, password)| !auth::is_password_hash(password))
+                .collect()
+        };
+        for (id, password) in plaintext {
+            self.conn.execute(
+                "UPDATE managers SET password = ?1 WHERE id = ?2",
+                params![auth::hash_password(&password), id],
+            )?;
+        }
         Ok(())
     }
 
-    pub fn update_product(&self, product: &Product) -> Result<()> {
-        self.conn.execute(
-            "UPDATE products 
-             SET name = ?1, description = ?2, price = ?3, quantity = ?4, updated_at = ?5
-             WHERE id = ?6",
-            params![
-                product.name,
-                product.description,
-                product.price,
-                product.quantity,
-                product.updated_at,
-                product.id
-            ],
+    /// Sales used to store `sale_date` as UTC `YYYY-MM-DD HH:MM:SS` text while every
+    /// other table stores Unix seconds. Rebuilds the table with an integer `sold_at`.
+    fn migrate_sale_dates(&self) -> Result<()> {
+        if !self.has_column("sales", "sale_date")? {
+            return Ok(());
+        }
+        self.conn.execute_batch(
+            "BEGIN;
+             CREATE TABLE sales_migrated (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                sale_id TEXT REFERENCES sale_headers(id),
+                product_id TEXT NOT NULL,
+                quantity REAL NOT NULL,
+                unit TEXT,
+                unit_quantity REAL,
+                unit_price REAL,
+                total_price REAL NOT NULL,
+                sold_at INTEGER NOT NULL,
+                FOREIGN KEY(product_id) REFERENCES products(id)
+             );
+             INSERT INTO sales_migrated (id, sale_id, product_id, quantity, unit, unit_quantity, unit_price,
+                                         total_price, sold_at)
+             SELECT id, sale_id, product_id, quantity, unit, unit_quantity, unit_price, total_price,
+                    COALESCE(CAST(strftime('%s', sale_date) AS INTEGER), 0)
+             FROM sales;
+             DROP TABLE sales;
+             ALTER TABLE sales_migrated RENAME TO sales;
+             COMMIT;",
         )?;
         Ok(())
     }
 
-    pub fn delete_product(&mut self, id: &str) -> Result<()> {
-        self.conn.execute("DELETE FROM products WHERE id = ?1", [id])?;
-        Ok(())
+    fn has_column(&self, table: &str, column: &str) -> Result<bool> {
+        let mut stmt = self.conn.prepare(&format!("PRAGMA table_info({})", table))?;
+        let columns = stmt
+            .query_map([], |row| row.get::<_, String>(1))?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+        Ok(columns.iter().any(|name| name == column))
     }
 
-    pub fn get_product(&self, id: &str) -> Result<Option<Product>> {
-        let mut stmt = self.conn.prepare("SELECT * FROM products WHERE id = ?")?;
-        let product = stmt.query_row([id], |row| {
-            Ok(Product {
-                id: row.get(0)?,
-                name: row.get(1)?,
-                description: row.get(2)?,
-                price: row.get(3)?,
-                quantity: row.get(4)?,
-                created_at: Utc::now().timestamp(),
-                updated_at: Utc::now().timestamp(),
-            })
-        }).optional()?;
+    /// Adds `column` to `table` unless it already exists. Returns true when the column was added.
+    fn add_column_if_missing(&self, table: &str, column: &str, definition: &str) -> Result<bool> {
+        if self.has_column(table, column)? {
+            return Ok(false);
+        }
 
-        Ok(product)
+        self.conn.execute(
+            &format!("ALTER TABLE {} ADD COLUMN {} {}", table, column, definition),
+            [],
+        )?;
+        Ok(true)
     }
 
-    pub fn get_all_products(&self) -> Result<Vec<Product>> {
-        let mut stmt = self.conn.prepare(
-            "SELECT id, name, description, price, quantity, created_at, updated_at 
-             FROM products"
-        )?;
-        
-        let products = stmt.query_map([], |row| {
+    pub fn get_products(&self) -> Result<Vec<Product>> {
+        self.query_products("WHERE archived_at IS NULL", [])
+    }
+
+    fn query_products(&self, filter: &str, params: impl rusqlite::Params) -> Result<Vec<Product>> {
+        let mut stmt = self.conn.prepare(&format!(
+            "SELECT id, name, description, price, quantity, created_at, updated_at, archived_at, version, unit, sku, tags
+             FROM products {}",
+            filter
+        ))?;
+
+        let products = stmt.query_map(params, |row| {
             Ok(Product {
                 id: row.get(0)?,
                 name: row.get(1)?,
@@ -147,261 +688,2906 @@ impl Database {
                 quantity: row.get(4)?,
                 created_at: row.get(5)?,
                 updated_at: row.get(6)?,
+                archived_at: row.get(7)?,
+                version: row.get(8)?,
+                unit: row.get(9)?,
+                sku: row.get(10)?,
+                tags: product::parse_tags(&row.get::<_, String>(11)?),
             })
         })?;
 
-        let mut result = Vec::new();
-        for product in products {
-            result.push(product?);
-        }
-        Ok(result)
+        Ok(products.collect::<rusqlite::Result<_>>()?)
     }
 
-    pub fn get_products(&self) -> Result<Vec<Product>> {
-        let mut stmt = self.conn.prepare("SELECT * FROM products")?;
-        let products = stmt.query_map([], |row| {
-            Ok(Product {
-                id: row.get(0)?,
-                name: row.get(1)?,
-                description: row.get(2)?,
-                price: row.get(3)?,
-                quantity: row.get(4)?,
-                created_at: Utc::now().timestamp(),
-                updated_at: Utc::now().timestamp(),
-            })
+    /// The most recent sales, newest first, as (sale number, item count, total amount, timestamp).
+    pub fn get_recent_sales(&self, limit: usize) -> Result<Vec<(i64, i64, f64, i64)>> {
+        let mut stmt = self.conn.prepare(
+            "SELECT h.sale_number, (SELECT COUNT(*) FROM sales s WHERE s.sale_id = h.id), h.total_amount, h.created_at
+             FROM sale_headers h
+             ORDER BY h.sale_number DESC
+             LIMIT ?1"
+        )?;
+
+        let sales = stmt.query_map([limit as i64], |row| {
+            Ok((row.get(0)?, row.get(1)?, row.get(2)?, row.get(3)?))
         })?;
 
-        products.collect()
+        Ok(sales.collect::<rusqlite::Result<_>>()?)
     }
 
-    pub fn record_sale(&mut self, sale: &Sale) -> Result<()> {
-        let tx = self.conn.transaction()?;
-
-        for item in &sale.items {
-            let mut stmt = tx.prepare("SELECT quantity FROM products WHERE id = ?")?;
-            let current_quantity: i32 = stmt.query_row([&item.product_id], |row| row.get(0))?;
+    /// One page of sale rows, newest first, as (product name, quantity, total, sold at),
+    /// net of returns.
+    pub fn sales_page(&self, offset: usize, limit: usize) -> Result<Vec<(String, f64, f64, i64)>> {
+        self.query_sales(offset as i64, limit as i64)
+    }
 
-            if current_quantity < item.quantity {
-                return Err(rusqlite::Error::InvalidParameterCount(0, 1));
-            }
+    /// Number of sale rows not returned in full, for paging through them.
+    pub fn sales_count(&self) -> Result<usize> {
+        let count: i64 = self.conn.query_row(
+            &format!("WITH {}, {} SELECT COUNT(*) FROM net_sales WHERE returned_share < 1 - 1e-9", PAID_SALES, NET_SALES),
+            [],
+            |row| row.get(0),
+        )?;
+        Ok(count as usize)
+    }
 
-            tx.execute(
-                "UPDATE products SET quantity = quantity - ? WHERE id = ?",
-                params![item.quantity, item.product_id],
-            )?;
+    /// Sale rows net of returns, newest first, leaving out rows returned in full.
+    /// A negative `limit` returns every row.
+    fn query_sales(&self, offset: i64, limit: i64) -> Result<Vec<(String, f64, f64, i64)>> {
+        let mut stmt = self.conn.prepare(&format!(
+            "WITH {}, {}
+             SELECT p.name, ROUND(s.quantity * (1 - s.returned_share), 6), ROUND(s.paid - s.refunded, 2), s.sold_at
+             FROM net_sales s
+             JOIN products p ON s.product_id = p.id
+             WHERE s.returned_share < 1 - 1e-9
+             ORDER BY s.sold_at DESC, s.id DESC
+             LIMIT ?1 OFFSET ?2",
+            PAID_SALES, NET_SALES
+        ))?;
+        let sales = stmt.query_map([limit, offset], |row| {
+            Ok((row.get(0)?, row.get(1)?, row.get(2)?, row.get(3)?))
+        })?;
+        Ok(sales.collect::<rusqlite::Result<_>>()?)
+    }
 
-            tx.execute(
-                "INSERT INTO sales (product_id, quantity, total_price, sale_date) 
-                 VALUES (?, ?, ?, datetime('now'))",
-                params![
-                    item.product_id,
-                    item.quantity,
-                    item.total_price,
-                ],
-            )?;
-        }
+    /// Stores `sale` in its own transaction. Returns the sale number.
+    fn write_sale(&mut self, sale: &Sale) -> Result<i64> {
+        let base_quantities = sale
+            .items
+            .iter()
+            .map(|item| self.to_base_quantity(&item.product_id, item.unit.as_deref(), item.quantity))
+            .collect::<Result<Vec<f64>>>()?;
 
+        // Take the write lock before reading stock so another terminal cannot sell
+        // the same units between our check and our update
+        let tx = self.conn.transaction_with_behavior(TransactionBehavior::Immediate)?;
+        let sale_number = insert_sale(&tx, sale, &base_quantities)?;
         tx.commit()?;
+        Ok(sale_number)
+    }
+
+    // Manager management functions
+    fn create_default_admin_if_needed(&self) -> Result<()> {
+        let count: i32 = self.conn.query_row(
+            "SELECT COUNT(*) FROM managers",
+            [],
+            |row| row.get(0)
+        )?;
+
+        if count == 0 {
+            self.add_manager(&Manager::default_admin())?;
+        }
+
         Ok(())
     }
+}
 
-    pub fn record_purchase(&mut self, purchase: &Purchase) -> Result<()> {
-        let tx = self.conn.transaction()?;
+impl Repository for Database {
+    fn add_product(&self, product: &Product) -> Result<()> {
+        ensure_unique_sku(&self.conn, product)?;
+        self.conn.execute(
+            "INSERT INTO products (id, name, description, price, quantity, unit, sku, tags, created_at, updated_at)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10)",
+            params![
+                product.id,
+                product.name,
+                product.description,
+                product.price,
+                product.quantity,
+                unit::normalize(&product.unit),
+                product.sku,
+                product.tags.join(", "),
+                product.created_at,
+                product.updated_at
+            ],
+        )?;
+        index_product(&self.conn, product)?;
+        self.record_price_change(&product.id, None, product.price, product.created_at, pricing::REASON_INITIAL)?;
+        Ok(())
+    }
 
-        tx.execute(
-            "INSERT INTO purchases (id, product_id, quantity, purchase_price, total_cost, purchase_date) 
-             VALUES (?, ?, ?, ?, ?, ?)",
+    fn update_product(&self, product: &Product) -> Result<()> {
+        let old_price: Option<f64> = self.conn
+            .query_row("SELECT price FROM products WHERE id = ?1", [&product.id], |row| row.get(0))
+            .optional()?;
+
+        ensure_unique_sku(&self.conn, product)?;
+        let updated = self.conn.execute(
+            "UPDATE products 
+             SET name = ?1, description = ?2, price = ?3, quantity = ?4, sku = ?5, tags = ?6, updated_at = ?7,
+                 version = version + 1
+             WHERE id = ?8 AND version = ?9",
             params![
-                purchase.id,
-                purchase.product_id,
-                purchase.quantity,
-                purchase.purchase_price,
-                purchase.total_cost,
-                purchase.purchase_date,
+                product.name,
+                product.description,
+                product.price,
+                product.quantity,
+                product.sku,
+                product.tags.join(", "),
+                product.updated_at,
+                product.id,
+                product.version
             ],
         )?;
+        if updated == 0 {
+            return Err(match old_price {
+                Some(_) => stale_product(product),
+                None => Error::not_found("Product", &product.id),
+            });
+        }
+        index_product(&self.conn, product)?;
+        if let Some(old_price) = old_price {
+            if old_price != product.price {
+                self.record_price_change(
+                    &product.id,
+                    Some(old_price),
+                    product.price,
+                    product.updated_at,
+                    pricing::REASON_MANUAL,
+                )?;
+            }
+        }
+        Ok(())
+    }
+
+    fn delete_product(&mut self, id: &str) -> Result<()> {
+        let tx = self.conn.transaction()?;
+
+        let history: i64 = tx.query_row(
+            "SELECT (SELECT COUNT(*) FROM sales WHERE product_id = ?1)
+                  + (SELECT COUNT(*) FROM purchases WHERE product_id = ?1)
+                  + (SELECT COUNT(*) FROM customer_orders WHERE product_id = ?1)
+                  + (SELECT COUNT(*) FROM stock_adjustments WHERE product_id = ?1)",
+            [id],
+            |row| row.get(0),
+        )?;
+        if history > 0 {
+            return Err(Error::conflict(
+                "Product has sales or purchase history and can only be archived",
+            ));
+        }
+        let bundles: i64 = tx.query_row(
+            "SELECT COUNT(*) FROM bundle_components WHERE component_id = ?1",
+            [id],
+            |row| row.get(0),
+        )?;
+        if bundles > 0 {
+            return Err(Error::conflict("Product is a component of a bundle; remove it from the bundle first"));
+        }
+
+        let deleted = tx.execute("DELETE FROM products WHERE id = ?1", [id])?;
+        if deleted == 0 {
+            return Err(Error::not_found("Product", id));
+        }
+        tx.execute("DELETE FROM product_search WHERE product_id = ?1", [id])?;
+        tx.execute(
+            "DELETE FROM attachments WHERE owner_kind = ?1 AND owner_id = ?2",
+            params![OwnerKind::Product.as_str(), id],
+        )?;
+        let orphans = prune_attachment_contents(&tx)?;
 
         tx.commit()?;
+        remove_content_files(&orphans);
         Ok(())
     }
 
-    #[allow(dead_code)]
-    pub fn get_sale(&self, id: &str) -> Result<Option<Sale>> {
-        let mut stmt = self.conn.prepare(
-            "SELECT id, total_amount, total_profit, timestamp FROM sales WHERE id = ?1"
+    fn archive_product(&self, id: &str) -> Result<()> {
+        let archived = self.conn.execute(
+            "UPDATE products SET archived_at = COALESCE(archived_at, ?1) WHERE id = ?2",
+            params![Utc::now().timestamp(), id],
         )?;
+        if archived == 0 {
+            return Err(Error::not_found("Product", id));
+        }
+        Ok(())
+    }
 
-        let sale = stmt.query_row([id], |row| {
-            let sale_id: String = row.get(0)?;
-            
-            let mut stmt = self.conn.prepare(
-                "SELECT product_id, quantity, unit_price, total_price 
-                 FROM sale_items WHERE sale_id = ?1"
-            )?;
-            
-            let items: Result<Vec<SaleItem>> = stmt.query_map([&sale_id], |row| {
-                Ok(SaleItem {
-                    product_id: row.get(0)?,
-                    quantity: row.get(1)?,
-                    unit_price: row.get(2)?,
-                    total_price: row.get(3)?,
-                })
-            })?.collect();
-
-            Ok(Sale {
-                id: sale_id,
-                items: items?,
-                total_amount: row.get(1)?,
-                total_profit: row.get(2)?,
-                timestamp: row.get(3)?,
-            })
-        }).optional()?;
+    fn restore_product(&self, id: &str) -> Result<()> {
+        let restored = self.conn.execute("UPDATE products SET archived_at = NULL WHERE id = ?1", [id])?;
+        if restored == 0 {
+            return Err(Error::not_found("Product", id));
+        }
+        Ok(())
+    }
+
+    fn get_product(&self, id: &str) -> Result<Option<Product>> {
+        Ok(self.query_products("WHERE id = ?1", [id])?.pop())
+    }
 
-        Ok(sale)
+    fn get_all_products(&self) -> Result<Vec<Product>> {
+        self.query_products("WHERE archived_at IS NULL", [])
     }
 
-    pub fn get_all_sales(&self) -> Result<Vec<(String, i32, f64, String)>> {
+    fn get_archived_products(&self) -> Result<Vec<Product>> {
+        self.query_products("WHERE archived_at IS NOT NULL ORDER BY archived_at DESC", [])
+    }
+
+    fn record_sale(&mut self, sale: &Sale) -> Result<i64> {
+        if sale.customer_id.is_some() {
+            return Err(Error::validation("Sales to loyalty customers are recorded with record_customer_sale"));
+        }
+        self.write_sale(sale)
+    }
+
+    fn get_sale_by_number(&self, sale_number: i64) -> Result<Option<Sale>> {
+        let header = self.conn.query_row(
+            "SELECT id, total_amount, created_at, customer_id, discount,
+                    (SELECT COALESCE(-SUM(points), 0) FROM loyalty_points WHERE sale_id = h.id AND kind = 'redeemed')
+             FROM sale_headers h WHERE sale_number = ?1",
+            [sale_number],
+            |row| Ok((
+                row.get::<_, String>(0)?,
+                row.get::<_, f64>(1)?,
+                row.get::<_, i64>(2)?,
+                row.get::<_, Option<String>>(3)?,
+                row.get::<_, f64>(4)?,
+                row.get::<_, i64>(5)?,
+            )),
+        ).optional()?;
+
+        let (sale_id, total_amount, timestamp, customer_id, discount, points_redeemed) = match header {
+            Some(header) => header,
+            None => return Ok(None),
+        };
+
         let mut stmt = self.conn.prepare(
-            "SELECT s.product_id, s.quantity, s.total_price, s.sale_date, p.name 
-             FROM sales s
-             JOIN products p ON s.product_id = p.id
-             ORDER BY s.sale_date DESC"
+            "SELECT product_id, COALESCE(unit_quantity, quantity), unit,
+                    COALESCE(unit_price, total_price / quantity), total_price
+             FROM sales WHERE sale_id = ?1 ORDER BY id"
         )?;
+        let items = stmt.query_map([&sale_id], |row| {
+            Ok(SaleItem {
+                product_id: row.get(0)?,
+                quantity: row.get(1)?,
+                unit: row.get(2)?,
+                unit_price: row.get(3)?,
+                total_price: row.get(4)?,
+            })
+        })?.collect::<rusqlite::Result<Vec<_>>>()?;
 
-        let sales = stmt.query_map([], |row| {
-            Ok((
-                row.get::<_, String>(4)?,
-                row.get(1)?,
-                row.get(2)?,
-                row.get(3)?,
-            ))
-        })?;
+        Ok(Some(Sale {
+            id: sale_id,
+            sale_number: Some(sale_number),
+            items,
+            total_amount,
+            total_profit: 0.0,
+            timestamp,
+            customer_id,
+            points_redeemed,
+            discount,
+        }))
+    }
+
+    fn record_purchase(&mut self, purchase: &Purchase) -> Result<()> {
+        match self.get_product(&purchase.product_id)? {
+            None => return Err(Error::not_found("Product", &purchase.product_id)),
+            Some(product) if product.is_archived() => {
+                return Err(Error::conflict(format!("Product '{}' is archived", purchase.product_id)));
+            }
+            Some(_) => {}
+        }
+        let quantity = self.to_base_quantity(&purchase.product_id, purchase.unit.as_deref(), purchase.quantity)?;
+        let currency = currency::normalize(&purchase.currency)?;
+        let exchange_rate = self.exchange_rate(&currency, purchase.purchase_date)?;
 
-        sales.collect()
+        let tx = self.conn.transaction_with_behavior(TransactionBehavior::Immediate)?;
+
+        tx.execute(
+            "INSERT INTO purchases (id, product_id, quantity, unit, unit_quantity, purchase_price, total_cost,
+                                    purchase_date, currency, exchange_rate) 
+             VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?)",
+            params![
+                purchase.id,
+                purchase.product_id,
+                quantity,
+                purchase.unit.as_deref().map(unit::normalize),
+                purchase.unit.as_ref().map(|_| purchase.quantity),
+                purchase.purchase_price,
+                purchase.total_cost,
+                purchase.purchase_date,
+                currency,
+                exchange_rate,
+            ],
+        )?;
+        tx.execute(
+            "UPDATE products SET quantity = ROUND(quantity + ?1, 6), updated_at = ?2, version = version + 1 WHERE id = ?3",
+            params![quantity, Utc::now().timestamp(), purchase.product_id],
+        )?;
+        // The stock has arrived, so waiting backorders can be filled from it
+        allocate_backorders(&tx, &purchase.product_id)?;
+
+        tx.commit()?;
+        Ok(())
+    }
+
+    fn get_all_sales(&self) -> Result<Vec<(String, f64, f64, i64)>> {
+        self.query_sales(0, -1)
     }
 
-    pub fn get_all_purchases(&self) -> Result<Vec<Purchase>> {
+    fn get_all_purchases(&self) -> Result<Vec<Purchase>> {
         let mut stmt = self.conn.prepare(
-            "SELECT id, product_id, quantity, purchase_price, total_cost, purchase_date 
+            "SELECT id, product_id, COALESCE(unit_quantity, quantity), unit, purchase_price, total_cost, purchase_date,
+                    COALESCE(currency, ?1), exchange_rate 
              FROM purchases
              ORDER BY purchase_date DESC"
         )?;
 
-        let purchases = stmt.query_map([], |row| {
+        let purchases = stmt.query_map([currency::base_currency()], |row| {
             Ok(Purchase {
                 id: row.get(0)?,
                 product_id: row.get(1)?,
                 quantity: row.get(2)?,
-                purchase_price: row.get(3)?,
-                total_cost: row.get(4)?,
-                purchase_date: row.get(5)?,
+                unit: row.get(3)?,
+                purchase_price: row.get(4)?,
+                total_cost: row.get(5)?,
+                purchase_date: row.get(6)?,
+                currency: row.get(7)?,
+                exchange_rate: row.get(8)?,
             })
         })?;
 
-        purchases.collect()
+        Ok(purchases.collect::<rusqlite::Result<_>>()?)
     }
 
-    // Manager management functions
-    fn create_default_admin_if_needed(&self) -> Result<()> {
-        let count: i32 = self.conn.query_row(
-            "SELECT COUNT(*) FROM managers",
-            [],
-            |row| row.get(0)
-        )?;
-
-        if count == 0 {
-            let admin = Manager::new(
-                "admin".to_string(),
-                "admin123".to_string(),
-                "System Administrator".to_string()
-            );
-            self.add_manager(&admin)?;
+    fn add_manager(&self, manager: &Manager) -> Result<()> {
+        if self.get_manager_by_username(&manager.username)?.is_some() {
+            return Err(Error::conflict(format!("Username '{}' already exists", manager.username)));
         }
 
-        Ok(())
-    }
-
-    pub fn add_manager(&self, manager: &Manager) -> Result<()> {
         self.conn.execute(
-            "INSERT INTO managers (id, username, password, full_name, created_at, is_active)
-             VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+            "INSERT INTO managers (id, username, password, full_name, created_at, is_active,
+                                   failed_attempts, locked_until, must_change_password)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9)",
             params![
                 manager.id,
                 manager.username,
-                manager.password,
+                auth::hash_password(&manager.password),
                 manager.full_name,
                 manager.created_at,
-                manager.is_active as i32
+                manager.is_active as i32,
+                manager.failed_attempts,
+                manager.locked_until,
+                manager.must_change_password as i32
             ],
         )?;
         Ok(())
     }
 
-    pub fn get_manager_by_username(&self, username: &str) -> Result<Option<Manager>> {
+    fn get_manager_by_username(&self, username: &str) -> Result<Option<Manager>> {
+        let mut stmt = self.conn.prepare(&format!(
+            "SELECT {} FROM managers WHERE username = ?1",
+            MANAGER_COLUMNS
+        ))?;
+
+        Ok(stmt.query_row([username], manager_from_row).optional()?)
+    }
+
+    fn get_manager(&self, manager_id: &str) -> Result<Option<Manager>> {
+        let mut stmt = self.conn.prepare(&format!(
+            "SELECT {} FROM managers WHERE id = ?1",
+            MANAGER_COLUMNS
+        ))?;
+
+        Ok(stmt.query_row([manager_id], manager_from_row).optional()?)
+    }
+
+    fn get_all_managers(&self) -> Result<Vec<Manager>> {
+        let mut stmt = self.conn.prepare(&format!(
+            "SELECT {} FROM managers ORDER BY created_at DESC",
+            MANAGER_COLUMNS
+        ))?;
+
+        let managers = stmt.query_map([], manager_from_row)?;
+
+        Ok(managers.collect::<rusqlite::Result<_>>()?)
+    }
+
+    fn update_manager_status(&self, manager_id: &str, is_active: bool) -> Result<()> {
+        let updated = self.conn.execute(
+            "UPDATE managers SET is_active = ?1 WHERE id = ?2 AND removed_at IS NULL",
+            params![is_active as i32, manager_id],
+        )?;
+        if updated == 0 {
+            return Err(Error::not_found("Manager", manager_id));
+        }
+        Ok(())
+    }
+
+    fn update_manager_password(&self, manager_id: &str, password: &str) -> Result<()> {
+        let updated = self.conn.execute(
+            "UPDATE managers SET password = ?1, must_change_password = 0 WHERE id = ?2",
+            params![auth::hash_password(password), manager_id],
+        )?;
+        if updated == 0 {
+            return Err(Error::not_found("Manager", manager_id));
+        }
+        Ok(())
+    }
+
+    fn update_manager_profile(&self, manager_id: &str, username: &str, full_name: &str) -> Result<()> {
+        validate_profile(username, full_name)?;
+        if self.get_manager_by_username(username)?.is_some_and(|other| other.id != manager_id) {
+            return Err(Error::conflict(format!("Username '{}' already exists", username)));
+        }
+        let updated = self.conn.execute(
+            "UPDATE managers SET username = ?1, full_name = ?2 WHERE id = ?3 AND removed_at IS NULL",
+            params![username, full_name, manager_id],
+        )?;
+        if updated == 0 {
+            return Err(Error::not_found("Manager", manager_id));
+        }
+        Ok(())
+    }
+
+    fn reset_manager_password(&self, manager_id: &str, temporary_password: &str) -> Result<()> {
+        let updated = self.conn.execute(
+            "UPDATE managers SET password = ?1, must_change_password = 1, failed_attempts = 0, locked_until = NULL
+             WHERE id = ?2 AND removed_at IS NULL",
+            params![auth::hash_password(temporary_password), manager_id],
+        )?;
+        if updated == 0 {
+            return Err(Error::not_found("Manager", manager_id));
+        }
+        Ok(())
+    }
+
+    fn get_price_history(&self, product_id: &str) -> Result<Vec<PriceChange>> {
         let mut stmt = self.conn.prepare(
-            "SELECT id, username, password, full_name, created_at, is_active 
-             FROM managers WHERE username = ?1"
+            "SELECT product_id, old_price, new_price, changed_at, reason
+             FROM price_history WHERE product_id = ?1
+             ORDER BY changed_at, id"
         )?;
-        
-        let manager = stmt.query_row([username], |row| {
-            Ok(Manager {
-                id: row.get(0)?,
-                username: row.get(1)?,
-                password: row.get(2)?,
-                full_name: row.get(3)?,
-                created_at: row.get(4)?,
-                is_active: row.get::<_, i32>(5)? == 1,
+
+        let history = stmt.query_map([product_id], |row| {
+            Ok(PriceChange {
+                product_id: row.get(0)?,
+                old_price: row.get(1)?,
+                new_price: row.get(2)?,
+                changed_at: row.get(3)?,
+                reason: row.get(4)?,
             })
-        }).optional()?;
+        })?;
+
+        Ok(history.collect::<rusqlite::Result<_>>()?)
+    }
+
+    fn set_product_unit(&self, product_unit: &ProductUnit) -> Result<()> {
+        let product = self
+            .get_product(&product_unit.product_id)?
+            .ok_or_else(|| Error::not_found("Product", &product_unit.product_id))?;
+        product_unit.validate(&product)?;
+
+        self.conn.execute(
+            "INSERT INTO product_units (product_id, unit, factor) VALUES (?1, ?2, ?3)
+             ON CONFLICT(product_id, unit) DO UPDATE SET factor = excluded.factor",
+            params![product_unit.product_id, product_unit.unit, product_unit.factor],
+        )?;
+        Ok(())
+    }
 
-        Ok(manager)
+    fn remove_product_unit(&self, product_id: &str, unit: &str) -> Result<()> {
+        let removed = self.conn.execute(
+            "DELETE FROM product_units WHERE product_id = ?1 AND unit = ?2",
+            params![product_id, unit::normalize(unit)],
+        )?;
+        if removed == 0 {
+            return Err(Error::not_found("Unit", unit));
+        }
+        Ok(())
     }
 
-    pub fn get_all_managers(&self) -> Result<Vec<Manager>> {
+    fn get_product_units(&self, product_id: &str) -> Result<Vec<ProductUnit>> {
         let mut stmt = self.conn.prepare(
-            "SELECT id, username, password, full_name, created_at, is_active 
-             FROM managers ORDER BY created_at DESC"
+            "SELECT product_id, unit, factor FROM product_units WHERE product_id = ?1 ORDER BY factor"
         )?;
+        let units = stmt.query_map([product_id], |row| {
+            Ok(ProductUnit {
+                product_id: row.get(0)?,
+                unit: row.get(1)?,
+                factor: row.get(2)?,
+            })
+        })?;
+        Ok(units.collect::<rusqlite::Result<_>>()?)
+    }
 
-        let managers = stmt.query_map([], |row| {
-            Ok(Manager {
-                id: row.get(0)?,
-                username: row.get(1)?,
-                password: row.get(2)?,
-                full_name: row.get(3)?,
-                created_at: row.get(4)?,
-                is_active: row.get::<_, i32>(5)? == 1,
+    fn set_bundle_component(&self, component: &BundleComponent) -> Result<()> {
+        if self.get_product(&component.bundle_id)?.is_none() {
+            return Err(Error::not_found("Product", &component.bundle_id));
+        }
+        let product = self
+            .get_product(&component.component_id)?
+            .ok_or_else(|| Error::not_found("Product", &component.component_id))?;
+        let (component_is_bundle, bundle_is_component): (bool, bool) = self.conn.query_row(
+            "SELECT EXISTS(SELECT 1 FROM bundle_components WHERE bundle_id = ?1),
+                    EXISTS(SELECT 1 FROM bundle_components WHERE component_id = ?2)",
+            params![component.component_id, component.bundle_id],
+            |row| Ok((row.get(0)?, row.get(1)?)),
+        )?;
+        component.validate(&product, component_is_bundle, bundle_is_component)?;
+
+        self.conn.execute(
+            "INSERT INTO bundle_components (bundle_id, component_id, quantity) VALUES (?1, ?2, ?3)
+             ON CONFLICT(bundle_id, component_id) DO UPDATE SET quantity = excluded.quantity",
+            params![component.bundle_id, component.component_id, component.quantity],
+        )?;
+        Ok(())
+    }
+
+    fn remove_bundle_component(&self, bundle_id: &str, component_id: &str) -> Result<()> {
+        let removed = self.conn.execute(
+            "DELETE FROM bundle_components WHERE bundle_id = ?1 AND component_id = ?2",
+            params![bundle_id, component_id],
+        )?;
+        if removed == 0 {
+            return Err(Error::not_found("Bundle component", component_id));
+        }
+        Ok(())
+    }
+
+    fn reserved_quantity(&self, product_id: &str) -> Result<f64> {
+        reserved_quantity(&self.conn, product_id)
+    }
+
+    fn get_bundle_components(&self, bundle_id: &str) -> Result<Vec<BundleComponent>> {
+        bundle_components(&self.conn, bundle_id)
+    }
+
+    fn assemble_bundle(&mut self, bundle_id: &str, count: f64) -> Result<()> {
+        if !count.is_finite() || count <= 0.0 || count.fract() != 0.0 {
+            return Err(Error::validation("Bundles are assembled in whole, positive numbers"));
+        }
+        let tx = self.conn.transaction_with_behavior(TransactionBehavior::Immediate)?;
+
+        let components = bundle_components(&tx, bundle_id)?;
+        if components.is_empty() {
+            return Err(Error::validation("Product has no bill of materials"));
+        }
+        for component in &components {
+            let needed = unit::round_quantity(component.quantity * count);
+            let available: f64 = tx.query_row(
+                "SELECT quantity FROM products WHERE id = ?1",
+                [&component.component_id],
+                |row| row.get(0),
+            )?;
+            if available < needed {
+                return Err(Error::InsufficientStock {
+                    product_id: component.component_id.clone(),
+                    available,
+                    requested: needed,
+                });
+            }
+            tx.execute(
+                "UPDATE products SET quantity = ROUND(quantity - ?1, 6), version = version + 1 WHERE id = ?2",
+                params![needed, component.component_id],
+            )?;
+        }
+        tx.execute(
+            "UPDATE products SET quantity = ROUND(quantity + ?1, 6), version = version + 1, updated_at = ?2 WHERE id = ?3",
+            params![count, Utc::now().timestamp(), bundle_id],
+        )?;
+
+        tx.commit()?;
+        Ok(())
+    }
+
+    fn set_exchange_rate(&self, rate: &ExchangeRate) -> Result<()> {
+        rate.validate()?;
+        self.conn.execute(
+            "INSERT INTO exchange_rates (currency, effective_from, rate) VALUES (?1, ?2, ?3)
+             ON CONFLICT(currency, effective_from) DO UPDATE SET rate = excluded.rate",
+            params![rate.currency, rate.effective_from, rate.rate],
+        )?;
+        Ok(())
+    }
+
+    fn get_exchange_rates(&self) -> Result<Vec<ExchangeRate>> {
+        let mut stmt = self.conn.prepare(
+            "SELECT currency, rate, effective_from FROM exchange_rates ORDER BY effective_from DESC, currency"
+        )?;
+        let rates = stmt.query_map([], |row| {
+            Ok(ExchangeRate {
+                currency: row.get(0)?,
+                rate: row.get(1)?,
+                effective_from: row.get(2)?,
             })
         })?;
+        Ok(rates.collect::<rusqlite::Result<_>>()?)
+    }
 
-        managers.collect()
+    fn save_login_state(&self, manager_id: &str, failed_attempts: u32, locked_until: Option<i64>) -> Result<()> {
+        self.conn.execute(
+            "UPDATE managers SET failed_attempts = ?1, locked_until = ?2 WHERE id = ?3",
+            params![failed_attempts, locked_until, manager_id],
+        )?;
+        Ok(())
     }
 
-    pub fn update_manager_status(&self, manager_id: &str, is_active: bool) -> Result<()> {
+}
+
+// Price history and scheduled price changes
+impl Database {
+    fn record_price_change(
+        &self,
+        product_id: &str,
+        old_price: Option<f64>,
+        new_price: f64,
+        changed_at: i64,
+        reason: &str,
+    ) -> Result<()> {
         self.conn.execute(
-            "UPDATE managers SET is_active = ?1 WHERE id = ?2",
-            params![is_active as i32, manager_id],
+            "INSERT INTO price_history (product_id, old_price, new_price, changed_at, reason)
+             VALUES (?1, ?2, ?3, ?4, ?5)",
+            params![product_id, old_price, new_price, changed_at, reason],
         )?;
         Ok(())
     }
 
-    pub fn authenticate_manager(&self, username: &str, password: &str) -> Result<Option<Manager>> {
+    /// Sets the list price outside of a product edit and records why it changed.
+    fn set_price(&self, product_id: &str, price: f64, at: i64, reason: &str) -> Result<f64> {
+        let old_price: f64 = self.conn
+            .query_row("SELECT price FROM products WHERE id = ?1", [product_id], |row| row.get(0))
+            .optional()?
+            .ok_or_else(|| Error::not_found("Product", product_id))?;
+
+        self.conn.execute(
+            "UPDATE products SET price = ?1, updated_at = ?2, version = version + 1 WHERE id = ?3",
+            params![price, at, product_id],
+        )?;
+        self.record_price_change(product_id, Some(old_price), price, at, reason)?;
+        Ok(old_price)
+    }
+
+    pub fn schedule_price_change(&self, schedule: &ScheduledPrice) -> Result<()> {
+        schedule.validate()?;
+        if self.get_product(&schedule.product_id)?.is_none() {
+            return Err(Error::not_found("Product", &schedule.product_id));
+        }
+
+        self.conn.execute(
+            "INSERT INTO scheduled_prices (id, product_id, kind, price, starts_at, ends_at,
+                                           previous_price, status, created_at)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9)",
+            params![
+                schedule.id,
+                schedule.product_id,
+                schedule.kind.as_str(),
+                schedule.price,
+                schedule.starts_at,
+                schedule.ends_at,
+                schedule.previous_price,
+                schedule.status.as_str(),
+                schedule.created_at
+            ],
+        )?;
+        Ok(())
+    }
+
+    /// Pending and active schedules, soonest first. Pass a product id to narrow the list.
+    pub fn get_open_price_schedules(&self, product_id: Option<&str>) -> Result<Vec<ScheduledPrice>> {
         let mut stmt = self.conn.prepare(
-            "SELECT id, username, password, full_name, created_at, is_active 
-             FROM managers WHERE username = ?1 AND password = ?2 AND is_active = 1"
+            "SELECT id, product_id, kind, price, starts_at, ends_at, previous_price, status, created_at
+             FROM scheduled_prices
+             WHERE status IN ('pending', 'active') AND (?1 IS NULL OR product_id = ?1)
+             ORDER BY starts_at"
         )?;
-        
-        let manager = stmt.query_row([username, password], |row| {
-            Ok(Manager {
+
+        let schedules = stmt.query_map([product_id], |row| {
+            let kind: String = row.get(2)?;
+            let status: String = row.get(7)?;
+            Ok(ScheduledPrice {
                 id: row.get(0)?,
-                username: row.get(1)?,
-                password: row.get(2)?,
-                full_name: row.get(3)?,
-                created_at: row.get(4)?,
-                is_active: row.get::<_, i32>(5)? == 1,
+                product_id: row.get(1)?,
+                kind: ScheduleKind::parse(&kind).unwrap_or(ScheduleKind::Permanent),
+                price: row.get(3)?,
+                starts_at: row.get(4)?,
+                ends_at: row.get(5)?,
+                previous_price: row.get(6)?,
+                status: ScheduleStatus::parse(&status).unwrap_or(ScheduleStatus::Pending),
+                created_at: row.get(8)?,
             })
-        }).optional()?;
+        })?;
+
+        Ok(schedules.collect::<rusqlite::Result<_>>()?)
+    }
+
+    pub fn cancel_price_schedule(&self, schedule_id: &str) -> Result<()> {
+        let updated = self.conn.execute(
+            "UPDATE scheduled_prices SET status = 'cancelled' WHERE id = ?1 AND status = 'pending'",
+            [schedule_id],
+        )?;
+        if updated == 0 {
+            return Err(Error::not_found("Pending price schedule", schedule_id));
+        }
+        Ok(())
+    }
+
+    /// Starts schedules whose start time has passed and ends markdowns whose end time has passed.
+    /// Returns the number of price changes applied.
+    pub fn apply_scheduled_prices(&self, now: i64) -> Result<usize> {
+        let mut applied = 0;
+
+        for schedule in self.get_open_price_schedules(None)? {
+            if schedule.status == ScheduleStatus::Pending && schedule.starts_at <= now {
+                let reason = match schedule.kind {
+                    ScheduleKind::Permanent => pricing::REASON_SCHEDULED,
+                    ScheduleKind::Markdown => pricing::REASON_MARKDOWN,
+                };
+                let previous = self.set_price(&schedule.product_id, schedule.price, schedule.starts_at, reason)?;
+                let status = match schedule.kind {
+                    ScheduleKind::Permanent => ScheduleStatus::Completed,
+                    ScheduleKind::Markdown => ScheduleStatus::Active,
+                };
+                self.conn.execute(
+                    "UPDATE scheduled_prices SET status = ?1, previous_price = ?2 WHERE id = ?3",
+                    params![status.as_str(), previous, schedule.id],
+                )?;
+                applied += 1;
 
-        Ok(manager)
+                if status == ScheduleStatus::Active {
+                    applied += self.end_markdown_if_due(&schedule.id, now)?;
+                }
+            } else if schedule.status == ScheduleStatus::Active {
+                applied += self.end_markdown_if_due(&schedule.id, now)?;
+            }
+        }
+
+        Ok(applied)
     }
-} 
\ No newline at end of file
+
+    fn end_markdown_if_due(&self, schedule_id: &str, now: i64) -> Result<usize> {
+        let (product_id, price, ends_at, previous_price): (String, f64, Option<i64>, Option<f64>) = self.conn.query_row(
+            "SELECT product_id, price, ends_at, previous_price FROM scheduled_prices WHERE id = ?1",
+            [schedule_id],
+            |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?, row.get(3)?)),
+        )?;
+
+        let (ends_at, previous_price) = match (ends_at, previous_price) {
+            (Some(end), Some(previous)) if end <= now => (end, previous),
+            _ => return Ok(0),
+        };
+
+        // Leave the price alone if someone changed it manually during the markdown
+        let current: Option<f64> = self.get_product(&product_id)?.map(|p| p.price);
+        let reverted = if current == Some(price) {
+            self.set_price(&product_id, previous_price, ends_at, pricing::REASON_MARKDOWN_END)?;
+            1
+        } else {
+            0
+        };
+
+        self.conn.execute(
+            "UPDATE scheduled_prices SET status = 'completed' WHERE id = ?1",
+            [schedule_id],
+        )?;
+        Ok(reverted)
+    }
+
+    /// Units sold and revenue for each period a price was in effect, oldest first.
+    pub fn price_effect_report(&self, product_id: &str) -> Result<Vec<PricePeriod>> {
+        let history = self.get_price_history(product_id)?;
+        let mut periods = pricing::price_periods(&history);
+
+        // Returns and voids count against the period of the sale they undo
+        let mut stmt = self.conn.prepare(&format!(
+            "WITH {}, {}
+             SELECT COALESCE(SUM(quantity * (1 - returned_share)), 0), COALESCE(SUM(paid - refunded), 0)
+             FROM net_sales
+             WHERE product_id = ?1
+               AND sold_at >= ?2
+               AND (?3 IS NULL OR sold_at < ?3)",
+            PAID_SALES, NET_SALES
+        ))?;
+
+        for period in &mut periods {
+            let (units, revenue): (f64, f64) = stmt.query_row(
+                params![product_id, period.starts_at, period.ends_at],
+                |row| Ok((row.get(0)?, row.get(1)?)),
+            )?;
+            period.units_sold = units;
+            period.revenue = revenue;
+        }
+
+        Ok(periods)
+    }
+}
+
+/// Sale rows with their share of what was paid once the sale's loyalty discount
+/// is taken off, for use in a `WITH` clause.
+const PAID_SALES: &str = "paid_sales AS (
+    SELECT s.id, s.sale_id, s.product_id, s.quantity, s.sold_at,
+           s.total_price * COALESCE(MIN(1.0, h.total_amount / NULLIF(
+               (SELECT SUM(total_price) FROM sales WHERE sale_id = h.id), 0)), 1.0) AS paid
+    FROM sales s
+    LEFT JOIN sale_headers h ON h.id = s.sale_id
+)";
+
+/// `paid_sales` with the share of each row taken back by returns and the part of
+/// the refunds that falls on it. Returns of a product are shared between the
+/// rows of the sale that carry it.
+const NET_SALES: &str = "net_sales AS (
+    SELECT s.id, s.product_id, s.sold_at, s.quantity, s.paid,
+           (SELECT COALESCE(SUM(quantity), 0) FROM sale_returns WHERE sale_id = s.sale_id AND product_id = s.product_id)
+               / (SELECT SUM(quantity) FROM sales WHERE sale_id = s.sale_id AND product_id = s.product_id) AS returned_share,
+           (SELECT COALESCE(SUM(refund), 0) FROM sale_returns WHERE sale_id = s.sale_id AND product_id = s.product_id)
+               * s.quantity / (SELECT SUM(quantity) FROM sales WHERE sale_id = s.sale_id AND product_id = s.product_id) AS refunded
+    FROM paid_sales s
+)";
+
+const MANAGER_COLUMNS: &str =
+    "id, username, password, full_name, created_at, is_active, failed_attempts, locked_until, must_change_password, removed_at";
+
+// Notification delivery log
+impl Database {
+    pub fn log_deliveries(&self, deliveries: &[Delivery]) -> Result<()> {
+        for delivery in deliveries {
+            self.conn.execute(
+                "INSERT INTO notification_log (event, subject, sink, attempts, delivered, error, created_at)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+                params![
+                    delivery.event.as_str(),
+                    delivery.subject,
+                    delivery.sink,
+                    delivery.attempts,
+                    delivery.delivered,
+                    delivery.error,
+                    delivery.created_at,
+                ],
+            )?;
+        }
+        Ok(())
+    }
+
+    /// The most recent deliveries, newest first.
+    pub fn get_delivery_log(&self, limit: usize) -> Result<Vec<Delivery>> {
+        let mut stmt = self.conn.prepare(
+            "SELECT event, subject, sink, attempts, delivered, error, created_at
+             FROM notification_log ORDER BY id DESC LIMIT ?1",
+        )?;
+        let rows = stmt.query_map([limit as i64], |row| {
+            let event: String = row.get(0)?;
+            Ok(Delivery {
+                event: EventKind::parse(&event).ok_or_else(|| {
+                    rusqlite::Error::FromSqlConversionFailure(0, rusqlite::types::Type::Text, event.into())
+                })?,
+                subject: row.get(1)?,
+                sink: row.get(2)?,
+                attempts: row.get(3)?,
+                delivered: row.get(4)?,
+                error: row.get(5)?,
+                created_at: row.get(6)?,
+            })
+        })?;
+        Ok(rows.collect::<rusqlite::Result<_>>()?)
+    }
+}
+
+// Customer orders
+impl Database {
+    /// Places `order`, allocating stock to it. Reservations and layaways need the
+    /// whole quantity free; a backorder takes what is free and waits for the rest.
+    pub fn place_order(&mut self, order: &CustomerOrder) -> Result<CustomerOrder> {
+        order.validate()?;
+        let tx = self.conn.transaction_with_behavior(TransactionBehavior::Immediate)?;
+
+        let (stock, archived): (f64, bool) = tx
+            .query_row(
+                "SELECT quantity, archived_at IS NOT NULL FROM products WHERE id = ?1",
+                [&order.product_id],
+                |row| Ok((row.get(0)?, row.get(1)?)),
+            )
+            .optional()?
+            .ok_or_else(|| Error::not_found("Product", &order.product_id))?;
+        if archived {
+            return Err(Error::conflict(format!("Product '{}' is archived", order.product_id)));
+        }
+        let free = unit::round_quantity(stock - reserved_quantity(&tx, &order.product_id)?).max(0.0);
+        let allocated = match order.kind {
+            OrderKind::Backorder => free.min(order.quantity),
+            _ if free < order.quantity => {
+                return Err(Error::InsufficientStock {
+                    product_id: order.product_id.clone(),
+                    available: free,
+                    requested: order.quantity,
+                });
+            }
+            _ => order.quantity,
+        };
+
+        let order_number: i64 = tx.query_row(
+            "SELECT COALESCE(MAX(order_number), 0) + 1 FROM customer_orders",
+            [],
+            |row| row.get(0),
+        )?;
+        tx.execute(
+            "INSERT INTO customer_orders (id, order_number, kind, customer, product_id, quantity, allocated,
+                                          unit_price, paid, status, created_at)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, 'open', ?10)",
+            params![
+                order.id,
+                order_number,
+                order.kind.as_str(),
+                order.customer.trim(),
+                order.product_id,
+                order.quantity,
+                allocated,
+                order.unit_price,
+                order.paid,
+                order.created_at,
+            ],
+        )?;
+        if order.paid > 0.0 {
+            tx.execute(
+                "INSERT INTO order_deposits (order_id, amount, paid_at) VALUES (?1, ?2, ?3)",
+                params![order.id, order.paid, order.created_at],
+            )?;
+        }
+        tx.commit()?;
+
+        self.open_order(order_number)
+    }
+
+    pub fn get_order(&self, order_number: i64) -> Result<Option<CustomerOrder>> {
+        Ok(self.query_orders("WHERE order_number = ?1", [order_number])?.pop())
+    }
+
+    /// Open orders, oldest first.
+    pub fn open_orders(&self) -> Result<Vec<CustomerOrder>> {
+        self.query_orders("WHERE status = 'open' ORDER BY created_at, order_number", [])
+    }
+
+    fn query_orders(&self, filter: &str, params: impl rusqlite::Params) -> Result<Vec<CustomerOrder>> {
+        let mut stmt = self.conn.prepare(&format!(
+            "SELECT id, order_number, kind, customer, product_id, quantity, allocated, unit_price, paid, status,
+                    created_at, closed_at, sale_number
+             FROM customer_orders {}",
+            filter
+        ))?;
+        let rows = stmt.query_map(params, |row| {
+            Ok((
+                row.get::<_, String>(2)?,
+                row.get::<_, String>(9)?,
+                CustomerOrder {
+                    id: row.get(0)?,
+                    order_number: row.get(1)?,
+                    kind: OrderKind::Reservation,
+                    customer: row.get(3)?,
+                    product_id: row.get(4)?,
+                    quantity: row.get(5)?,
+                    allocated: row.get(6)?,
+                    unit_price: row.get(7)?,
+                    paid: row.get(8)?,
+                    status: OrderStatus::Open,
+                    created_at: row.get(10)?,
+                    closed_at: row.get(11)?,
+                    sale_number: row.get(12)?,
+                },
+            ))
+        })?;
+        rows.map(|row| {
+            let (kind, status, mut order) = row?;
+            order.kind = OrderKind::parse(&kind)
+                .ok_or_else(|| Error::validation(format!("Unknown order kind '{}'", kind)))?;
+            order.status = OrderStatus::parse(&status)
+                .ok_or_else(|| Error::validation(format!("Unknown order status '{}'", status)))?;
+            Ok(order)
+        })
+        .collect()
+    }
+
+    /// Takes a deposit towards an open order.
+    pub fn add_deposit(&self, order_number: i64, amount: f64) -> Result<CustomerOrder> {
+        let order = self.open_order(order_number)?;
+        if !amount.is_finite() || amount <= 0.0 {
+            return Err(Error::validation("Deposit must be positive"));
+        }
+        if amount > order.balance() + 0.005 {
+            return Err(Error::validation(format!("Only {} is left to pay", currency::format_base(order.balance()))));
+        }
+        let tx = self.conn.unchecked_transaction()?;
+        tx.execute(
+            "UPDATE customer_orders SET paid = ROUND(paid + ?1, 2) WHERE id = ?2",
+            params![amount, order.id],
+        )?;
+        tx.execute(
+            "INSERT INTO order_deposits (order_id, amount, paid_at) VALUES (?1, ?2, ?3)",
+            params![order.id, amount, Utc::now().timestamp()],
+        )?;
+        tx.commit()?;
+        self.open_order(order_number)
+    }
+
+    /// Deposits taken for an order as (amount, paid at), oldest first.
+    pub fn get_deposits(&self, order_number: i64) -> Result<Vec<(f64, i64)>> {
+        let mut stmt = self.conn.prepare(
+            "SELECT d.amount, d.paid_at FROM order_deposits d
+             JOIN customer_orders o ON o.id = d.order_id
+             WHERE o.order_number = ?1
+             ORDER BY d.id",
+        )?;
+        let deposits = stmt.query_map([order_number], |row| Ok((row.get(0)?, row.get(1)?)))?;
+        Ok(deposits.collect::<rusqlite::Result<_>>()?)
+    }
+
+    /// Hands the goods over, turning the order's stock into a sale at the agreed
+    /// price. Returns the sale number.
+    pub fn fulfil_order(&mut self, order_number: i64) -> Result<i64> {
+        let order = self.open_order(order_number)?;
+        if let Some(blocker) = order.collect_blocker() {
+            return Err(Error::conflict(format!("Order #{} cannot be collected: {}", order_number, blocker)));
+        }
+        let sale = Sale::new(vec![SaleItem {
+            product_id: order.product_id.clone(),
+            quantity: order.quantity,
+            unit: None,
+            unit_price: order.unit_price,
+            total_price: order.total(),
+        }]);
+
+        let tx = self.conn.transaction_with_behavior(TransactionBehavior::Immediate)?;
+        // Close the order first so the stock it holds is free for the sale
+        let closed = tx.execute(
+            "UPDATE customer_orders SET status = 'fulfilled', closed_at = ?1 WHERE id = ?2 AND status = 'open'",
+            params![sale.timestamp, order.id],
+        )?;
+        if closed == 0 {
+            return Err(Error::conflict(format!("Order #{} is no longer open", order_number)));
+        }
+        let sale_number = insert_sale(&tx, &sale, &[order.quantity])?;
+        tx.execute(
+            "UPDATE customer_orders SET sale_number = ?1 WHERE id = ?2",
+            params![sale_number, order.id],
+        )?;
+        tx.commit()?;
+        Ok(sale_number)
+    }
+
+    /// Cancels an open order and passes its stock on to waiting backorders.
+    /// Refunding any deposits is left to the caller.
+    pub fn cancel_order(&mut self, order_number: i64) -> Result<CustomerOrder> {
+        let order = self.open_order(order_number)?;
+        let tx = self.conn.transaction_with_behavior(TransactionBehavior::Immediate)?;
+        tx.execute(
+            "UPDATE customer_orders SET status = 'cancelled', closed_at = ?1 WHERE id = ?2",
+            params![Utc::now().timestamp(), order.id],
+        )?;
+        allocate_backorders(&tx, &order.product_id)?;
+        tx.commit()?;
+        self.get_order(order_number)?.ok_or_else(|| Error::not_found("Order", order_number.to_string()))
+    }
+
+    fn open_order(&self, order_number: i64) -> Result<CustomerOrder> {
+        let order = self
+            .get_order(order_number)?
+            .ok_or_else(|| Error::not_found("Order", order_number.to_string()))?;
+        if !order.is_open() {
+            return Err(Error::conflict(format!("Order #{} is {}", order_number, order.status.as_str())));
+        }
+        Ok(order)
+    }
+}
+
+// Point of sale
+impl Database {
+    /// Opens `shift`; a manager can only have one shift open at a time.
+    pub fn open_shift(&self, shift: &Shift) -> Result<()> {
+        shift.validate()?;
+        if self.current_shift(&shift.manager_id)?.is_some() {
+            return Err(Error::conflict("A shift is already open; close it with a Z report first"));
+        }
+        self.conn.execute(
+            "INSERT INTO shifts (id, manager_id, opened_at, opening_float, closed_at) VALUES (?1, ?2, ?3, ?4, NULL)",
+            params![shift.id, shift.manager_id, shift.opened_at, shift.opening_float],
+        )?;
+        Ok(())
+    }
+
+    pub fn current_shift(&self, manager_id: &str) -> Result<Option<Shift>> {
+        Ok(self.query_shifts("WHERE manager_id = ?1 AND closed_at IS NULL", [manager_id])?.pop())
+    }
+
+    pub fn get_shift(&self, id: &str) -> Result<Option<Shift>> {
+        Ok(self.query_shifts("WHERE id = ?1", [id])?.pop())
+    }
+
+    /// The most recent shifts, newest first.
+    pub fn get_shifts(&self, limit: usize) -> Result<Vec<Shift>> {
+        self.query_shifts("ORDER BY opened_at DESC, rowid DESC LIMIT ?1", [limit as i64])
+    }
+
+    fn query_shifts(&self, filter: &str, params: impl rusqlite::Params) -> Result<Vec<Shift>> {
+        let mut stmt = self.conn.prepare(&format!(
+            "SELECT id, manager_id, opened_at, opening_float, closed_at FROM shifts {}",
+            filter
+        ))?;
+        let shifts = stmt.query_map(params, |row| {
+            Ok(Shift {
+                id: row.get(0)?,
+                manager_id: row.get(1)?,
+                opened_at: row.get(2)?,
+                opening_float: row.get(3)?,
+                closed_at: row.get(4)?,
+            })
+        })?;
+        Ok(shifts.collect::<rusqlite::Result<_>>()?)
+    }
+
+    /// Records `sale` in an open shift together with how it was paid. Returns the
+    /// sale number and the settlement, including any change due.
+    pub fn record_pos_sale(&mut self, sale: &Sale, shift_id: &str, payments: &[Payment]) -> Result<(i64, Settlement)> {
+        let settlement = pos::settle(sale.total_amount, payments)?;
+        let base_quantities = sale
+            .items
+            .iter()
+            .map(|item| self.to_base_quantity(&item.product_id, item.unit.as_deref(), item.quantity))
+            .collect::<Result<Vec<f64>>>()?;
+
+        let tx = self.conn.transaction_with_behavior(TransactionBehavior::Immediate)?;
+        let closed_at: Option<i64> = tx
+            .query_row("SELECT closed_at FROM shifts WHERE id = ?1", [shift_id], |row| row.get(0))
+            .optional()?
+            .ok_or_else(|| Error::not_found("Shift", shift_id))?;
+        if closed_at.is_some() {
+            return Err(Error::conflict("The shift is closed; open a new one to take payments"));
+        }
+
+        let sale_number = insert_sale(&tx, sale, &base_quantities)?;
+        tx.execute(
+            "UPDATE sale_headers SET shift_id = ?1, change_given = ?2 WHERE id = ?3",
+            params![shift_id, settlement.change, sale.id],
+        )?;
+        for (payment, amount) in &settlement.payments {
+            tx.execute(
+                "INSERT INTO sale_payments (sale_id, method, tendered, amount) VALUES (?1, ?2, ?3, ?4)",
+                params![sale.id, payment.method.as_str(), payment.tendered, amount],
+            )?;
+        }
+        tx.commit()?;
+        Ok((sale_number, settlement))
+    }
+
+    /// Payments kept against a sale, as recorded at the till.
+    pub fn get_sale_payments(&self, sale_number: i64) -> Result<Vec<(Payment, f64)>> {
+        let mut stmt = self.conn.prepare(
+            "SELECT p.method, p.tendered, p.amount
+             FROM sale_payments p
+             JOIN sale_headers h ON h.id = p.sale_id
+             WHERE h.sale_number = ?1
+             ORDER BY p.id",
+        )?;
+        let payments = stmt.query_map([sale_number], |row| {
+            let method: String = row.get(0)?;
+            Ok((method, row.get::<_, f64>(1)?, row.get::<_, f64>(2)?))
+        })?;
+        payments
+            .map(|payment| {
+                let (method, tendered, amount) = payment?;
+                let method = PaymentMethod::parse(&method)
+                    .ok_or_else(|| Error::validation(format!("Unknown payment method '{}'", method)))?;
+                Ok((Payment { method, tendered }, amount))
+            })
+            .collect()
+    }
+
+    /// The X report of an open shift, or the stored Z report of a closed one.
+    pub fn shift_report(&self, shift_id: &str) -> Result<ShiftReport> {
+        let shift = self.get_shift(shift_id)?.ok_or_else(|| Error::not_found("Shift", shift_id))?;
+        let manager: String = self
+            .conn
+            .query_row("SELECT full_name FROM managers WHERE id = ?1", [&shift.manager_id], |row| row.get(0))
+            .optional()?
+            .unwrap_or_else(|| shift.manager_id.clone());
+        let (sales, total_sales, change_given): (i64, f64, f64) = self.conn.query_row(
+            "SELECT COUNT(*), COALESCE(SUM(total_amount), 0), COALESCE(SUM(change_given), 0)
+             FROM sale_headers h
+             WHERE shift_id = ?1
+               AND NOT EXISTS (SELECT 1 FROM voids v WHERE v.kind = 'sale' AND v.reference = CAST(h.sale_number AS TEXT))",
+            [shift_id],
+            |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)),
+        )?;
+        let refunds: f64 = self.conn.query_row(
+            "SELECT COALESCE(SUM(r.refund), 0)
+             FROM sale_returns r
+             JOIN sale_headers h ON h.id = r.sale_id
+             WHERE r.shift_id = ?1
+               AND NOT EXISTS (SELECT 1 FROM voids v WHERE v.kind = 'sale' AND v.reference = CAST(h.sale_number AS TEXT))",
+            [shift_id],
+            |row| row.get(0),
+        )?;
+
+        let lines = if shift.is_open() {
+            self.expected_takings(&shift)?
+                .into_iter()
+                .map(|(method, expected)| TenderLine { method, expected, counted: None })
+                .collect()
+        } else {
+            let mut stmt = self.conn.prepare(
+                "SELECT method, expected, counted FROM shift_counts WHERE shift_id = ?1",
+            )?;
+            let counts = stmt
+                .query_map([shift_id], |row| Ok((row.get::<_, String>(0)?, row.get::<_, f64>(1)?, row.get::<_, f64>(2)?)))?
+                .collect::<rusqlite::Result<Vec<_>>>()?;
+            PaymentMethod::ALL
+                .iter()
+                .filter_map(|method| {
+                    counts.iter().find(|(m, ..)| m == method.as_str()).map(|(_, expected, counted)| TenderLine {
+                        method: *method,
+                        expected: *expected,
+                        counted: Some(*counted),
+                    })
+                })
+                .collect()
+        };
+
+        Ok(ShiftReport {
+            kind: if shift.is_open() { ReportKind::X } else { ReportKind::Z },
+            shift,
+            manager,
+            sales,
+            total_sales,
+            change_given,
+            refunds: (refunds * 100.0).round() / 100.0,
+            lines,
+        })
+    }
+
+    /// Closes an open shift with the takings counted per payment method (missing
+    /// methods count as zero) and returns its Z report.
+    pub fn close_shift(&mut self, shift_id: &str, counted: &[(PaymentMethod, f64)]) -> Result<ShiftReport> {
+        if counted.iter().any(|(_, amount)| !amount.is_finite() || *amount < 0.0) {
+            return Err(Error::validation("Counted amounts cannot be negative"));
+        }
+        let shift = self.get_shift(shift_id)?.ok_or_else(|| Error::not_found("Shift", shift_id))?;
+        if !shift.is_open() {
+            return Err(Error::conflict("The shift is already closed"));
+        }
+        let expected = self.expected_takings(&shift)?;
+
+        let tx = self.conn.transaction_with_behavior(TransactionBehavior::Immediate)?;
+        let closed = tx.execute(
+            "UPDATE shifts SET closed_at = ?1 WHERE id = ?2 AND closed_at IS NULL",
+            params![Utc::now().timestamp(), shift_id],
+        )?;
+        if closed == 0 {
+            return Err(Error::conflict("The shift is already closed"));
+        }
+        for (method, expected) in expected {
+            let counted: f64 = counted.iter().filter(|(m, _)| *m == method).map(|(_, amount)| amount).sum();
+            tx.execute(
+                "INSERT INTO shift_counts (shift_id, method, expected, counted) VALUES (?1, ?2, ?3, ?4)",
+                params![shift_id, method.as_str(), expected, counted],
+            )?;
+        }
+        tx.commit()?;
+
+        self.shift_report(shift_id)
+    }
+
+    /// What each payment method should hold: the float plus cash kept, and card
+    /// takings, less the refunds paid out of the shift in that tender. Voided
+    /// sales drop out together with any refunds given against them.
+    fn expected_takings(&self, shift: &Shift) -> Result<Vec<(PaymentMethod, f64)>> {
+        let mut stmt = self.conn.prepare(
+            "SELECT (SELECT COALESCE(SUM(p.amount), 0)
+                     FROM sale_payments p
+                     JOIN sale_headers h ON h.id = p.sale_id
+                     WHERE h.shift_id = ?1 AND p.method = ?2
+                       AND NOT EXISTS (SELECT 1 FROM voids v WHERE v.kind = 'sale' AND v.reference = CAST(h.sale_number AS TEXT)))
+                  - (SELECT COALESCE(SUM(r.refund), 0)
+                     FROM sale_returns r
+                     JOIN sale_headers h ON h.id = r.sale_id
+                     WHERE r.shift_id = ?1 AND r.method = ?2
+                       AND NOT EXISTS (SELECT 1 FROM voids v WHERE v.kind = 'sale' AND v.reference = CAST(h.sale_number AS TEXT)))",
+        )?;
+        PaymentMethod::ALL
+            .iter()
+            .map(|method| {
+                let taken: f64 = stmt.query_row(params![shift.id, method.as_str()], |row| row.get(0))?;
+                let float = if *method == PaymentMethod::Cash { shift.opening_float } else { 0.0 };
+                Ok((*method, ((float + taken) * 100.0).round() / 100.0))
+            })
+            .collect()
+    }
+}
+
+// Inventory analytics
+impl Database {
+    /// Sales and purchase totals of every active product for `range`. Sales are
+    /// net of returns and revenue is what was paid after loyalty discounts.
+    pub fn product_activity(&self, range: &DateRange) -> Result<Vec<ProductActivity>> {
+        // Returns count on the day they were taken back, so the stock rebuilt from
+        // these movements matches what was on the shelf
+        let mut stmt = self.conn.prepare(&format!(
+            "WITH {}
+             SELECT p.id, p.name, p.created_at, p.quantity, p.price,
+                    (SELECT SUM(total_cost * exchange_rate) / SUM(quantity) FROM purchases WHERE product_id = p.id),
+                    (SELECT COALESCE(SUM(quantity), 0) FROM paid_sales
+                     WHERE product_id = p.id AND (?1 IS NULL OR sold_at >= ?1) AND (?2 IS NULL OR sold_at < ?2))
+                    - (SELECT COALESCE(SUM(quantity), 0) FROM sale_returns
+                       WHERE product_id = p.id AND (?1 IS NULL OR returned_at >= ?1) AND (?2 IS NULL OR returned_at < ?2)),
+                    (SELECT COALESCE(SUM(paid), 0) FROM paid_sales
+                     WHERE product_id = p.id AND (?1 IS NULL OR sold_at >= ?1) AND (?2 IS NULL OR sold_at < ?2))
+                    - (SELECT COALESCE(SUM(refund), 0) FROM sale_returns
+                       WHERE product_id = p.id AND (?1 IS NULL OR returned_at >= ?1) AND (?2 IS NULL OR returned_at < ?2)),
+                    (SELECT COALESCE(SUM(quantity), 0) FROM purchases
+                     WHERE product_id = p.id AND (?1 IS NULL OR purchase_date >= ?1) AND (?2 IS NULL OR purchase_date < ?2)),
+                    (SELECT COALESCE(SUM(quantity), 0) FROM paid_sales
+                     WHERE product_id = p.id AND ?2 IS NOT NULL AND sold_at >= ?2)
+                    - (SELECT COALESCE(SUM(quantity), 0) FROM sale_returns
+                       WHERE product_id = p.id AND ?2 IS NOT NULL AND returned_at >= ?2),
+                    (SELECT COALESCE(SUM(quantity), 0) FROM purchases
+                     WHERE product_id = p.id AND ?2 IS NOT NULL AND purchase_date >= ?2),
+                    (SELECT MAX(s.sold_at) FROM paid_sales s
+                     WHERE s.product_id = p.id AND (?2 IS NULL OR s.sold_at < ?2)
+                       AND s.quantity > (SELECT COALESCE(SUM(r.quantity), 0) FROM sale_returns r
+                                         WHERE r.sale_id = s.sale_id AND r.product_id = s.product_id) + 1e-9)
+             FROM products p
+             WHERE p.archived_at IS NULL
+             ORDER BY p.name",
+            PAID_SALES
+        ))?;
+
+        let activity = stmt.query_map(params![range.from, range.to], |row| {
+            Ok(ProductActivity {
+                product_id: row.get(0)?,
+                name: row.get(1)?,
+                created_at: row.get(2)?,
+                quantity: row.get(3)?,
+                price: row.get(4)?,
+                unit_cost: row.get(5)?,
+                units_sold: row.get(6)?,
+                revenue: row.get(7)?,
+                units_purchased: row.get(8)?,
+                units_sold_after: row.get(9)?,
+                units_purchased_after: row.get(10)?,
+                last_sale_at: row.get(11)?,
+            })
+        })?;
+
+        Ok(activity.collect::<rusqlite::Result<_>>()?)
+    }
+}
+
+// Attachments
+impl Database {
+    /// Attaches `data` to a product or purchase. Content already stored under the
+    /// same hash is shared rather than stored again; attaching the same content
+    /// twice to one record is a conflict.
+    pub fn add_attachment(
+        &mut self,
+        owner_kind: OwnerKind,
+        owner_id: &str,
+        file_name: &str,
+        data: &[u8],
+        config: &AttachmentConfig,
+    ) -> Result<Attachment> {
+        let attachment = Attachment::new(owner_kind, owner_id.to_string(), file_name, data);
+        attachment.validate(config)?;
+
+        let tx = self.conn.transaction_with_behavior(TransactionBehavior::Immediate)?;
+        let (table, entity) = match owner_kind {
+            OwnerKind::Product => ("products", "Product"),
+            OwnerKind::Purchase => ("purchases", "Purchase"),
+        };
+        let owner_exists: bool = tx.query_row(
+            &format!("SELECT EXISTS(SELECT 1 FROM {} WHERE id = ?1)", table),
+            [owner_id],
+            |row| row.get(0),
+        )?;
+        if !owner_exists {
+            return Err(Error::not_found(entity, owner_id));
+        }
+        let existing: Option<String> = tx
+            .query_row(
+                "SELECT file_name FROM attachments WHERE owner_kind = ?1 AND owner_id = ?2 AND hash = ?3",
+                params![owner_kind.as_str(), owner_id, attachment.hash],
+                |row| row.get(0),
+            )
+            .optional()?;
+        if let Some(existing) = existing {
+            return Err(Error::conflict(format!("The same content is already attached as {}", existing)));
+        }
+
+        let stored: bool = tx.query_row(
+            "SELECT EXISTS(SELECT 1 FROM attachment_contents WHERE hash = ?1)",
+            [&attachment.hash],
+            |row| row.get(0),
+        )?;
+        if !stored {
+            let (blob, path) = match config.storage {
+                StorageKind::Database => (Some(data), None),
+                StorageKind::Files => {
+                    let path = config.path_for(&attachment.hash);
+                    write_content_file(&path, data)?;
+                    (None, Some(path.to_string_lossy().into_owned()))
+                }
+            };
+            tx.execute(
+                "INSERT INTO attachment_contents (hash, size, data, path, created_at) VALUES (?1, ?2, ?3, ?4, ?5)",
+                params![attachment.hash, attachment.size as i64, blob, path, attachment.created_at],
+            )?;
+        }
+        tx.execute(
+            "INSERT INTO attachments (id, owner_kind, owner_id, file_name, content_type, hash, size, created_at)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)",
+            params![
+                attachment.id,
+                owner_kind.as_str(),
+                owner_id,
+                attachment.file_name,
+                attachment.content_type,
+                attachment.hash,
+                attachment.size as i64,
+                attachment.created_at
+            ],
+        )?;
+        tx.commit()?;
+        Ok(attachment)
+    }
+
+    /// Attachments of one record, oldest first.
+    pub fn get_attachments(&self, owner_kind: OwnerKind, owner_id: &str) -> Result<Vec<Attachment>> {
+        self.query_attachments(
+            "WHERE owner_kind = ?1 AND owner_id = ?2 ORDER BY created_at, file_name",
+            params![owner_kind.as_str(), owner_id],
+        )
+    }
+
+    pub fn get_attachment(&self, id: &str) -> Result<Option<Attachment>> {
+        Ok(self.query_attachments("WHERE id = ?1", [id])?.pop())
+    }
+
+    fn query_attachments(&self, filter: &str, params: impl rusqlite::Params) -> Result<Vec<Attachment>> {
+        let mut stmt = self.conn.prepare(&format!(
+            "SELECT id, owner_kind, owner_id, file_name, content_type, size, hash, created_at FROM attachments {}",
+            filter
+        ))?;
+        let attachments = stmt.query_map(params, |row| {
+            Ok(Attachment {
+                id: row.get(0)?,
+                owner_kind: OwnerKind::parse(&row.get::<_, String>(1)?).unwrap_or(OwnerKind::Product),
+                owner_id: row.get(2)?,
+                file_name: row.get(3)?,
+                content_type: row.get(4)?,
+                size: row.get::<_, i64>(5)? as u64,
+                hash: row.get(6)?,
+                created_at: row.get(7)?,
+            })
+        })?;
+        Ok(attachments.collect::<rusqlite::Result<_>>()?)
+    }
+
+    /// The content of an attachment, checked against its hash so a damaged or
+    /// replaced file is reported rather than returned.
+    pub fn attachment_data(&self, id: &str) -> Result<Vec<u8>> {
+        let attachment = self.get_attachment(id)?.ok_or_else(|| Error::not_found("Attachment", id))?;
+        let (blob, path): (Option<Vec<u8>>, Option<String>) = self.conn.query_row(
+            "SELECT data, path FROM attachment_contents WHERE hash = ?1",
+            [&attachment.hash],
+            |row| Ok((row.get(0)?, row.get(1)?)),
+        )?;
+        let data = match (blob, path) {
+            (Some(blob), _) => blob,
+            (None, Some(path)) => std::fs::read(&path)?,
+            (None, None) => return Err(Error::not_found("Attachment content", &attachment.hash)),
+        };
+        if attachment::content_hash(&data) != attachment.hash {
+            return Err(Error::Io(std::io::Error::new(
+                std::io::ErrorKind::InvalidData,
+                format!("Stored content of {} does not match its hash", attachment.file_name),
+            )));
+        }
+        Ok(data)
+    }
+
+    /// Removes an attachment, and its content once nothing else refers to it.
+    pub fn remove_attachment(&mut self, id: &str) -> Result<()> {
+        let tx = self.conn.transaction()?;
+        let removed = tx.execute("DELETE FROM attachments WHERE id = ?1", [id])?;
+        if removed == 0 {
+            return Err(Error::not_found("Attachment", id));
+        }
+        let orphans = prune_attachment_contents(&tx)?;
+        tx.commit()?;
+        remove_content_files(&orphans);
+        Ok(())
+    }
+}
+
+// Store settings
+impl Database {
+    /// The settings file and environment with the values saved from the console
+    /// applied on top. Saved values that are no longer valid are returned as errors.
+    pub fn store_settings(&self) -> Result<(Settings, Vec<Error>)> {
+        let (mut settings, mut errors) = Settings::load();
+        errors.extend(settings.apply(&self.saved_settings()?));
+        Ok((settings, errors))
+    }
+
+    /// Values saved from the console, as (key, value).
+    pub fn saved_settings(&self) -> Result<Vec<(String, String)>> {
+        let mut stmt = self.conn.prepare("SELECT key, value FROM settings ORDER BY key")?;
+        let values = stmt.query_map([], |row| Ok((row.get(0)?, row.get(1)?)))?;
+        Ok(values.collect::<rusqlite::Result<_>>()?)
+    }
+
+    /// Validates `value` against the current settings and saves it, returning the
+    /// settings with it applied.
+    pub fn save_setting(&self, key: &str, value: &str) -> Result<Settings> {
+        let (mut settings, _) = self.store_settings()?;
+        let base_currency = settings.currency.clone();
+        settings.set(key, value)?;
+        self.check_currency_change(&base_currency, &settings.currency)?;
+        self.conn.execute(
+            "INSERT INTO settings (key, value, updated_at) VALUES (?1, ?2, ?3)
+             ON CONFLICT(key) DO UPDATE SET value = excluded.value, updated_at = excluded.updated_at",
+            params![key, value.trim(), Utc::now().timestamp()],
+        )?;
+        Ok(settings)
+    }
+
+    /// Drops the saved value of `key` so the file or environment value applies again.
+    pub fn reset_setting(&self, key: &str) -> Result<Settings> {
+        let base_currency = self.store_settings()?.0.currency;
+        let tx = self.conn.unchecked_transaction()?;
+        tx.execute("DELETE FROM settings WHERE key = ?1", [key])?;
+        let settings = self.store_settings()?.0;
+        self.check_currency_change(&base_currency, &settings.currency)?;
+        tx.commit()?;
+        Ok(settings)
+    }
+
+    /// Amounts are stored without their currency, so once money has been recorded
+    /// the base currency can no longer be changed from the console.
+    fn check_currency_change(&self, from: &str, to: &str) -> Result<()> {
+        if from == to {
+            return Ok(());
+        }
+        let recorded: bool = self.conn.query_row(
+            "SELECT EXISTS (SELECT 1 FROM sales) OR EXISTS (SELECT 1 FROM purchases)
+                 OR EXISTS (SELECT 1 FROM exchange_rates) OR EXISTS (SELECT 1 FROM customer_orders)",
+            [],
+            |row| row.get(0),
+        )?;
+        if recorded {
+            return Err(Error::conflict(format!(
+                "Sales and purchases are already recorded in {}; the base currency can no longer be changed",
+                from
+            )));
+        }
+        Ok(())
+    }
+}
+
+// Product search
+impl Database {
+    /// Products matching `query`, best first, archived ones included. Every term
+    /// must match a word in the name, description, SKU or tags, either as a
+    /// prefix or within `search::max_typos` edits.
+    pub fn search_products(&self, query: &str, limit: usize) -> Result<Vec<Product>> {
+        let terms = search::tokenize(query);
+        if terms.is_empty() {
+            return Ok(Vec::new());
+        }
+        let candidates = terms
+            .iter()
+            .map(|term| self.correction_candidates(term))
+            .collect::<Result<Vec<_>>>()?;
+        let exact: Vec<(String, Vec<&str>)> = terms.iter().map(|term| (term.clone(), Vec::new())).collect();
+        let tolerant: Vec<(String, Vec<&str>)> = terms
+            .iter()
+            .zip(&candidates)
+            .map(|(term, candidates)| (term.clone(), search::corrections(term, candidates)))
+            .collect();
+
+        // Name, SKU and tag hits outweigh a mention in the description
+        let mut stmt = self.conn.prepare(
+            "SELECT product_id FROM product_search
+             WHERE product_search MATCH ?1
+             ORDER BY product_id IN (SELECT product_id FROM product_search WHERE product_search MATCH ?2) DESC,
+                      bm25(product_search, 0.0, 10.0, 1.0, 8.0, 5.0)
+             LIMIT ?3",
+        )?;
+        let ids = stmt
+            .query_map(
+                params![search::match_expression(&tolerant), search::match_expression(&exact), limit as i64],
+                |row| row.get::<_, String>(0),
+            )?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+
+        let mut products = Vec::with_capacity(ids.len());
+        for id in ids {
+            products.extend(self.query_products("WHERE id = ?1", [&id])?);
+        }
+        Ok(products)
+    }
+
+    /// Indexed words that could be within `term`'s typo allowance: close enough in
+    /// length and containing one of its `search::anchors`. Only these are compared
+    /// edit by edit, so a search never reads the whole vocabulary.
+    fn correction_candidates(&self, term: &str) -> Result<Vec<String>> {
+        let allowed = search::max_typos(term);
+        if allowed == 0 {
+            return Ok(Vec::new());
+        }
+        let length = term.chars().count();
+        let anchors = search::anchors(term);
+        let mut stmt = self.conn.prepare(&format!(
+            "SELECT term FROM product_search_vocab
+             WHERE length(term) BETWEEN ?1 AND ?2 AND ({})",
+            vec!["instr(term, ?) > 0"; anchors.len()].join(" OR ")
+        ))?;
+        let values = [Value::Integer((length - allowed) as i64), Value::Integer((length + allowed) as i64)]
+            .into_iter()
+            .chain(anchors.into_iter().map(Value::Text));
+        let words = stmt
+            .query_map(params_from_iter(values), |row| row.get::<_, String>(0))?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+        Ok(words)
+    }
+}
+
+// Demand forecasting
+impl Database {
+    /// Daily units sold of every active product over the `days` full days before
+    /// `now`'s day, with days running midnight to midnight in `tz`. Units later
+    /// returned are taken off the day they were sold.
+    pub fn daily_demand(&self, days: usize, now: i64, tz: Tz) -> Result<Vec<DemandSeries>> {
+        let today = timezone::local_date(now, tz);
+        let first_date = today - chrono::Days::new(days as u64);
+        let first_day = timezone::day_number(first_date);
+
+        let mut series: Vec<DemandSeries> = self
+            .get_products()?
+            .into_iter()
+            .map(|product| DemandSeries {
+                product_id: product.id,
+                name: product.name,
+                unit: product.unit,
+                on_hand: product.quantity,
+                first_day,
+                daily: vec![0.0; days],
+            })
+            .collect();
+
+        let mut stmt = self.conn.prepare(
+            "SELECT product_id, sold_at, quantity FROM sales WHERE sold_at >= ?1 AND sold_at < ?2
+             UNION ALL
+             SELECT r.product_id, h.created_at, -r.quantity
+             FROM sale_returns r
+             JOIN sale_headers h ON h.id = r.sale_id
+             WHERE h.created_at >= ?1 AND h.created_at < ?2",
+        )?;
+        let sales = stmt.query_map(
+            [timezone::start_of_day(first_date, tz), timezone::start_of_day(today, tz)],
+            |row| Ok((row.get::<_, String>(0)?, row.get::<_, i64>(1)?, row.get::<_, f64>(2)?)),
+        )?;
+        let positions: HashMap<String, usize> =
+            series.iter().enumerate().map(|(i, s)| (s.product_id.clone(), i)).collect();
+        for sale in sales {
+            let (product_id, sold_at, quantity) = sale?;
+            let day = timezone::day_number(timezone::local_date(sold_at, tz)) - first_day;
+            if let Some(&i) = positions.get(&product_id) {
+                if let Some(total) = series[i].daily.get_mut(day as usize) {
+                    *total += quantity;
+                }
+            }
+        }
+        Ok(series)
+    }
+}
+
+// Returns and stock adjustments
+impl Database {
+    /// Takes back `quantity` (in the stock unit) of `product_id` from sale
+    /// `sale_number`, restocking it and refunding its share of the line total.
+    /// Returns the refund.
+    pub fn record_return(&mut self, sale_number: i64, product_id: &str, quantity: f64, reason: &str) -> Result<f64> {
+        self.return_item(sale_number, product_id, quantity, reason, None)
+    }
+
+    /// Records a return like `record_return`, paying the refund out of the open
+    /// shift `shift_id` in `method` so the shift's expected takings drop by it.
+    pub fn record_pos_return(
+        &mut self,
+        sale_number: i64,
+        product_id: &str,
+        quantity: f64,
+        reason: &str,
+        shift_id: &str,
+        method: PaymentMethod,
+    ) -> Result<f64> {
+        self.return_item(sale_number, product_id, quantity, reason, Some((shift_id, method)))
+    }
+
+    fn return_item(
+        &mut self,
+        sale_number: i64,
+        product_id: &str,
+        quantity: f64,
+        reason: &str,
+        till: Option<(&str, PaymentMethod)>,
+    ) -> Result<f64> {
+        if !quantity.is_finite() || quantity <= 0.0 {
+            return Err(Error::validation("Return quantity must be positive"));
+        }
+        let now = Utc::now().timestamp();
+        let tx = self.conn.transaction_with_behavior(TransactionBehavior::Immediate)?;
+        if let Some((shift_id, _)) = till {
+            let closed_at: Option<i64> = tx
+                .query_row("SELECT closed_at FROM shifts WHERE id = ?1", [shift_id], |row| row.get(0))
+                .optional()?
+                .ok_or_else(|| Error::not_found("Shift", shift_id))?;
+            if closed_at.is_some() {
+                return Err(Error::conflict("The shift is closed; open a new one to give refunds"));
+            }
+        }
+
+        let (sale_id, sold_at): (String, i64) = tx
+            .query_row(
+                "SELECT id, created_at FROM sale_headers WHERE sale_number = ?1",
+                [sale_number],
+                |row| Ok((row.get(0)?, row.get(1)?)),
+            )
+            .optional()?
+            .ok_or_else(|| Error::not_found("Sale", sale_number.to_string()))?;
+        let (sold, total): (Option<f64>, Option<f64>) = tx.query_row(
+            "SELECT SUM(quantity), SUM(total_price) FROM sales WHERE sale_id = ?1 AND product_id = ?2",
+            params![sale_id, product_id],
+            |row| Ok((row.get(0)?, row.get(1)?)),
+        )?;
+        let (Some(sold), Some(total)) = (sold, total) else {
+            return Err(Error::not_found("Sale item", format!("{} in sale #{}", product_id, sale_number)));
+        };
+        let returned: f64 = tx.query_row(
+            "SELECT COALESCE(SUM(quantity), 0) FROM sale_returns WHERE sale_id = ?1 AND product_id = ?2",
+            params![sale_id, product_id],
+            |row| row.get(0),
+        )?;
+        let returnable = unit::round_quantity(sold - returned);
+        if quantity > returnable {
+            return Err(Error::validation(format!(
+                "Only {} of this item can still be returned from sale #{}",
+                returnable, sale_number
+            )));
+        }
+
+        let refund = (total * quantity / sold * paid_share(&tx, &sale_id)? * 100.0).round() / 100.0;
+        tx.execute(
+            "INSERT INTO sale_returns (sale_id, product_id, quantity, refund, unit_cost, reason, returned_at, shift_id, method)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9)",
+            params![
+                sale_id,
+                product_id,
+                quantity,
+                refund,
+                average_cost(&tx, product_id, sold_at)?,
+                reason.trim(),
+                now,
+                till.map(|(shift_id, _)| shift_id),
+                till.map(|(_, method)| method.as_str()),
+            ],
+        )?;
+        tx.execute(
+            "UPDATE products SET quantity = ROUND(quantity + ?1, 6), updated_at = ?2, version = version + 1
+             WHERE id = ?3",
+            params![quantity, now, product_id],
+        )?;
+        allocate_backorders(&tx, product_id)?;
+        reverse_loyalty(&tx, &sale_id, now)?;
+
+        tx.commit()?;
+        Ok(refund)
+    }
+
+    /// Quantity of `product_id` already returned from sale `sale_number`.
+    pub fn returned_quantity(&self, sale_number: i64, product_id: &str) -> Result<f64> {
+        Ok(self.conn.query_row(
+            "SELECT COALESCE(SUM(r.quantity), 0) FROM sale_returns r
+             JOIN sale_headers h ON h.id = r.sale_id
+             WHERE h.sale_number = ?1 AND r.product_id = ?2",
+            params![sale_number, product_id],
+            |row| row.get(0),
+        )?)
+    }
+
+    /// Changes the stock of `product_id` by `change` stock units and records why,
+    /// returning the new quantity.
+    pub fn adjust_stock(&mut self, product_id: &str, change: f64, reason: &str) -> Result<f64> {
+        if !change.is_finite() || change == 0.0 {
+            return Err(Error::validation("Adjustment must change the stock"));
+        }
+        if reason.trim().is_empty() {
+            return Err(Error::validation("A stock adjustment needs a reason"));
+        }
+        let now = Utc::now().timestamp();
+        let tx = self.conn.transaction_with_behavior(TransactionBehavior::Immediate)?;
+
+        let stock: f64 = tx
+            .query_row("SELECT quantity FROM products WHERE id = ?1", [product_id], |row| row.get(0))
+            .optional()?
+            .ok_or_else(|| Error::not_found("Product", product_id))?;
+        let quantity = unit::round_quantity(stock + change);
+        if quantity < 0.0 {
+            return Err(Error::validation(format!("Only {} in stock; stock cannot go below zero", stock)));
+        }
+
+        tx.execute(
+            "INSERT INTO stock_adjustments (product_id, quantity, unit_cost, reason, adjusted_at)
+             VALUES (?1, ?2, ?3, ?4, ?5)",
+            params![product_id, change, average_cost(&tx, product_id, now)?, reason.trim(), now],
+        )?;
+        tx.execute(
+            "UPDATE products SET quantity = ?1, updated_at = ?2, version = version + 1 WHERE id = ?3",
+            params![quantity, now, product_id],
+        )?;
+        if change > 0.0 {
+            allocate_backorders(&tx, product_id)?;
+        }
+
+        tx.commit()?;
+        Ok(quantity)
+    }
+}
+
+// Customers and loyalty
+impl Database {
+    /// Records a sale rung up for a loyalty customer the way `record_sale` records
+    /// any other, spending the points it redeems and crediting the points it earns.
+    /// Returns the sale number.
+    pub fn record_customer_sale(&mut self, sale: &Sale) -> Result<i64> {
+        if sale.customer_id.is_none() {
+            return Err(Error::validation("The sale is not for a loyalty customer"));
+        }
+        self.write_sale(sale)
+    }
+
+    pub fn add_customer(&self, customer: &Customer) -> Result<()> {
+        customer.validate()?;
+        if let Some(phone) = &customer.phone {
+            if self.find_customer_by_phone(phone)?.is_some() {
+                return Err(Error::conflict(format!("A customer with phone '{}' already exists", phone)));
+            }
+        }
+        self.conn.execute(
+            "INSERT INTO customers (id, name, phone, email, created_at) VALUES (?1, ?2, ?3, ?4, ?5)",
+            params![customer.id, customer.name, customer.phone, customer.email, customer.created_at],
+        )?;
+        Ok(())
+    }
+
+    pub fn get_customer(&self, id: &str) -> Result<Option<Customer>> {
+        Ok(self.query_customers("WHERE id = ?1", [id])?.pop())
+    }
+
+    pub fn find_customer_by_phone(&self, phone: &str) -> Result<Option<Customer>> {
+        Ok(self.query_customers("WHERE phone = ?1", [phone.trim()])?.pop())
+    }
+
+    /// Customers whose name, phone or email contains `query`, by name.
+    pub fn search_customers(&self, query: &str, limit: usize) -> Result<Vec<Customer>> {
+        self.query_customers(
+            "WHERE instr(lower(name), lower(?1)) > 0 OR instr(phone, ?1) > 0 OR instr(lower(email), lower(?1)) > 0
+             ORDER BY name COLLATE NOCASE LIMIT ?2",
+            params![query.trim(), limit as i64],
+        )
+    }
+
+    fn query_customers(&self, filter: &str, params: impl rusqlite::Params) -> Result<Vec<Customer>> {
+        let mut stmt = self
+            .conn
+            .prepare(&format!("SELECT id, name, phone, email, created_at FROM customers {}", filter))?;
+        let customers = stmt.query_map(params, |row| {
+            Ok(Customer {
+                id: row.get(0)?,
+                name: row.get(1)?,
+                phone: row.get(2)?,
+                email: row.get(3)?,
+                created_at: row.get(4)?,
+            })
+        })?;
+        Ok(customers.collect::<rusqlite::Result<_>>()?)
+    }
+
+    pub fn points_balance(&self, customer_id: &str) -> Result<i64> {
+        points_balance(&self.conn, customer_id)
+    }
+
+    /// Sales, lifetime value, points balance and tier of a customer.
+    pub fn customer_summary(&self, customer_id: &str, program: &LoyaltyProgram) -> Result<CustomerSummary> {
+        let customer = self.get_customer(customer_id)?.ok_or_else(|| Error::not_found("Customer", customer_id))?;
+        let sales: i64 = self.conn.query_row(
+            "SELECT COUNT(*) FROM sale_headers WHERE customer_id = ?1",
+            [customer_id],
+            |row| row.get(0),
+        )?;
+        let lifetime_value = lifetime_value(&self.conn, customer_id, "")?;
+        Ok(CustomerSummary {
+            customer,
+            sales: sales as usize,
+            lifetime_value,
+            points_balance: points_balance(&self.conn, customer_id)?,
+            tier: program.tier(lifetime_value).map(|tier| tier.name.clone()),
+        })
+    }
+
+    /// A customer's sales, newest first.
+    pub fn customer_history(&self, customer_id: &str) -> Result<Vec<CustomerSale>> {
+        let mut stmt = self.conn.prepare(
+            "SELECT h.sale_number, h.created_at, h.total_amount, h.discount,
+                    (SELECT COUNT(*) FROM sales s WHERE s.sale_id = h.id),
+                    (SELECT COALESCE(SUM(refund), 0) FROM sale_returns r WHERE r.sale_id = h.id),
+                    (SELECT COALESCE(SUM(points), 0) FROM loyalty_points l WHERE l.sale_id = h.id)
+             FROM sale_headers h
+             WHERE h.customer_id = ?1
+             ORDER BY h.sale_number DESC",
+        )?;
+        let sales = stmt.query_map([customer_id], |row| {
+            Ok(CustomerSale {
+                sale_number: row.get(0)?,
+                timestamp: row.get(1)?,
+                total: row.get(2)?,
+                discount: row.get(3)?,
+                items: row.get::<_, i64>(4)? as usize,
+                refunded: row.get(5)?,
+                points: row.get(6)?,
+            })
+        })?;
+        Ok(sales.collect::<rusqlite::Result<_>>()?)
+    }
+}
+
+// Manager accounts
+impl Database {
+    /// Removes a manager on behalf of `removed_by`. Nobody can remove themselves or
+    /// the last active manager. Open shifts are handed to `successor`, and a manager
+    /// who opened shifts or approved voids is retired rather than deleted so that
+    /// history keeps pointing at a real account.
+    pub fn remove_manager(&mut self, manager_id: &str, removed_by: &str, successor: Option<&str>) -> Result<ManagerRemoval> {
+        if manager_id == removed_by {
+            return Err(Error::conflict("You cannot remove your own account"));
+        }
+
+        let tx = self.conn.transaction_with_behavior(TransactionBehavior::Immediate)?;
+        let select = format!("SELECT {} FROM managers WHERE id = ?1", MANAGER_COLUMNS);
+        let manager = tx
+            .query_row(&select, [manager_id], manager_from_row)
+            .optional()?
+            .ok_or_else(|| Error::not_found("Manager", manager_id))?;
+        if manager.is_removed() {
+            return Err(Error::conflict(format!("'{}' has already been removed", manager.username)));
+        }
+        let others_active: i64 = tx.query_row(
+            "SELECT COUNT(*) FROM managers WHERE is_active = 1 AND removed_at IS NULL AND id != ?1",
+            [manager_id],
+            |row| row.get(0),
+        )?;
+        if others_active == 0 {
+            return Err(Error::conflict("At least one active manager must remain"));
+        }
+
+        let open_shifts: i64 = tx.query_row(
+            "SELECT COUNT(*) FROM shifts WHERE manager_id = ?1 AND closed_at IS NULL",
+            [manager_id],
+            |row| row.get(0),
+        )?;
+        if open_shifts > 0 {
+            let successor_id = successor.ok_or_else(|| {
+                Error::conflict(format!("'{}' has an open shift; choose a manager to take it over", manager.username))
+            })?;
+            let heir = tx
+                .query_row(&select, [successor_id], manager_from_row)
+                .optional()?
+                .ok_or_else(|| Error::not_found("Manager", successor_id))?;
+            if heir.id == manager.id || !heir.is_active || heir.is_removed() {
+                return Err(Error::validation("Open shifts can only be handed to another active manager"));
+            }
+            let heir_open: i64 = tx.query_row(
+                "SELECT COUNT(*) FROM shifts WHERE manager_id = ?1 AND closed_at IS NULL",
+                [&heir.id],
+                |row| row.get(0),
+            )?;
+            if heir_open > 0 {
+                return Err(Error::conflict(format!(
+                    "'{}' already has an open shift; close it with a Z report first",
+                    heir.username
+                )));
+            }
+            tx.execute(
+                "UPDATE shifts SET manager_id = ?1 WHERE manager_id = ?2 AND closed_at IS NULL",
+                params![heir.id, manager_id],
+            )?;
+        }
+
+        let history: i64 = tx.query_row(
+            "SELECT (SELECT COUNT(*) FROM shifts WHERE manager_id = ?1)
+                  + (SELECT COUNT(*) FROM voids WHERE manager_id = ?1)",
+            [manager_id],
+            |row| row.get(0),
+        )?;
+        let removal = if history == 0 {
+            tx.execute("DELETE FROM managers WHERE id = ?1", [manager_id])?;
+            ManagerRemoval::Deleted
+        } else {
+            tx.execute(
+                "UPDATE managers SET is_active = 0, removed_at = ?1, failed_attempts = 0, locked_until = NULL
+                 WHERE id = ?2",
+                params![Utc::now().timestamp(), manager_id],
+            )?;
+            ManagerRemoval::Retired { reassigned_shifts: open_shifts as usize }
+        };
+
+        tx.commit()?;
+        Ok(removal)
+    }
+}
+
+// Voids
+impl Database {
+    /// The most recent operation of `kind` that has not been voided and is not
+    /// itself a compensating record.
+    pub fn last_operation(&self, kind: OperationKind) -> Result<Option<Operation>> {
+        let found: Option<(String, String, i64)> = match kind {
+            OperationKind::Sale => self
+                .conn
+                .query_row(
+                    "SELECT h.sale_number, h.total_amount, h.created_at,
+                            (SELECT COUNT(*) FROM sales s WHERE s.sale_id = h.id)
+                     FROM sale_headers h
+                     WHERE NOT EXISTS (SELECT 1 FROM voids v
+                                       WHERE v.kind = 'sale' AND v.reference = CAST(h.sale_number AS TEXT))
+                     ORDER BY h.sale_number DESC LIMIT 1",
+                    [],
+                    |row| {
+                        let number: i64 = row.get(0)?;
+                        let description = format!(
+                            "Sale #{} of {} item(s) for {}",
+                            number,
+                            row.get::<_, i64>(3)?,
+                            currency::format_base(row.get(1)?)
+                        );
+                        Ok((number.to_string(), description, row.get(2)?))
+                    },
+                )
+                .optional()?,
+            OperationKind::Purchase => self
+                .conn
+                .query_row(
+                    "SELECT pu.id, pu.quantity, p.unit, p.name, pu.total_cost, COALESCE(pu.currency, ?1), pu.purchase_date
+                     FROM purchases pu
+                     JOIN products p ON p.id = pu.product_id
+                     WHERE pu.quantity > 0
+                       AND NOT EXISTS (SELECT 1 FROM voids v WHERE v.kind = 'purchase' AND v.reference = pu.id)
+                     ORDER BY pu.purchase_date DESC, pu.rowid DESC LIMIT 1",
+                    [currency::base_currency()],
+                    |row| {
+                        let description = format!(
+                            "Purchase of {} {} {} for {}",
+                            row.get::<_, f64>(1)?,
+                            row.get::<_, String>(2)?,
+                            row.get::<_, String>(3)?,
+                            currency::format_amount(row.get(4)?, &row.get::<_, String>(5)?)
+                        );
+                        Ok((row.get(0)?, description, row.get(6)?))
+                    },
+                )
+                .optional()?,
+            OperationKind::Adjustment => self
+                .conn
+                .query_row(
+                    "SELECT a.id, a.quantity, p.unit, p.name, a.reason, a.adjusted_at
+                     FROM stock_adjustments a
+                     JOIN products p ON p.id = a.product_id
+                     WHERE NOT EXISTS (SELECT 1 FROM voids v
+                                       WHERE v.kind = 'adjustment'
+                                         AND (v.reference = CAST(a.id AS TEXT) OR v.compensation = CAST(a.id AS TEXT)))
+                     ORDER BY a.id DESC LIMIT 1",
+                    [],
+                    |row| {
+                        let description = format!(
+                            "Adjustment of {:+} {} {} ({})",
+                            row.get::<_, f64>(1)?,
+                            row.get::<_, String>(2)?,
+                            row.get::<_, String>(3)?,
+                            row.get::<_, String>(4)?
+                        );
+                        Ok((row.get::<_, i64>(0)?.to_string(), description, row.get(5)?))
+                    },
+                )
+                .optional()?,
+        };
+
+        Ok(found.map(|(reference, description, timestamp)| Operation { kind, reference, description, timestamp }))
+    }
+
+    /// Voids the most recent operation of `kind` if it is still `reference` and
+    /// within `window_minutes`, writing the compensating records in the same
+    /// transaction.
+    pub fn void_operation(
+        &mut self,
+        kind: OperationKind,
+        reference: &str,
+        manager_id: &str,
+        reason: &str,
+        window_minutes: u32,
+    ) -> Result<VoidRecord> {
+        if reason.trim().is_empty() {
+            return Err(Error::validation("A void needs a reason"));
+        }
+        let now = Utc::now().timestamp();
+        let operation = self
+            .last_operation(kind)?
+            .ok_or_else(|| Error::not_found(kind.label(), reference))?;
+        if operation.reference != reference {
+            return Err(Error::conflict(format!(
+                "Only the most recent {} can be voided",
+                kind.label().to_lowercase()
+            )));
+        }
+        operation.ensure_voidable(window_minutes, now)?;
+
+        let tx = self.conn.transaction_with_behavior(TransactionBehavior::Immediate)?;
+        let note = format!("Void: {}", reason.trim());
+        let (compensation, refund) = match kind {
+            OperationKind::Sale => (None, void_sale(&tx, reference, &note, now)?),
+            OperationKind::Purchase => (Some(void_purchase(&tx, reference, now)?), 0.0),
+            OperationKind::Adjustment => (Some(void_adjustment(&tx, reference, &note, now)?), 0.0),
+        };
+        tx.execute(
+            "INSERT INTO voids (kind, reference, description, compensation, refund, manager_id, reason, voided_at)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)",
+            params![kind.as_str(), reference, operation.description, compensation, refund, manager_id, reason.trim(), now],
+        )?;
+        let id = tx.last_insert_rowid();
+        tx.commit()?;
+
+        Ok(VoidRecord {
+            id,
+            kind,
+            reference: reference.to_string(),
+            description: operation.description,
+            compensation,
+            refund,
+            manager_id: manager_id.to_string(),
+            reason: reason.trim().to_string(),
+            voided_at: now,
+        })
+    }
+
+    /// Voids, newest first.
+    pub fn get_voids(&self, limit: usize) -> Result<Vec<VoidRecord>> {
+        let mut stmt = self.conn.prepare(
+            "SELECT id, kind, reference, description, compensation, refund, manager_id, reason, voided_at
+             FROM voids ORDER BY id DESC LIMIT ?1",
+        )?;
+        let voids = stmt.query_map([limit as i64], |row| {
+            let kind: String = row.get(1)?;
+            Ok(VoidRecord {
+                id: row.get(0)?,
+                kind: OperationKind::parse(&kind).unwrap_or(OperationKind::Sale),
+                reference: row.get(2)?,
+                description: row.get(3)?,
+                compensation: row.get(4)?,
+                refund: row.get(5)?,
+                manager_id: row.get(6)?,
+                reason: row.get(7)?,
+                voided_at: row.get(8)?,
+            })
+        })?;
+        Ok(voids.collect::<rusqlite::Result<_>>()?)
+    }
+}
+
+// Accounting journal
+impl Database {
+    /// Sales, returns, purchases and stock adjustments in `range`, valued in the
+    /// base currency, ready for `journal::journal`.
+    pub fn journal_transactions(&self, range: &DateRange) -> Result<Vec<Transaction>> {
+        let mut transactions = Vec::new();
+
+        // Sale lines are costed at the average purchase cost up to the sale, from
+        // running purchase totals rather than a subquery per line
+        let mut purchase_totals: HashMap<String, Vec<(i64, f64, f64)>> = HashMap::new();
+        let mut stmt = self.conn.prepare(
+            "SELECT product_id, purchase_date, quantity, total_cost * exchange_rate FROM purchases
+             ORDER BY product_id, purchase_date",
+        )?;
+        let mut rows = stmt.query([])?;
+        while let Some(row) = rows.next()? {
+            let totals = purchase_totals.entry(row.get(0)?).or_default();
+            let (_, quantity, cost) = totals.last().copied().unwrap_or_default();
+            totals.push((row.get(1)?, quantity + row.get::<_, f64>(2)?, cost + row.get::<_, f64>(3)?));
+        }
+        let average_cost = |product_id: &str, at: i64| -> Option<f64> {
+            let totals = purchase_totals.get(product_id)?;
+            let (_, quantity, cost) = totals[..totals.partition_point(|(date, _, _)| *date <= at)].last()?;
+            // Voiding every purchase leaves nothing to average over
+            (quantity.abs() > 1e-9).then(|| cost / quantity)
+        };
+
+        let mut costs: HashMap<String, f64> = HashMap::new();
+        let mut stmt = self.conn.prepare(
+            "SELECT sale_id, product_id, quantity, sold_at FROM sales
+             WHERE (?1 IS NULL OR sold_at >= ?1) AND (?2 IS NULL OR sold_at < ?2)",
+        )?;
+        let mut rows = stmt.query(params![range.from, range.to])?;
+        while let Some(row) = rows.next()? {
+            if let Some(unit_cost) = average_cost(&row.get::<_, String>(1)?, row.get(3)?) {
+                *costs.entry(row.get(0)?).or_default() += unit_cost * row.get::<_, f64>(2)?;
+            }
+        }
+
+        let mut stmt = self.conn.prepare(
+            "SELECT h.sale_number, h.created_at, h.total_amount,
+                    (SELECT COALESCE(SUM(amount), 0) FROM sale_payments WHERE sale_id = h.id AND method = ?3),
+                    h.id
+             FROM sale_headers h
+             WHERE (?1 IS NULL OR h.created_at >= ?1) AND (?2 IS NULL OR h.created_at < ?2)",
+        )?;
+        let sales = stmt.query_map(params![range.from, range.to, PaymentMethod::Card.as_str()], |row| {
+            Ok(Transaction::Sale {
+                sale_number: row.get(0)?,
+                timestamp: row.get(1)?,
+                total: row.get(2)?,
+                card: row.get(3)?,
+                cost: costs.get(&row.get::<_, String>(4)?).copied(),
+            })
+        })?;
+        transactions.extend(sales.collect::<rusqlite::Result<Vec<_>>>()?);
+
+        let mut stmt = self.conn.prepare(
+            "SELECT h.sale_number, r.returned_at, p.name, r.refund, r.quantity * r.unit_cost
+             FROM sale_returns r
+             JOIN sale_headers h ON h.id = r.sale_id
+             JOIN products p ON p.id = r.product_id
+             WHERE (?1 IS NULL OR r.returned_at >= ?1) AND (?2 IS NULL OR r.returned_at < ?2)",
+        )?;
+        let returns = stmt.query_map(params![range.from, range.to], |row| {
+            Ok(Transaction::Return {
+                sale_number: row.get(0)?,
+                timestamp: row.get(1)?,
+                product: row.get(2)?,
+                refund: row.get(3)?,
+                cost: row.get(4)?,
+            })
+        })?;
+        transactions.extend(returns.collect::<rusqlite::Result<Vec<_>>>()?);
+
+        let mut stmt = self.conn.prepare(
+            "SELECT pu.purchase_date, p.name, pu.quantity, pu.total_cost * pu.exchange_rate
+             FROM purchases pu
+             JOIN products p ON p.id = pu.product_id
+             WHERE (?1 IS NULL OR pu.purchase_date >= ?1) AND (?2 IS NULL OR pu.purchase_date < ?2)",
+        )?;
+        let purchases = stmt.query_map(params![range.from, range.to], |row| {
+            Ok(Transaction::Purchase {
+                timestamp: row.get(0)?,
+                product: row.get(1)?,
+                quantity: row.get(2)?,
+                cost: row.get(3)?,
+            })
+        })?;
+        transactions.extend(purchases.collect::<rusqlite::Result<Vec<_>>>()?);
+
+        let mut stmt = self.conn.prepare(
+            "SELECT a.adjusted_at, p.name, a.quantity, a.reason, a.quantity * a.unit_cost
+             FROM stock_adjustments a
+             JOIN products p ON p.id = a.product_id
+             WHERE (?1 IS NULL OR a.adjusted_at >= ?1) AND (?2 IS NULL OR a.adjusted_at < ?2)",
+        )?;
+        let adjustments = stmt.query_map(params![range.from, range.to], |row| {
+            Ok(Transaction::Adjustment {
+                timestamp: row.get(0)?,
+                product: row.get(1)?,
+                quantity: row.get(2)?,
+                reason: row.get(3)?,
+                value: row.get(4)?,
+            })
+        })?;
+        transactions.extend(adjustments.collect::<rusqlite::Result<Vec<_>>>()?);
+
+        Ok(transactions)
+    }
+}
+
+// Demo data
+impl Database {
+    /// Writes a generated store in one transaction. Only an empty product
+    /// registry can be seeded, so real data is never mixed with demo data.
+    pub fn load_demo_data(&mut self, data: &DemoData) -> Result<()> {
+        let tx = self.conn.transaction_with_behavior(TransactionBehavior::Immediate)?;
+        let existing: i64 = tx.query_row("SELECT COUNT(*) FROM products", [], |row| row.get(0))?;
+        if existing > 0 {
+            return Err(Error::conflict("The database already has products; demo data can only seed an empty one"));
+        }
+
+        {
+            let mut insert_product = tx.prepare(
+                "INSERT INTO products (id, name, description, price, quantity, unit, sku, tags, created_at, updated_at)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10)",
+            )?;
+            let mut insert_price = tx.prepare(
+                "INSERT INTO price_history (product_id, old_price, new_price, changed_at, reason)
+                 VALUES (?1, NULL, ?2, ?3, ?4)",
+            )?;
+            for product in &data.products {
+                insert_product.execute(params![
+                    product.id,
+                    product.name,
+                    product.description,
+                    product.price,
+                    product.quantity,
+                    unit::normalize(&product.unit),
+                    product.sku,
+                    product.tags.join(", "),
+                    product.created_at,
+                    product.updated_at
+                ])?;
+                insert_price.execute(params![product.id, product.price, product.created_at, pricing::REASON_INITIAL])?;
+                index_product(&tx, product)?;
+            }
+
+            let mut insert_purchase = tx.prepare(
+                "INSERT INTO purchases (id, product_id, quantity, purchase_price, total_cost, purchase_date, currency,
+                                        exchange_rate)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)",
+            )?;
+            for purchase in &data.purchases {
+                insert_purchase.execute(params![
+                    purchase.id,
+                    purchase.product_id,
+                    purchase.quantity,
+                    purchase.purchase_price,
+                    purchase.total_cost,
+                    purchase.purchase_date,
+                    purchase.currency,
+                    purchase.exchange_rate
+                ])?;
+            }
+
+            let first_number: i64 =
+                tx.query_row("SELECT COALESCE(MAX(sale_number), 0) + 1 FROM sale_headers", [], |row| row.get(0))?;
+            let mut insert_header = tx.prepare(
+                "INSERT INTO sale_headers (id, sale_number, total_amount, created_at) VALUES (?1, ?2, ?3, ?4)",
+            )?;
+            let mut insert_item = tx.prepare(
+                "INSERT INTO sales (sale_id, product_id, quantity, unit_price, total_price, sold_at)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+            )?;
+            for (sale_number, sale) in (first_number..).zip(&data.sales) {
+                insert_header.execute(params![sale.id, sale_number, sale.total_amount, sale.timestamp])?;
+                for item in &sale.items {
+                    insert_item.execute(params![
+                        sale.id,
+                        item.product_id,
+                        item.quantity,
+                        item.unit_price,
+                        item.total_price,
+                        sale.timestamp
+                    ])?;
+                }
+            }
+        }
+
+        tx.commit()?;
+        // Refresh the planner's statistics so the new indexes are used on the bulk data
+        self.conn.execute_batch("ANALYZE")?;
+        Ok(())
+    }
+}
+
+/// Numbers `sale`, checks stock and writes its header and rows, drawing bundles
+/// from assembled stock first. Runs inside the caller's transaction.
+fn insert_sale(conn: &Connection, sale: &Sale, base_quantities: &[f64]) -> Result<i64> {
+    if let Some(customer_id) = &sale.customer_id {
+        let exists: i64 =
+            conn.query_row("SELECT COUNT(*) FROM customers WHERE id = ?1", [customer_id], |row| row.get(0))?;
+        if exists == 0 {
+            return Err(Error::not_found("Customer", customer_id));
+        }
+    }
+    let sale_number: i64 = conn.query_row(
+        "SELECT COALESCE(MAX(sale_number), 0) + 1 FROM sale_headers",
+        [],
+        |row| row.get(0),
+    )?;
+    conn.execute(
+        "INSERT INTO sale_headers (id, sale_number, total_amount, created_at, customer_id, discount)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+        params![sale.id, sale_number, sale.total_amount, sale.timestamp, sale.customer_id, sale.discount],
+    )?;
+
+    for (item, &quantity) in sale.items.iter().zip(base_quantities) {
+        let mut stmt = conn.prepare("SELECT quantity, archived_at IS NOT NULL FROM products WHERE id = ?")?;
+        let (current_quantity, archived): (f64, bool) = stmt
+            .query_row([&item.product_id], |row| Ok((row.get(0)?, row.get(1)?)))
+            .optional()?
+            .ok_or_else(|| Error::not_found("Product", &item.product_id))?;
+
+        if archived {
+            return Err(Error::conflict(format!("Product '{}' is archived", item.product_id)));
+        }
+
+        // Stock held for customer orders cannot be sold
+        let free_quantity = current_quantity - reserved_quantity(conn, &item.product_id)?;
+        let components = bundle_components(conn, &item.product_id)?;
+        let component_stock = components
+            .iter()
+            .map(|c| {
+                let stock: f64 = conn.query_row("SELECT quantity FROM products WHERE id = ?1", [&c.component_id], |row| row.get(0))?;
+                Ok(stock - reserved_quantity(conn, &c.component_id)?)
+            })
+            .collect::<Result<Vec<f64>>>()?;
+        let available = free_quantity + bundle::buildable(&components, &component_stock);
+        if available < quantity {
+            return Err(Error::InsufficientStock {
+                product_id: item.product_id.clone(),
+                available,
+                requested: quantity,
+            });
+        }
+
+        let (from_stock, needed) = bundle::allocate(free_quantity, &components, quantity);
+        let from_stock = if components.is_empty() { quantity } else { from_stock };
+        conn.execute(
+            "UPDATE products SET quantity = ROUND(quantity - ?, 6), version = version + 1 WHERE id = ?",
+            params![from_stock, item.product_id],
+        )?;
+        for (component, needed) in components.iter().zip(needed).filter(|(_, needed)| *needed > 0.0) {
+            conn.execute(
+                "UPDATE products SET quantity = ROUND(quantity - ?, 6), version = version + 1 WHERE id = ?",
+                params![needed, component.component_id],
+            )?;
+        }
+
+        conn.execute(
+            "INSERT INTO sales (sale_id, product_id, quantity, unit, unit_quantity, unit_price, total_price, sold_at) 
+             VALUES (?, ?, ?, ?, ?, ?, ?, ?)",
+            params![
+                sale.id,
+                item.product_id,
+                quantity,
+                item.unit.as_deref().map(unit::normalize),
+                item.unit.as_ref().map(|_| item.quantity),
+                item.unit_price,
+                item.total_price,
+                sale.timestamp,
+            ],
+        )?;
+    }
+
+    match &sale.customer_id {
+        Some(customer_id) => credit_loyalty(conn, sale, customer_id, &settings::current().loyalty)?,
+        None if sale.discount > 0.0 || sale.points_redeemed > 0 => {
+            return Err(Error::validation("Discounts are only given for a loyalty customer's points"));
+        }
+        None => {}
+    }
+
+    Ok(sale_number)
+}
+
+/// Spends the points `sale` redeems and adds the points it earns to the customer's ledger.
+fn credit_loyalty(conn: &Connection, sale: &Sale, customer_id: &str, program: &LoyaltyProgram) -> Result<()> {
+    if sale.points_redeemed > 0 {
+        if program.point_value <= 0.0 {
+            return Err(Error::validation("Loyalty points cannot be redeemed"));
+        }
+        let balance = points_balance(conn, customer_id)?;
+        if sale.points_redeemed > balance {
+            return Err(Error::validation(format!("The customer has only {} points to redeem", balance)));
+        }
+        if (program.discount(sale.points_redeemed, sale.subtotal()) - sale.discount).abs() > 0.005 {
+            return Err(Error::validation("The discount does not match the points redeemed"));
+        }
+        conn.execute(
+            "INSERT INTO loyalty_points (customer_id, sale_id, kind, points, created_at) VALUES (?1, ?2, 'redeemed', ?3, ?4)",
+            params![customer_id, sale.id, -sale.points_redeemed, sale.timestamp],
+        )?;
+    } else if sale.discount > 0.0 {
+        return Err(Error::validation("Discounts are only given for a loyalty customer's points"));
+    }
+
+    let earned = program.points_earned(sale.total_amount, lifetime_value(conn, customer_id, &sale.id)?);
+    if earned > 0 {
+        conn.execute(
+            "INSERT INTO loyalty_points (customer_id, sale_id, kind, points, created_at) VALUES (?1, ?2, 'earned', ?3, ?4)",
+            params![customer_id, sale.id, earned, sale.timestamp],
+        )?;
+    }
+    Ok(())
+}
+
+fn points_balance(conn: &Connection, customer_id: &str) -> Result<i64> {
+    Ok(conn.query_row(
+        "SELECT COALESCE(SUM(points), 0) FROM loyalty_points WHERE customer_id = ?1",
+        [customer_id],
+        |row| row.get(0),
+    )?)
+}
+
+/// What the customer has paid over all sales except `excluding_sale`, less refunds.
+fn lifetime_value(conn: &Connection, customer_id: &str, excluding_sale: &str) -> Result<f64> {
+    let value: f64 = conn.query_row(
+        "SELECT COALESCE(SUM(h.total_amount), 0)
+              - (SELECT COALESCE(SUM(r.refund), 0) FROM sale_returns r
+                 JOIN sale_headers s ON s.id = r.sale_id
+                 WHERE s.customer_id = ?1 AND s.id != ?2)
+         FROM sale_headers h WHERE h.customer_id = ?1 AND h.id != ?2",
+        params![customer_id, excluding_sale],
+        |row| row.get(0),
+    )?;
+    Ok((value * 100.0).round() / 100.0)
+}
+
+/// The part of a sale's item totals the customer actually paid; below 1 when
+/// loyalty points bought a discount, so refunds are reduced to match.
+fn paid_share(conn: &Connection, sale_id: &str) -> Result<f64> {
+    let share: Option<f64> = conn.query_row(
+        "SELECT h.total_amount / NULLIF((SELECT SUM(total_price) FROM sales WHERE sale_id = h.id), 0)
+         FROM sale_headers h WHERE h.id = ?1",
+        [sale_id],
+        |row| row.get(0),
+    )?;
+    Ok(share.unwrap_or(1.0).clamp(0.0, 1.0))
+}
+
+/// After a return from `sale_id`, takes back the points the refunded share of
+/// the sale earned and gives back the share of the points spent on it.
+fn reverse_loyalty(conn: &Connection, sale_id: &str, now: i64) -> Result<()> {
+    let (customer_id, total): (Option<String>, f64) = conn.query_row(
+        "SELECT customer_id, total_amount FROM sale_headers WHERE id = ?1",
+        [sale_id],
+        |row| Ok((row.get(0)?, row.get(1)?)),
+    )?;
+    let Some(customer_id) = customer_id else {
+        return Ok(());
+    };
+    let refunded: f64 = conn.query_row(
+        "SELECT COALESCE(SUM(refund), 0) FROM sale_returns WHERE sale_id = ?1",
+        [sale_id],
+        |row| row.get(0),
+    )?;
+    let share = if total > 0.0 { (refunded / total).min(1.0) } else { 1.0 };
+
+    for (original, reversal) in [("earned", "returned"), ("redeemed", "restored")] {
+        let points = |kind: &str| -> rusqlite::Result<i64> {
+            conn.query_row(
+                "SELECT COALESCE(SUM(points), 0) FROM loyalty_points WHERE sale_id = ?1 AND kind = ?2",
+                params![sale_id, kind],
+                |row| row.get(0),
+            )
+        };
+        let due = -(points(original)? as f64 * share).round() as i64 - points(reversal)?;
+        if due != 0 {
+            conn.execute(
+                "INSERT INTO loyalty_points (customer_id, sale_id, kind, points, created_at) VALUES (?1, ?2, ?3, ?4, ?5)",
+                params![customer_id, sale_id, reversal, due, now],
+            )?;
+        }
+    }
+    Ok(())
+}
+
+fn manager_from_row(row: &rusqlite::Row) -> rusqlite::Result<Manager> {
+    Ok(Manager {
+        id: row.get(0)?,
+        username: row.get(1)?,
+        password: row.get(2)?,
+        full_name: row.get(3)?,
+        created_at: row.get(4)?,
+        is_active: row.get::<_, i32>(5)? == 1,
+        failed_attempts: row.get(6)?,
+        locked_until: row.get(7)?,
+        must_change_password: row.get::<_, i32>(8)? == 1,
+        removed_at: row.get(9)?,
+    })
+}
+
+fn open_connection(path: &Path, passphrase: Option<&str>) -> Result<Connection> {
+    let conn = Connection::open(path)?;
+    if let Some(key) = passphrase {
+        ensure_cipher_support(&conn)?;
+        conn.pragma_update(None, "key", key)?;
+    }
+    // Touch the schema so a wrong passphrase or an encrypted file opened without one fails here
+    conn.query_row("SELECT COUNT(*) FROM sqlite_master", [], |row| row.get::<_, i64>(0))?;
+
+    // WAL lets several terminals read while one writes; in-memory databases stay in "memory" mode
+    conn.busy_timeout(Duration::from_millis(env_or("RUSTOCK_BUSY_TIMEOUT_MS", DEFAULT_BUSY_TIMEOUT_MS)))?;
+    conn.query_row("PRAGMA journal_mode = WAL", [], |row| row.get::<_, String>(0))?;
+    Ok(conn)
+}
+
+/// Writes attachment content via a temporary file so a half-written file never
+/// sits under the content's hash.
+fn write_content_file(path: &Path, data: &[u8]) -> Result<()> {
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    let partial = path.with_extension("partial");
+    std::fs::write(&partial, data)?;
+    std::fs::rename(&partial, path)?;
+    Ok(())
+}
+
+/// Deletes stored content no attachment refers to any more, returning the paths
+/// of content files to remove once the transaction has committed.
+fn prune_attachment_contents(conn: &Connection) -> Result<Vec<String>> {
+    let paths = conn
+        .prepare(
+            "SELECT path FROM attachment_contents
+             WHERE path IS NOT NULL AND hash NOT IN (SELECT hash FROM attachments)",
+        )?
+        .query_map([], |row| row.get::<_, String>(0))?
+        .collect::<rusqlite::Result<Vec<_>>>()?;
+    conn.execute("DELETE FROM attachment_contents WHERE hash NOT IN (SELECT hash FROM attachments)", [])?;
+    Ok(paths)
+}
+
+fn remove_content_files(paths: &[String]) {
+    for path in paths {
+        // A file that is already gone needs no cleanup
+        let _ = std::fs::remove_file(path);
+    }
+}
+
+fn ensure_unique_sku(conn: &Connection, product: &Product) -> Result<()> {
+    let Some(sku) = &product.sku else {
+        return Ok(());
+    };
+    let owner: Option<String> = conn
+        .query_row(
+            "SELECT name FROM products WHERE sku = ?1 COLLATE NOCASE AND id <> ?2",
+            params![sku, product.id],
+            |row| row.get(0),
+        )
+        .optional()?;
+    match owner {
+        Some(name) => Err(Error::conflict(format!("SKU '{}' is already used by {}", sku, name))),
+        None => Ok(()),
+    }
+}
+
+/// Replaces the search index entry of `product` with its current text.
+fn index_product(conn: &Connection, product: &Product) -> Result<()> {
+    conn.execute("DELETE FROM product_search WHERE product_id = ?1", [&product.id])?;
+    conn.execute(
+        "INSERT INTO product_search (product_id, name, description, sku, tags) VALUES (?1, ?2, ?3, ?4, ?5)",
+        params![
+            product.id,
+            product.name,
+            product.description,
+            product.sku.as_deref().unwrap_or_default(),
+            product.tags.join(", ")
+        ],
+    )?;
+    Ok(())
+}
+
+/// Returns whatever is left of sale `sale_number`, returning the refund.
+fn void_sale(conn: &Connection, sale_number: &str, reason: &str, now: i64) -> Result<f64> {
+    let (sale_id, sold_at): (String, i64) = conn
+        .query_row(
+            "SELECT id, created_at FROM sale_headers WHERE CAST(sale_number AS TEXT) = ?1",
+            [sale_number],
+            |row| Ok((row.get(0)?, row.get(1)?)),
+        )
+        .optional()?
+        .ok_or_else(|| Error::not_found("Sale", sale_number))?;
+    let collected: i64 = conn.query_row(
+        "SELECT COUNT(*) FROM customer_orders WHERE CAST(sale_number AS TEXT) = ?1",
+        [sale_number],
+        |row| row.get(0),
+    )?;
+    if collected > 0 {
+        return Err(Error::conflict("This sale collected a customer order; record a return instead"));
+    }
+
+    let mut stmt = conn.prepare(
+        "SELECT s.product_id, SUM(s.quantity), SUM(s.total_price),
+                (SELECT COALESCE(SUM(r.quantity), 0) FROM sale_returns r
+                 WHERE r.sale_id = s.sale_id AND r.product_id = s.product_id)
+         FROM sales s WHERE s.sale_id = ?1 GROUP BY s.product_id",
+    )?;
+    let lines = stmt
+        .query_map([&sale_id], |row| {
+            Ok((row.get::<_, String>(0)?, row.get::<_, f64>(1)?, row.get::<_, f64>(2)?, row.get::<_, f64>(3)?))
+        })?
+        .collect::<rusqlite::Result<Vec<_>>>()?;
+    let share = paid_share(conn, &sale_id)?;
+    let mut refunded = 0.0;
+    let mut remaining = 0.0;
+    for (product_id, sold, total, returned) in lines {
+        let quantity = unit::round_quantity(sold - returned);
+        if quantity <= 0.0 {
+            continue;
+        }
+        remaining += quantity;
+        let refund = (total * quantity / sold * share * 100.0).round() / 100.0;
+        refunded += refund;
+        conn.execute(
+            "INSERT INTO sale_returns (sale_id, product_id, quantity, refund, unit_cost, reason, returned_at)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+            params![sale_id, product_id, quantity, refund, average_cost(conn, &product_id, sold_at)?, reason, now],
+        )?;
+        conn.execute(
+            "UPDATE products SET quantity = ROUND(quantity + ?1, 6), updated_at = ?2, version = version + 1
+             WHERE id = ?3",
+            params![quantity, now, product_id],
+        )?;
+        allocate_backorders(conn, &product_id)?;
+    }
+    if remaining == 0.0 {
+        return Err(Error::conflict(format!("Everything from sale #{} has already been returned", sale_number)));
+    }
+    reverse_loyalty(conn, &sale_id, now)?;
+    Ok((refunded * 100.0).round() / 100.0)
+}
+
+/// Offsets purchase `purchase_id` with a negative purchase and takes its stock
+/// back out, returning the offsetting purchase's ID.
+fn void_purchase(conn: &Connection, purchase_id: &str, now: i64) -> Result<String> {
+    let (product_id, quantity, price, total, currency, rate): (String, f64, f64, f64, Option<String>, f64) = conn
+        .query_row(
+            "SELECT product_id, quantity, purchase_price, total_cost, currency, exchange_rate
+             FROM purchases WHERE id = ?1",
+            [purchase_id],
+            |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?, row.get(3)?, row.get(4)?, row.get(5)?)),
+        )
+        .optional()?
+        .ok_or_else(|| Error::not_found("Purchase", purchase_id))?;
+
+    let stock: f64 = conn.query_row("SELECT quantity FROM products WHERE id = ?1", [&product_id], |row| row.get(0))?;
+    let free = unit::round_quantity(stock - reserved_quantity(conn, &product_id)?);
+    if free < quantity {
+        return Err(Error::conflict(format!(
+            "Only {} of the {} purchased are still free in stock; the rest has been sold or reserved",
+            free, quantity
+        )));
+    }
+
+    let offset_id = uuid::Uuid::new_v4().to_string();
+    conn.execute(
+        "INSERT INTO purchases (id, product_id, quantity, purchase_price, total_cost, purchase_date, currency,
+                                exchange_rate)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)",
+        params![offset_id, product_id, -quantity, price, -total, now, currency, rate],
+    )?;
+    conn.execute(
+        "UPDATE products SET quantity = ROUND(quantity - ?1, 6), updated_at = ?2, version = version + 1 WHERE id = ?3",
+        params![quantity, now, product_id],
+    )?;
+    Ok(offset_id)
+}
+
+/// Records the opposite of adjustment `adjustment_id`, returning its ID.
+fn void_adjustment(conn: &Connection, adjustment_id: &str, reason: &str, now: i64) -> Result<String> {
+    let (product_id, change, unit_cost): (String, f64, Option<f64>) = conn
+        .query_row(
+            "SELECT product_id, quantity, unit_cost FROM stock_adjustments WHERE CAST(id AS TEXT) = ?1",
+            [adjustment_id],
+            |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)),
+        )
+        .optional()?
+        .ok_or_else(|| Error::not_found("Stock adjustment", adjustment_id))?;
+
+    let stock: f64 = conn.query_row("SELECT quantity FROM products WHERE id = ?1", [&product_id], |row| row.get(0))?;
+    let quantity = unit::round_quantity(stock - change);
+    if quantity < 0.0 {
+        return Err(Error::conflict(format!("Only {} in stock; the adjusted stock has been sold", stock)));
+    }
+
+    conn.execute(
+        "INSERT INTO stock_adjustments (product_id, quantity, unit_cost, reason, adjusted_at)
+         VALUES (?1, ?2, ?3, ?4, ?5)",
+        params![product_id, -change, unit_cost, reason, now],
+    )?;
+    let offset_id = conn.last_insert_rowid();
+    conn.execute(
+        "UPDATE products SET quantity = ?1, updated_at = ?2, version = version + 1 WHERE id = ?3",
+        params![quantity, now, product_id],
+    )?;
+    if change < 0.0 {
+        allocate_backorders(conn, &product_id)?;
+    }
+    Ok(offset_id.to_string())
+}
+
+/// Weighted average base-currency cost of the purchases of `product_id` made
+/// up to `at`, or `None` if it was never purchased.
+fn average_cost(conn: &Connection, product_id: &str, at: i64) -> Result<Option<f64>> {
+    Ok(conn.query_row(
+        "SELECT SUM(total_cost * exchange_rate) / SUM(quantity) FROM purchases
+         WHERE product_id = ?1 AND purchase_date <= ?2",
+        params![product_id, at],
+        |row| row.get(0),
+    )?)
+}
+
+fn reserved_quantity(conn: &Connection, product_id: &str) -> Result<f64> {
+    Ok(conn.query_row(
+        "SELECT COALESCE(SUM(allocated), 0) FROM customer_orders WHERE product_id = ?1 AND status = 'open'",
+        [product_id],
+        |row| row.get(0),
+    )?)
+}
+
+/// Hands free stock of `product_id` to open backorders, oldest first.
+fn allocate_backorders(conn: &Connection, product_id: &str) -> Result<()> {
+    let stock: f64 = conn.query_row("SELECT quantity FROM products WHERE id = ?1", [product_id], |row| row.get(0))?;
+    let mut free = unit::round_quantity(stock - reserved_quantity(conn, product_id)?);
+
+    let mut stmt = conn.prepare(
+        "SELECT id, quantity - allocated FROM customer_orders
+         WHERE product_id = ?1 AND status = 'open' AND kind = 'backorder' AND allocated < quantity
+         ORDER BY created_at, order_number",
+    )?;
+    let waiting = stmt
+        .query_map([product_id], |row| Ok((row.get::<_, String>(0)?, row.get::<_, f64>(1)?)))?
+        .collect::<rusqlite::Result<Vec<_>>>()?;
+
+    for (order_id, outstanding) in waiting {
+        if free <= 0.0 {
+            break;
+        }
+        let share = outstanding.min(free);
+        conn.execute(
+            "UPDATE customer_orders SET allocated = ROUND(allocated + ?1, 6) WHERE id = ?2",
+            params![share, order_id],
+        )?;
+        free = unit::round_quantity(free - share);
+    }
+    Ok(())
+}
+
+/// Bill of materials of `bundle_id`, read through a plain connection or an open transaction.
+fn bundle_components(conn: &Connection, bundle_id: &str) -> Result<Vec<BundleComponent>> {
+    let mut stmt = conn.prepare(
+        "SELECT bundle_id, component_id, quantity FROM bundle_components WHERE bundle_id = ?1 ORDER BY rowid",
+    )?;
+    let components = stmt.query_map([bundle_id], |row| {
+        Ok(BundleComponent {
+            bundle_id: row.get(0)?,
+            component_id: row.get(1)?,
+            quantity: row.get(2)?,
+        })
+    })?;
+    Ok(components.collect::<rusqlite::Result<_>>()?)
+}
+
+fn stale_product(product: &Product) -> Error {
+    Error::conflict(format!(
+        "Product '{}' was changed in another terminal since it was loaded; reload it and try again",
+        product.name
+    ))
+}
+
+fn ensure_cipher_support(conn: &Connection) -> Result<()> {
+    let version: Option<String> = conn
+        .query_row("PRAGMA cipher_version", [], |row| row.get(0))
+        .optional()?;
+    match version {
+        Some(_) => Ok(()),
+        None => Err(Error::validation("Encryption requires RuStock to be built with the `sqlcipher` feature")),
+    }
+}
@@ -0,0 +1,117 @@
+use std::fmt;
+use std::io;
+
+/// Errors returned by the database and domain layer.
+#[derive(Debug)]
+pub enum Error {
+    InsufficientStock {
+        product_id: String,
+        available: f64,
+        requested: f64,
+    },
+    NotFound {
+        entity: &'static str,
+        id: String,
+    },
+    Validation(String),
+    Conflict(String),
+    Unauthorized(String),
+    Storage(rusqlite::Error),
+    Io(io::Error),
+}
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+impl Error {
+    pub fn not_found(entity: &'static str, id: impl Into<String>) -> Self {
+        Error::NotFound { entity, id: id.into() }
+    }
+
+    pub fn validation(message: impl Into<String>) -> Self {
+        Error::Validation(message.into())
+    }
+
+    pub fn conflict(message: impl Into<String>) -> Self {
+        Error::Conflict(message.into())
+    }
+
+    /// Process exit code used when the error ends a command-line invocation.
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            Error::Validation(_) => 2,
+            Error::NotFound { .. } => 3,
+            Error::Conflict(_) => 4,
+            Error::InsufficientStock { .. } => 5,
+            Error::Unauthorized(_) => 6,
+            Error::Storage(_) => 10,
+            Error::Io(_) => 11,
+        }
+    }
+
+    /// True when SQLite could not read the file as a database, which is what an
+    /// encrypted file looks like when opened without the right passphrase.
+    pub fn is_not_a_database(&self) -> bool {
+        matches!(
+            self,
+            Error::Storage(rusqlite::Error::SqliteFailure(e, _))
+                if e.code == rusqlite::ErrorCode::NotADatabase
+        )
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::InsufficientStock { product_id, available, requested } => write!(
+                f,
+                "Insufficient stock for product {}: {} available, {} requested",
+                product_id, available, requested
+            ),
+            Error::NotFound { entity, id } => write!(f, "{} '{}' not found", entity, id),
+            Error::Validation(message) => write!(f, "{}", message),
+            Error::Conflict(message) => write!(f, "{}", message),
+            Error::Unauthorized(message) => write!(f, "{}", message),
+            Error::Storage(e) => write!(f, "Database error: {}", e),
+            Error::Io(e) => write!(f, "I/O error: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Storage(e) => Some(e),
+            Error::Io(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<rusqlite::Error> for Error {
+    fn from(e: rusqlite::Error) -> Self {
+        match e {
+            rusqlite::Error::SqliteFailure(ref err, ref message)
+                if matches!(
+                    err.extended_code,
+                    rusqlite::ffi::SQLITE_CONSTRAINT_UNIQUE | rusqlite::ffi::SQLITE_CONSTRAINT_PRIMARYKEY
+                ) =>
+            {
+                Error::Conflict(
+                    message.clone().unwrap_or_else(|| "Record already exists".to_string()),
+                )
+            }
+            rusqlite::Error::SqliteFailure(ref err, _)
+                if matches!(err.code, rusqlite::ErrorCode::DatabaseBusy | rusqlite::ErrorCode::DatabaseLocked) =>
+            {
+                Error::Conflict("The database is busy in another terminal; please try again".to_string())
+            }
+            other => Error::Storage(other),
+        }
+    }
+}
+
+impl From<io::Error> for Error {
+    fn from(e: io::Error) -> Self {
+        Error::Io(e)
+    }
+}
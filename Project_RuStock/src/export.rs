@@ -0,0 +1,143 @@
+use crate::error::{Error, Result};
+use chrono::Local;
+use rust_xlsxwriter::{Format, Workbook, XlsxError};
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+pub const EXPORT_DIR: &str = "exports";
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Cell {
+    Text(String),
+    Number(f64),
+    Empty,
+}
+
+impl Cell {
+    pub fn text(value: impl Into<String>) -> Self {
+        Cell::Text(value.into())
+    }
+
+    pub fn optional(value: Option<f64>) -> Self {
+        value.map_or(Cell::Empty, Cell::Number)
+    }
+
+    fn to_csv(&self) -> String {
+        match self {
+            Cell::Text(text) => csv_escape(text),
+            Cell::Number(number) => number.to_string(),
+            Cell::Empty => String::new(),
+        }
+    }
+}
+
+/// A titled grid of cells that can be written as CSV or as an Excel worksheet.
+#[derive(Debug, Clone)]
+pub struct Table {
+    pub title: String,
+    pub headers: Vec<String>,
+    pub rows: Vec<Vec<Cell>>,
+}
+
+impl Table {
+    pub fn new(title: impl Into<String>, headers: &[&str]) -> Self {
+        Table {
+            title: title.into(),
+            headers: headers.iter().map(|h| h.to_string()).collect(),
+            rows: Vec::new(),
+        }
+    }
+
+    pub fn push(&mut self, row: Vec<Cell>) {
+        self.rows.push(row);
+    }
+
+    pub fn to_csv(&self) -> String {
+        let mut out = self.headers.iter().map(|h| csv_escape(h)).collect::<Vec<_>>().join(",");
+        out.push('\n');
+        for row in &self.rows {
+            out.push_str(&row.iter().map(Cell::to_csv).collect::<Vec<_>>().join(","));
+            out.push('\n');
+        }
+        out
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExportFormat {
+    Csv,
+    Xlsx,
+}
+
+impl ExportFormat {
+    pub fn extension(&self) -> &'static str {
+        match self {
+            ExportFormat::Csv => "csv",
+            ExportFormat::Xlsx => "xlsx",
+        }
+    }
+}
+
+/// Writes `table` into `dir` under a timestamped name derived from its title.
+pub fn save(table: &Table, dir: &Path, format: ExportFormat) -> Result<PathBuf> {
+    fs::create_dir_all(dir)?;
+    let path = dir.join(format!(
+        "{}-{}.{}",
+        slug(&table.title),
+        Local::now().format("%Y%m%d-%H%M%S"),
+        format.extension()
+    ));
+
+    match format {
+        ExportFormat::Csv => fs::write(&path, table.to_csv())?,
+        ExportFormat::Xlsx => write_xlsx(table, &path).map_err(xlsx_error)?,
+    }
+    Ok(path)
+}
+
+fn write_xlsx(table: &Table, path: &Path) -> std::result::Result<(), XlsxError> {
+    let mut workbook = Workbook::new();
+    let sheet = workbook.add_worksheet();
+    // Sheet names are limited to 31 characters
+    sheet.set_name(table.title.chars().take(31).collect::<String>())?;
+
+    let bold = Format::new().set_bold();
+    for (col, header) in table.headers.iter().enumerate() {
+        sheet.write_string_with_format(0, col as u16, header, &bold)?;
+    }
+    for (idx, row) in table.rows.iter().enumerate() {
+        let row_num = idx as u32 + 1;
+        for (col, cell) in row.iter().enumerate() {
+            match cell {
+                Cell::Text(text) => sheet.write_string(row_num, col as u16, text)?,
+                Cell::Number(number) => sheet.write_number(row_num, col as u16, *number)?,
+                Cell::Empty => sheet,
+            };
+        }
+    }
+    sheet.autofit();
+
+    workbook.save(path)
+}
+
+fn xlsx_error(e: XlsxError) -> Error {
+    Error::Io(io::Error::other(e.to_string()))
+}
+
+fn csv_escape(value: &str) -> String {
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+fn slug(title: &str) -> String {
+    title
+        .to_lowercase()
+        .split(|c: char| !c.is_ascii_alphanumeric())
+        .filter(|part| !part.is_empty())
+        .collect::<Vec<_>>()
+        .join("-")
+}
@@ -0,0 +1,308 @@
+//! Demand forecasting from daily sales and the purchase plan built on it.
+//!
+//! Each product's sales are bucketed into a daily series (days without sales
+//! count as zero demand). Every method is backtested one day ahead over the
+//! series so the plan can use whichever method would have predicted best.
+
+use crate::export::{Cell, Table};
+use crate::unit;
+
+pub const SECONDS_PER_DAY: i64 = 86_400;
+
+/// Days of history used when none is given.
+pub const DEFAULT_HISTORY_DAYS: usize = 90;
+/// Weekly seasonality.
+pub const SEASON_LENGTH: usize = 7;
+
+// Service-level factor (about 95%) and the usual ratio of standard deviation to mean absolute error
+const SAFETY_Z: f64 = 1.65;
+const MAE_TO_SIGMA: f64 = 1.25;
+
+/// Units sold per day for one product, oldest day first.
+#[derive(Debug, Clone, Default)]
+pub struct DemandSeries {
+    pub product_id: String,
+    pub name: String,
+    pub unit: String,
+    /// Stock on hand today.
+    pub on_hand: f64,
+    /// Unix day number (timestamp / 86400) of `daily[0]`.
+    pub first_day: i64,
+    pub daily: Vec<f64>,
+}
+
+impl DemandSeries {
+    pub fn total(&self) -> f64 {
+        self.daily.iter().sum()
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Method {
+    /// Mean of the last `window` days.
+    MovingAverage { window: usize },
+    /// Simple exponential smoothing with smoothing factor `alpha`.
+    ExponentialSmoothing { alpha: f64 },
+    /// Exponential smoothing of deseasonalised demand, multiplied back by day-of-week indices.
+    Seasonal { alpha: f64 },
+}
+
+impl Method {
+    /// The methods compared for every product.
+    pub const CANDIDATES: [Method; 4] = [
+        Method::MovingAverage { window: 7 },
+        Method::MovingAverage { window: 28 },
+        Method::ExponentialSmoothing { alpha: 0.3 },
+        Method::Seasonal { alpha: 0.3 },
+    ];
+
+    pub fn describe(&self) -> String {
+        match self {
+            Method::MovingAverage { window } => format!("MA({})", window),
+            Method::ExponentialSmoothing { alpha } => format!("SES({})", alpha),
+            Method::Seasonal { alpha } => format!("Seasonal({})", alpha),
+        }
+    }
+
+    /// Forecast demand for each of the `horizon` days following `history`.
+    pub fn forecast(&self, history: &[f64], first_day: i64, horizon: usize) -> Vec<f64> {
+        if history.is_empty() {
+            return vec![0.0; horizon];
+        }
+        match *self {
+            Method::MovingAverage { window } => {
+                let recent = &history[history.len().saturating_sub(window.max(1))..];
+                vec![mean(recent); horizon]
+            }
+            Method::ExponentialSmoothing { alpha } => vec![smooth(history, alpha); horizon],
+            Method::Seasonal { alpha } => {
+                let indices = seasonal_indices(history, first_day);
+                let adjusted: Vec<f64> = history
+                    .iter()
+                    .enumerate()
+                    .map(|(idx, demand)| {
+                        let index = indices[season_slot(first_day + idx as i64)];
+                        if index > 0.0 { demand / index } else { 0.0 }
+                    })
+                    .collect();
+                let level = smooth(&adjusted, alpha);
+                let next_day = first_day + history.len() as i64;
+                (0..horizon)
+                    .map(|h| level * indices[season_slot(next_day + h as i64)])
+                    .collect()
+            }
+        }
+    }
+}
+
+/// One-day-ahead forecast errors of a method over a series.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ErrorMetrics {
+    /// Mean absolute error.
+    pub mae: f64,
+    /// Root mean squared error.
+    pub rmse: f64,
+    /// Mean absolute percentage error over days with demand; `None` if there were none.
+    pub mape: Option<f64>,
+}
+
+/// Forecasts each day from the days before it, skipping the first `SEASON_LENGTH`
+/// days so every method has some history. `None` for series too short to test.
+pub fn backtest(series: &DemandSeries, method: Method) -> Option<ErrorMetrics> {
+    let daily = &series.daily;
+    if daily.len() <= SEASON_LENGTH {
+        return None;
+    }
+
+    let mut absolute = Vec::new();
+    let mut squared = Vec::new();
+    let mut percentage = Vec::new();
+    for t in SEASON_LENGTH..daily.len() {
+        let predicted = method.forecast(&daily[..t], series.first_day, 1)[0];
+        let error = daily[t] - predicted;
+        absolute.push(error.abs());
+        squared.push(error * error);
+        if daily[t] > 0.0 {
+            percentage.push(error.abs() / daily[t] * 100.0);
+        }
+    }
+
+    Some(ErrorMetrics {
+        mae: mean(&absolute),
+        rmse: mean(&squared).sqrt(),
+        mape: (!percentage.is_empty()).then(|| mean(&percentage)),
+    })
+}
+
+/// The candidate method with the lowest backtested MAE; moving average over
+/// four weeks when the series is too short to compare.
+pub fn best_method(series: &DemandSeries) -> (Method, Option<ErrorMetrics>) {
+    Method::CANDIDATES
+        .iter()
+        .filter_map(|method| backtest(series, *method).map(|metrics| (*method, Some(metrics))))
+        .min_by(|a, b| mae(a.1).total_cmp(&mae(b.1)))
+        .unwrap_or((Method::MovingAverage { window: 28 }, None))
+}
+
+fn mae(metrics: Option<ErrorMetrics>) -> f64 {
+    metrics.map_or(f64::INFINITY, |m| m.mae)
+}
+
+/// Replenishment settings for the purchase plan.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PlanSettings {
+    /// Days between placing an order and receiving it.
+    pub lead_time_days: usize,
+    /// Days of demand an order should cover once it arrives.
+    pub review_days: usize,
+}
+
+impl Default for PlanSettings {
+    fn default() -> Self {
+        PlanSettings {
+            lead_time_days: 7,
+            review_days: 14,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct PlanRow {
+    pub name: String,
+    pub unit: String,
+    pub on_hand: f64,
+    pub method: Method,
+    pub metrics: Option<ErrorMetrics>,
+    /// Forecast demand until an order placed today arrives.
+    pub lead_time_demand: f64,
+    pub safety_stock: f64,
+    /// Order when stock falls to this level.
+    pub reorder_point: f64,
+    /// Quantity to order now; zero when stock covers the reorder point.
+    pub suggested_order: f64,
+}
+
+/// Reorder points and order suggestions for every product that sold in the
+/// history, largest suggested order first.
+pub fn purchase_plan(series: &[DemandSeries], settings: &PlanSettings) -> Vec<PlanRow> {
+    let mut rows: Vec<PlanRow> = series
+        .iter()
+        .filter(|s| s.total() > 0.0)
+        .map(|s| {
+            let (method, metrics) = best_method(s);
+            let horizon = settings.lead_time_days + settings.review_days;
+            let forecast = method.forecast(&s.daily, s.first_day, horizon);
+            let lead_time_demand: f64 = forecast[..settings.lead_time_days].iter().sum();
+            let cover_demand: f64 = forecast.iter().sum();
+
+            let error = metrics.map_or_else(|| mean(&s.daily), |m| m.mae);
+            let safety_stock = SAFETY_Z * MAE_TO_SIGMA * error * (settings.lead_time_days as f64).sqrt();
+            let reorder_point = lead_time_demand + safety_stock;
+            let shortfall = cover_demand + safety_stock - s.on_hand;
+            let suggested_order = if s.on_hand <= reorder_point && shortfall > 0.0 {
+                round_order(shortfall, &s.unit)
+            } else {
+                0.0
+            };
+
+            PlanRow {
+                name: s.name.clone(),
+                unit: s.unit.clone(),
+                on_hand: s.on_hand,
+                method,
+                metrics,
+                lead_time_demand,
+                safety_stock,
+                reorder_point,
+                suggested_order,
+            }
+        })
+        .collect();
+
+    rows.sort_by(|a, b| b.suggested_order.total_cmp(&a.suggested_order).then_with(|| a.name.cmp(&b.name)));
+    rows
+}
+
+pub fn plan_table(rows: &[PlanRow], settings: &PlanSettings) -> Table {
+    let mut table = Table::new(
+        format!("Purchase plan {} day lead time", settings.lead_time_days),
+        &[
+            "Product",
+            "Unit",
+            "On Hand",
+            "Method",
+            "MAE",
+            "MAPE %",
+            "Lead-time Demand",
+            "Safety Stock",
+            "Reorder Point",
+            "Suggested Order",
+        ],
+    );
+    for row in rows {
+        table.push(vec![
+            Cell::text(&row.name),
+            Cell::text(&row.unit),
+            Cell::Number(row.on_hand),
+            Cell::text(row.method.describe()),
+            Cell::optional(row.metrics.map(|m| m.mae)),
+            Cell::optional(row.metrics.and_then(|m| m.mape)),
+            Cell::Number(row.lead_time_demand),
+            Cell::Number(row.safety_stock),
+            Cell::Number(row.reorder_point),
+            Cell::Number(row.suggested_order),
+        ]);
+    }
+    table
+}
+
+/// Counted units are ordered whole; measured units to two decimals.
+fn round_order(quantity: f64, unit: &str) -> f64 {
+    if unit::allows_fraction(unit) {
+        (quantity * 100.0).ceil() / 100.0
+    } else {
+        quantity.ceil()
+    }
+}
+
+fn mean(values: &[f64]) -> f64 {
+    if values.is_empty() {
+        0.0
+    } else {
+        values.iter().sum::<f64>() / values.len() as f64
+    }
+}
+
+fn smooth(values: &[f64], alpha: f64) -> f64 {
+    values
+        .iter()
+        .skip(1)
+        .fold(values.first().copied().unwrap_or(0.0), |level, value| alpha * value + (1.0 - alpha) * level)
+}
+
+fn season_slot(day: i64) -> usize {
+    day.rem_euclid(SEASON_LENGTH as i64) as usize
+}
+
+/// Average demand per day of the week relative to overall average demand; all ones without demand.
+fn seasonal_indices(history: &[f64], first_day: i64) -> [f64; SEASON_LENGTH] {
+    let overall = mean(history);
+    let mut indices = [1.0; SEASON_LENGTH];
+    if overall <= 0.0 {
+        return indices;
+    }
+
+    let mut sums = [0.0; SEASON_LENGTH];
+    let mut counts = [0usize; SEASON_LENGTH];
+    for (idx, demand) in history.iter().enumerate() {
+        let slot = season_slot(first_day + idx as i64);
+        sums[slot] += demand;
+        counts[slot] += 1;
+    }
+    for slot in 0..SEASON_LENGTH {
+        if counts[slot] > 0 {
+            indices[slot] = sums[slot] / counts[slot] as f64 / overall;
+        }
+    }
+    indices
+}
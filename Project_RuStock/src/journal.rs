@@ -0,0 +1,267 @@
+//! Double-entry journal for the store's accountant.
+//!
+//! Sales, customer returns, purchases and stock adjustments become balanced
+//! journal entries posted to the accounts named in the `[accounts]` table of the
+//! settings file. Prices are tax-inclusive, so a sale's takings are split into
+//! net revenue and tax at the store's tax rate. Goods sold, returned or adjusted
+//! are valued at the weighted average purchase cost up to that moment; stock
+//! that was never purchased has no cost and posts no cost of goods sold. The
+//! journal exports as a CSV table or as an hledger / ledger-cli journal file.
+
+use crate::error::{Error, Result};
+use crate::export::{Cell, Table};
+use crate::timezone;
+use chrono::Local;
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Chart-of-accounts mapping; every account can be renamed in the settings file.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct AccountMap {
+    pub cash: String,
+    /// Card takings waiting to be settled by the card processor.
+    pub card: String,
+    pub inventory: String,
+    /// Suppliers are paid on account, so purchases credit this.
+    pub payable: String,
+    pub sales_tax: String,
+    pub revenue: String,
+    /// Contra-revenue account refunds are posted to.
+    pub returns: String,
+    pub cost_of_goods_sold: String,
+    /// Shrinkage and count corrections.
+    pub adjustments: String,
+}
+
+impl Default for AccountMap {
+    fn default() -> Self {
+        AccountMap {
+            cash: "Assets:Cash".to_string(),
+            card: "Assets:Card Receivable".to_string(),
+            inventory: "Assets:Inventory".to_string(),
+            payable: "Liabilities:Accounts Payable".to_string(),
+            sales_tax: "Liabilities:Sales Tax".to_string(),
+            revenue: "Income:Sales".to_string(),
+            returns: "Income:Sales Returns".to_string(),
+            cost_of_goods_sold: "Expenses:Cost of Goods Sold".to_string(),
+            adjustments: "Expenses:Inventory Adjustments".to_string(),
+        }
+    }
+}
+
+impl AccountMap {
+    pub fn validate(&self) -> Result<()> {
+        let accounts = [
+            &self.cash,
+            &self.card,
+            &self.inventory,
+            &self.payable,
+            &self.sales_tax,
+            &self.revenue,
+            &self.returns,
+            &self.cost_of_goods_sold,
+            &self.adjustments,
+        ];
+        // Ledger files separate the account from the amount with two spaces
+        if accounts.iter().any(|account| account.trim().is_empty() || account.contains("  ")) {
+            return Err(Error::validation("Account names cannot be empty or contain double spaces"));
+        }
+        Ok(())
+    }
+}
+
+/// A stored record that affects the books, with its amounts in the base currency.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Transaction {
+    Sale {
+        sale_number: i64,
+        timestamp: i64,
+        total: f64,
+        /// Part of the total paid by card; the rest was cash.
+        card: f64,
+        cost: Option<f64>,
+    },
+    Return {
+        sale_number: i64,
+        timestamp: i64,
+        product: String,
+        refund: f64,
+        cost: Option<f64>,
+    },
+    Purchase {
+        timestamp: i64,
+        product: String,
+        quantity: f64,
+        cost: f64,
+    },
+    Adjustment {
+        timestamp: i64,
+        product: String,
+        /// Stock units gained (positive) or lost (negative).
+        quantity: f64,
+        reason: String,
+        /// Cost of the units adjusted, signed like `quantity`.
+        value: Option<f64>,
+    },
+}
+
+/// One line of an entry; debits are positive and credits negative.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Posting {
+    pub account: String,
+    pub amount: f64,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct JournalEntry {
+    pub date: i64,
+    pub description: String,
+    pub postings: Vec<Posting>,
+}
+
+impl JournalEntry {
+    fn new(date: i64, description: String) -> Self {
+        JournalEntry { date, description, postings: Vec::new() }
+    }
+
+    /// Adds a posting unless it rounds to nothing.
+    fn post(&mut self, account: &str, amount: f64) {
+        let amount = round_cents(amount);
+        if amount != 0.0 {
+            self.postings.push(Posting { account: account.to_string(), amount });
+        }
+    }
+
+    pub fn is_balanced(&self) -> bool {
+        round_cents(self.postings.iter().map(|p| p.amount).sum()) == 0.0
+    }
+}
+
+fn round_cents(amount: f64) -> f64 {
+    let rounded = (amount * 100.0).round() / 100.0;
+    // Avoid printing -0.00
+    if rounded == 0.0 { 0.0 } else { rounded }
+}
+
+/// Tax-inclusive `gross` split into (net, tax) so the two add back up to it.
+pub fn split_tax(gross: f64, tax_rate: f64) -> (f64, f64) {
+    let net = round_cents(gross / (1.0 + tax_rate / 100.0));
+    (net, round_cents(gross - net))
+}
+
+/// Journal entries for `transactions`, in date order. Transactions that move no
+/// money, such as an adjustment of stock with no known cost, produce no entry.
+pub fn journal(transactions: &[Transaction], accounts: &AccountMap, tax_rate: f64) -> Vec<JournalEntry> {
+    let mut entries: Vec<JournalEntry> = transactions
+        .iter()
+        .map(|transaction| entry_for(transaction, accounts, tax_rate))
+        .filter(|entry| !entry.postings.is_empty())
+        .collect();
+    entries.sort_by_key(|entry| entry.date);
+    entries
+}
+
+fn entry_for(transaction: &Transaction, accounts: &AccountMap, tax_rate: f64) -> JournalEntry {
+    match transaction {
+        Transaction::Sale { sale_number, timestamp, total, card, cost } => {
+            let mut entry = JournalEntry::new(*timestamp, format!("Sale #{}", sale_number));
+            let (net, tax) = split_tax(*total, tax_rate);
+            entry.post(&accounts.card, *card);
+            entry.post(&accounts.cash, total - card);
+            entry.post(&accounts.revenue, -net);
+            entry.post(&accounts.sales_tax, -tax);
+            if let Some(cost) = cost {
+                entry.post(&accounts.cost_of_goods_sold, *cost);
+                entry.post(&accounts.inventory, -cost);
+            }
+            entry
+        }
+        Transaction::Return { sale_number, timestamp, product, refund, cost } => {
+            let mut entry = JournalEntry::new(*timestamp, format!("Return of {} from sale #{}", product, sale_number));
+            let (net, tax) = split_tax(*refund, tax_rate);
+            entry.post(&accounts.returns, net);
+            entry.post(&accounts.sales_tax, tax);
+            entry.post(&accounts.cash, -refund);
+            if let Some(cost) = cost {
+                entry.post(&accounts.inventory, *cost);
+                entry.post(&accounts.cost_of_goods_sold, -cost);
+            }
+            entry
+        }
+        Transaction::Purchase { timestamp, product, quantity, cost } => {
+            // A voided purchase is offset by one with negative quantity and cost
+            let description = if *quantity < 0.0 {
+                format!("Void of purchase of {} {}", -quantity, product)
+            } else {
+                format!("Purchase of {} {}", quantity, product)
+            };
+            let mut entry = JournalEntry::new(*timestamp, description);
+            entry.post(&accounts.inventory, *cost);
+            entry.post(&accounts.payable, -cost);
+            entry
+        }
+        Transaction::Adjustment { timestamp, product, quantity, reason, value } => {
+            let mut entry = JournalEntry::new(
+                *timestamp,
+                format!("Stock adjustment of {:+} {}: {}", quantity, product, reason),
+            );
+            if let Some(value) = value {
+                entry.post(&accounts.inventory, *value);
+                entry.post(&accounts.adjustments, -value);
+            }
+            entry
+        }
+    }
+}
+
+/// Balance of every account over `entries`, debits positive.
+pub fn trial_balance(entries: &[JournalEntry]) -> Vec<(String, f64)> {
+    let mut balances: BTreeMap<&str, f64> = BTreeMap::new();
+    for posting in entries.iter().flat_map(|entry| &entry.postings) {
+        *balances.entry(&posting.account).or_default() += posting.amount;
+    }
+    balances.into_iter().map(|(account, balance)| (account.to_string(), round_cents(balance))).collect()
+}
+
+/// The journal as one row per posting, for CSV or Excel export.
+pub fn journal_table(entries: &[JournalEntry]) -> Table {
+    let mut table = Table::new("Journal", &["Entry", "Date", "Description", "Account", "Debit", "Credit"]);
+    for (number, entry) in entries.iter().enumerate() {
+        for posting in &entry.postings {
+            table.push(vec![
+                Cell::Number((number + 1) as f64),
+                Cell::text(timezone::format_date(entry.date)),
+                Cell::text(&entry.description),
+                Cell::text(&posting.account),
+                Cell::optional((posting.amount > 0.0).then_some(posting.amount)),
+                Cell::optional((posting.amount < 0.0).then_some(-posting.amount)),
+            ]);
+        }
+    }
+    table
+}
+
+/// The journal in the plain-text format read by hledger and ledger-cli.
+pub fn to_ledger(entries: &[JournalEntry], currency: &str) -> String {
+    let mut out = String::new();
+    for entry in entries {
+        out.push_str(&format!("{} * {}\n", timezone::format_date(entry.date), entry.description));
+        for posting in &entry.postings {
+            out.push_str(&format!("    {:<40}  {:>12.2} {}\n", posting.account, posting.amount, currency));
+        }
+        out.push('\n');
+    }
+    out
+}
+
+/// Writes the ledger journal into `dir` under a timestamped name, like the
+/// table exports.
+pub fn save_ledger(entries: &[JournalEntry], currency: &str, dir: &Path) -> Result<PathBuf> {
+    fs::create_dir_all(dir)?;
+    let path = dir.join(format!("journal-{}.journal", Local::now().format("%Y%m%d-%H%M%S")));
+    fs::write(&path, to_ledger(entries, currency))?;
+    Ok(path)
+}
@@ -0,0 +1,31 @@
+pub mod analytics;
+pub mod attachment;
+pub mod auth;
+pub mod backup;
+pub mod bundle;
+pub mod config;
+pub mod currency;
+pub mod db;
+pub mod error;
+pub mod export;
+pub mod forecast;
+pub mod journal;
+pub mod loyalty;
+pub mod memory;
+pub mod notify;
+pub mod order;
+pub mod pdf;
+pub mod pos;
+pub mod pricing;
+pub mod product;
+pub mod purchase;
+pub mod receipt;
+pub mod repository;
+pub mod sale;
+pub mod search;
+pub mod seed;
+pub mod session;
+pub mod settings;
+pub mod timezone;
+pub mod unit;
+pub mod void;
@@ -0,0 +1,203 @@
+//! Customer records and the loyalty program.
+//!
+//! A sale rung up for a registered customer earns points for every unit of the
+//! base currency paid, multiplied by the customer's tier. Tiers are reached by
+//! lifetime value: everything the customer has paid, less refunds. Points can
+//! be redeemed at checkout as a discount worth `point_value` each. Points are
+//! kept in a ledger; returning goods takes back the points the refunded share
+//! earned and gives back the share of any points that paid for it. The program
+//! is configured in the `[loyalty]` table of the settings file.
+
+use crate::error::{Error, Result};
+use crate::export::{Cell, Table};
+use crate::timezone;
+use chrono::Utc;
+use serde::Deserialize;
+use uuid::Uuid;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Customer {
+    pub id: String,
+    pub name: String,
+    /// Used to find the customer at the till; unique when given.
+    pub phone: Option<String>,
+    pub email: Option<String>,
+    pub created_at: i64,
+}
+
+impl Customer {
+    pub fn new(name: String, phone: Option<String>, email: Option<String>) -> Self {
+        let blank_to_none = |value: Option<String>| value.map(|v| v.trim().to_string()).filter(|v| !v.is_empty());
+        Customer {
+            id: Uuid::new_v4().to_string(),
+            name: name.trim().to_string(),
+            phone: blank_to_none(phone),
+            email: blank_to_none(email),
+            created_at: Utc::now().timestamp(),
+        }
+    }
+
+    pub fn validate(&self) -> Result<()> {
+        if self.name.trim().is_empty() {
+            return Err(Error::validation("Customer name cannot be empty"));
+        }
+        if let Some(phone) = &self.phone {
+            if !phone.chars().any(|c| c.is_ascii_digit())
+                || phone.chars().any(|c| !(c.is_ascii_digit() || " +-()".contains(c)))
+            {
+                return Err(Error::validation(format!("'{}' is not a phone number", phone)));
+            }
+        }
+        if let Some(email) = &self.email {
+            if !email.contains('@') || email.contains(char::is_whitespace) {
+                return Err(Error::validation(format!("'{}' is not an email address", email)));
+            }
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Tier {
+    pub name: String,
+    /// Lifetime value at which the tier is reached.
+    pub threshold: f64,
+    /// Points earned are multiplied by this.
+    #[serde(default = "default_multiplier")]
+    pub multiplier: f64,
+}
+
+fn default_multiplier() -> f64 {
+    1.0
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct LoyaltyProgram {
+    /// Points earned per unit of the base currency paid; 0 stops earning.
+    pub points_per_unit: f64,
+    /// Discount one point is worth at checkout; 0 stops redemption.
+    pub point_value: f64,
+    pub tiers: Vec<Tier>,
+}
+
+impl Default for LoyaltyProgram {
+    fn default() -> Self {
+        let tier = |name: &str, threshold, multiplier| Tier { name: name.to_string(), threshold, multiplier };
+        LoyaltyProgram {
+            points_per_unit: 1.0,
+            point_value: 0.01,
+            tiers: vec![tier("Bronze", 0.0, 1.0), tier("Silver", 500.0, 1.25), tier("Gold", 2000.0, 1.5)],
+        }
+    }
+}
+
+impl LoyaltyProgram {
+    pub fn validate(&self) -> Result<()> {
+        if !self.points_per_unit.is_finite() || self.points_per_unit < 0.0 {
+            return Err(Error::validation("Loyalty points per unit cannot be negative"));
+        }
+        if !self.point_value.is_finite() || self.point_value < 0.0 {
+            return Err(Error::validation("Loyalty point value cannot be negative"));
+        }
+        for tier in &self.tiers {
+            if tier.name.trim().is_empty() {
+                return Err(Error::validation("Loyalty tier names cannot be empty"));
+            }
+            if !tier.threshold.is_finite() || tier.threshold < 0.0 {
+                return Err(Error::validation(format!("Tier '{}' needs a threshold of 0 or more", tier.name)));
+            }
+            if !tier.multiplier.is_finite() || tier.multiplier < 0.0 {
+                return Err(Error::validation(format!("Tier '{}' cannot have a negative multiplier", tier.name)));
+            }
+        }
+        Ok(())
+    }
+
+    /// The highest tier reached with `lifetime_value`, if any.
+    pub fn tier(&self, lifetime_value: f64) -> Option<&Tier> {
+        self.tiers
+            .iter()
+            .filter(|tier| tier.threshold <= lifetime_value + 1e-9)
+            .max_by(|a, b| a.threshold.total_cmp(&b.threshold))
+    }
+
+    /// The next tier above `lifetime_value`, if any.
+    pub fn next_tier(&self, lifetime_value: f64) -> Option<&Tier> {
+        self.tiers
+            .iter()
+            .filter(|tier| tier.threshold > lifetime_value + 1e-9)
+            .min_by(|a, b| a.threshold.total_cmp(&b.threshold))
+    }
+
+    /// Whole points earned by paying `amount` at the tier reached with `lifetime_value`.
+    pub fn points_earned(&self, amount: f64, lifetime_value: f64) -> i64 {
+        let multiplier = self.tier(lifetime_value).map_or(1.0, |tier| tier.multiplier);
+        // Rounded first so that amounts like 19.99 * 100 are not cut short
+        ((amount.max(0.0) * self.points_per_unit * multiplier * 1e6).round() / 1e6).floor() as i64
+    }
+
+    /// The discount `points` buy on a sale of `subtotal`, never more than the subtotal.
+    pub fn discount(&self, points: i64, subtotal: f64) -> f64 {
+        round_cents((points.max(0) as f64 * self.point_value).min(subtotal.max(0.0)))
+    }
+
+    /// The most points worth spending on a sale of `subtotal` from `balance`.
+    pub fn max_redeemable(&self, balance: i64, subtotal: f64) -> i64 {
+        if self.point_value <= 0.0 || balance <= 0 {
+            return 0;
+        }
+        let covering = (round_cents(subtotal.max(0.0)) / self.point_value - 1e-9).ceil() as i64;
+        balance.min(covering)
+    }
+}
+
+fn round_cents(amount: f64) -> f64 {
+    (amount * 100.0).round() / 100.0
+}
+
+/// A customer's standing in the loyalty program.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CustomerSummary {
+    pub customer: Customer,
+    pub sales: usize,
+    /// Paid over all sales, less refunds.
+    pub lifetime_value: f64,
+    pub points_balance: i64,
+    pub tier: Option<String>,
+}
+
+/// A past sale as it appears in a customer's history.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CustomerSale {
+    pub sale_number: i64,
+    pub timestamp: i64,
+    pub items: usize,
+    /// Paid after the loyalty discount.
+    pub total: f64,
+    pub discount: f64,
+    pub refunded: f64,
+    /// Net points the sale earned or spent, including reversals from returns.
+    pub points: i64,
+}
+
+/// A customer's sales, newest first, for export.
+pub fn history_table(customer: &Customer, sales: &[CustomerSale]) -> Table {
+    let mut table = Table::new(
+        format!("Purchase history of {}", customer.name),
+        &["Sale", "Date", "Items", "Discount", "Paid", "Refunded", "Points"],
+    );
+    for sale in sales {
+        table.push(vec![
+            Cell::Number(sale.sale_number as f64),
+            Cell::text(timezone::format_date(sale.timestamp)),
+            Cell::Number(sale.items as f64),
+            Cell::Number(sale.discount),
+            Cell::Number(sale.total),
+            Cell::Number(sale.refunded),
+            Cell::Number(sale.points as f64),
+        ]);
+    }
+    table
+}
@@ -1,16 +1,31 @@
-mod product;
-mod db;
-mod sale;
-mod purchase;
-mod auth;
-
 use std::io::{self, Write};
-use chrono::{DateTime, Utc};
-use crate::product::Product;
-use crate::sale::{Sale, SaleItem};
-use crate::db::Database;
-use crate::purchase::Purchase;
-use crate::auth::{Manager, AuthService};
+use std::sync::OnceLock;
+use std::path::{Path, PathBuf};
+use chrono::Utc;
+use rustock::product::{self, Product};
+use rustock::sale::{Sale, SaleItem};
+use rustock::db::{Database, DB_PATH};
+use rustock::error::Error;
+use rustock::purchase::Purchase;
+use rustock::auth::{self, AuthService, LoginOutcome, Manager, ManagerRemoval, SecurityPolicy};
+use rustock::bundle::BundleComponent;
+use rustock::order::{self, CustomerOrder, OrderKind};
+use rustock::pos::{self, Payment, PaymentMethod, Shift};
+use rustock::pricing::{self, ScheduleKind, ScheduledPrice};
+use rustock::receipt::{self, Receipt, ReceiptConfig, ReceiptFormat};
+use rustock::repository::Repository;
+use rustock::analytics::{self, DateRange};
+use rustock::attachment::{self, AttachmentConfig, OwnerKind};
+use rustock::config::env_or;
+use rustock::currency::{self, ExchangeRate};
+use rustock::export::{self, ExportFormat, Table};
+use rustock::forecast::{self, DemandSeries, Method, PlanSettings};
+use rustock::loyalty::{self, Customer};
+use rustock::notify::{Dispatcher, Event, Notifier, NotifyConfig};
+use rustock::unit::{self, ProductUnit};
+use rustock::void::OperationKind;
+use rustock::settings::{self, KEYS};
+use rustock::{backup, journal, search, seed, session, timezone};
 
 #[allow(dead_code)]
 fn clear_screen() {
@@ -19,13 +34,19 @@ fn clear_screen() {
 }
 
 fn display_logo() {
+    let store_name: String = settings::current().store_name.chars().take(40).collect();
     println!("╔══════════════════════════════════════════╗");
     println!("║             R u S T O C K                ║");
-    println!("║      Inventory Management System         ║");
+    println!("║{:^42}║", store_name);
     println!("╚══════════════════════════════════════════╝\n");
 }
 
-fn prompt(message: &str) -> String {
+/// What a screen returns: its result, or `Expired` when the session timed out
+/// while it waited for input and the screen was left without saving anything.
+type Screen<T = ()> = Result<T, session::Expired>;
+
+/// Reads a line of input, ahead of any session.
+fn read_line(message: &str) -> String {
     print!("{}", message);
     io::stdout().flush().unwrap();
     let mut input = String::new();
@@ -33,19 +54,91 @@ fn prompt(message: &str) -> String {
     input.trim().to_string()
 }
 
+/// Reads a line of input for a screen of the logged-in session.
+fn prompt(message: &str) -> Screen<String> {
+    let input = read_line(message);
+    if session::touch() {
+        // Whatever screen was waiting is abandoned so that nothing is saved for an expired session
+        return Err(session::Expired);
+    }
+    Ok(input)
+}
+
+/// Asks for a product by ID, SKU or search terms. Anything that is not an exact
+/// ID is searched and, when several products match, the user picks one from the
+/// ranked results. Returns the chosen product's ID, or the input unchanged when
+/// nothing was chosen.
+fn prompt_product_id(db: &Database, message: &str) -> Screen<String> {
+    let input = prompt(message)?;
+    if input.is_empty() || matches!(db.get_product(&input), Ok(Some(_))) {
+        return Ok(input);
+    }
+
+    let matches = match db.search_products(&input, search::DEFAULT_LIMIT) {
+        Ok(matches) => matches,
+        Err(e) => {
+            eprintln!("Error searching products: {}", e);
+            return Ok(input);
+        }
+    };
+    if let Some(product) = matches
+        .iter()
+        .find(|p| p.sku.as_deref().is_some_and(|sku| sku.eq_ignore_ascii_case(&input)))
+    {
+        return Ok(product.id.clone());
+    }
+    Ok(match matches.as_slice() {
+        [] => {
+            println!("No products match '{}'.", input);
+            input
+        }
+        [product] => {
+            println!("→ {}", describe_match(product));
+            product.id.clone()
+        }
+        _ => {
+            for (i, product) in matches.iter().enumerate() {
+                println!("  [{}] {}", i + 1, describe_match(product));
+            }
+            prompt("Select product number (Enter to cancel): ")?
+                .parse::<usize>()
+                .ok()
+                .and_then(|n| n.checked_sub(1))
+                .and_then(|i| matches.get(i))
+                .map_or(input, |product| product.id.clone())
+        }
+    })
+}
+
+fn describe_match(product: &Product) -> String {
+    let mut line = product.name.clone();
+    if let Some(sku) = &product.sku {
+        line.push_str(&format!(" [{}]", sku));
+    }
+    line.push_str(&format!(" - {} {} in stock", product.quantity, product.unit));
+    if product.is_archived() {
+        line.push_str(" (archived)");
+    }
+    line
+}
+
 #[allow(dead_code)]
-fn add_product(db: &Database) {
+fn add_product(db: &Database) -> Screen {
     clear_screen();
     display_logo();
     println!("\nAdd New Product");
     println!("---------------");
 
-    let name = prompt("Product Name: ");
-    let description = prompt("Description: ");
-    let price = prompt("Price: ").parse::<f64>().unwrap_or(-1.0);
-    let quantity = prompt("Quantity: ").parse::<i32>().unwrap_or(-1);
+    let name = prompt("Product Name: ")?;
+    let description = prompt("Description: ")?;
+    let price = prompt("Price: ")?.parse::<f64>().unwrap_or(-1.0);
+    let quantity = prompt("Quantity: ")?.parse::<f64>().unwrap_or(-1.0);
+    let sku = prompt("SKU (optional): ")?;
+    let tags = prompt("Tags (comma-separated, optional): ")?;
 
-    let product = Product::new(name, description, price, quantity);
+    let mut product = Product::new(name, description, price, quantity);
+    product.sku = (!sku.is_empty()).then_some(sku);
+    product.tags = product::parse_tags(&tags);
 
     match product.validate() {
         Ok(()) => {
@@ -58,10 +151,11 @@ fn add_product(db: &Database) {
     }
 
     println!("\nPress Enter to continue...");
-    prompt("");
+    prompt("")?;
+    Ok(())
 }
 
-fn list_products(db: &Database) {
+fn list_products(db: &Database) -> Screen {
     clear_screen();
     display_logo();
     println!("╔══════════════════════════════════════════╗");
@@ -76,55 +170,115 @@ fn list_products(db: &Database) {
                 for product in &products {
                     println!("┌─ {} ─", product.name);
                     println!("│  ID: {}", product.id);
-                    println!("│  Price: ${:.2}", product.price);
-                    println!("│  Stock Level: {}", product.quantity);
+                    if let Some(sku) = &product.sku {
+                        println!("│  SKU: {}", sku);
+                    }
+                    println!("│  Price: {} per {}", money(product.price), product.unit);
+                    println!("│  Stock Level: {} {}", product.quantity, product.unit);
+                    if !product.tags.is_empty() {
+                        println!("│  Tags: {}", product.tags.join(", "));
+                    }
+                    if let Ok(components) = db.get_bundle_components(&product.id) {
+                        if !components.is_empty() {
+                            let available = db.available_quantity(product).unwrap_or(product.quantity);
+                            println!("│  Bundle of {} products, {} available incl. buildable", components.len(), available);
+                        }
+                    }
                     if !product.description.is_empty() {
                         println!("│  Description: {}", product.description);
                     }
+                    let attachments = db.get_attachments(OwnerKind::Product, &product.id).unwrap_or_default();
+                    if !attachments.is_empty() {
+                        let images = attachments.iter().filter(|a| a.is_image()).count();
+                        println!("│  Attachments: {} ({} image(s))", attachments.len(), images);
+                    }
                     println!("└──────────────────────────────────────");
                 }
                 println!("\nTotal Items in Registry: {}", products.len());
+
+                let catalogue: Vec<_> = products
+                    .into_iter()
+                    .map(|product| {
+                        let attachments = db.get_attachments(OwnerKind::Product, &product.id).unwrap_or_default();
+                        (product, attachments)
+                    })
+                    .collect();
+                offer_export(&attachment::catalogue_table(&catalogue))?;
             }
         }
         Err(e) => println!("Error fetching cargo items: {}", e),
     }
 
-    prompt("\nPress Enter to continue...");
+    prompt("\nPress Enter to continue...")?;
+    Ok(())
 }
 
-fn edit_product(db: &Database) {
+fn edit_product(db: &mut Database) -> Screen {
     clear_screen();
     display_logo();
     println!("\nEdit Product");
     println!("-----------");
 
-    let id = prompt("Enter Product ID: ");
+    let id = prompt_product_id(db, "Enter Product (ID, SKU or search): ")?;
     
     match db.get_product(&id) {
         Ok(Some(mut product)) => {
             println!("\nCurrent Product Details:");
             println!("Name: {}", product.name);
             println!("Description: {}", product.description);
-            println!("Price: ${:.2}", product.price);
-            println!("Quantity: {}", product.quantity);
+            println!("Price: {}", money(product.price));
+            println!("Quantity: {} {}", product.quantity, product.unit);
+            println!("SKU: {}", product.sku.as_deref().unwrap_or("-"));
+            println!("Tags: {}", product.tags.join(", "));
             println!("\nEnter new details (press Enter to keep current value):");
 
-            let name = prompt("New Name: ");
-            let description = prompt("New Description: ");
-            let price_str = prompt("New Price: ");
-            let quantity_str = prompt("New Quantity: ");
+            let name = prompt("New Name: ")?;
+            let description = prompt("New Description: ")?;
+            let price_str = prompt("New Price: ")?;
+            let quantity_str = prompt("New Quantity: ")?;
+            let sku = prompt("New SKU (- to clear): ")?;
+            let tags = prompt("New Tags (comma-separated, - to clear): ")?;
 
             let name = if name.is_empty() { None } else { Some(name) };
             let description = if description.is_empty() { None } else { Some(description) };
             let price = price_str.parse::<f64>().ok();
-            let quantity = quantity_str.parse::<i32>().ok();
+            // Stock changes are recorded as adjustments so the books can account for them
+            let change = quantity_str
+                .parse::<f64>()
+                .ok()
+                .map(|quantity| unit::round_quantity(quantity - product.quantity))
+                .filter(|change| *change != 0.0);
+            let reason = match change {
+                Some(_) => prompt("Reason for the stock change (e.g. damaged, count correction): ")?,
+                None => String::new(),
+            };
 
-            product.update(name, description, price, quantity);
+            let previous_quantity = product.quantity;
+            product.update(name, description, price, None);
+            match sku.as_str() {
+                "" => {}
+                "-" => product.sku = None,
+                _ => product.sku = Some(sku),
+            }
+            match tags.as_str() {
+                "" => {}
+                "-" => product.tags.clear(),
+                _ => product.tags = product::parse_tags(&tags),
+            }
 
             match product.validate() {
                 Ok(()) => {
-                    match db.update_product(&product) {
-                        Ok(()) => println!("\nProduct updated successfully!"),
+                    let result = db.update_product(&product).and_then(|()| match change {
+                        Some(change) => db.adjust_stock(&product.id, change, &reason).map(|quantity| {
+                            product.quantity = quantity;
+                        }),
+                        None => Ok(()),
+                    });
+                    match result {
+                        Ok(()) => {
+                            println!("\nProduct updated successfully!");
+                            notify(notifier().stock_event(&product, previous_quantity).into_iter().collect());
+                        }
                         Err(e) => eprintln!("\nError updating product: {}", e),
                     }
                 }
@@ -136,33 +290,37 @@ fn edit_product(db: &Database) {
     }
 
     println!("\nPress Enter to continue...");
-    prompt("");
+    prompt("")?;
+    Ok(())
 }
 
-fn delete_product(db: &mut Database) {
+fn archive_product(db: &Database) -> Screen {
     clear_screen();
     display_logo();
-    println!("\nDelete Product");
-    println!("-------------");
+    println!("\nArchive Product");
+    println!("---------------");
 
-    let id = prompt("Enter Product ID: ");
+    let id = prompt_product_id(db, "Enter Product (ID, SKU or search): ")?;
     
     match db.get_product(&id) {
+        Ok(Some(product)) if product.is_archived() => println!("\nProduct is already archived."),
         Ok(Some(product)) => {
             println!("\nProduct Details:");
             println!("Name: {}", product.name);
             println!("Description: {}", product.description);
-            println!("Price: ${:.2}", product.price);
+            println!("Price: {}", money(product.price));
             println!("Quantity: {}", product.quantity);
 
-            let confirm = prompt("\nAre you sure you want to delete this product? (y/N): ");
+            println!("\nArchived products are hidden from sales and the inventory;");
+            println!("their sales and purchase history is kept.");
+            let confirm = prompt("\nAre you sure you want to archive this product? (y/N): ")?;
             if confirm.to_lowercase() == "y" {
-                match db.delete_product(&id) {
-                    Ok(()) => println!("\nProduct deleted successfully!"),
-                    Err(e) => eprintln!("\nError deleting product: {}", e),
+                match db.archive_product(&id) {
+                    Ok(()) => println!("\nProduct archived successfully!"),
+                    Err(e) => eprintln!("\nError archiving product: {}", e),
                 }
             } else {
-                println!("\nDeletion cancelled.");
+                println!("\nArchiving cancelled.");
             }
         }
         Ok(None) => println!("\nProduct not found."),
@@ -170,639 +328,3103 @@ fn delete_product(db: &mut Database) {
     }
 
     println!("\nPress Enter to continue...");
-    prompt("");
+    prompt("")?;
+    Ok(())
 }
 
-fn record_sale(db: &mut Database) {
+fn archived_products(db: &mut Database) -> Screen {
     clear_screen();
     display_logo();
     println!("╔══════════════════════════════════════════╗");
-    println!("║             NEW TRADE OUT                ║");
+    println!("║            ARCHIVED CARGO                ║");
     println!("╚══════════════════════════════════════════╝\n");
 
-    
-    let mut sale_items = Vec::new();
-    loop {
-        match db.get_all_products() {
-            Ok(products) => {   
-                if products.is_empty() {
-                    println!("No products available.");
-                    println!("Please add products through Supply Chain → New Trade In first.");
-                    println!("\nPress Enter to continue...");
-                    prompt("");
-                    return;
-                }
-                println!("\nAvailable Products:");
-                println!("------------------");
-                for product in &products {
-                    println!("ID: {}", product.id);
-                    println!("Name: {}", product.name);
-                    println!("Price: ${:.2}", product.price);
-                    println!("Available Quantity: {}", product.quantity);
-                    println!("------------------");
-                }
-
-                let product_id = prompt("Enter Product ID (or press Enter to finish): ");
-                if product_id.is_empty() {
-                    break;
-                }
-
-                if let Ok(Some(product)) = db.get_product(&product_id) {
-                    let quantity_str = prompt("Enter quantity: ");
-                    if let Ok(quantity) = quantity_str.parse::<i32>() {
-                        if quantity <= 0 {
-                            println!("Quantity must be positive.");
-                            continue;
-                        }
-                        if quantity > product.quantity {
-                            println!("Insufficient stock. Available: {}", product.quantity);
-                            continue;
-                        }
-
-                        let unit_price = product.price;
-                        let total_price = unit_price * quantity as f64;
+    let products = match db.get_archived_products() {
+        Ok(products) => products,
+        Err(e) => {
+            eprintln!("Error fetching archived cargo: {}", e);
+            prompt("\nPress Enter to continue...")?;
+            return Ok(());
+        }
+    };
 
-                        sale_items.push(SaleItem {
-                            product_id: product.id,
-                            quantity,
-                            unit_price,
-                            total_price,
-                        });
+    if products.is_empty() {
+        println!("No archived cargo.");
+        prompt("\nPress Enter to continue...")?;
+        return Ok(());
+    }
 
-                        println!("Item added to sale.");
-                    } else {
-                        println!("Invalid quantity.");
-                    }
-                } else {
-                    println!("Product not found.");
-                }
-            }
-            Err(e) => {
-                println!("DEBUG: Error fetching products: {}", e);
-                eprintln!("Error fetching products: {}", e);
-                println!("Press Enter to continue...");
-                prompt("");
-                return;
-            }
+    for product in &products {
+        println!("┌─ {} ─", product.name);
+        println!("│  ID: {}", product.id);
+        println!("│  Price: {}", money(product.price));
+        println!("│  Stock Level: {}", product.quantity);
+        if let Some(archived_at) = product.archived_at {
+            println!("│  Archived: {}", format_timestamp(archived_at));
         }
+        println!("└──────────────────────────────────────");
     }
 
-    if sale_items.is_empty() {
-        println!("No items added to sale.");
-        return;
+    println!("\n[R] Restore  [D] Delete permanently  [Enter] Return");
+    let action = prompt("Choice: ")?.to_lowercase();
+    if action != "r" && action != "d" {
+        return Ok(());
     }
 
-    let sale = Sale::new(sale_items);
-    
-    println!("\nSale Summary:");
-    println!("-------------");
-    for item in &sale.items {
-        if let Ok(Some(product)) = db.get_product(&item.product_id) {
-            println!("Product: {}", product.name);
-            println!("Quantity: {}", item.quantity);
-            println!("Unit Price: ${:.2}", item.unit_price);
-            println!("Total: ${:.2}", item.total_price);
-            println!("-------------");
+    let id = prompt_product_id(db, "Enter Product (ID, SKU or search): ")?;
+    if !products.iter().any(|p| p.id == id) {
+        println!("\nNo archived product with that ID.");
+    } else if action == "r" {
+        match db.restore_product(&id) {
+            Ok(()) => println!("\nProduct restored to the active inventory."),
+            Err(e) => eprintln!("\nError restoring product: {}", e),
         }
-    }
-    println!("Total Amount: ${:.2}", sale.total_amount);
-
-    if prompt("\nConfirm sale? (y/N): ").to_lowercase() == "y" {
-        match sale.validate() {
-            Ok(()) => {
-                match db.record_sale(&sale) {
-                    Ok(()) => println!("\nSale recorded successfully!"),
-                    Err(e) => eprintln!("\nError recording sale: {}", e),
-                }
-            }
-            Err(e) => eprintln!("\nValidation error: {}", e),
+    } else if prompt("\nThis cannot be undone. Delete permanently? (y/N): ")?.to_lowercase() == "y" {
+        match db.delete_product(&id) {
+            Ok(()) => println!("\nProduct deleted permanently."),
+            Err(e) => eprintln!("\nCannot delete product: {}", e),
         }
     } else {
-        println!("\nSale cancelled.");
+        println!("\nDeletion cancelled.");
     }
 
-    println!("\nPress Enter to continue...");
-    prompt("");
+    prompt("\nPress Enter to continue...")?;
+    Ok(())
 }
 
-fn view_sales(db: &Database) {
+fn manage_units(db: &Database) -> Screen {
     clear_screen();
     display_logo();
     println!("╔══════════════════════════════════════════╗");
-    println!("║          TRADING HISTORY                 ║");
+    println!("║            UNITS OF MEASURE              ║");
     println!("╚══════════════════════════════════════════╝\n");
 
-    match db.get_all_sales() {
-        Ok(sales) => {
-            if sales.is_empty() {
-                println!("No trades recorded yet.");
-            } else {
-                for (product_name, quantity, total_price, sale_date) in sales {
-                    println!("┌─ Trade Details ─");
-                    println!("│  Product: {}", product_name);
-                    println!("│  Quantity: {}", quantity);
-                    println!("│  Total Price: ${:.2}", total_price);
-                    println!("│  Date: {}", sale_date);
-                    println!("└──────────────────────────────────────\n");
+    let id = prompt_product_id(db, "Enter Product (ID, SKU or search): ")?;
+    let product = match db.get_product(&id) {
+        Ok(Some(product)) => product,
+        Ok(None) => {
+            println!("\nProduct not found.");
+            prompt("\nPress Enter to continue...")?;
+            return Ok(());
+        }
+        Err(e) => {
+            eprintln!("\nError fetching product: {}", e);
+            prompt("\nPress Enter to continue...")?;
+            return Ok(());
+        }
+    };
+
+    loop {
+        clear_screen();
+        display_logo();
+        println!("Units for {} (stock kept in {})\n", product.name, product.unit);
+        match db.get_product_units(&product.id) {
+            Ok(units) if units.is_empty() => println!("No other units. Sold and bought by the {} only.", product.unit),
+            Ok(units) => {
+                for unit in &units {
+                    println!("  1 {} = {} {}", unit.unit, unit.factor, product.unit);
                 }
             }
+            Err(e) => println!("Error fetching units: {}", e),
         }
-        Err(_e) => {
-            println!("No trades recorded yet.");
-            println!("Press Enter to continue...");
-            prompt("");
-            return;
+
+        println!("\n[A] Add or change a unit  [R] Remove a unit  [Enter] Return");
+        match prompt("Choice: ")?.to_lowercase().as_str() {
+            "a" => {
+                let name = prompt("Unit name (e.g. case, pack, g): ")?;
+                let factor = prompt(&format!("How many {} in one {}? ", product.unit, name.trim()))?
+                    .parse::<f64>()
+                    .unwrap_or(0.0);
+                match db.set_product_unit(&ProductUnit::new(product.id.clone(), &name, factor)) {
+                    Ok(()) => println!("\nUnit saved."),
+                    Err(e) => eprintln!("\nError saving unit: {}", e),
+                }
+            }
+            "r" => {
+                let name = prompt("Unit to remove: ")?;
+                match db.remove_product_unit(&product.id, &name) {
+                    Ok(()) => println!("\nUnit removed."),
+                    Err(e) => eprintln!("\nError removing unit: {}", e),
+                }
+            }
+            _ => break,
         }
+        prompt("\nPress Enter to continue...")?;
     }
-
-    println!("Press Enter to continue...");
-    prompt("");
-}
-
-fn display_main_menu() {
-    println!("╔══════════════════════════════════════════╗");
-    println!("║            RuSTOCK CONSOLE               ║");
-    println!("╠══════════════════════════════════════════╣");
-    println!("║  [1] Cargo Management                    ║");
-    println!("║  [2] Trading Operations                  ║");
-    println!("║  [3] Supply Chain                        ║");
-    println!("║  [4] Reports                             ║");
-    println!("║  [5] Manager Administration              ║");
-    println!("║  [6] Exit Terminal                       ║");
-    println!("╚══════════════════════════════════════════╝");
-    println!("\nEnter your choice (1-6): ");
-}
-
-fn display_product_menu() {
-    println!("╔══════════════════════════════════════════╗");
-    println!("║            CARGO MANAGEMENT              ║");
-    println!("╠══════════════════════════════════════════╣");
-    println!("║  [1] View Cargo Registry                 ║");
-    println!("║  [2] Modify Cargo                        ║");
-    println!("║  [3] Remove Cargo                        ║");
-    println!("║  [4] Return to Console                   ║");
-    println!("╚══════════════════════════════════════════╝");
-    println!("\nEnter your choice (1-4): ");
-}
-
-fn display_sales_menu() {
-    println!("╔══════════════════════════════════════════╗");
-    println!("║          TRADING OPERATIONS              ║");
-    println!("╠══════════════════════════════════════════╣");
-    println!("║  [1] New Trade Out                       ║");
-    println!("║  [2] View Trade History                  ║");
-    println!("║  [3] Return to Console                   ║");
-    println!("╚══════════════════════════════════════════╝");
-    println!("\nEnter your choice (1-3): ");
-}
-
-fn display_reports_menu() {
-    println!("╔══════════════════════════════════════════╗");
-    println!("║               REPORTS                    ║");
-    println!("╠══════════════════════════════════════════╣");
-    println!("║  [1] Inventory Report                    ║");
-    println!("║  [2] Sales Report                        ║");
-    println!("║  [3] Purchase History Report             ║");
-    println!("║  [4] Return to Console                   ║");
-    println!("╚══════════════════════════════════════════╝");
-    println!("\nEnter your choice (1-4): ");
-}
-
-fn display_purchase_menu() {
-    println!("╔══════════════════════════════════════════╗");
-    println!("║            SUPPLY CHAIN                  ║");
-    println!("╠══════════════════════════════════════════╣");
-    println!("║  [1] New Trade In                        ║");
-    println!("║  [2] View Supply History                 ║");
-    println!("║  [3] Return to Console                   ║");
-    println!("╚══════════════════════════════════════════╝");
-    println!("\nEnter your choice (1-3): ");
+    Ok(())
 }
 
-fn display_manager_menu() {
+fn manage_bundle(db: &mut Database) -> Screen {
+    clear_screen();
+    display_logo();
     println!("╔══════════════════════════════════════════╗");
-    println!("║         MANAGER ADMINISTRATION           ║");
-    println!("╠══════════════════════════════════════════╣");
-    println!("║  [1] Add New Manager                     ║");
-    println!("║  [2] View All Managers                   ║");
-    println!("║  [3] Activate/Deactivate Manager         ║");
-    println!("║  [4] Return to Console                   ║");
-    println!("╚══════════════════════════════════════════╝");
-    println!("\nEnter your choice (1-4): ");
-}
+    println!("║             BUNDLES & KITS               ║");
+    println!("╚══════════════════════════════════════════╝\n");
 
-#[allow(dead_code)]
-fn display_product_details(product: &Product) {
-    println!("╔══════════════════════════════════════════╗");
-    println!("║            CARGO DETAILS                 ║");
-    println!("╠══════════════════════════════════════════╣");
-    println!("║  ID: {}", product.id);
-    println!("║  Name: {}", product.name);
-    println!("║  Description: {}", product.description);
-    println!("║  Price: ${:.2}", product.price);
-    println!("║  Stock Level: {}", product.quantity);
-    println!("╚══════════════════════════════════════════╝");
-}
+    let id = prompt_product_id(db, "Enter Bundle Product (ID, SKU or search): ")?;
+    let bundle = match db.get_product(&id) {
+        Ok(Some(product)) => product,
+        Ok(None) => {
+            println!("\nProduct not found.");
+            prompt("\nPress Enter to continue...")?;
+            return Ok(());
+        }
+        Err(e) => {
+            eprintln!("\nError fetching product: {}", e);
+            prompt("\nPress Enter to continue...")?;
+            return Ok(());
+        }
+    };
 
-fn handle_product_menu(db: &mut Database) {
     loop {
         clear_screen();
         display_logo();
-        display_product_menu();
+        let bundle = db.get_product(&bundle.id).ok().flatten().unwrap_or_else(|| bundle.clone());
+        println!("Bill of materials for {} ({} assembled)\n", bundle.name, bundle.quantity);
+        match db.get_bundle_components(&bundle.id) {
+            Ok(components) if components.is_empty() => println!("No components. Add some to make this product a bundle."),
+            Ok(components) => {
+                for component in &components {
+                    match db.get_product(&component.component_id) {
+                        Ok(Some(product)) => println!(
+                            "  {} {} {}  (in stock: {} {})  [{}]",
+                            component.quantity, product.unit, product.name, product.quantity, product.unit, product.id
+                        ),
+                        _ => println!("  {} x {}", component.quantity, component.component_id),
+                    }
+                }
+                if let Ok(available) = db.available_quantity(&bundle) {
+                    println!("\nAvailable to sell: {} (assembled and buildable)", available);
+                }
+            }
+            Err(e) => println!("Error fetching components: {}", e),
+        }
 
-        let choice = prompt("");
-        match choice.trim() {
-            "1" => list_products(db),
-            "2" => edit_product(db),
-            "3" => delete_product(db),
-            "4" => break,
-            _ => {
-                println!("Invalid option. Press Enter to continue...");
-                prompt("");
+        println!("\n[A] Add or change a component  [R] Remove a component  [B] Assemble  [Enter] Return");
+        match prompt("Choice: ")?.to_lowercase().as_str() {
+            "a" => {
+                let component_id = prompt_product_id(db, "Component Product (ID, SKU or search): ")?;
+                let quantity = prompt("Quantity per bundle (in the component's stock unit): ")?
+                    .parse::<f64>()
+                    .unwrap_or(0.0);
+                match db.set_bundle_component(&BundleComponent::new(bundle.id.clone(), component_id, quantity)) {
+                    Ok(()) => println!("\nComponent saved."),
+                    Err(e) => eprintln!("\nError saving component: {}", e),
+                }
             }
+            "r" => {
+                let component_id = prompt_product_id(db, "Component Product to remove (ID, SKU or search): ")?;
+                match db.remove_bundle_component(&bundle.id, &component_id) {
+                    Ok(()) => println!("\nComponent removed."),
+                    Err(e) => eprintln!("\nError removing component: {}", e),
+                }
+            }
+            "b" => {
+                let count = prompt("How many bundles to assemble? ")?.parse::<f64>().unwrap_or(0.0);
+                match db.assemble_bundle(&bundle.id, count) {
+                    Ok(()) => println!("\n✅ Assembled {} x {}.", count, bundle.name),
+                    Err(e) => eprintln!("\nError assembling bundle: {}", e),
+                }
+            }
+            _ => break,
         }
+        prompt("\nPress Enter to continue...")?;
     }
+    Ok(())
 }
 
-fn handle_sales_menu(db: &mut Database) {
-    loop {
-        clear_screen();
-        display_logo();
-        display_sales_menu();
+fn product_attachments(db: &mut Database) -> Screen {
+    clear_screen();
+    display_logo();
+    println!("\nProduct Attachments");
+    println!("-------------------");
 
-        let choice = prompt("");
-        println!("DEBUG: User entered choice: '{}'", choice.trim());
-        match choice.trim() {
-            "1" => {
-                println!("DEBUG: Calling record_sale...");
-                record_sale(db);
-                println!("DEBUG: Returned from record_sale");
-            },
-            "2" => view_sales(db),
-            "3" => break,
-            _ => {
-                println!("Invalid option. Press Enter to continue...");
-                prompt("");
-            }
+    let id = prompt_product_id(db, "Enter Product (ID, SKU or search): ")?;
+    match db.get_product(&id) {
+        Ok(Some(product)) => manage_attachments(db, OwnerKind::Product, &product.id, &product.name)?,
+        Ok(None) => {
+            println!("\nProduct not found.");
+            prompt("\nPress Enter to continue...")?;
+        }
+        Err(e) => {
+            eprintln!("\nError fetching product: {}", e);
+            prompt("\nPress Enter to continue...")?;
         }
     }
+    Ok(())
 }
 
-fn handle_reports_menu(db: &mut Database) {
+/// Lists the attachments of one product or purchase and lets the user add,
+/// save copies of or remove them.
+fn manage_attachments(db: &mut Database, owner_kind: OwnerKind, owner_id: &str, title: &str) -> Screen {
     loop {
         clear_screen();
         display_logo();
-        display_reports_menu();
+        println!("Attachments: {}\n", title);
 
-        let choice = prompt("");
-        match choice.trim() {
-            "1" => list_products(db),
-            "2" => view_sales(db),
-            "3" => view_purchases(db),
-            "4" => break,
-            _ => {
-                println!("Invalid option. Press Enter to continue...");
-                prompt("");
+        let attachments = match db.get_attachments(owner_kind, owner_id) {
+            Ok(attachments) => attachments,
+            Err(e) => {
+                eprintln!("Error fetching attachments: {}", e);
+                prompt("\nPress Enter to continue...")?;
+                return Ok(());
             }
+        };
+        if attachments.is_empty() {
+            println!("No attachments.");
+        }
+        for (i, attachment) in attachments.iter().enumerate() {
+            println!(
+                "  [{}] {:<28} {:<18} {:>9}  {}",
+                i + 1,
+                attachment.file_name,
+                attachment.content_type,
+                attachment::format_size(attachment.size),
+                format_timestamp(attachment.created_at)
+            );
         }
-    }
-}
 
-fn record_purchase(db: &mut Database) {
+        println!("\n[A] Attach file  [S] Save a copy  [R] Remove  [Enter] Return");
+        match prompt("Choice: ")?.to_lowercase().as_str() {
+            "a" => {
+                let path = prompt("File path: ")?;
+                let result = std::fs::read(&path)
+                    .map_err(Error::from)
+                    .and_then(|data| db.add_attachment(owner_kind, owner_id, &path, &data, &AttachmentConfig::from_env()));
+                match result {
+                    Ok(attachment) => println!("\nAttached {} ({}).", attachment.file_name, attachment::format_size(attachment.size)),
+                    Err(e) => eprintln!("\nError attaching file: {}", e),
+                }
+            }
+            "s" => {
+                let Some(attachment) = prompt("Attachment number: ")?
+                    .parse::<usize>()
+                    .ok()
+                    .and_then(|n| n.checked_sub(1))
+                    .and_then(|i| attachments.get(i))
+                else {
+                    println!("\nInvalid selection.");
+                    prompt("\nPress Enter to continue...")?;
+                    continue;
+                };
+                let dir = prompt("Save to directory [.]: ")?;
+                let path = Path::new(if dir.is_empty() { "." } else { &dir }).join(&attachment.file_name);
+                if path.exists() && prompt(&format!("{} exists. Overwrite? (y/N): ", path.display()))?.to_lowercase() != "y" {
+                    continue;
+                }
+                match db.attachment_data(&attachment.id).and_then(|data| std::fs::write(&path, data).map_err(Error::from)) {
+                    Ok(()) => println!("\nSaved to {}", path.display()),
+                    Err(e) => eprintln!("\nError saving attachment: {}", e),
+                }
+            }
+            "r" => {
+                let selected = prompt("Attachment number: ")?
+                    .parse::<usize>()
+                    .ok()
+                    .and_then(|n| n.checked_sub(1))
+                    .and_then(|i| attachments.get(i));
+                match selected {
+                    Some(attachment) => match db.remove_attachment(&attachment.id) {
+                        Ok(()) => println!("\nRemoved {}.", attachment.file_name),
+                        Err(e) => eprintln!("\nError removing attachment: {}", e),
+                    },
+                    None => println!("\nInvalid selection."),
+                }
+            }
+            _ => return Ok(()),
+        }
+        prompt("\nPress Enter to continue...")?;
+    }
+}
+
+/// Offers to attach the supplier's invoice to a purchase just recorded.
+fn offer_invoice_attachment(db: &mut Database, purchase_id: &str) -> Screen {
+    let path = prompt("Attach supplier invoice (file path, Enter to skip): ")?;
+    if path.is_empty() {
+        return Ok(());
+    }
+    let result = std::fs::read(&path)
+        .map_err(Error::from)
+        .and_then(|data| db.add_attachment(OwnerKind::Purchase, purchase_id, &path, &data, &AttachmentConfig::from_env()));
+    match result {
+        Ok(attachment) => println!("Attached {}.", attachment.file_name),
+        Err(e) => eprintln!("Error attaching invoice: {}", e),
+    }
+    Ok(())
+}
+
+fn apply_due_price_changes(db: &Database) {
+    if let Err(e) = db.apply_scheduled_prices(Utc::now().timestamp()) {
+        eprintln!("Error applying scheduled price changes: {}", e);
+    }
+}
+
+fn format_timestamp(timestamp: i64) -> String {
+    timezone::format_datetime(timestamp)
+}
+
+fn money(amount: f64) -> String {
+    currency::format_base(amount)
+}
+
+fn schedule_price_change(db: &mut Database) -> Screen {
+    clear_screen();
+    display_logo();
+    println!("\nSchedule Price Change");
+    println!("---------------------");
+
+    let id = prompt_product_id(db, "Enter Product (ID, SKU or search): ")?;
+    let product = match db.get_product(&id) {
+        Ok(Some(product)) => product,
+        Ok(None) => {
+            println!("\nProduct not found.");
+            prompt("\nPress Enter to continue...")?;
+            return Ok(());
+        }
+        Err(e) => {
+            eprintln!("\nError fetching product: {}", e);
+            prompt("\nPress Enter to continue...")?;
+            return Ok(());
+        }
+    };
+
+    println!("\n{} - current price {}", product.name, money(product.price));
+    match db.get_open_price_schedules(Some(&product.id)) {
+        Ok(schedules) if !schedules.is_empty() => {
+            println!("\nUpcoming changes:");
+            for schedule in &schedules {
+                let until = schedule.ends_at.map(format_timestamp).unwrap_or_else(|| "-".to_string());
+                println!(
+                    "  {} {} from {} until {} [{}]",
+                    schedule.kind.as_str(),
+                    money(schedule.price),
+                    format_timestamp(schedule.starts_at),
+                    until,
+                    schedule.status.as_str()
+                );
+            }
+        }
+        Ok(_) => {}
+        Err(e) => eprintln!("\nError fetching schedules: {}", e),
+    }
+
+    println!("\nType:");
+    println!("1. Permanent price change");
+    println!("2. Temporary markdown");
+    let kind = match prompt("\nEnter your choice (1-2): ")?.as_str() {
+        "1" => ScheduleKind::Permanent,
+        "2" => ScheduleKind::Markdown,
+        _ => {
+            println!("\nInvalid option.");
+            prompt("\nPress Enter to continue...")?;
+            return Ok(());
+        }
+    };
+
+    let price = prompt(&format!("New Price ({}): ", currency::base_currency()))?.parse::<f64>().unwrap_or(-1.0);
+    // Dates are read in the store timezone
+    let tz = timezone::store_timezone();
+    let starts_at = match pricing::parse_date(&prompt(&format!("Start (YYYY-MM-DD [HH:MM], {}): ", tz))?) {
+        Ok(ts) => ts,
+        Err(e) => {
+            println!("\nValidation error: {}", e);
+            prompt("\nPress Enter to continue...")?;
+            return Ok(());
+        }
+    };
+    let ends_at = match kind {
+        ScheduleKind::Permanent => None,
+        ScheduleKind::Markdown => match pricing::parse_date(&prompt(&format!("End (YYYY-MM-DD [HH:MM], {}): ", tz))?) {
+            Ok(ts) => Some(ts),
+            Err(e) => {
+                println!("\nValidation error: {}", e);
+                prompt("\nPress Enter to continue...")?;
+                return Ok(());
+            }
+        },
+    };
+
+    let schedule = ScheduledPrice::new(product.id.clone(), kind, price, starts_at, ends_at);
+    match db.schedule_price_change(&schedule) {
+        Ok(()) => {
+            println!("\n✅ Price change scheduled.");
+            apply_due_price_changes(db);
+        }
+        Err(e) => println!("\nError scheduling price change: {}", e),
+    }
+
+    prompt("\nPress Enter to continue...")?;
+    Ok(())
+}
+
+fn view_price_history(db: &Database) -> Screen {
+    clear_screen();
+    display_logo();
+    println!("\nPrice History");
+    println!("-------------");
+
+    let id = prompt_product_id(db, "Enter Product (ID, SKU or search): ")?;
+    match db.get_price_history(&id) {
+        Ok(history) if history.is_empty() => println!("\nNo price history for this product."),
+        Ok(history) => {
+            println!();
+            for change in &history {
+                let old = change.old_price.map(money).unwrap_or_else(|| "-".to_string());
+                println!(
+                    "{}  {:>10} → {:<11} ({})",
+                    format_timestamp(change.changed_at),
+                    old,
+                    money(change.new_price),
+                    change.reason
+                );
+            }
+        }
+        Err(e) => eprintln!("\nError fetching price history: {}", e),
+    }
+
+    prompt("\nPress Enter to continue...")?;
+    Ok(())
+}
+
+fn price_evolution_report(db: &Database) -> Screen {
+    clear_screen();
+    display_logo();
+    println!("╔══════════════════════════════════════════╗");
+    println!("║          PRICE EVOLUTION REPORT          ║");
+    println!("╚══════════════════════════════════════════╝\n");
+
+    let products = match db.get_all_products() {
+        Ok(products) => products,
+        Err(e) => {
+            println!("Error fetching products: {}", e);
+            prompt("\nPress Enter to continue...")?;
+            return Ok(());
+        }
+    };
+
+    let now = Utc::now().timestamp();
+    for product in &products {
+        let periods = match db.price_effect_report(&product.id) {
+            Ok(periods) => periods,
+            Err(e) => {
+                println!("Error building report for {}: {}", product.name, e);
+                continue;
+            }
+        };
+
+        println!("┌─ {} ─", product.name);
+        for period in &periods {
+            let until = period.ends_at.map(format_timestamp).unwrap_or_else(|| "now".to_string());
+            println!(
+                "│  {:<10} {} → {}  sold {:>5} ({:.2}/day)  revenue {}  [{}]",
+                money(period.price),
+                format_timestamp(period.starts_at),
+                until,
+                period.units_sold,
+                period.units_per_day(now),
+                money(period.revenue),
+                period.reason
+            );
+        }
+        println!("└──────────────────────────────────────");
+    }
+
+    if products.is_empty() {
+        println!("No products in registry.");
+    }
+
+    prompt("\nPress Enter to continue...")?;
+    Ok(())
+}
+
+/// Asks for an optional date range; a date without a time includes that whole day as the end.
+fn prompt_date_range() -> Screen<Option<DateRange>> {
+    let parse = |input: &str, end_of_day: bool| -> Result<Option<i64>, Error> {
+        if input.is_empty() {
+            return Ok(None);
+        }
+        let timestamp = pricing::parse_date(input)?;
+        let whole_day = end_of_day && !input.contains(' ');
+        Ok(Some(if whole_day { timezone::end_of_day(timestamp, timezone::store_timezone()) } else { timestamp }))
+    };
+
+    let from = prompt("From (YYYY-MM-DD, Enter for all history): ")?;
+    let to = prompt("To (YYYY-MM-DD, Enter for today): ")?;
+    Ok(match (parse(&from, false), parse(&to, true)) {
+        (Ok(from), Ok(to)) => Some(DateRange::new(from, to)),
+        (Err(e), _) | (_, Err(e)) => {
+            println!("\n{}", e);
+            None
+        }
+    })
+}
+
+fn offer_export(table: &Table) -> Screen {
+    let format = match prompt("\nExport: [C]SV, [X]LSX, Enter to skip: ")?.to_lowercase().as_str() {
+        "c" => ExportFormat::Csv,
+        "x" => ExportFormat::Xlsx,
+        _ => return Ok(()),
+    };
+
+    let dir = env_or("RUSTOCK_EXPORT_DIR", PathBuf::from(export::EXPORT_DIR));
+    match export::save(table, &dir, format) {
+        Ok(path) => println!("Report exported to {}", path.display()),
+        Err(e) => eprintln!("Error exporting report: {}", e),
+    }
+    Ok(())
+}
+
+fn format_optional(value: Option<f64>) -> String {
+    value.map_or_else(|| "-".to_string(), |v| format!("{:.1}", v))
+}
+
+fn turnover_report(db: &Database) -> Screen {
+    clear_screen();
+    display_logo();
+    println!("╔══════════════════════════════════════════╗");
+    println!("║        TURNOVER & DAYS OF SUPPLY         ║");
+    println!("╚══════════════════════════════════════════╝\n");
+
+    let range = match prompt_date_range()? {
+        Some(range) => range,
+        None => {
+            prompt("\nPress Enter to continue...")?;
+            return Ok(());
+        }
+    };
+
+    match db.product_activity(&range) {
+        Ok(activity) => {
+            let rows = analytics::turnover(&activity, &range, Utc::now().timestamp());
+            println!("\nPeriod: {}\n", range.describe());
+            println!("{:<24} {:>8} {:>9} {:>8} {:>8} {:>8}", "Product", "Sold", "Avg Stock", "Turns", "On Hand", "Days");
+            for row in &rows {
+                println!(
+                    "{:<24} {:>8} {:>9.1} {:>8} {:>8} {:>8}",
+                    row.name,
+                    row.units_sold,
+                    row.average_stock,
+                    format_optional(row.turnover),
+                    row.on_hand,
+                    format_optional(row.days_of_supply)
+                );
+            }
+            if rows.is_empty() {
+                println!("No products in registry.");
+            } else {
+                offer_export(&analytics::turnover_table(&rows, &range))?;
+            }
+        }
+        Err(e) => println!("Error building report: {}", e),
+    }
+
+    prompt("\nPress Enter to continue...")?;
+    Ok(())
+}
+
+fn dead_stock_report(db: &Database) -> Screen {
+    clear_screen();
+    display_logo();
+    println!("╔══════════════════════════════════════════╗");
+    println!("║            DEAD STOCK REPORT             ║");
+    println!("╚══════════════════════════════════════════╝\n");
+
+    let days = prompt("No sales in how many days? [90]: ")?.parse::<i64>().unwrap_or(90).max(1);
+    let as_of = prompt("As of (YYYY-MM-DD, Enter for today): ")?;
+    let as_of = if as_of.is_empty() {
+        None
+    } else {
+        match pricing::parse_date(&as_of) {
+            Ok(timestamp) => Some(timezone::end_of_day(timestamp, timezone::store_timezone())),
+            Err(e) => {
+                println!("\n{}", e);
+                prompt("\nPress Enter to continue...")?;
+                return Ok(());
+            }
+        }
+    };
+
+    let range = DateRange::new(None, as_of);
+    match db.product_activity(&range) {
+        Ok(activity) => {
+            let rows = analytics::dead_stock(&activity, range.end(Utc::now().timestamp()), days);
+            println!("\nProducts with stock and no sales in {} days:\n", days);
+            println!("{:<24} {:>8} {:>12} {:>14}", "Product", "On Hand", "Last Sale", "Tied-up Value");
+            for row in &rows {
+                let last_sale = row.last_sale_at.map_or_else(|| "never".to_string(), timezone::format_date);
+                println!("{:<24} {:>8} {:>12} {:>14}", row.name, row.on_hand, last_sale, money(row.tied_up_value));
+            }
+            if rows.is_empty() {
+                println!("No dead stock.");
+            } else {
+                let tied_up: f64 = rows.iter().map(|row| row.tied_up_value).sum();
+                println!("\nTotal value tied up: {}", money(tied_up));
+                offer_export(&analytics::dead_stock_table(&rows, days))?;
+            }
+        }
+        Err(e) => println!("Error building report: {}", e),
+    }
+
+    prompt("\nPress Enter to continue...")?;
+    Ok(())
+}
+
+fn abc_report(db: &Database) -> Screen {
+    clear_screen();
+    display_logo();
+    println!("╔══════════════════════════════════════════╗");
+    println!("║              ABC ANALYSIS                ║");
+    println!("╚══════════════════════════════════════════╝\n");
+
+    let range = match prompt_date_range()? {
+        Some(range) => range,
+        None => {
+            prompt("\nPress Enter to continue...")?;
+            return Ok(());
+        }
+    };
+
+    match db.product_activity(&range) {
+        Ok(activity) => {
+            let rows = analytics::abc_classification(&activity);
+            println!("\nPeriod: {}\n", range.describe());
+            println!(
+                "{:<24} {:>12} {:>12} {:>8} {:>8} {:>6}",
+                "Product", "Revenue", "Gross Profit", "Share", "Cum.", "Class"
+            );
+            for row in &rows {
+                println!(
+                    "{:<24} {:>12} {:>12} {:>7.1}% {:>7.1}% {:>6}",
+                    row.name,
+                    money(row.revenue),
+                    row.gross_profit.map_or_else(|| "-".to_string(), money),
+                    row.share * 100.0,
+                    row.cumulative_share * 100.0,
+                    row.class.as_str()
+                );
+            }
+            if rows.is_empty() {
+                println!("No products in registry.");
+            } else {
+                offer_export(&analytics::abc_table(&rows, &range))?;
+            }
+        }
+        Err(e) => println!("Error building report: {}", e),
+    }
+
+    prompt("\nPress Enter to continue...")?;
+    Ok(())
+}
+
+fn purchase_plan_report(db: &Database) -> Screen {
+    clear_screen();
+    display_logo();
+    println!("╔══════════════════════════════════════════╗");
+    println!("║     DEMAND FORECAST & PURCHASE PLAN      ║");
+    println!("╚══════════════════════════════════════════╝\n");
+
+    let defaults = PlanSettings {
+        lead_time_days: env_or("RUSTOCK_LEAD_TIME_DAYS", PlanSettings::default().lead_time_days),
+        ..PlanSettings::default()
+    };
+    let days = prompt(&format!("Days of sales history [{}]: ", forecast::DEFAULT_HISTORY_DAYS))?
+        .parse::<usize>()
+        .unwrap_or(forecast::DEFAULT_HISTORY_DAYS)
+        .max(1);
+    let settings = PlanSettings {
+        lead_time_days: prompt(&format!("Supplier lead time in days [{}]: ", defaults.lead_time_days))?
+            .parse::<usize>()
+            .unwrap_or(defaults.lead_time_days)
+            .max(1),
+        review_days: prompt(&format!("Days an order should cover [{}]: ", defaults.review_days))?
+            .parse::<usize>()
+            .unwrap_or(defaults.review_days),
+    };
+
+    let series = match db.daily_demand(days, Utc::now().timestamp(), timezone::store_timezone()) {
+        Ok(series) => series,
+        Err(e) => {
+            println!("Error building forecast: {}", e);
+            prompt("\nPress Enter to continue...")?;
+            return Ok(());
+        }
+    };
+    let rows = forecast::purchase_plan(&series, &settings);
+    if rows.is_empty() {
+        println!("\nNo sales in the {} full days before today to forecast from.", days);
+        prompt("\nPress Enter to continue...")?;
+        return Ok(());
+    }
+
+    println!(
+        "\n{:<20} {:>8} {:<12} {:>6} {:>8} {:>8} {:>8} {:>8}",
+        "Product", "On Hand", "Method", "MAE", "LT Dem.", "Safety", "Reorder", "Order"
+    );
+    for row in &rows {
+        println!(
+            "{:<20} {:>8} {:<12} {:>6} {:>8.1} {:>8.1} {:>8.1} {:>8}",
+            row.name,
+            row.on_hand,
+            row.method.describe(),
+            format_optional(row.metrics.map(|m| m.mae)),
+            row.lead_time_demand,
+            row.safety_stock,
+            row.reorder_point,
+            if row.suggested_order > 0.0 { format!("{} {}", row.suggested_order, row.unit) } else { "-".to_string() }
+        );
+    }
+    offer_export(&forecast::plan_table(&rows, &settings))?;
+
+    let name = prompt("\nCompare methods for a product (name, Enter to skip): ")?;
+    if let Some(product) = series.iter().find(|s| !name.is_empty() && s.name.eq_ignore_ascii_case(&name)) {
+        compare_forecasts(product);
+    }
+
+    prompt("\nPress Enter to continue...")?;
+    Ok(())
+}
+
+fn compare_forecasts(series: &DemandSeries) {
+    println!("\n{} - {} {} sold in {} days\n", series.name, series.total(), series.unit, series.daily.len());
+    println!("{:<14} {:>8} {:>8} {:>8} {:>12}", "Method", "MAE", "RMSE", "MAPE %", "Next 7 days");
+    for method in Method::CANDIDATES {
+        let metrics = forecast::backtest(series, method);
+        let next_week: f64 = method.forecast(&series.daily, series.first_day, 7).iter().sum();
+        println!(
+            "{:<14} {:>8} {:>8} {:>8} {:>12.1}",
+            method.describe(),
+            format_optional(metrics.map(|m| m.mae)),
+            format_optional(metrics.map(|m| m.rmse)),
+            format_optional(metrics.and_then(|m| m.mape)),
+            next_week
+        );
+    }
+}
+
+fn accounting_journal(db: &Database) -> Screen {
+    clear_screen();
+    display_logo();
+    println!("╔══════════════════════════════════════════╗");
+    println!("║          ACCOUNTING JOURNAL              ║");
+    println!("╚══════════════════════════════════════════╝\n");
+
+    let Some(range) = prompt_date_range()? else {
+        prompt("\nPress Enter to continue...")?;
+        return Ok(());
+    };
+    let settings = settings::current();
+    let entries = match db.journal_transactions(&range) {
+        Ok(transactions) => journal::journal(&transactions, &settings.accounts, settings.tax_rate),
+        Err(e) => {
+            println!("Error building journal: {}", e);
+            prompt("\nPress Enter to continue...")?;
+            return Ok(());
+        }
+    };
+    if entries.is_empty() {
+        println!("\nNothing to post for {}.", range.describe());
+        prompt("\nPress Enter to continue...")?;
+        return Ok(());
+    }
+
+    println!("\n{} journal entries for {}\n", entries.len(), range.describe());
+    println!("{:<36} {:>14} {:>14}", "Account", "Debit", "Credit");
+    for (account, balance) in journal::trial_balance(&entries) {
+        let (debit, credit) = if balance >= 0.0 { (money(balance), String::new()) } else { (String::new(), money(-balance)) };
+        println!("{:<36} {:>14} {:>14}", account, debit, credit);
+    }
+
+    let dir = env_or("RUSTOCK_EXPORT_DIR", PathBuf::from(export::EXPORT_DIR));
+    let table = journal::journal_table(&entries);
+    let saved = match prompt("\nExport: [C]SV, [X]LSX, [L]edger / hledger journal, Enter to skip: ")?.to_lowercase().as_str() {
+        "c" => Some(export::save(&table, &dir, ExportFormat::Csv)),
+        "x" => Some(export::save(&table, &dir, ExportFormat::Xlsx)),
+        "l" => Some(journal::save_ledger(&entries, &settings.currency, &dir)),
+        _ => None,
+    };
+    match saved {
+        Some(Ok(path)) => println!("Journal exported to {}", path.display()),
+        Some(Err(e)) => eprintln!("Error exporting journal: {}", e),
+        None => {}
+    }
+
+    prompt("\nPress Enter to continue...")?;
+    Ok(())
+}
+
+/// Asks which unit to trade `product` in when it has more than its stock unit.
+/// Returns the chosen unit (`None` for the stock unit) and how many stock units it holds.
+fn choose_unit(db: &Database, product: &Product) -> Screen<Option<(Option<String>, f64)>> {
+    let units = match db.get_product_units(&product.id) {
+        Ok(units) => units,
+        Err(e) => {
+            println!("Error fetching units: {}", e);
+            return Ok(None);
+        }
+    };
+    if units.is_empty() {
+        return Ok(Some((None, 1.0)));
+    }
+
+    let names: Vec<&str> = std::iter::once(product.unit.as_str())
+        .chain(units.iter().map(|u| u.unit.as_str()))
+        .collect();
+    let choice = unit::normalize(&prompt(&format!("Unit [{}] (Enter for {}): ", names.join("/"), product.unit))?);
+    if choice.is_empty() || choice == product.unit {
+        return Ok(Some((None, 1.0)));
+    }
+    Ok(match units.into_iter().find(|u| u.unit == choice) {
+        Some(unit) => Some((Some(unit.unit), unit.factor)),
+        None => {
+            println!("Unknown unit '{}'.", choice);
+            None
+        }
+    })
+}
+
+fn record_sale(db: &mut Database, manager: &Manager) -> Screen {
+    apply_due_price_changes(db);
+    clear_screen();
+    display_logo();
+    println!("╔══════════════════════════════════════════╗");
+    println!("║             NEW TRADE OUT                ║");
+    println!("╚══════════════════════════════════════════╝\n");
+
+    
+    let mut sale_items = Vec::new();
+    loop {
+        match db.get_all_products() {
+            Ok(products) => {   
+                if products.is_empty() {
+                    println!("No products available.");
+                    println!("Please add products through Supply Chain → New Trade In first.");
+                    println!("\nPress Enter to continue...");
+                    prompt("")?;
+                    return Ok(());
+                }
+                println!("\nAvailable Products:");
+                println!("------------------");
+                for product in &products {
+                    println!("ID: {}", product.id);
+                    println!("Name: {}", product.name);
+                    println!("Price: {} per {}", money(product.price), product.unit);
+                    let available = db.available_quantity(product).unwrap_or(product.quantity);
+                    println!("Available Quantity: {} {}", available, product.unit);
+                    println!("------------------");
+                }
+
+                let product_id = prompt_product_id(db, "Enter Product (ID, SKU or search; Enter to finish): ")?;
+                if product_id.is_empty() {
+                    break;
+                }
+
+                if let Ok(Some(product)) = db.get_product(&product_id) {
+                    if product.is_archived() {
+                        println!("Product is archived and cannot be sold.");
+                        continue;
+                    }
+                    let (unit, factor) = match choose_unit(db, &product)? {
+                        Some(choice) => choice,
+                        None => continue,
+                    };
+                    let unit_name = unit.clone().unwrap_or_else(|| product.unit.clone());
+                    let quantity_str = prompt(&format!("Enter quantity ({}): ", unit_name))?;
+                    if let Ok(quantity) = quantity_str.parse::<f64>() {
+                        if quantity <= 0.0 {
+                            println!("Quantity must be positive.");
+                            continue;
+                        }
+                        let base_quantity = match db.to_base_quantity(&product.id, unit.as_deref(), quantity) {
+                            Ok(base_quantity) => base_quantity,
+                            Err(e) => {
+                                println!("{}", e);
+                                continue;
+                            }
+                        };
+                        let available = db.available_quantity(&product).unwrap_or(product.quantity);
+                        if base_quantity > available {
+                            println!("Insufficient stock. Available: {} {}", available, product.unit);
+                            continue;
+                        }
+
+                        let unit_price = product.price * factor;
+                        let total_price = unit_price * quantity;
+
+                        sale_items.push(SaleItem {
+                            product_id: product.id,
+                            quantity,
+                            unit,
+                            unit_price,
+                            total_price,
+                        });
+
+                        println!("Item added to sale.");
+                    } else {
+                        println!("Invalid quantity.");
+                    }
+                } else {
+                    println!("Product not found.");
+                }
+            }
+            Err(e) => {
+                println!("DEBUG: Error fetching products: {}", e);
+                eprintln!("Error fetching products: {}", e);
+                println!("Press Enter to continue...");
+                prompt("")?;
+                return Ok(());
+            }
+        }
+    }
+
+    if sale_items.is_empty() {
+        println!("No items added to sale.");
+        return Ok(());
+    }
+
+    let mut sale = Sale::new(sale_items);
+    
+    println!("\nSale Summary:");
+    println!("-------------");
+    for item in &sale.items {
+        if let Ok(Some(product)) = db.get_product(&item.product_id) {
+            println!("Product: {}", product.name);
+            println!("Quantity: {} {}", item.quantity, item.unit.as_deref().unwrap_or(&product.unit));
+            println!("Unit Price: {}", money(item.unit_price));
+            println!("Total: {}", money(item.total_price));
+            println!("-------------");
+        }
+    }
+    println!("Total Amount: {}", money(sale.total_amount));
+    sale = loyalty_checkout(db, sale)?;
+
+    if prompt("\nConfirm sale? (y/N): ")?.to_lowercase() == "y" {
+        match sale.validate() {
+            Ok(()) => {
+                // Bundles also draw down their components
+                let previous: Vec<(String, f64)> = sale
+                    .items
+                    .iter()
+                    .flat_map(|item| {
+                        let components = db.get_bundle_components(&item.product_id).unwrap_or_default();
+                        std::iter::once(item.product_id.clone()).chain(components.into_iter().map(|c| c.component_id))
+                    })
+                    .filter_map(|id| db.get_product(&id).ok().flatten())
+                    .map(|product| (product.id, product.quantity))
+                    .collect();
+                let shift = db.current_shift(&manager.id).ok().flatten();
+                let result = match &shift {
+                    Some(shift) => match prompt_payments(sale.total_amount)? {
+                        Some(payments) => db.record_pos_sale(&sale, &shift.id, &payments).map(|(number, settlement)| {
+                            (number, Some(settlement))
+                        }),
+                        None => {
+                            println!("\nSale cancelled.");
+                            prompt("\nPress Enter to continue...")?;
+                            return Ok(());
+                        }
+                    },
+                    None if sale.customer_id.is_some() => db.record_customer_sale(&sale).map(|number| (number, None)),
+                    None => db.record_sale(&sale).map(|number| (number, None)),
+                };
+                match result {
+                    Ok((sale_number, settlement)) => {
+                        println!("\nSale #{} recorded successfully!", sale_number);
+                        if let Some(settlement) = settlement {
+                            for (payment, _) in &settlement.payments {
+                                println!("Paid {}: {}", payment.method.label(), money(payment.tendered));
+                            }
+                            if settlement.change > 0.0 {
+                                println!("💵 Change due: {}", money(settlement.change));
+                            }
+                        }
+                        if let Some(customer_id) = &sale.customer_id {
+                            if let Ok(balance) = db.points_balance(customer_id) {
+                                println!("⭐ Loyalty points balance: {}", balance);
+                            }
+                        }
+                        notify_sale(db, sale_number, sale.total_amount, &previous);
+                        show_receipt(db, sale_number)?;
+                    }
+                    Err(e) => eprintln!("\nError recording sale: {}", e),
+                }
+            }
+            Err(e) => eprintln!("\nValidation error: {}", e),
+        }
+    } else {
+        println!("\nSale cancelled.");
+    }
+
+    println!("\nPress Enter to continue...");
+    prompt("")?;
+    Ok(())
+}
+
+/// Finds a customer by phone number, or by part of their name, phone or email.
+fn find_customer(db: &Database, question: &str) -> Screen<Option<Customer>> {
+    let query = prompt(question)?;
+    if query.is_empty() {
+        return Ok(None);
+    }
+    if let Ok(Some(customer)) = db.find_customer_by_phone(&query) {
+        return Ok(Some(customer));
+    }
+    let matches = match db.search_customers(&query, 10) {
+        Ok(matches) => matches,
+        Err(e) => {
+            eprintln!("Error searching customers: {}", e);
+            return Ok(None);
+        }
+    };
+    Ok(match matches.len() {
+        0 => {
+            println!("No customer matches '{}'.", query);
+            None
+        }
+        1 => matches.into_iter().next(),
+        _ => {
+            for (idx, customer) in matches.iter().enumerate() {
+                println!("  [{}] {} {}", idx + 1, customer.name, customer.phone.as_deref().unwrap_or(""));
+            }
+            let choice = prompt("Customer number (Enter to cancel): ")?;
+            choice.parse::<usize>().ok().and_then(|n| n.checked_sub(1)).and_then(|i| matches.get(i)).cloned()
+        }
+    })
+}
+
+/// Credits the sale to a loyalty customer and lets them spend points on a discount.
+fn loyalty_checkout(db: &Database, sale: Sale) -> Screen<Sale> {
+    let Some(customer) = find_customer(db, "\nLoyalty customer (phone or name, Enter to skip): ")? else {
+        return Ok(sale);
+    };
+    let program = settings::current().loyalty;
+    let summary = match db.customer_summary(&customer.id, &program) {
+        Ok(summary) => summary,
+        Err(e) => {
+            eprintln!("Error fetching customer: {}", e);
+            return Ok(sale);
+        }
+    };
+    println!(
+        "{} - {} tier, {} points",
+        customer.name,
+        summary.tier.as_deref().unwrap_or("no"),
+        summary.points_balance
+    );
+
+    let mut points = 0;
+    let redeemable = program.max_redeemable(summary.points_balance, sale.subtotal());
+    if redeemable > 0 {
+        let input = prompt(&format!(
+            "Points to redeem (up to {}, worth {}) [0]: ",
+            redeemable,
+            money(program.discount(redeemable, sale.subtotal()))
+        ))?;
+        points = match input.parse::<i64>() {
+            Ok(points) if (0..=redeemable).contains(&points) => points,
+            _ if input.is_empty() => 0,
+            _ => {
+                println!("No points redeemed.");
+                0
+            }
+        };
+    }
+
+    let discount = program.discount(points, sale.subtotal());
+    let sale = sale.for_customer(customer.id, points, discount);
+    if discount > 0.0 {
+        println!("Loyalty discount: -{}", money(discount));
+        println!("Total Amount: {}", money(sale.total_amount));
+    }
+    Ok(sale)
+}
+
+fn customers_and_loyalty(db: &Database) -> Screen {
+    loop {
+        clear_screen();
+        display_logo();
+        println!("╔══════════════════════════════════════════╗");
+        println!("║          CUSTOMERS & LOYALTY             ║");
+        println!("╚══════════════════════════════════════════╝\n");
+
+        let program = settings::current().loyalty;
+        println!(
+            "Earning {} point(s) per {}; a point is worth {} at checkout.",
+            program.points_per_unit,
+            money(1.0),
+            money(program.point_value)
+        );
+        if !program.tiers.is_empty() {
+            let tiers: Vec<String> = program
+                .tiers
+                .iter()
+                .map(|tier| format!("{} from {} (x{})", tier.name, money(tier.threshold), tier.multiplier))
+                .collect();
+            println!("Tiers: {}", tiers.join(", "));
+        }
+
+        println!("\n  [1] Customer History");
+        println!("  [2] Register Customer");
+        println!("  [3] Return");
+        match prompt("\nEnter your choice (1-3): ")?.as_str() {
+            "1" => {
+                if let Some(customer) = find_customer(db, "\nCustomer (phone or name): ")? {
+                    customer_history(db, &customer)?;
+                } else {
+                    prompt("\nPress Enter to continue...")?;
+                }
+            }
+            "2" => register_customer(db)?,
+            "3" | "" => break,
+            _ => {
+                println!("Invalid option. Press Enter to continue...");
+                prompt("")?;
+            }
+        }
+    }
+    Ok(())
+}
+
+fn register_customer(db: &Database) -> Screen {
+    println!("\nRegister Customer");
+    println!("-----------------");
+    let name = prompt("Name: ")?;
+    let phone = prompt("Phone (optional): ")?;
+    let email = prompt("Email (optional): ")?;
+
+    let customer = Customer::new(name, Some(phone), Some(email));
+    match db.add_customer(&customer) {
+        Ok(()) => println!("\n✅ Customer '{}' registered.", customer.name),
+        Err(e) => println!("\nError registering customer: {}", e),
+    }
+    prompt("\nPress Enter to continue...")?;
+    Ok(())
+}
+
+fn customer_history(db: &Database, customer: &Customer) -> Screen {
+    clear_screen();
+    display_logo();
+    let program = settings::current().loyalty;
+    let summary = match db.customer_summary(&customer.id, &program) {
+        Ok(summary) => summary,
+        Err(e) => {
+            eprintln!("Error fetching customer: {}", e);
+            prompt("\nPress Enter to continue...")?;
+            return Ok(());
+        }
+    };
+
+    println!("{}", customer.name);
+    println!("{}", "-".repeat(customer.name.chars().count()));
+    if let Some(phone) = &customer.phone {
+        println!("Phone:          {}", phone);
+    }
+    if let Some(email) = &customer.email {
+        println!("Email:          {}", email);
+    }
+    println!("Customer since: {}", timezone::format_date(customer.created_at));
+    println!("Sales:          {}", summary.sales);
+    println!("Lifetime value: {}", money(summary.lifetime_value));
+    println!("Tier:           {}", summary.tier.as_deref().unwrap_or("-"));
+    if let Some(next) = program.next_tier(summary.lifetime_value) {
+        println!("Next tier:      {} after {} more", next.name, money(next.threshold - summary.lifetime_value));
+    }
+    println!(
+        "Points:         {} (worth {})",
+        summary.points_balance,
+        money(summary.points_balance.max(0) as f64 * program.point_value)
+    );
+
+    let sales = match db.customer_history(&customer.id) {
+        Ok(sales) => sales,
+        Err(e) => {
+            eprintln!("Error fetching purchase history: {}", e);
+            prompt("\nPress Enter to continue...")?;
+            return Ok(());
+        }
+    };
+    if sales.is_empty() {
+        println!("\nNo purchases yet.");
+        prompt("\nPress Enter to continue...")?;
+        return Ok(());
+    }
+
+    println!("\n{:<8} {:<17} {:>5} {:>11} {:>11} {:>11} {:>7}", "Sale", "Date", "Items", "Discount", "Paid", "Refunded", "Points");
+    for sale in &sales {
+        println!(
+            "{:<8} {:<17} {:>5} {:>11} {:>11} {:>11} {:>7}",
+            format!("#{}", sale.sale_number),
+            format_timestamp(sale.timestamp),
+            sale.items,
+            money(sale.discount),
+            money(sale.total),
+            money(sale.refunded),
+            sale.points
+        );
+    }
+    offer_export(&loyalty::history_table(customer, &sales))?;
+    prompt("\nPress Enter to continue...")?;
+    Ok(())
+}
+
+/// Asks how a sale of `total` is paid until the payments cover it; `None` cancels the sale.
+fn prompt_payments(total: f64) -> Screen<Option<Vec<Payment>>> {
+    let amount = |message: &str, default: f64| -> Screen<Option<f64>> {
+        let input = prompt(&format!("{} [{:.2}]: ", message, default))?;
+        Ok(if input.is_empty() {
+            Some(default)
+        } else {
+            input.parse::<f64>().ok()
+        })
+    };
+
+    loop {
+        println!("\nAmount due: {}", money(total));
+        println!("Payment: [1] Cash  [2] Card  [3] Split card + cash  [Enter] Cancel");
+        let payments = match prompt("Choice: ")?.as_str() {
+            "1" => amount("Cash tendered", total)?.map(|cash| vec![Payment::cash(cash)]),
+            "2" => Some(vec![Payment::card(total)]),
+            "3" => match amount("Card amount", total / 2.0)? {
+                Some(card) => {
+                    let remaining = ((total - card) * 100.0).round() / 100.0;
+                    amount("Cash tendered", remaining)?.map(|cash| vec![Payment::card(card), Payment::cash(cash)])
+                }
+                None => None,
+            },
+            "" => return Ok(None),
+            _ => {
+                println!("Invalid option.");
+                continue;
+            }
+        };
+        match payments.map(|payments| pos::settle(total, &payments).map(|_| payments)) {
+            Some(Ok(payments)) => return Ok(Some(payments)),
+            Some(Err(e)) => println!("{}", e),
+            None => println!("Invalid amount."),
+        }
+    }
+}
+
+fn manage_shift(db: &mut Database, manager: &Manager) -> Screen {
+    loop {
+        clear_screen();
+        display_logo();
+        println!("╔══════════════════════════════════════════╗");
+        println!("║           CASH DRAWER & SHIFT            ║");
+        println!("╚══════════════════════════════════════════╝\n");
+
+        let shift = match db.current_shift(&manager.id) {
+            Ok(shift) => shift,
+            Err(e) => {
+                eprintln!("Error fetching shift: {}", e);
+                prompt("\nPress Enter to continue...")?;
+                return Ok(());
+            }
+        };
+        match &shift {
+            Some(shift) => {
+                println!("Shift open since {} with a float of {}.", format_timestamp(shift.opened_at), money(shift.opening_float));
+                println!("Sales rung up now record their payments.\n");
+                println!("[X] X Report  [Z] Close Shift (Z Report)  [H] Past Z Reports  [Enter] Return");
+            }
+            None => {
+                println!("No shift open. Sales are recorded without payment details.\n");
+                println!("[O] Open Shift  [H] Past Z Reports  [Enter] Return");
+            }
+        }
+
+        match (prompt("Choice: ")?.to_lowercase().as_str(), &shift) {
+            ("o", None) => {
+                let float = prompt("Opening float in the drawer: ")?.parse::<f64>().unwrap_or(-1.0);
+                match db.open_shift(&Shift::new(manager.id.clone(), float)) {
+                    Ok(()) => println!("\n✅ Shift opened."),
+                    Err(e) => eprintln!("\nError opening shift: {}", e),
+                }
+            }
+            ("x", Some(shift)) => match db.shift_report(&shift.id) {
+                Ok(report) => println!("\n{}", report.to_text()),
+                Err(e) => eprintln!("\nError building X report: {}", e),
+            },
+            ("z", Some(shift)) => {
+                println!("\nCount the drawer and the card terminal totals.");
+                let mut counted = Vec::new();
+                for method in PaymentMethod::ALL {
+                    match prompt(&format!("Counted {}: ", method.label().to_lowercase()))?.parse::<f64>() {
+                        Ok(amount) => counted.push((method, amount)),
+                        Err(_) => {
+                            println!("Invalid amount. Shift left open.");
+                            counted.clear();
+                            break;
+                        }
+                    }
+                }
+                if counted.len() == PaymentMethod::ALL.len() {
+                    match db.close_shift(&shift.id, &counted) {
+                        Ok(report) => println!("\n{}", report.to_text()),
+                        Err(e) => eprintln!("\nError closing shift: {}", e),
+                    }
+                }
+            }
+            ("h", _) => past_z_reports(db)?,
+            ("", _) => break,
+            _ => println!("Invalid option."),
+        }
+        prompt("\nPress Enter to continue...")?;
+    }
+    Ok(())
+}
+
+fn past_z_reports(db: &Database) -> Screen {
+    let shifts: Vec<Shift> = match db.get_shifts(20) {
+        Ok(shifts) => shifts.into_iter().filter(|shift| !shift.is_open()).collect(),
+        Err(e) => {
+            eprintln!("Error fetching shifts: {}", e);
+            return Ok(());
+        }
+    };
+    if shifts.is_empty() {
+        println!("\nNo closed shifts yet.");
+        return Ok(());
+    }
+
+    println!();
+    for (idx, shift) in shifts.iter().enumerate() {
+        println!(
+            "[{}] {} to {}",
+            idx + 1,
+            format_timestamp(shift.opened_at),
+            shift.closed_at.map_or_else(|| "-".to_string(), format_timestamp)
+        );
+    }
+    let choice = prompt("\nReport to view (Enter to return): ")?.parse::<usize>().unwrap_or(0);
+    if let Some(shift) = choice.checked_sub(1).and_then(|idx| shifts.get(idx)) {
+        match db.shift_report(&shift.id) {
+            Ok(report) => println!("\n{}", report.to_text()),
+            Err(e) => eprintln!("\nError building Z report: {}", e),
+        }
+    }
+    Ok(())
+}
+
+fn manage_orders(db: &mut Database) -> Screen {
+    loop {
+        clear_screen();
+        display_logo();
+        println!("╔══════════════════════════════════════════╗");
+        println!("║             CUSTOMER ORDERS              ║");
+        println!("╚══════════════════════════════════════════╝\n");
+
+        match db.open_orders() {
+            Ok(orders) if orders.is_empty() => println!("No open orders."),
+            Ok(orders) => {
+                for order in &orders {
+                    let product = db.get_product(&order.product_id).ok().flatten().map_or_else(|| "Unknown".to_string(), |p| p.name);
+                    let ready = if order.collect_blocker().is_none() { "  ✅ ready" } else { "" };
+                    println!(
+                        "#{:<4} {:<11} {:<16} {} x {}  held {}  total {}  paid {}{}",
+                        order.order_number.unwrap_or_default(),
+                        order.kind.label(),
+                        order.customer,
+                        order.quantity,
+                        product,
+                        order.allocated,
+                        money(order.total()),
+                        money(order.paid),
+                        ready
+                    );
+                }
+            }
+            Err(e) => println!("Error fetching orders: {}", e),
+        }
+
+        println!("\n[N] New Order  [D] Take Deposit  [F] Collect  [C] Cancel  [Enter] Return");
+        let choice = prompt("Choice: ")?.to_lowercase();
+        if choice.is_empty() {
+            break;
+        }
+        if choice == "n" {
+            place_order(db)?;
+            prompt("\nPress Enter to continue...")?;
+            continue;
+        }
+        let order_number = match prompt("Order number: ")?.trim_start_matches('#').parse::<i64>() {
+            Ok(number) => number,
+            Err(_) => {
+                println!("Invalid order number.");
+                prompt("\nPress Enter to continue...")?;
+                continue;
+            }
+        };
+        match choice.as_str() {
+            "d" => {
+                let amount = prompt("Deposit amount: ")?.parse::<f64>().unwrap_or(0.0);
+                match db.add_deposit(order_number, amount) {
+                    Ok(order) => println!("\nDeposit taken. Balance due: {}", money(order.balance())),
+                    Err(e) => eprintln!("\nError taking deposit: {}", e),
+                }
+            }
+            "f" => match db.fulfil_order(order_number) {
+                Ok(sale_number) => {
+                    println!("\nOrder #{} collected as sale #{}.", order_number, sale_number);
+                    show_receipt(db, sale_number)?;
+                }
+                Err(e) => eprintln!("\nError collecting order: {}", e),
+            },
+            "c" => {
+                if prompt(&format!("Cancel order #{}? (y/N): ", order_number))?.to_lowercase() != "y" {
+                    continue;
+                }
+                match db.cancel_order(order_number) {
+                    Ok(order) if order.paid > 0.0 => {
+                        println!("\nOrder cancelled. Refund deposits of {} to {}.", money(order.paid), order.customer)
+                    }
+                    Ok(_) => println!("\nOrder cancelled."),
+                    Err(e) => eprintln!("\nError cancelling order: {}", e),
+                }
+            }
+            _ => println!("Invalid option."),
+        }
+        prompt("\nPress Enter to continue...")?;
+    }
+    Ok(())
+}
+
+fn place_order(db: &mut Database) -> Screen {
+    println!("\n[1] Reservation  [2] Layaway  [3] Backorder");
+    let kind = match prompt("Order type: ")?.as_str() {
+        "1" => OrderKind::Reservation,
+        "2" => OrderKind::Layaway,
+        "3" => OrderKind::Backorder,
+        _ => {
+            println!("Invalid order type.");
+            return Ok(());
+        }
+    };
+    let customer = prompt("Customer name: ")?;
+    let product = match db.get_product(&prompt_product_id(db, "Product (ID, SKU or search): ")?) {
+        Ok(Some(product)) => product,
+        Ok(None) => {
+            println!("Product not found.");
+            return Ok(());
+        }
+        Err(e) => {
+            eprintln!("Error fetching product: {}", e);
+            return Ok(());
+        }
+    };
+    let available = db.available_quantity(&product).unwrap_or(product.quantity);
+    println!("{}: {} {} available to sell at {} per {}", product.name, available, product.unit, money(product.price), product.unit);
+    let quantity = prompt(&format!("Quantity ({}): ", product.unit))?.parse::<f64>().unwrap_or(0.0);
+    let mut order = CustomerOrder::new(kind, customer, product.id.clone(), quantity, product.price);
+    if kind == OrderKind::Layaway {
+        println!("Layaway total: {}", money(order.total()));
+        order = order.with_deposit(prompt("Deposit taken now: ")?.parse::<f64>().unwrap_or(0.0));
+    }
+
+    match db.place_order(&order) {
+        Ok(order) => {
+            println!("\n✅ {} #{} placed for {}.", order.kind.label(), order.order_number.unwrap_or_default(), order.customer);
+            if order.outstanding() > 0.0 {
+                println!("{} {} held now; {} will be allocated as stock arrives.", order.allocated, product.unit, order.outstanding());
+            }
+        }
+        Err(e) => eprintln!("\nError placing order: {}", e),
+    }
+    Ok(())
+}
+
+/// Mentions backorders for `product_id` that incoming stock has completed.
+fn report_ready_backorders(db: &Database, product_id: &str) {
+    let orders = db.open_orders().unwrap_or_default();
+    for order in orders.iter().filter(|o| o.product_id == product_id && o.kind == OrderKind::Backorder) {
+        if order.outstanding() <= 0.0 {
+            println!("📦 Backorder #{} for {} is ready to collect.", order.order_number.unwrap_or_default(), order.customer);
+        } else if order.allocated > 0.0 {
+            println!("📦 Backorder #{} for {} now has {} of {} held.", order.order_number.unwrap_or_default(), order.customer, order.allocated, order.quantity);
+        }
+    }
+}
+
+fn open_orders_report(db: &Database) -> Screen {
+    clear_screen();
+    display_logo();
+    println!("╔══════════════════════════════════════════╗");
+    println!("║            OPEN ORDERS REPORT            ║");
+    println!("╚══════════════════════════════════════════╝\n");
+
+    let orders = match db.open_orders() {
+        Ok(orders) => orders,
+        Err(e) => {
+            println!("Error fetching orders: {}", e);
+            prompt("\nPress Enter to continue...")?;
+            return Ok(());
+        }
+    };
+    let rows: Vec<(CustomerOrder, String)> = orders
+        .into_iter()
+        .map(|order| {
+            let name = db.get_product(&order.product_id).ok().flatten().map_or_else(|| "Unknown".to_string(), |p| p.name);
+            (order, name)
+        })
+        .collect();
+
+    println!("{:<6} {:<11} {:<16} {:<18} {:>7} {:>8} {:>11}", "Order", "Kind", "Customer", "Product", "Qty", "Awaiting", "Balance");
+    for (order, product) in &rows {
+        println!(
+            "{:<6} {:<11} {:<16} {:<18} {:>7} {:>8} {:>11}",
+            order.order_number.unwrap_or_default(),
+            order.kind.label(),
+            order.customer,
+            product,
+            order.quantity,
+            order.outstanding(),
+            money(order.balance())
+        );
+    }
+    if rows.is_empty() {
+        println!("No open orders.");
+    } else {
+        let deposits: f64 = rows.iter().map(|(order, _)| order.paid).sum();
+        let awaiting = rows.iter().filter(|(order, _)| order.outstanding() > 0.0).count();
+        println!("\n{} open orders, {} awaiting stock, {} held in deposits", rows.len(), awaiting, money(deposits));
+        offer_export(&order::open_orders_table(&rows))?;
+    }
+    prompt("\nPress Enter to continue...")?;
+    Ok(())
+}
+
+// Delivers notifications on a background thread for the lifetime of the console
+static NOTIFICATIONS: OnceLock<Dispatcher> = OnceLock::new();
+
+fn notifier() -> Notifier {
+    Notifier::new(NotifyConfig::from_env().0)
+}
+
+/// Starts delivering notifications in the background. Deliveries are written to the
+/// log through a connection of their own, so the console never waits on a sink.
+fn start_notifications(db: &Database) {
+    let notifier = notifier();
+    if notifier.config().sinks.is_empty() {
+        return;
+    }
+    let log = match db.reopen() {
+        Ok(log) => log,
+        Err(e) => {
+            eprintln!("Notifications disabled: {}", e);
+            return;
+        }
+    };
+    let dispatcher = Dispatcher::spawn(notifier, move |event, deliveries| {
+        if let Err(e) = log.log_deliveries(&deliveries) {
+            eprintln!("Error writing notification log: {}", e);
+        }
+        for delivery in deliveries.iter().filter(|d| !d.delivered) {
+            eprintln!(
+                "⚠️  Could not deliver {} notification to {}: {}",
+                event.kind.as_str(),
+                delivery.sink,
+                delivery.error.as_deref().unwrap_or("unknown error")
+            );
+        }
+    });
+    let _ = NOTIFICATIONS.set(dispatcher);
+}
+
+/// Waits for queued notifications to be delivered before the console exits.
+fn finish_notifications() {
+    if let Some(dispatcher) = NOTIFICATIONS.get() {
+        dispatcher.finish();
+    }
+}
+
+/// Queues `events` for delivery to the configured sinks; each delivery is recorded in the log.
+fn notify(events: Vec<Event>) {
+    if let Some(dispatcher) = NOTIFICATIONS.get() {
+        for event in events {
+            dispatcher.dispatch(event);
+        }
+    }
+}
+
+/// Raises large-sale and stock events for a recorded sale; `previous` holds stock levels before it.
+fn notify_sale(db: &Database, sale_number: i64, total_amount: f64, previous: &[(String, f64)]) {
+    let notifier = notifier();
+    let mut events: Vec<Event> = notifier.sale_event(sale_number, total_amount).into_iter().collect();
+    let mut seen = Vec::new();
+    for (product_id, quantity) in previous {
+        if seen.contains(&product_id) {
+            continue;
+        }
+        seen.push(product_id);
+        if let Ok(Some(product)) = db.get_product(product_id) {
+            events.extend(notifier.stock_event(&product, *quantity));
+        }
+    }
+    notify(events);
+}
+
+/// Prints the plain-text receipt for a stored sale and offers to save it in another format.
+fn show_receipt(db: &Database, sale_number: i64) -> Screen {
+    let config = ReceiptConfig::from_env();
+
+    let sale = match db.get_sale_by_number(sale_number) {
+        Ok(Some(sale)) => sale,
+        Ok(None) => {
+            println!("\nSale #{} not found.", sale_number);
+            return Ok(());
+        }
+        Err(e) => {
+            eprintln!("\nError fetching sale: {}", e);
+            return Ok(());
+        }
+    };
+
+    let receipt = match Receipt::from_sale(db, &sale) {
+        Ok(receipt) => receipt,
+        Err(e) => {
+            eprintln!("\nError building receipt: {}", e);
+            return Ok(());
+        }
+    };
+
+    match receipt::render_text(&receipt, &config) {
+        Ok(text) => println!("\n{}", text),
+        Err(e) => eprintln!("\nError rendering receipt: {}", e),
+    }
+
+    println!("Save a copy: [t] Text  [h] HTML invoice  [p] PDF invoice  (Enter to skip)");
+    let format = match prompt("Choice: ")?.to_lowercase().as_str() {
+        "t" => ReceiptFormat::Text,
+        "h" => ReceiptFormat::Html,
+        "p" => ReceiptFormat::Pdf,
+        _ => return Ok(()),
+    };
+
+    match receipt::save(&receipt, &config, format) {
+        Ok(path) => println!("\n✅ Saved to {}", path.display()),
+        Err(e) => eprintln!("\nError saving receipt: {}", e),
+    }
+    Ok(())
+}
+
+fn reprint_receipt(db: &Database) -> Screen {
+    clear_screen();
+    display_logo();
+    println!("\nReprint Receipt / Invoice");
+    println!("-------------------------");
+
+    match db.get_recent_sales(10) {
+        Ok(sales) if sales.is_empty() => {
+            println!("No trades recorded yet.");
+            prompt("\nPress Enter to continue...")?;
+            return Ok(());
+        }
+        Ok(sales) => {
+            println!("\nRecent Sales:");
+            for (sale_number, item_count, total_amount, timestamp) in sales {
+                println!(
+                    "  #{:<6} {}  {} item(s)  {}",
+                    sale_number,
+                    format_timestamp(timestamp),
+                    item_count,
+                    money(total_amount)
+                );
+            }
+        }
+        Err(e) => eprintln!("Error fetching sales: {}", e),
+    }
+
+    let input = prompt("\nEnter sale number (or press Enter to cancel): ")?;
+    if input.is_empty() {
+        return Ok(());
+    }
+
+    match input.trim_start_matches('#').parse::<i64>() {
+        Ok(sale_number) => show_receipt(db, sale_number)?,
+        Err(_) => println!("\nInvalid sale number."),
+    }
+
+    prompt("\nPress Enter to continue...")?;
+    Ok(())
+}
+
+fn view_sales(db: &Database) -> Screen {
+    const PAGE_SIZE: usize = 20;
+    let mut page = 0;
+    loop {
+        clear_screen();
+        display_logo();
+        println!("╔══════════════════════════════════════════╗");
+        println!("║          TRADING HISTORY                 ║");
+        println!("╚══════════════════════════════════════════╝\n");
+
+        let total = db.sales_count().unwrap_or(0);
+        let sales = db.sales_page(page * PAGE_SIZE, PAGE_SIZE).unwrap_or_default();
+        if sales.is_empty() {
+            println!("No trades recorded yet.");
+            println!("Press Enter to continue...");
+            prompt("")?;
+            return Ok(());
+        }
+        for (product_name, quantity, total_price, sold_at) in sales {
+            println!("┌─ Trade Details ─");
+            println!("│  Product: {}", product_name);
+            println!("│  Quantity: {}", quantity);
+            println!("│  Total Price: {}", money(total_price));
+            println!("│  Date: {}", format_timestamp(sold_at));
+            println!("└──────────────────────────────────────\n");
+        }
+
+        let pages = total.div_ceil(PAGE_SIZE);
+        println!("Page {} of {} ({} trades)", page + 1, pages, total);
+        let has_next = page + 1 < pages;
+        match prompt("[N] Older  [P] Newer  [Enter] Return: ")?.to_lowercase().as_str() {
+            "n" if has_next => page += 1,
+            "p" if page > 0 => page -= 1,
+            "n" | "p" => {}
+            _ => return Ok(()),
+        }
+    }
+}
+
+fn customer_returns(db: &mut Database, manager: &Manager) -> Screen {
+    clear_screen();
+    display_logo();
+    println!("\nCustomer Returns");
+    println!("----------------");
+
+    let input = prompt("\nSale number (Enter to cancel): ")?;
+    if input.is_empty() {
+        return Ok(());
+    }
+    let Ok(sale_number) = input.trim_start_matches('#').parse::<i64>() else {
+        println!("\nInvalid sale number.");
+        prompt("\nPress Enter to continue...")?;
+        return Ok(());
+    };
+    let sale = match db.get_sale_by_number(sale_number) {
+        Ok(Some(sale)) => sale,
+        Ok(None) => {
+            println!("\nSale #{} not found.", sale_number);
+            prompt("\nPress Enter to continue...")?;
+            return Ok(());
+        }
+        Err(e) => {
+            eprintln!("\nError fetching sale: {}", e);
+            prompt("\nPress Enter to continue...")?;
+            return Ok(());
+        }
+    };
+
+    println!("\nSale #{} on {}", sale_number, format_timestamp(sale.timestamp));
+    let mut products = Vec::new();
+    for (i, item) in sale.items.iter().enumerate() {
+        let product = match db.get_product(&item.product_id) {
+            Ok(Some(product)) => product,
+            _ => continue,
+        };
+        let sold = db.to_base_quantity(&item.product_id, item.unit.as_deref(), item.quantity).unwrap_or(item.quantity);
+        let returned = db.returned_quantity(sale_number, &item.product_id).unwrap_or(0.0);
+        println!(
+            "  [{}] {}: {} {} for {}, {} returned",
+            i + 1,
+            product.name,
+            sold,
+            product.unit,
+            money(item.total_price),
+            returned
+        );
+        products.push(product);
+    }
+
+    let choice = prompt("\nItem to return (number, Enter to cancel): ")?;
+    let Some(product) = choice.parse::<usize>().ok().and_then(|n| n.checked_sub(1)).and_then(|i| products.get(i)) else {
+        return Ok(());
+    };
+    let quantity = match prompt(&format!("Quantity to return (in {}): ", product.unit))?.parse::<f64>() {
+        Ok(quantity) => quantity,
+        Err(_) => {
+            println!("\nInvalid quantity.");
+            prompt("\nPress Enter to continue...")?;
+            return Ok(());
+        }
+    };
+    let reason = prompt("Reason (optional): ")?;
+
+    // With a shift open the refund is paid from the drawer, by default in the tender the sale was paid with
+    let result = match db.current_shift(&manager.id).ok().flatten() {
+        Some(shift) => {
+            let paid_by = db
+                .get_sale_payments(sale_number)
+                .ok()
+                .and_then(|payments| payments.first().map(|(payment, _)| payment.method))
+                .unwrap_or(PaymentMethod::Cash);
+            let method = match prompt(&format!("Refund by [1] Cash  [2] Card [{}]: ", paid_by.label()))?.as_str() {
+                "1" => PaymentMethod::Cash,
+                "2" => PaymentMethod::Card,
+                _ => paid_by,
+            };
+            db.record_pos_return(sale_number, &product.id, quantity, &reason, &shift.id, method)
+        }
+        None => db.record_return(sale_number, &product.id, quantity, &reason),
+    };
+    match result {
+        Ok(refund) => {
+            println!("\n✅ Returned {} {} of {}. Refund {} to the customer.", quantity, product.unit, product.name, money(refund));
+            report_ready_backorders(db, &product.id);
+        }
+        Err(e) => eprintln!("\nError recording return: {}", e),
+    }
+
+    prompt("\nPress Enter to continue...")?;
+    Ok(())
+}
+
+fn display_main_menu() {
+    println!("╔══════════════════════════════════════════╗");
+    println!("║            RuSTOCK CONSOLE               ║");
+    println!("╠══════════════════════════════════════════╣");
+    println!("║  [1] Cargo Management                    ║");
+    println!("║  [2] Trading Operations                  ║");
+    println!("║  [3] Supply Chain                        ║");
+    println!("║  [4] Reports                             ║");
+    println!("║  [5] Manager Administration              ║");
+    println!("║  [6] Exit Terminal                       ║");
+    println!("╚══════════════════════════════════════════╝");
+    println!("\nEnter your choice (1-6): ");
+}
+
+fn display_product_menu() {
+    println!("╔══════════════════════════════════════════╗");
+    println!("║            CARGO MANAGEMENT              ║");
+    println!("╠══════════════════════════════════════════╣");
+    println!("║  [1] View Cargo Registry                 ║");
+    println!("║  [2] Modify Cargo                        ║");
+    println!("║  [3] Archive Cargo                       ║");
+    println!("║  [4] Archived Cargo                      ║");
+    println!("║  [5] Units of Measure                    ║");
+    println!("║  [6] Schedule Price Change               ║");
+    println!("║  [7] Price History                       ║");
+    println!("║  [8] Bundles & Kits                      ║");
+    println!("║  [9] Attachments                         ║");
+    println!("║  [10] Return to Console                  ║");
+    println!("╚══════════════════════════════════════════╝");
+    println!("\nEnter your choice (1-10): ");
+}
+
+fn display_sales_menu() {
+    println!("╔══════════════════════════════════════════╗");
+    println!("║          TRADING OPERATIONS              ║");
+    println!("╠══════════════════════════════════════════╣");
+    println!("║  [1] New Trade Out                       ║");
+    println!("║  [2] View Trade History                  ║");
+    println!("║  [3] Reprint Receipt / Invoice           ║");
+    println!("║  [4] Cash Drawer & Shift                 ║");
+    println!("║  [5] Customer Orders                     ║");
+    println!("║  [6] Customer Returns                    ║");
+    println!("║  [7] Customers & Loyalty                 ║");
+    println!("║  [8] Return to Console                   ║");
+    println!("╚══════════════════════════════════════════╝");
+    println!("\nEnter your choice (1-8): ");
+}
+
+fn display_reports_menu() {
+    println!("╔══════════════════════════════════════════╗");
+    println!("║               REPORTS                    ║");
+    println!("╠══════════════════════════════════════════╣");
+    println!("║  [1] Inventory Report                    ║");
+    println!("║  [2] Sales Report                        ║");
+    println!("║  [3] Purchase History Report             ║");
+    println!("║  [4] Price Evolution Report              ║");
+    println!("║  [5] Turnover & Days of Supply           ║");
+    println!("║  [6] Dead Stock Report                   ║");
+    println!("║  [7] ABC Analysis                        ║");
+    println!("║  [8] Demand Forecast & Purchase Plan     ║");
+    println!("║  [9] Open Orders                         ║");
+    println!("║  [10] Accounting Journal                 ║");
+    println!("║  [11] Return to Console                  ║");
+    println!("╚══════════════════════════════════════════╝");
+    println!("\nEnter your choice (1-11): ");
+}
+
+fn display_purchase_menu() {
+    println!("╔══════════════════════════════════════════╗");
+    println!("║            SUPPLY CHAIN                  ║");
+    println!("╠══════════════════════════════════════════╣");
+    println!("║  [1] New Trade In                        ║");
+    println!("║  [2] View Supply History                 ║");
+    println!("║  [3] Exchange Rates                      ║");
+    println!("║  [4] Return to Console                   ║");
+    println!("╚══════════════════════════════════════════╝");
+    println!("\nEnter your choice (1-4): ");
+}
+
+fn display_manager_menu() {
+    println!("╔══════════════════════════════════════════╗");
+    println!("║         MANAGER ADMINISTRATION           ║");
+    println!("╠══════════════════════════════════════════╣");
+    println!("║  [1] Add New Manager                     ║");
+    println!("║  [2] View All Managers                   ║");
+    println!("║  [3] Activate/Deactivate Manager         ║");
+    println!("║  [4] Backup Database                     ║");
+    println!("║  [5] Restore Database                    ║");
+    println!("║  [6] My Profile                          ║");
+    println!("║  [7] Notification Log                    ║");
+    println!("║  [8] Store Settings                      ║");
+    println!("║  [9] Void Last Operation                 ║");
+    println!("║  [10] Reset Manager Password             ║");
+    println!("║  [11] Remove Manager                     ║");
+    println!("║  [12] Return to Console                  ║");
+    println!("╚══════════════════════════════════════════╝");
+    println!("\nEnter your choice (1-12): ");
+}
+
+#[allow(dead_code)]
+fn display_product_details(product: &Product) {
+    println!("╔══════════════════════════════════════════╗");
+    println!("║            CARGO DETAILS                 ║");
+    println!("╠══════════════════════════════════════════╣");
+    println!("║  ID: {}", product.id);
+    println!("║  Name: {}", product.name);
+    println!("║  Description: {}", product.description);
+    println!("║  Price: {}", money(product.price));
+    println!("║  Stock Level: {}", product.quantity);
+    println!("╚══════════════════════════════════════════╝");
+}
+
+fn handle_product_menu(db: &mut Database) -> Screen {
+    loop {
+        clear_screen();
+        display_logo();
+        display_product_menu();
+
+        let choice = prompt("")?;
+        match choice.trim() {
+            "1" => list_products(db)?,
+            "2" => edit_product(db)?,
+            "3" => archive_product(db)?,
+            "4" => archived_products(db)?,
+            "5" => manage_units(db)?,
+            "6" => schedule_price_change(db)?,
+            "7" => view_price_history(db)?,
+            "8" => manage_bundle(db)?,
+            "9" => product_attachments(db)?,
+            "10" => break,
+            _ => {
+                println!("Invalid option. Press Enter to continue...");
+                prompt("")?;
+            }
+        }
+    }
+    Ok(())
+}
+
+fn handle_sales_menu(db: &mut Database, manager: &Manager) -> Screen {
+    loop {
+        clear_screen();
+        display_logo();
+        display_sales_menu();
+
+        let choice = prompt("")?;
+        println!("DEBUG: User entered choice: '{}'", choice.trim());
+        match choice.trim() {
+            "1" => {
+                println!("DEBUG: Calling record_sale...");
+                record_sale(db, manager)?;
+                println!("DEBUG: Returned from record_sale");
+            },
+            "2" => view_sales(db)?,
+            "3" => reprint_receipt(db)?,
+            "4" => manage_shift(db, manager)?,
+            "5" => manage_orders(db)?,
+            "6" => customer_returns(db, manager)?,
+            "7" => customers_and_loyalty(db)?,
+            "8" => break,
+            _ => {
+                println!("Invalid option. Press Enter to continue...");
+                prompt("")?;
+            }
+        }
+    }
+    Ok(())
+}
+
+fn handle_reports_menu(db: &mut Database) -> Screen {
+    loop {
+        clear_screen();
+        display_logo();
+        display_reports_menu();
+
+        let choice = prompt("")?;
+        match choice.trim() {
+            "1" => list_products(db)?,
+            "2" => view_sales(db)?,
+            "3" => view_purchases(db)?,
+            "4" => price_evolution_report(db)?,
+            "5" => turnover_report(db)?,
+            "6" => dead_stock_report(db)?,
+            "7" => abc_report(db)?,
+            "8" => purchase_plan_report(db)?,
+            "9" => open_orders_report(db)?,
+            "10" => accounting_journal(db)?,
+            "11" => break,
+            _ => {
+                println!("Invalid option. Press Enter to continue...");
+                prompt("")?;
+            }
+        }
+    }
+    Ok(())
+}
+
+fn record_purchase(db: &mut Database) -> Screen {
+    clear_screen();
+    display_logo();
+    println!("╔══════════════════════════════════════════╗");
+    println!("║             NEW TRADE IN                 ║");
+    println!("╚══════════════════════════════════════════╝\n");
+
+    println!("Select operation:");
+    println!("1. Purchase existing cargo");
+    println!("2. Purchase new cargo");
+    println!("3. Return to menu");
+
+    match prompt("\nEnter your choice (1-3): ")?.trim() {
+        "1" => {
+            match db.get_products() {
+                Ok(products) => {
+                    if products.is_empty() {
+                        println!("\nNo existing cargo items found.");
+                        prompt("\nPress Enter to continue...")?;
+                        return Ok(());
+                    }
+
+                    println!("\nExisting Cargo Items:");
+                    for product in &products {
+                        println!("\n┌─ {} ─", product.name);
+                        println!("│  ID: {}", product.id);
+                        println!("│  Current Price: {} per {}", money(product.price), product.unit);
+                        println!("│  Current Stock: {} {}", product.quantity, product.unit);
+                        println!("└──────────────────────────────────────");
+                    }
+
+                    let product_id = prompt_product_id(db, "\nEnter Cargo (ID, SKU or search): ")?;
+                    let product = match db.get_product(&product_id) {
+                        Ok(Some(product)) if product.is_archived() => {
+                            println!("\nProduct is archived. Restore it before purchasing more stock.");
+                            prompt("\nPress Enter to continue...")?;
+                            return Ok(());
+                        }
+                        Ok(Some(product)) => product,
+                        _ => {
+                            println!("\nProduct with ID {} not found.", product_id);
+                            prompt("\nPress Enter to continue...")?;
+                            return Ok(());
+                        }
+                    };
+
+                    let unit = match choose_unit(db, &product)? {
+                        Some((unit, _)) => unit,
+                        None => {
+                            prompt("\nPress Enter to continue...")?;
+                            return Ok(());
+                        }
+                    };
+                    let (currency_code, exchange_rate) = match prompt_currency(db)? {
+                        Some(currency) => currency,
+                        None => {
+                            prompt("\nPress Enter to continue...")?;
+                            return Ok(());
+                        }
+                    };
+                    let unit_name = unit.clone().unwrap_or_else(|| product.unit.clone());
+                    let quantity = prompt(&format!("Enter Quantity ({}): ", unit_name))?.parse::<f64>().unwrap_or(0.0);
+                    let purchase_price = prompt(&format!("Enter Purchase Price per {} ({}): ", unit_name, currency_code))?
+                        .parse::<f64>()
+                        .unwrap_or(0.0);
+
+                    if quantity <= 0.0 || purchase_price <= 0.0 {
+                        println!("\nInvalid quantity or price. Purchase cancelled.");
+                        prompt("\nPress Enter to continue...")?;
+                        return Ok(());
+                    }
+
+                    let mut purchase = Purchase::new(product_id, quantity, purchase_price).in_currency(&currency_code);
+                    if let Some(unit) = &unit {
+                        purchase = purchase.in_unit(unit);
+                    }
+                    purchase.exchange_rate = exchange_rate;
+
+                    match db.record_purchase(&purchase) {
+                        Ok(_) => {
+                            println!("\nPurchase recorded successfully!");
+                            println!("Total Cost: {}", describe_cost(&purchase));
+                            report_ready_backorders(db, &purchase.product_id);
+                            offer_invoice_attachment(db, &purchase.id)?;
+                        }
+                        Err(e) => {
+                            println!("\nError recording purchase: {}", e);
+                        }
+                    }
+                }
+                Err(e) => println!("Error fetching products: {}", e),
+            }
+        }
+        "2" => {
+            println!("\nEnter New Cargo Details:");
+            let name = prompt("Name: ")?;
+            let description = prompt("Description: ")?;
+            let sku = prompt("SKU (optional): ")?;
+            let tags = product::parse_tags(&prompt("Tags (comma-separated, optional): ")?);
+            let unit = unit::normalize(&prompt(&format!("Stock Unit (each, kg, l, ...) [{}]: ", unit::DEFAULT_UNIT))?);
+            let unit = if unit.is_empty() { unit::DEFAULT_UNIT.to_string() } else { unit };
+            let selling_price = prompt(&format!("Selling Price per {} ({}): ", unit, currency::base_currency()))?.parse::<f64>().unwrap_or(0.0);
+            let quantity = prompt(&format!("Purchase Quantity ({}): ", unit))?.parse::<f64>().unwrap_or(0.0);
+            let (currency_code, exchange_rate) = match prompt_currency(db)? {
+                Some(currency) => currency,
+                None => {
+                    prompt("\nPress Enter to continue...")?;
+                    return Ok(());
+                }
+            };
+            let purchase_price = prompt(&format!("Purchase Price per {} ({}): ", unit, currency_code))?
+                .parse::<f64>()
+                .unwrap_or(0.0);
+
+            if quantity <= 0.0 || purchase_price <= 0.0 || selling_price <= 0.0 {
+                println!("\nInvalid quantity or price. Purchase cancelled.");
+                prompt("\nPress Enter to continue...")?;
+                return Ok(());
+            }
+
+            let product = Product {
+                id: uuid::Uuid::new_v4().to_string(),
+                name: name.clone(),
+                description,
+                price: selling_price,
+                quantity: 0.0,
+                unit,
+                sku: (!sku.is_empty()).then_some(sku),
+                tags,
+                created_at: Utc::now().timestamp(),
+                updated_at: Utc::now().timestamp(),
+                archived_at: None,
+                version: 0,
+            };
+
+            if let Err(e) = product.validate().and_then(|_| unit::check_quantity(&product.unit, quantity)) {
+                println!("\n{}", e);
+                prompt("\nPress Enter to continue...")?;
+                return Ok(());
+            }
+
+            match db.add_product(&product) {
+                Ok(_) => {
+                    let mut purchase = Purchase::new(product.id.clone(), quantity, purchase_price).in_currency(&currency_code);
+                    purchase.exchange_rate = exchange_rate;
+
+                    match db.record_purchase(&purchase) {
+                        Ok(_) => {
+                            println!("\nNew cargo created and purchase recorded successfully!");
+                            println!("Total Cost: {}", describe_cost(&purchase));
+                            report_ready_backorders(db, &purchase.product_id);
+                            offer_invoice_attachment(db, &purchase.id)?;
+                        }
+                        Err(e) => {
+                            println!("\nError recording purchase: {}", e);
+                        }
+                    }
+                }
+                Err(e) => {
+                    println!("\nError creating new product: {}", e);
+                }
+            }
+        }
+        "3" => return Ok(()),
+        _ => {
+            println!("\nInvalid option.");
+        }
+    }
+
+    prompt("\nPress Enter to continue...")?;
+    Ok(())
+}
+
+/// Asks for the supplier's currency and returns it with today's rate to the base currency.
+fn prompt_currency(db: &Database) -> Screen<Option<(String, f64)>> {
+    let base = currency::base_currency();
+    let input = prompt(&format!("Currency [{}]: ", base))?;
+    let code = if input.is_empty() { Ok(base.clone()) } else { currency::normalize(&input) };
+    let code = match code {
+        Ok(code) => code,
+        Err(e) => {
+            println!("\n{}", e);
+            return Ok(None);
+        }
+    };
+
+    Ok(match db.exchange_rate(&code, Utc::now().timestamp()) {
+        Ok(rate) => {
+            if code != base {
+                println!("Using 1 {} = {} {}", code, rate, base);
+            }
+            Some((code, rate))
+        }
+        Err(e) => {
+            println!("\n{}. Add one under Supply Chain > Exchange Rates.", e);
+            None
+        }
+    })
+}
+
+/// Total cost in the supplier's currency, with its base-currency value for foreign purchases.
+fn describe_cost(purchase: &Purchase) -> String {
+    let cost = currency::format_amount(purchase.total_cost, &purchase.currency);
+    if purchase.currency == currency::base_currency() {
+        cost
+    } else {
+        format!("{} ({} at {})", cost, money(purchase.base_total_cost()), purchase.exchange_rate)
+    }
+}
+
+fn manage_exchange_rates(db: &Database) -> Screen {
+    let base = currency::base_currency();
+    loop {
+        clear_screen();
+        display_logo();
+        println!("╔══════════════════════════════════════════╗");
+        println!("║            EXCHANGE RATES                ║");
+        println!("╚══════════════════════════════════════════╝\n");
+        println!("Base currency: {}\n", base);
+
+        match db.get_exchange_rates() {
+            Ok(rates) if rates.is_empty() => println!("No exchange rates maintained yet."),
+            Ok(rates) => {
+                for rate in &rates {
+                    println!(
+                        "  {}  1 {} = {} {}",
+                        timezone::format_date(rate.effective_from),
+                        rate.currency,
+                        rate.rate,
+                        base
+                    );
+                }
+            }
+            Err(e) => println!("Error fetching exchange rates: {}", e),
+        }
+
+        println!("\n[A] Add or correct a rate  [Enter] Return");
+        match prompt("Choice: ")?.to_lowercase().as_str() {
+            "a" => {
+                let code = prompt("Currency (e.g. EUR, GBP): ")?;
+                let rate = prompt(&format!("Value of 1 {} in {}: ", code.trim().to_uppercase(), base))?
+                    .parse::<f64>()
+                    .unwrap_or(0.0);
+                let date = prompt("Effective from (YYYY-MM-DD, Enter for today): ")?;
+                let effective_from = if date.is_empty() {
+                    Ok(timezone::today_start())
+                } else {
+                    pricing::parse_date(&date)
+                };
+                let result = effective_from.and_then(|at| db.set_exchange_rate(&ExchangeRate::new(&code, rate, at)));
+                match result {
+                    Ok(()) => println!("\nExchange rate saved."),
+                    Err(e) => eprintln!("\nError saving exchange rate: {}", e),
+                }
+            }
+            _ => break,
+        }
+        prompt("\nPress Enter to continue...")?;
+    }
+    Ok(())
+}
+
+fn view_purchases(db: &mut Database) -> Screen {
+    clear_screen();
+    display_logo();
+    println!("=== Purchase History ===\n");
+
+    match db.get_all_purchases() {
+        Ok(purchases) => {
+            if purchases.is_empty() {
+                println!("No purchase history available.");
+            } else {
+                let mut total_cost = 0.0;
+                for (i, purchase) in purchases.iter().enumerate() {
+                    let (product_name, stock_unit) = db.get_product(&purchase.product_id)
+                        .ok()
+                        .flatten()
+                        .map_or_else(|| ("Unknown".to_string(), unit::DEFAULT_UNIT.to_string()), |p| (p.name, p.unit));
+                    let unit_name = purchase.unit.clone().unwrap_or(stock_unit);
+
+                    println!("[{}] Product: {}", i + 1, product_name);
+                    println!("Quantity: {} {}", purchase.quantity, unit_name);
+                    println!("Purchase Price: {}/{}", currency::format_amount(purchase.purchase_price, &purchase.currency), unit_name);
+                    println!("Total Cost: {}", describe_cost(purchase));
+                    println!("Date: {}", format_timestamp(purchase.purchase_date));
+                    let attachments = db.get_attachments(OwnerKind::Purchase, &purchase.id).unwrap_or_default();
+                    if !attachments.is_empty() {
+                        let names: Vec<&str> = attachments.iter().map(|a| a.file_name.as_str()).collect();
+                        println!("Attachments: {}", names.join(", "));
+                    }
+                    println!("------------------");
+                    total_cost += purchase.base_total_cost();
+                }
+                println!("\nTotal Purchases Cost: {}", money(total_cost));
+
+                let selected = prompt("\nPurchase number to manage its attachments (or press Enter to return): ")?
+                    .parse::<usize>()
+                    .ok()
+                    .and_then(|n| n.checked_sub(1))
+                    .and_then(|i| purchases.get(i));
+                if let Some(purchase) = selected {
+                    let title = format!("purchase of {}", format_timestamp(purchase.purchase_date));
+                    manage_attachments(db, OwnerKind::Purchase, &purchase.id, &title)?;
+                }
+                return Ok(());
+            }
+        }
+        Err(e) => {
+            println!("Error fetching purchase history: {}", e);
+        }
+    }
+
+    prompt("\nPress Enter to continue...")?;
+    Ok(())
+}
+
+fn handle_purchase_menu(db: &mut Database) -> Screen {
+    loop {
+        clear_screen();
+        display_logo();
+        display_purchase_menu();
+
+        let choice = prompt("")?;
+        match choice.trim() {
+            "1" => record_purchase(db)?,
+            "2" => view_purchases(db)?,
+            "3" => manage_exchange_rates(db)?,
+            "4" => break,
+            _ => {
+                println!("Invalid option. Press Enter to continue...");
+                prompt("")?;
+            }
+        }
+    }
+    Ok(())
+}
+
+fn add_new_manager(db: &Database, policy: &SecurityPolicy) -> Screen {
+    clear_screen();
+    display_logo();
+    println!("\nAdd New Manager");
+    println!("---------------");
+    println!("Passwords need {}.\n", policy.password.describe());
+
+    let username = prompt("Username: ")?;
+    let password = prompt("Password: ")?;
+    let full_name = prompt("Full Name: ")?;
+
+    // Check if username already exists
+    match db.get_manager_by_username(&username) {
+        Ok(Some(_)) => {
+            println!("\nError: Username '{}' already exists!", username);
+            prompt("\nPress Enter to continue...")?;
+            return Ok(());
+        }
+        Ok(None) => {
+            // Username is available, proceed
+        }
+        Err(e) => {
+            println!("\nDatabase error: {}", e);
+            prompt("\nPress Enter to continue...")?;
+            return Ok(());
+        }
+    }
+
+    let manager = Manager::new(username, password, full_name);
+
+    match manager.validate(&policy.password) {
+        Ok(()) => {
+            match db.add_manager(&manager) {
+                Ok(()) => {
+                    println!("\n✅ Manager '{}' added successfully!", manager.username);
+                    println!("Full Name: {}", manager.full_name);
+                }
+                Err(e) => println!("\nError adding manager: {}", e),
+            }
+        }
+        Err(e) => println!("\nValidation error: {}", e),
+    }
+
+    prompt("\nPress Enter to continue...")?;
+    Ok(())
+}
+
+fn view_all_managers(db: &Database) -> Screen {
     clear_screen();
     display_logo();
     println!("╔══════════════════════════════════════════╗");
-    println!("║             NEW TRADE IN                 ║");
+    println!("║           MANAGER REGISTRY               ║");
     println!("╚══════════════════════════════════════════╝\n");
 
-    println!("Select operation:");
-    println!("1. Purchase existing cargo");
-    println!("2. Purchase new cargo");
-    println!("3. Return to menu");
-
-    match prompt("\nEnter your choice (1-3): ").trim() {
-        "1" => {
-            match db.get_products() {
-                Ok(products) => {
-                    if products.is_empty() {
-                        println!("\nNo existing cargo items found.");
-                        prompt("\nPress Enter to continue...");
-                        return;
+    match db.get_all_managers() {
+        Ok(managers) => {
+            if managers.is_empty() {
+                println!("No managers found in the system.");
+            } else {
+                for manager in &managers {
+                    let status = match manager.removed_at {
+                        Some(removed_at) => format!("⚫ Removed {}", format_timestamp(removed_at)),
+                        None if manager.is_active => "🟢 Active".to_string(),
+                        None => "🔴 Inactive".to_string(),
+                    };
+                    println!("┌─ {} ─", manager.full_name);
+                    println!("│  Username: {}", manager.username);
+                    println!("│  Status: {}", status);
+                    if manager.must_change_password && !manager.is_removed() {
+                        println!("│  Password: must be changed at next login");
                     }
+                    println!("│  Created: {}", format_timestamp(manager.created_at));
+                    println!("└──────────────────────────────────────");
+                }
+                println!("\nTotal Managers: {}", managers.len());
+            }
+        }
+        Err(e) => println!("Error fetching managers: {}", e),
+    }
 
-                    println!("\nExisting Cargo Items:");
-                    for product in &products {
-                        println!("\n┌─ {} ─", product.name);
-                        println!("│  ID: {}", product.id);
-                        println!("│  Current Price: ${:.2}", product.price);
-                        println!("│  Current Stock: {}", product.quantity);
-                        println!("└──────────────────────────────────────");
-                    }
+    prompt("\nPress Enter to continue...")?;
+    Ok(())
+}
 
-                    let product_id = prompt("\nEnter Cargo ID: ");
-                    let quantity = prompt("Enter Quantity: ").parse::<i32>().unwrap_or(0);
-                    let purchase_price = prompt("Enter Purchase Price per Unit: $").parse::<f64>().unwrap_or(0.0);
+fn manage_manager_status(db: &Database) -> Screen {
+    clear_screen();
+    display_logo();
+    println!("\nActivate/Deactivate Manager");
+    println!("---------------------------");
 
-                    if quantity <= 0 || purchase_price <= 0.0 {
-                        println!("\nInvalid quantity or price. Purchase cancelled.");
-                        prompt("\nPress Enter to continue...");
-                        return;
-                    }
+    match db.get_all_managers() {
+        Ok(mut managers) => {
+            managers.retain(|manager| !manager.is_removed());
+            if managers.is_empty() {
+                println!("No managers found in the system.");
+                prompt("\nPress Enter to continue...")?;
+                return Ok(());
+            }
+
+            println!("\nCurrent Managers:");
+            for (idx, manager) in managers.iter().enumerate() {
+                let status = if manager.is_active { "🟢 Active" } else { "🔴 Inactive" };
+                println!("  [{}] {} ({}) - {}", idx + 1, manager.full_name, manager.username, status);
+            }
+
+            let selection = prompt("\nSelect manager number (or press Enter to cancel): ")?;
+            if selection.trim().is_empty() {
+                return Ok(());
+            }
 
-                    if let Ok(Some(mut product)) = db.get_product(&product_id) {
-                        product.quantity += quantity;
-                        if let Err(e) = db.update_product(&product) {
-                            println!("\nError updating product quantity: {}", e);
-                            prompt("\nPress Enter to continue...");
-                            return;
+            if let Ok(idx) = selection.parse::<usize>() {
+                if idx > 0 && idx <= managers.len() {
+                    let manager = &managers[idx - 1];
+                    let new_status = !manager.is_active;
+                    let action = if new_status { "activate" } else { "deactivate" };
+                    
+                    let confirm = prompt(&format!("\nAre you sure you want to {} '{}'? (y/N): ", action, manager.full_name))?;
+                    if confirm.to_lowercase() == "y" {
+                        match db.update_manager_status(&manager.id, new_status) {
+                            Ok(()) => {
+                                println!("\n✅ Manager '{}' has been {}d successfully!", manager.full_name, action);
+                                if !new_status {
+                                    notify(vec![notifier().manager_event(manager)]);
+                                }
+                            }
+                            Err(e) => println!("\nError updating manager status: {}", e),
                         }
                     } else {
-                        println!("\nProduct with ID {} not found.", product_id);
-                        prompt("\nPress Enter to continue...");
-                        return;
+                        println!("\nOperation cancelled.");
                     }
-                    
-                    let total_cost = quantity as f64 * purchase_price;
+                } else {
+                    println!("\nInvalid selection.");
+                }
+            } else {
+                println!("\nInvalid input.");
+            }
+        }
+        Err(e) => println!("Error fetching managers: {}", e),
+    }
 
-                    let purchase = Purchase::new(product_id, quantity, purchase_price);
+    prompt("\nPress Enter to continue...")?;
+    Ok(())
+}
 
-                    match db.record_purchase(&purchase) {
-                        Ok(_) => {
-                            println!("\nPurchase recorded successfully!");
-                            println!("Total Cost: ${:.2}", total_cost);
-                        }
-                        Err(e) => {
-                            println!("\nError recording purchase: {}", e);
-                        }
+/// Lists `managers` and returns the one picked, or `None` when cancelled.
+fn choose_manager<'a>(managers: &'a [Manager], question: &str) -> Screen<Option<&'a Manager>> {
+    for (idx, manager) in managers.iter().enumerate() {
+        let status = if manager.is_active { "🟢 Active" } else { "🔴 Inactive" };
+        println!("  [{}] {} ({}) - {}", idx + 1, manager.full_name, manager.username, status);
+    }
+
+    let selection = prompt(question)?;
+    if selection.trim().is_empty() {
+        return Ok(None);
+    }
+    Ok(match selection.parse::<usize>() {
+        Ok(idx) if idx > 0 && idx <= managers.len() => Some(&managers[idx - 1]),
+        _ => {
+            println!("\nInvalid selection.");
+            None
+        }
+    })
+}
+
+/// Managers other than `current_manager` that have not been removed.
+fn other_managers(db: &Database, current_manager: &Manager) -> Option<Vec<Manager>> {
+    match db.get_all_managers() {
+        Ok(mut managers) => {
+            managers.retain(|manager| !manager.is_removed() && manager.id != current_manager.id);
+            Some(managers)
+        }
+        Err(e) => {
+            println!("Error fetching managers: {}", e);
+            None
+        }
+    }
+}
+
+/// The logged-in manager's own account: rename it or change its password.
+fn my_profile(db: &Database, current_manager: &mut Manager, policy: &SecurityPolicy) -> Screen {
+    loop {
+        clear_screen();
+        display_logo();
+        println!("╔══════════════════════════════════════════╗");
+        println!("║               MY PROFILE                 ║");
+        println!("╚══════════════════════════════════════════╝\n");
+        println!("Full Name: {}", current_manager.full_name);
+        println!("Username:  {}", current_manager.username);
+        println!("Created:   {}", format_timestamp(current_manager.created_at));
+
+        println!("\n  [1] Edit Name and Username");
+        println!("  [2] Change Password");
+        println!("  [3] Return");
+        match prompt("\nEnter your choice (1-3): ")?.as_str() {
+            "1" => {
+                let full_name = prompt(&format!("\nFull Name [{}]: ", current_manager.full_name))?;
+                let full_name = if full_name.is_empty() { current_manager.full_name.clone() } else { full_name };
+                let username = prompt(&format!("Username [{}]: ", current_manager.username))?;
+                let username = if username.is_empty() { current_manager.username.clone() } else { username };
+
+                match db.update_manager_profile(&current_manager.id, &username, &full_name) {
+                    Ok(()) => {
+                        current_manager.full_name = full_name;
+                        current_manager.username = username;
+                        println!("\n✅ Profile updated.");
                     }
+                    Err(e) => println!("\nError updating profile: {}", e),
+                }
+                prompt("\nPress Enter to continue...")?;
+            }
+            "2" => {
+                change_password(db, current_manager, policy, false)?;
+            }
+            "3" | "" => break,
+            _ => {
+                println!("Invalid option. Press Enter to continue...");
+                prompt("")?;
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Gives another manager a one-time password to log in with after forgetting theirs.
+fn reset_manager_password(db: &Database, current_manager: &Manager, policy: &SecurityPolicy) -> Screen {
+    clear_screen();
+    display_logo();
+    println!("\nReset Manager Password");
+    println!("----------------------");
+    println!("Use My Profile to change your own password.\n");
+
+    let Some(managers) = other_managers(db, current_manager) else {
+        prompt("\nPress Enter to continue...")?;
+        return Ok(());
+    };
+    if managers.is_empty() {
+        println!("There are no other managers.");
+        prompt("\nPress Enter to continue...")?;
+        return Ok(());
+    }
+    let Some(manager) = choose_manager(&managers, "\nSelect manager number (or press Enter to cancel): ")? else {
+        prompt("\nPress Enter to continue...")?;
+        return Ok(());
+    };
+
+    let confirm = prompt(&format!("\nReset the password of '{}'? (y/N): ", manager.full_name))?;
+    if confirm.to_lowercase() != "y" {
+        println!("\nOperation cancelled.");
+        prompt("\nPress Enter to continue...")?;
+        return Ok(());
+    }
+
+    let temporary = auth::temporary_password(&policy.password);
+    match db.reset_manager_password(&manager.id, &temporary) {
+        Ok(()) => {
+            println!("\n✅ Temporary password for '{}': {}", manager.username, temporary);
+            println!("It is shown only once and must be changed at the next login.");
+            if !manager.is_active {
+                println!("⚠️  The account is inactive; activate it before they can log in.");
+            }
+        }
+        Err(e) => println!("\nError resetting password: {}", e),
+    }
+
+    prompt("\nPress Enter to continue...")?;
+    Ok(())
+}
+
+fn remove_manager(db: &mut Database, current_manager: &Manager) -> Screen {
+    clear_screen();
+    display_logo();
+    println!("\nRemove Manager");
+    println!("--------------");
+    println!("Managers who opened shifts or approved voids are retired rather than deleted,");
+    println!("so their history stays attributed to them.\n");
+
+    let Some(managers) = other_managers(db, current_manager) else {
+        prompt("\nPress Enter to continue...")?;
+        return Ok(());
+    };
+    if managers.is_empty() {
+        println!("There are no other managers.");
+        prompt("\nPress Enter to continue...")?;
+        return Ok(());
+    }
+    let Some(manager) = choose_manager(&managers, "\nSelect manager number (or press Enter to cancel): ")? else {
+        prompt("\nPress Enter to continue...")?;
+        return Ok(());
+    };
+
+    let mut successor = None;
+    match db.current_shift(&manager.id) {
+        Ok(Some(shift)) => {
+            println!("\n'{}' has a shift open since {}.", manager.full_name, format_timestamp(shift.opened_at));
+            let candidates: Vec<Manager> =
+                managers.iter().filter(|m| m.id != manager.id && m.is_active).cloned().collect();
+            if candidates.is_empty() {
+                println!("No other active manager can take it over; close it with a Z report first.");
+                prompt("\nPress Enter to continue...")?;
+                return Ok(());
+            }
+            println!("Choose who takes it over:");
+            match choose_manager(&candidates, "\nSelect manager number (or press Enter to cancel): ")? {
+                Some(heir) => successor = Some(heir.id.clone()),
+                None => {
+                    prompt("\nPress Enter to continue...")?;
+                    return Ok(());
                 }
-                Err(e) => println!("Error fetching products: {}", e),
             }
         }
-        "2" => {
-            println!("\nEnter New Cargo Details:");
-            let name = prompt("Name: ");
-            let description = prompt("Description: ");
-            let selling_price = prompt("Selling Price per Unit: $").parse::<f64>().unwrap_or(0.0);
-            let quantity = prompt("Purchase Quantity: ").parse::<i32>().unwrap_or(0);
-            let purchase_price = prompt("Purchase Price per Unit: $").parse::<f64>().unwrap_or(0.0);
+        Ok(None) => {}
+        Err(e) => {
+            println!("\nError checking shifts: {}", e);
+            prompt("\nPress Enter to continue...")?;
+            return Ok(());
+        }
+    }
 
-            if quantity <= 0 || purchase_price <= 0.0 || selling_price <= 0.0 {
-                println!("\nInvalid quantity or price. Purchase cancelled.");
-                prompt("\nPress Enter to continue...");
-                return;
+    let confirm = prompt(&format!("\nRemove '{}'? (y/N): ", manager.full_name))?;
+    if confirm.to_lowercase() != "y" {
+        println!("\nOperation cancelled.");
+        prompt("\nPress Enter to continue...")?;
+        return Ok(());
+    }
+
+    match db.remove_manager(&manager.id, &current_manager.id, successor.as_deref()) {
+        Ok(ManagerRemoval::Deleted) => println!("\n✅ Manager '{}' has been deleted.", manager.full_name),
+        Ok(ManagerRemoval::Retired { reassigned_shifts }) => {
+            println!("\n✅ Manager '{}' has been retired; their history is kept.", manager.full_name);
+            if reassigned_shifts > 0 {
+                println!("Their open shift was handed over.");
             }
+        }
+        Err(e) => println!("\nError removing manager: {}", e),
+    }
 
-            let mut product = Product {
-                id: uuid::Uuid::new_v4().to_string(),
-                name: name.clone(),
-                description,
-                price: selling_price,
-                quantity: 0,
-                created_at: Utc::now().timestamp(),
-                updated_at: Utc::now().timestamp(),
-            };
+    prompt("\nPress Enter to continue...")?;
+    Ok(())
+}
 
-            match db.add_product(&product) {
-                Ok(_) => {
-                    product.quantity += quantity;
-                    if let Err(e) = db.update_product(&product) {
-                        println!("\nError updating product quantity: {}", e);
-                        prompt("\nPress Enter to continue...");
-                        return;
-                    }
+fn backup_database(db: &Database) -> Screen {
+    clear_screen();
+    display_logo();
+    println!("\nBackup Database");
+    println!("---------------");
 
-                    let purchase = Purchase::new(product.id.clone(), quantity, purchase_price);
+    let compress = prompt("Compress backup? (y/N): ")?.to_lowercase() == "y";
 
-                    match db.record_purchase(&purchase) {
-                        Ok(_) => {
-                            println!("\nNew cargo created and purchase recorded successfully!");
-                            println!("Total Cost: ${:.2}", purchase.total_cost);
-                        }
-                        Err(e) => {
-                            println!("\nError recording purchase: {}", e);
-                        }
-                    }
-                }
-                Err(e) => {
-                    println!("\nError creating new product: {}", e);
-                }
+    match backup::create_backup(db, Path::new(backup::BACKUP_DIR), compress) {
+        Ok(path) => {
+            println!("\n✅ Backup written to {}", path.display());
+            if db.is_encrypted() {
+                println!("The backup is encrypted with the current database passphrase.");
             }
         }
-        "3" => return,
-        _ => {
-            println!("\nInvalid option.");
-        }
+        Err(e) => println!("\nError creating backup: {}", e),
     }
 
-    prompt("\nPress Enter to continue...");
+    prompt("\nPress Enter to continue...")?;
+    Ok(())
 }
 
-fn view_purchases(db: &Database) {
+fn restore_database(db: &mut Database) -> Screen {
     clear_screen();
     display_logo();
-    println!("=== Purchase History ===\n");
+    println!("\nRestore Database");
+    println!("----------------");
 
-    match db.get_all_purchases() {
-        Ok(purchases) => {
-            if purchases.is_empty() {
-                println!("No purchase history available.");
-            } else {
-                let mut total_cost = 0.0;
-                for purchase in purchases {
-                    let product_name = db.get_product(&purchase.product_id)
-                        .ok()
-                        .flatten()
-                        .map_or_else(|| "Unknown".to_string(), |p| p.name);
-
-                    println!("Product: {}", product_name);
-                    println!("Quantity: {}", purchase.quantity);
-                    println!("Purchase Price: ${:.2}/unit", purchase.purchase_price);
-                    println!("Total Cost: ${:.2}", purchase.total_cost);
-                    let dt = DateTime::<Utc>::from_timestamp(purchase.purchase_date, 0).unwrap();
-                    println!("Date: {}", dt.format("%Y-%m-%d %H:%M:%S"));
-                    println!("------------------");
-                    total_cost += purchase.total_cost;
-                }
-                println!("\nTotal Purchases Cost: ${:.2}", total_cost);
-            }
-        }
+    let backups = match backup::list_backups(Path::new(backup::BACKUP_DIR)) {
+        Ok(backups) => backups,
         Err(e) => {
-            println!("Error fetching purchase history: {}", e);
+            println!("\nError reading backup directory: {}", e);
+            prompt("\nPress Enter to continue...")?;
+            return Ok(());
         }
+    };
+
+    if backups.is_empty() {
+        println!("No backups found in '{}'.", backup::BACKUP_DIR);
+        prompt("\nPress Enter to continue...")?;
+        return Ok(());
     }
 
-    prompt("\nPress Enter to continue...");
-}
+    println!("\nAvailable Backups:");
+    for (idx, path) in backups.iter().enumerate() {
+        println!("  [{}] {}", idx + 1, path.display());
+    }
 
-fn handle_purchase_menu(db: &mut Database) {
-    loop {
-        clear_screen();
-        display_logo();
-        display_purchase_menu();
+    let selection = prompt("\nSelect backup number (or press Enter to cancel): ")?;
+    if selection.trim().is_empty() {
+        return Ok(());
+    }
 
-        let choice = prompt("");
-        match choice.trim() {
-            "1" => record_purchase(db),
-            "2" => view_purchases(db),
-            "3" => break,
-            _ => {
-                println!("Invalid option. Press Enter to continue...");
-                prompt("");
+    match selection.parse::<usize>() {
+        Ok(idx) if idx > 0 && idx <= backups.len() => {
+            let path = &backups[idx - 1];
+            println!("\n⚠️  All current data will be replaced by this backup.");
+            let confirm = prompt(&format!("Restore from '{}'? (y/N): ", path.display()))?;
+            if confirm.to_lowercase() == "y" {
+                match backup::restore_backup(db, path) {
+                    Ok(()) => {
+                        println!("\n✅ Database restored successfully!");
+                        install_settings(db);
+                    }
+                    Err(e) => println!("\nError restoring backup: {}", e),
+                }
+            } else {
+                println!("\nRestore cancelled.");
             }
         }
+        _ => println!("\nInvalid selection."),
     }
+
+    prompt("\nPress Enter to continue...")?;
+    Ok(())
 }
 
-fn add_new_manager(db: &Database) {
+/// Lets the logged-in manager replace their password. When `forced` is set the
+/// screen is shown right after login and cannot be skipped without logging out.
+fn change_password(db: &Database, manager: &Manager, policy: &SecurityPolicy, forced: bool) -> Screen<bool> {
     clear_screen();
     display_logo();
-    println!("\nAdd New Manager");
+    println!("\nChange Password");
     println!("---------------");
+    if forced {
+        println!("⚠️  You must set a new password before continuing.");
+    }
+    println!("Passwords need {}.\n", policy.password.describe());
 
-    let username = prompt("Username: ");
-    let password = prompt("Password: ");
-    let full_name = prompt("Full Name: ");
-
-    // Check if username already exists
-    match db.get_manager_by_username(&username) {
-        Ok(Some(_)) => {
-            println!("\nError: Username '{}' already exists!", username);
-            prompt("\nPress Enter to continue...");
-            return;
-        }
+    let stored = match db.get_manager_by_username(&manager.username) {
+        Ok(Some(stored)) => stored,
         Ok(None) => {
-            // Username is available, proceed
+            println!("\nManager account no longer exists.");
+            prompt("\nPress Enter to continue...")?;
+            return Ok(false);
         }
         Err(e) => {
             println!("\nDatabase error: {}", e);
-            prompt("\nPress Enter to continue...");
-            return;
+            prompt("\nPress Enter to continue...")?;
+            return Ok(false);
         }
+    };
+
+    let current = prompt("Current Password: ")?;
+    if !auth::verify_password(&stored.password, &current) {
+        println!("\nCurrent password is incorrect.");
+        prompt("\nPress Enter to continue...")?;
+        return Ok(false);
     }
 
-    let manager = Manager::new(username, password, full_name);
+    let new_password = prompt("New Password: ")?;
+    if let Err(e) = policy.password.check(&new_password) {
+        println!("\nValidation error: {}", e);
+        prompt("\nPress Enter to continue...")?;
+        return Ok(false);
+    }
+    if auth::verify_password(&stored.password, &new_password) {
+        println!("\nNew password must differ from the current one.");
+        prompt("\nPress Enter to continue...")?;
+        return Ok(false);
+    }
+    if prompt("Confirm New Password: ")? != new_password {
+        println!("\nPasswords do not match.");
+        prompt("\nPress Enter to continue...")?;
+        return Ok(false);
+    }
 
-    match manager.validate() {
+    Ok(match db.update_manager_password(&stored.id, &new_password) {
         Ok(()) => {
-            match db.add_manager(&manager) {
-                Ok(()) => {
-                    println!("\n✅ Manager '{}' added successfully!", manager.username);
-                    println!("Full Name: {}", manager.full_name);
-                }
-                Err(e) => println!("\nError adding manager: {}", e),
-            }
+            println!("\n✅ Password changed successfully!");
+            prompt("\nPress Enter to continue...")?;
+            true
         }
-        Err(e) => println!("\nValidation error: {}", e),
-    }
-
-    prompt("\nPress Enter to continue...");
+        Err(e) => {
+            println!("\nError changing password: {}", e);
+            prompt("\nPress Enter to continue...")?;
+            false
+        }
+    })
 }
 
-fn view_all_managers(db: &Database) {
+fn view_notification_log(db: &Database) -> Screen {
     clear_screen();
     display_logo();
     println!("╔══════════════════════════════════════════╗");
-    println!("║           MANAGER REGISTRY               ║");
+    println!("║            NOTIFICATION LOG              ║");
     println!("╚══════════════════════════════════════════╝\n");
 
-    match db.get_all_managers() {
-        Ok(managers) => {
-            if managers.is_empty() {
-                println!("No managers found in the system.");
-            } else {
-                for manager in &managers {
-                    let status = if manager.is_active { "🟢 Active" } else { "🔴 Inactive" };
-                    println!("┌─ {} ─", manager.full_name);
-                    println!("│  Username: {}", manager.username);
-                    println!("│  Status: {}", status);
-                    let dt = DateTime::<Utc>::from_timestamp(manager.created_at, 0)
-                        .unwrap_or_else(|| Utc::now());
-                    println!("│  Created: {}", dt.format("%Y-%m-%d %H:%M:%S"));
-                    println!("└──────────────────────────────────────");
+    let sinks = NotifyConfig::from_env().0.sinks;
+    if sinks.is_empty() {
+        println!("No sinks configured. Set RUSTOCK_NOTIFY_SINKS to enable notifications.\n");
+    } else {
+        println!("Sinks: {}\n", sinks.iter().map(|sink| sink.describe()).collect::<Vec<_>>().join(", "));
+    }
+
+    match db.get_delivery_log(30) {
+        Ok(log) if log.is_empty() => println!("No notifications sent yet."),
+        Ok(log) => {
+            for delivery in &log {
+                let status = if delivery.delivered { "✅" } else { "❌" };
+                println!(
+                    "{} {}  {:<20} {}  ({} attempt(s))",
+                    status,
+                    format_timestamp(delivery.created_at),
+                    delivery.event.as_str(),
+                    delivery.sink,
+                    delivery.attempts
+                );
+                if let Some(error) = &delivery.error {
+                    println!("     {}", error);
                 }
-                println!("\nTotal Managers: {}", managers.len());
             }
         }
-        Err(e) => println!("Error fetching managers: {}", e),
+        Err(e) => eprintln!("Error fetching notification log: {}", e),
     }
 
-    prompt("\nPress Enter to continue...");
+    prompt("\nPress Enter to continue...")?;
+    Ok(())
 }
 
-fn manage_manager_status(db: &Database) {
-    clear_screen();
-    display_logo();
-    println!("\nActivate/Deactivate Manager");
-    println!("---------------------------");
+/// Installs the store settings saved in `db`, reporting values that were ignored.
+fn install_settings(db: &Database) {
+    match db.store_settings() {
+        Ok((store_settings, errors)) => {
+            for e in errors {
+                eprintln!("Setting ignored: {}", e);
+            }
+            settings::install(store_settings);
+        }
+        Err(e) => eprintln!("Error loading store settings: {}", e),
+    }
+}
 
-    match db.get_all_managers() {
-        Ok(managers) => {
-            if managers.is_empty() {
-                println!("No managers found in the system.");
-                prompt("\nPress Enter to continue...");
-                return;
+fn store_settings(db: &Database) -> Screen {
+    loop {
+        clear_screen();
+        display_logo();
+        println!("╔══════════════════════════════════════════╗");
+        println!("║            STORE SETTINGS                ║");
+        println!("╚══════════════════════════════════════════╝\n");
+
+        let saved = match db.saved_settings() {
+            Ok(saved) => saved,
+            Err(e) => {
+                eprintln!("Error fetching settings: {}", e);
+                prompt("\nPress Enter to continue...")?;
+                return Ok(());
             }
+        };
+        let current = settings::current();
+        for (i, key) in KEYS.iter().enumerate() {
+            let source = if saved.iter().any(|(name, _)| name == key.name) { "  (saved)" } else { "" };
+            println!(
+                "  [{}] {:<22} {}{}",
+                i + 1,
+                key.label,
+                current.get(key.name).unwrap_or_default(),
+                source
+            );
+        }
+        println!("\nSaved values override the settings file ({}) and environment.", settings::file_path().display());
+
+        let choice = prompt("\nSelect a setting to change (or press Enter to return): ")?;
+        let Some(key) = choice.parse::<usize>().ok().and_then(|n| n.checked_sub(1)).and_then(|i| KEYS.get(i)) else {
+            return Ok(());
+        };
+        if key.name == "currency" {
+            println!("\n⚠️  Amounts already recorded are not converted to the new currency.");
+        }
+        let value = prompt(&format!("New {} (Enter to keep, - to use the file/environment value): ", key.label))?;
+        let result = match value.as_str() {
+            "" => continue,
+            "-" => db.reset_setting(key.name),
+            _ => db.save_setting(key.name, &value),
+        };
+        match result {
+            Ok(updated) => {
+                settings::install(updated);
+                println!("\n✅ {} updated.", key.label);
+            }
+            Err(e) => println!("\nError saving setting: {}", e),
+        }
+        prompt("\nPress Enter to continue...")?;
+    }
+}
 
-            println!("\nCurrent Managers:");
-            for (idx, manager) in managers.iter().enumerate() {
-                let status = if manager.is_active { "🟢 Active" } else { "🔴 Inactive" };
-                println!("  [{}] {} ({}) - {}", idx + 1, manager.full_name, manager.username, status);
+/// Asks a manager to re-enter their credentials, returning them when accepted.
+fn approving_manager(db: &Database, current_manager: &Manager, policy: &SecurityPolicy) -> Screen<Option<Manager>> {
+    println!("\nManager approval required.");
+    let username = prompt(&format!("Username [{}]: ", current_manager.username))?;
+    let username = if username.is_empty() { current_manager.username.clone() } else { username };
+    let password = prompt("Password: ")?;
+    Ok(match db.attempt_login(&username, &password, policy) {
+        Ok(LoginOutcome::Success(manager)) => Some(manager),
+        Ok(LoginOutcome::InvalidCredentials) => {
+            println!("\n❌ Invalid credentials; nothing was voided.");
+            None
+        }
+        Ok(LoginOutcome::LockedOut { until }) => {
+            println!("\n🔒 Account locked until {}; nothing was voided.", format_timestamp(until));
+            None
+        }
+        Err(e) => {
+            eprintln!("\nError checking credentials: {}", e);
+            None
+        }
+    })
+}
+
+fn void_last_operation(db: &mut Database, current_manager: &Manager, policy: &SecurityPolicy) -> Screen {
+    clear_screen();
+    display_logo();
+    println!("\nVoid Last Operation");
+    println!("-------------------");
+
+    let window = settings::current().void_window_minutes;
+    let now = Utc::now().timestamp();
+    println!("Operations can be voided for {} minutes.\n", window);
+
+    let mut operations = Vec::new();
+    for (i, kind) in OperationKind::ALL.iter().enumerate() {
+        match db.last_operation(*kind) {
+            Ok(Some(operation)) => {
+                let status = if operation.within_window(window, now) { "" } else { "  (too old)" };
+                println!(
+                    "  [{}] {}, {} min ago{}",
+                    i + 1,
+                    operation.description,
+                    (now - operation.timestamp) / 60,
+                    status
+                );
+                operations.push(Some(operation));
+            }
+            Ok(None) => {
+                println!("  [{}] No {} to void", i + 1, kind.label().to_lowercase());
+                operations.push(None);
             }
+            Err(e) => {
+                eprintln!("Error fetching operations: {}", e);
+                operations.push(None);
+            }
+        }
+    }
 
-            let selection = prompt("\nSelect manager number (or press Enter to cancel): ");
-            if selection.trim().is_empty() {
-                return;
+    if let Ok(voids) = db.get_voids(5) {
+        if !voids.is_empty() {
+            println!("\nRecent voids:");
+            for void in voids {
+                println!("  {}  {} ({})", format_timestamp(void.voided_at), void.description, void.reason);
             }
+        }
+    }
 
-            if let Ok(idx) = selection.parse::<usize>() {
-                if idx > 0 && idx <= managers.len() {
-                    let manager = &managers[idx - 1];
-                    let new_status = !manager.is_active;
-                    let action = if new_status { "activate" } else { "deactivate" };
-                    
-                    let confirm = prompt(&format!("\nAre you sure you want to {} '{}'? (y/N): ", action, manager.full_name));
-                    if confirm.to_lowercase() == "y" {
-                        match db.update_manager_status(&manager.id, new_status) {
-                            Ok(()) => {
-                                println!("\n✅ Manager '{}' has been {}d successfully!", manager.full_name, action);
-                            }
-                            Err(e) => println!("\nError updating manager status: {}", e),
-                        }
-                    } else {
-                        println!("\nOperation cancelled.");
-                    }
-                } else {
-                    println!("\nInvalid selection.");
-                }
-            } else {
-                println!("\nInvalid input.");
+    let choice = prompt("\nOperation to void (number, Enter to cancel): ")?;
+    let Some(operation) = choice.parse::<usize>().ok().and_then(|n| n.checked_sub(1)).and_then(|i| operations.get(i).cloned().flatten()) else {
+        return Ok(());
+    };
+    if let Err(e) = operation.ensure_voidable(window, now) {
+        println!("\n{}", e);
+        prompt("\nPress Enter to continue...")?;
+        return Ok(());
+    }
+    if prompt(&format!("Void {}? (y/N): ", operation.description))?.to_lowercase() != "y" {
+        return Ok(());
+    }
+    let reason = prompt("Reason: ")?;
+    let Some(approver) = approving_manager(db, current_manager, policy)? else {
+        prompt("\nPress Enter to continue...")?;
+        return Ok(());
+    };
+
+    match db.void_operation(operation.kind, &operation.reference, &approver.id, &reason, window) {
+        Ok(void) => {
+            println!("\n✅ Voided {}, approved by {}.", void.description, approver.full_name);
+            if void.refund > 0.0 {
+                println!("Refund {} to the customer.", money(void.refund));
             }
         }
-        Err(e) => println!("Error fetching managers: {}", e),
+        Err(e) => eprintln!("\nError voiding {}: {}", operation.kind.label().to_lowercase(), e),
     }
 
-    prompt("\nPress Enter to continue...");
+    prompt("\nPress Enter to continue...")?;
+    Ok(())
 }
 
-fn handle_manager_menu(db: &Database) {
+fn handle_manager_menu(db: &mut Database, current_manager: &mut Manager, policy: &SecurityPolicy) -> Screen {
     loop {
         clear_screen();
         display_logo();
         display_manager_menu();
 
-        let choice = prompt("");
+        let choice = prompt("")?;
         match choice.trim() {
-            "1" => add_new_manager(db),
-            "2" => view_all_managers(db),
-            "3" => manage_manager_status(db),
-            "4" => break,
+            "1" => add_new_manager(db, policy)?,
+            "2" => view_all_managers(db)?,
+            "3" => manage_manager_status(db)?,
+            "4" => backup_database(db)?,
+            "5" => restore_database(db)?,
+            "6" => my_profile(db, current_manager, policy)?,
+            "7" => view_notification_log(db)?,
+            "8" => store_settings(db)?,
+            "9" => void_last_operation(db, current_manager, policy)?,
+            "10" => reset_manager_password(db, current_manager, policy)?,
+            "11" => remove_manager(db, current_manager)?,
+            "12" => break,
             _ => {
                 println!("Invalid option. Press Enter to continue...");
-                prompt("");
+                prompt("")?;
             }
         }
     }
+    Ok(())
 }
 
 #[allow(dead_code)]
@@ -833,47 +3455,67 @@ fn display_login_screen() {
     println!("╚══════════════════════════════════════════╝\n");
 }
 
-fn login(db: &Database) -> Option<Manager> {
+fn login(db: &Database, policy: &SecurityPolicy) -> Screen<Option<Manager>> {
     let mut attempts = 0;
     const MAX_ATTEMPTS: i32 = 3;
+    let mut notice: Option<String> = None;
 
     while attempts < MAX_ATTEMPTS {
         clear_screen();
         display_logo();
         display_login_screen();
 
-        if attempts > 0 {
-            println!("⚠️  Invalid credentials. Attempt {} of {}\n", attempts + 1, MAX_ATTEMPTS);
+        if let Some(message) = notice.take() {
+            println!("⚠️  {}\n", message);
         }
 
-        let username = prompt("Username: ");
-        let password = prompt("Password: ");
+        let username = prompt("Username: ")?;
+        let password = prompt("Password: ")?;
 
         if !AuthService::is_valid_credentials(&username, &password) {
             println!("\nPlease enter both username and password.");
             attempts += 1;
             println!("\nPress Enter to continue...");
-            prompt("");
+            prompt("")?;
             continue;
         }
 
-        match db.authenticate_manager(&username, &password) {
-            Ok(Some(manager)) => {
+        match db.attempt_login(&username, &password, policy) {
+            Ok(LoginOutcome::Success(manager)) => {
+                if manager.must_change_password {
+                    let mut changed = false;
+                    while !changed {
+                        changed = change_password(db, &manager, policy, true)?;
+                        if !changed && prompt("\nTry again? (Y/n): ")?.to_lowercase() == "n" {
+                            return Ok(None);
+                        }
+                    }
+                }
                 println!("\n✅ Login successful! Welcome, {}", manager.full_name);
                 println!("\nPress Enter to continue...");
-                prompt("");
-                return Some(manager);
+                prompt("")?;
+                session::start(policy.idle_timeout_minutes);
+                return Ok(Some(manager));
             }
-            Ok(None) => {
+            Ok(LoginOutcome::InvalidCredentials) => {
                 attempts += 1;
-                println!("\nPress Enter to continue...");
-                prompt("");
+                notice = Some(format!(
+                    "Invalid username or password. Accounts are locked after {} failed attempts.",
+                    policy.max_failed_attempts
+                ));
+            }
+            Ok(LoginOutcome::LockedOut { until }) => {
+                attempts += 1;
+                notice = Some(format!(
+                    "Account locked after repeated failures. Try again after {}.",
+                    format_timestamp(until)
+                ));
             }
             Err(e) => {
                 println!("\nDatabase error: {}", e);
                 attempts += 1;
                 println!("\nPress Enter to continue...");
-                prompt("");
+                prompt("")?;
             }
         }
     }
@@ -886,9 +3528,12 @@ fn login(db: &Database) -> Option<Manager> {
     println!("║  Maximum login attempts exceeded.        ║");
     println!("║  System access blocked.                  ║");
     println!("╚══════════════════════════════════════════╝\n");
+    if let Some(message) = notice {
+        println!("{}\n", message);
+    }
     println!("Press Enter to exit...");
-    prompt("");
-    None
+    prompt("")?;
+    Ok(None)
 }
 
 fn display_authenticated_header(manager: &Manager) {
@@ -898,45 +3543,231 @@ fn display_authenticated_header(manager: &Manager) {
     println!("╚══════════════════════════════════════════╝");
 }
 
+fn open_database() -> Result<Database, Error> {
+    let passphrase = std::env::var("RUSTOCK_DB_KEY").ok();
+
+    match Database::new(passphrase.as_deref()) {
+        Err(e) if passphrase.is_none() && e.is_not_a_database() => {
+            let passphrase = read_line("Database passphrase: ");
+            Database::new(Some(&passphrase))
+        }
+        result => result,
+    }
+}
+
+fn print_usage() {
+    println!("Usage: rustock [COMMAND]");
+    println!();
+    println!("Without a command the interactive console is started.");
+    println!();
+    println!("Commands:");
+    println!("  backup [--compress] [--dir <path>]   Write a timestamped snapshot of {}", DB_PATH);
+    println!("  restore <file>                       Replace the database with a snapshot");
+    println!("  encrypt                              Encrypt the database or change its passphrase");
+    println!("  seed [--products <n>] [--days <n>] [--sales-per-day <n>] [--seed <n>]");
+    println!("                                       Fill an empty database with a generated demo store");
+    println!("  help                                 Show this message");
+    println!();
+    println!("Set RUSTOCK_DB_KEY to supply the passphrase of an encrypted database.");
+}
+
+fn run_backup_command(db: &Database, args: &[String]) -> Result<(), Error> {
+    let mut compress = false;
+    let mut dir = PathBuf::from(backup::BACKUP_DIR);
+
+    let mut iter = args.iter();
+    while let Some(arg) = iter.next() {
+        match arg.as_str() {
+            "--compress" | "-z" => compress = true,
+            "--dir" => match iter.next() {
+                Some(path) => dir = PathBuf::from(path),
+                None => return Err(Error::validation("--dir requires a path")),
+            },
+            other => return Err(Error::validation(format!("Unknown backup option '{}'", other))),
+        }
+    }
+
+    let path = backup::create_backup(db, &dir, compress)?;
+    println!("Backup written to {}", path.display());
+    Ok(())
+}
+
+fn run_restore_command(db: &mut Database, args: &[String]) -> Result<(), Error> {
+    let source = match args {
+        [path] => PathBuf::from(path),
+        _ => return Err(Error::validation("restore requires exactly one backup file")),
+    };
+
+    backup::restore_backup(db, &source)?;
+    println!("Database restored from {}", source.display());
+    Ok(())
+}
+
+fn run_encrypt_command(mut db: Database) -> Result<(), Error> {
+    let passphrase = read_line("New database passphrase: ");
+    if passphrase.len() < 8 {
+        return Err(Error::validation("Passphrase must be at least 8 characters"));
+    }
+    if read_line("Confirm passphrase: ") != passphrase {
+        return Err(Error::validation("Passphrases do not match"));
+    }
+
+    if db.is_encrypted() {
+        db.change_passphrase(&passphrase)?;
+        println!("Database passphrase changed.");
+        return Ok(());
+    }
+
+    let encrypted = PathBuf::from(format!("{}.enc.tmp", DB_PATH));
+    db.export_encrypted(&encrypted, &passphrase)?;
+    db.replace_with(&encrypted)?;
+    println!("Database encrypted. Set RUSTOCK_DB_KEY or enter the passphrase at startup.");
+    Ok(())
+}
+
+fn run_seed_command(db: &mut Database, args: &[String]) -> Result<(), Error> {
+    fn value<T: std::str::FromStr>(option: &str, value: Option<&String>) -> Result<T, Error> {
+        value
+            .and_then(|v| v.parse().ok())
+            .ok_or_else(|| Error::validation(format!("{} requires a number", option)))
+    }
+
+    let mut options = seed::SeedOptions::default();
+    let mut iter = args.iter();
+    while let Some(arg) = iter.next() {
+        match arg.as_str() {
+            "--products" => options.products = value(arg, iter.next())?,
+            "--days" => options.days = value(arg, iter.next())?,
+            "--sales-per-day" => options.sales_per_day = value(arg, iter.next())?,
+            "--seed" => options.seed = value(arg, iter.next())?,
+            other => return Err(Error::validation(format!("Unknown seed option '{}'", other))),
+        }
+    }
+
+    let started = std::time::Instant::now();
+    let tz = timezone::store_timezone();
+    let data = seed::generate(&options, timezone::local_date(Utc::now().timestamp(), tz), tz);
+    db.load_demo_data(&data)?;
+    println!(
+        "Seeded {} products, {} purchases and {} sales over {} days in {:.1}s",
+        data.products.len(),
+        data.purchases.len(),
+        data.sales.len(),
+        options.days,
+        started.elapsed().as_secs_f64()
+    );
+    Ok(())
+}
+
+fn run_command(args: &[String]) -> Result<(), Error> {
+    let (command, rest) = match args.split_first() {
+        Some((command, rest)) => (command.as_str(), rest),
+        None => return Ok(()),
+    };
+
+    if matches!(command, "help" | "--help" | "-h") {
+        print_usage();
+        return Ok(());
+    }
+
+    let mut db = open_database()?;
+    if !matches!(login(&db, &SecurityPolicy::from_env()), Ok(Some(_))) {
+        return Err(Error::Unauthorized("Authentication required".to_string()));
+    }
+
+    match command {
+        "backup" => run_backup_command(&db, rest),
+        "restore" => run_restore_command(&mut db, rest),
+        "encrypt" => run_encrypt_command(db),
+        "seed" => run_seed_command(&mut db, rest),
+        other => {
+            print_usage();
+            Err(Error::validation(format!("Unknown command '{}'", other)))
+        }
+    }
+}
+
 fn main() {
-    let mut db = match Database::new() {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    if !args.is_empty() {
+        if let Err(e) = run_command(&args) {
+            eprintln!("Error: {}", e);
+            std::process::exit(e.exit_code());
+        }
+        return;
+    }
+
+    let mut db = match open_database() {
         Ok(db) => db,
         Err(e) => {
             eprintln!("Failed to initialize database: {}", e);
-            return;
+            std::process::exit(e.exit_code());
         }
     };
 
+    install_settings(&db);
+    let policy = SecurityPolicy::from_env();
+    for e in NotifyConfig::from_env().1 {
+        eprintln!("Notification sink ignored: {}", e);
+    }
+    if let Ok(name) = std::env::var("RUSTOCK_TIMEZONE") {
+        if let Err(e) = timezone::parse(&name) {
+            eprintln!("{}; using UTC", e);
+        }
+    }
+
     // Authentication required
-    let _current_manager = match login(&db) {
-        Some(manager) => manager,
-        None => {
+    let mut current_manager = match login(&db, &policy) {
+        Ok(Some(manager)) => manager,
+        _ => {
             println!("Exiting system...");
             return;
         }
     };
 
+    apply_due_price_changes(&db);
+    start_notifications(&db);
+
     loop {
+        if session::is_expired() {
+            clear_screen();
+            display_logo();
+            println!("Session locked after {} minutes of inactivity.", policy.idle_timeout_minutes);
+            read_line("\nPress Enter to log in again...");
+            current_manager = match login(&db, &policy) {
+                Ok(Some(manager)) => manager,
+                _ => {
+                    println!("Exiting system...");
+                    finish_notifications();
+                    return;
+                }
+            };
+        }
+
         clear_screen();
         display_logo();
-        display_authenticated_header(&_current_manager);
+        display_authenticated_header(&current_manager);
         display_main_menu();
 
-        let choice = prompt("");
-        match choice.trim() {
+        let Ok(choice) = prompt("") else {
+            continue;
+        };
+        // A screen left because the session expired is followed by the lock screen above
+        let _ = match choice.as_str() {
             "1" => handle_product_menu(&mut db),
-            "2" => handle_sales_menu(&mut db),
+            "2" => handle_sales_menu(&mut db, &current_manager),
             "3" => handle_purchase_menu(&mut db),
             "4" => handle_reports_menu(&mut db),
-            "5" => handle_manager_menu(&db),
+            "5" => handle_manager_menu(&mut db, &mut current_manager, &policy),
             "6" => {
-                println!("\nGoodbye, {}!", _current_manager.full_name);
+                println!("\nGoodbye, {}!", current_manager.full_name);
                 break;
             }
             _ => {
                 println!("Invalid option. Press Enter to continue...");
-                prompt("");
+                prompt("").map(drop)
             }
-        }
+        };
     }
+    finish_notifications();
 }
@@ -0,0 +1,587 @@
+use crate::auth::{self, validate_profile, Manager};
+use crate::bundle::{self, BundleComponent};
+use crate::currency::{self, ExchangeRate};
+use crate::error::{Error, Result};
+use crate::pricing::{self, PriceChange};
+use crate::product::Product;
+use crate::purchase::Purchase;
+use crate::repository::Repository;
+use crate::sale::{Sale, SaleItem};
+use crate::unit::{self, ProductUnit};
+use chrono::Utc;
+use std::cell::RefCell;
+use std::collections::HashMap;
+
+struct SaleRow {
+    sale_id: String,
+    product_id: String,
+    /// In the product's stock unit.
+    quantity: f64,
+    unit: Option<String>,
+    unit_quantity: f64,
+    unit_price: f64,
+    total_price: f64,
+    sold_at: i64,
+}
+
+struct SaleHeader {
+    id: String,
+    sale_number: i64,
+    total_amount: f64,
+    created_at: i64,
+}
+
+#[derive(Default)]
+struct MemoryState {
+    products: Vec<Product>,
+    sales: Vec<SaleRow>,
+    sale_headers: Vec<SaleHeader>,
+    purchases: Vec<Purchase>,
+    managers: Vec<Manager>,
+    price_history: Vec<PriceChange>,
+    product_units: Vec<ProductUnit>,
+    exchange_rates: Vec<ExchangeRate>,
+    bundle_components: Vec<BundleComponent>,
+}
+
+/// Non-persistent `Repository` used for tests and throwaway sessions.
+/// Seeds the same default admin account as a fresh SQLite database.
+pub struct MemoryRepository {
+    state: RefCell<MemoryState>,
+}
+
+impl MemoryRepository {
+    pub fn new() -> Self {
+        let repo = MemoryRepository {
+            state: RefCell::new(MemoryState::default()),
+        };
+        repo.add_manager(&Manager::default_admin()).expect("an empty repository has no admin yet");
+        repo
+    }
+
+    fn with_product(&self, product_id: &str, update: impl FnOnce(&mut Product)) -> Result<()> {
+        let mut state = self.state.borrow_mut();
+        let product = state
+            .products
+            .iter_mut()
+            .find(|p| p.id == product_id)
+            .ok_or_else(|| Error::not_found("Product", product_id))?;
+        update(product);
+        Ok(())
+    }
+
+    fn with_manager(&self, manager_id: &str, update: impl FnOnce(&mut Manager)) -> Result<()> {
+        let mut state = self.state.borrow_mut();
+        let manager = state
+            .managers
+            .iter_mut()
+            .find(|m| m.id == manager_id)
+            .ok_or_else(|| Error::not_found("Manager", manager_id))?;
+        update(manager);
+        Ok(())
+    }
+}
+
+impl Default for MemoryRepository {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Repository for MemoryRepository {
+    fn add_product(&self, product: &Product) -> Result<()> {
+        let mut state = self.state.borrow_mut();
+        if state.products.iter().any(|p| p.id == product.id) {
+            return Err(Error::conflict(format!("Product '{}' already exists", product.id)));
+        }
+        ensure_unique_sku(&state.products, product)?;
+        state.products.push(Product {
+            unit: unit::normalize(&product.unit),
+            ..product.clone()
+        });
+        state.price_history.push(PriceChange {
+            product_id: product.id.clone(),
+            old_price: None,
+            new_price: product.price,
+            changed_at: product.created_at,
+            reason: pricing::REASON_INITIAL.to_string(),
+        });
+        Ok(())
+    }
+
+    fn update_product(&self, product: &Product) -> Result<()> {
+        let mut state = self.state.borrow_mut();
+        ensure_unique_sku(&state.products, product)?;
+        let existing = state
+            .products
+            .iter_mut()
+            .find(|p| p.id == product.id)
+            .ok_or_else(|| Error::not_found("Product", &product.id))?;
+        if existing.version != product.version {
+            return Err(Error::conflict(format!(
+                "Product '{}' was changed in another terminal since it was loaded; reload it and try again",
+                product.name
+            )));
+        }
+        let old_price = existing.price;
+        existing.name = product.name.clone();
+        existing.description = product.description.clone();
+        existing.price = product.price;
+        existing.quantity = product.quantity;
+        existing.sku = product.sku.clone();
+        existing.tags = product.tags.clone();
+        existing.updated_at = product.updated_at;
+        existing.version += 1;
+        if old_price != product.price {
+            state.price_history.push(PriceChange {
+                product_id: product.id.clone(),
+                old_price: Some(old_price),
+                new_price: product.price,
+                changed_at: product.updated_at,
+                reason: pricing::REASON_MANUAL.to_string(),
+            });
+        }
+        Ok(())
+    }
+
+    fn delete_product(&mut self, id: &str) -> Result<()> {
+        let state = self.state.get_mut();
+        if state.sales.iter().any(|s| s.product_id == id) || state.purchases.iter().any(|p| p.product_id == id) {
+            return Err(Error::conflict(
+                "Product has sales or purchase history and can only be archived",
+            ));
+        }
+        if state.bundle_components.iter().any(|c| c.component_id == id) {
+            return Err(Error::conflict("Product is a component of a bundle; remove it from the bundle first"));
+        }
+        let before = state.products.len();
+        state.products.retain(|p| p.id != id);
+        if state.products.len() == before {
+            return Err(Error::not_found("Product", id));
+        }
+        state.price_history.retain(|c| c.product_id != id);
+        state.product_units.retain(|u| u.product_id != id);
+        state.bundle_components.retain(|c| c.bundle_id != id);
+        Ok(())
+    }
+
+    fn archive_product(&self, id: &str) -> Result<()> {
+        let now = Utc::now().timestamp();
+        self.with_product(id, |product| {
+            product.archived_at.get_or_insert(now);
+        })
+    }
+
+    fn restore_product(&self, id: &str) -> Result<()> {
+        self.with_product(id, |product| product.archived_at = None)
+    }
+
+    fn get_product(&self, id: &str) -> Result<Option<Product>> {
+        Ok(self.state.borrow().products.iter().find(|p| p.id == id).cloned())
+    }
+
+    fn get_all_products(&self) -> Result<Vec<Product>> {
+        Ok(self.state.borrow().products.iter().filter(|p| !p.is_archived()).cloned().collect())
+    }
+
+    fn get_archived_products(&self) -> Result<Vec<Product>> {
+        let mut archived: Vec<Product> =
+            self.state.borrow().products.iter().filter(|p| p.is_archived()).cloned().collect();
+        archived.sort_by_key(|p| std::cmp::Reverse(p.archived_at));
+        Ok(archived)
+    }
+
+    fn get_price_history(&self, product_id: &str) -> Result<Vec<PriceChange>> {
+        let mut history: Vec<PriceChange> = self
+            .state
+            .borrow()
+            .price_history
+            .iter()
+            .filter(|c| c.product_id == product_id)
+            .cloned()
+            .collect();
+        history.sort_by_key(|c| c.changed_at);
+        Ok(history)
+    }
+
+    fn record_sale(&mut self, sale: &Sale) -> Result<i64> {
+        if sale.customer_id.is_some() {
+            return Err(Error::validation("Sales to loyalty customers are recorded with record_customer_sale"));
+        }
+        let base_quantities = sale
+            .items
+            .iter()
+            .map(|item| self.to_base_quantity(&item.product_id, item.unit.as_deref(), item.quantity))
+            .collect::<Result<Vec<f64>>>()?;
+        let state = self.state.get_mut();
+
+        // Work on a copy of the stock levels so a failure leaves nothing behind
+        let mut stock: HashMap<String, f64> = state.products.iter().map(|p| (p.id.clone(), p.quantity)).collect();
+        let mut touched = Vec::new();
+        for (item, &quantity) in sale.items.iter().zip(&base_quantities) {
+            let product = state
+                .products
+                .iter()
+                .find(|p| p.id == item.product_id)
+                .ok_or_else(|| Error::not_found("Product", &item.product_id))?;
+            if product.is_archived() {
+                return Err(Error::conflict(format!("Product '{}' is archived", item.product_id)));
+            }
+
+            let components: Vec<BundleComponent> = state
+                .bundle_components
+                .iter()
+                .filter(|c| c.bundle_id == item.product_id)
+                .cloned()
+                .collect();
+            let component_stock: Vec<f64> = components.iter().map(|c| stock[&c.component_id]).collect();
+            let current = stock[&item.product_id];
+            let available = current + bundle::buildable(&components, &component_stock);
+            if available < quantity {
+                return Err(Error::InsufficientStock {
+                    product_id: item.product_id.clone(),
+                    available,
+                    requested: quantity,
+                });
+            }
+
+            let (from_stock, needed) = bundle::allocate(current, &components, quantity);
+            let from_stock = if components.is_empty() { quantity } else { from_stock };
+            stock.insert(item.product_id.clone(), unit::round_quantity(current - from_stock));
+            touched.push(item.product_id.clone());
+            for (component, needed) in components.iter().zip(needed).filter(|(_, needed)| *needed > 0.0) {
+                let left = unit::round_quantity(stock[&component.component_id] - needed);
+                stock.insert(component.component_id.clone(), left);
+                touched.push(component.component_id.clone());
+            }
+        }
+        for product in state.products.iter_mut().filter(|p| touched.contains(&p.id)) {
+            product.quantity = stock[&product.id];
+            product.version += 1;
+        }
+
+        let sale_number = state.sale_headers.iter().map(|h| h.sale_number).max().unwrap_or(0) + 1;
+        state.sale_headers.push(SaleHeader {
+            id: sale.id.clone(),
+            sale_number,
+            total_amount: sale.total_amount,
+            created_at: sale.timestamp,
+        });
+
+        for (item, &quantity) in sale.items.iter().zip(&base_quantities) {
+            state.sales.push(SaleRow {
+                sale_id: sale.id.clone(),
+                product_id: item.product_id.clone(),
+                quantity,
+                unit: item.unit.as_deref().map(unit::normalize),
+                unit_quantity: item.quantity,
+                unit_price: item.unit_price,
+                total_price: item.total_price,
+                sold_at: sale.timestamp,
+            });
+        }
+        Ok(sale_number)
+    }
+
+    fn get_sale_by_number(&self, sale_number: i64) -> Result<Option<Sale>> {
+        let state = self.state.borrow();
+        let header = match state.sale_headers.iter().find(|h| h.sale_number == sale_number) {
+            Some(header) => header,
+            None => return Ok(None),
+        };
+
+        let items = state
+            .sales
+            .iter()
+            .filter(|row| row.sale_id == header.id)
+            .map(|row| SaleItem {
+                product_id: row.product_id.clone(),
+                quantity: row.unit_quantity,
+                unit: row.unit.clone(),
+                unit_price: row.unit_price,
+                total_price: row.total_price,
+            })
+            .collect();
+
+        Ok(Some(Sale {
+            id: header.id.clone(),
+            sale_number: Some(header.sale_number),
+            items,
+            total_amount: header.total_amount,
+            total_profit: 0.0,
+            timestamp: header.created_at,
+            customer_id: None,
+            points_redeemed: 0,
+            discount: 0.0,
+        }))
+    }
+
+    fn record_purchase(&mut self, purchase: &Purchase) -> Result<()> {
+        let mut recorded = purchase.clone();
+        let mut quantity = purchase.quantity;
+        if self.get_product(&purchase.product_id)?.is_some() {
+            quantity = self.to_base_quantity(&purchase.product_id, purchase.unit.as_deref(), purchase.quantity)?;
+            recorded.currency = currency::normalize(&purchase.currency)?;
+            recorded.exchange_rate = self.exchange_rate(&recorded.currency, purchase.purchase_date)?;
+        }
+        let state = self.state.get_mut();
+        let product = match state.products.iter_mut().find(|p| p.id == purchase.product_id) {
+            None => return Err(Error::not_found("Product", &purchase.product_id)),
+            Some(product) if product.is_archived() => {
+                return Err(Error::conflict(format!("Product '{}' is archived", purchase.product_id)));
+            }
+            Some(product) => product,
+        };
+        if state.purchases.iter().any(|p| p.id == purchase.id) {
+            return Err(Error::conflict(format!("Purchase '{}' already exists", purchase.id)));
+        }
+        product.quantity = unit::round_quantity(product.quantity + quantity);
+        product.updated_at = Utc::now().timestamp();
+        product.version += 1;
+        state.purchases.push(recorded);
+        Ok(())
+    }
+
+    fn get_all_sales(&self) -> Result<Vec<(String, f64, f64, i64)>> {
+        let state = self.state.borrow();
+        let mut sales: Vec<(String, f64, f64, i64)> = state
+            .sales
+            .iter()
+            .filter_map(|sale| {
+                state.products.iter().find(|p| p.id == sale.product_id).map(|product| {
+                    (product.name.clone(), sale.quantity, sale.total_price, sale.sold_at)
+                })
+            })
+            .collect();
+        sales.reverse();
+        sales.sort_by_key(|sale| std::cmp::Reverse(sale.3));
+        Ok(sales)
+    }
+
+    fn get_all_purchases(&self) -> Result<Vec<Purchase>> {
+        let mut purchases = self.state.borrow().purchases.clone();
+        purchases.reverse();
+        purchases.sort_by_key(|p| std::cmp::Reverse(p.purchase_date));
+        Ok(purchases)
+    }
+
+    fn add_manager(&self, manager: &Manager) -> Result<()> {
+        let mut state = self.state.borrow_mut();
+        if state.managers.iter().any(|m| m.username == manager.username) {
+            return Err(Error::conflict(format!("Username '{}' already exists", manager.username)));
+        }
+        state.managers.push(Manager {
+            password: auth::hash_password(&manager.password),
+            ..manager.clone()
+        });
+        Ok(())
+    }
+
+    fn get_manager_by_username(&self, username: &str) -> Result<Option<Manager>> {
+        Ok(self.state.borrow().managers.iter().find(|m| m.username == username).cloned())
+    }
+
+    fn get_manager(&self, manager_id: &str) -> Result<Option<Manager>> {
+        Ok(self.state.borrow().managers.iter().find(|m| m.id == manager_id).cloned())
+    }
+
+    fn get_all_managers(&self) -> Result<Vec<Manager>> {
+        let mut managers = self.state.borrow().managers.clone();
+        managers.sort_by_key(|m| std::cmp::Reverse(m.created_at));
+        Ok(managers)
+    }
+
+    fn update_manager_status(&self, manager_id: &str, is_active: bool) -> Result<()> {
+        self.with_manager(manager_id, |manager| manager.is_active = is_active)
+    }
+
+    fn update_manager_password(&self, manager_id: &str, password: &str) -> Result<()> {
+        self.with_manager(manager_id, |manager| {
+            manager.password = auth::hash_password(password);
+            manager.must_change_password = false;
+        })
+    }
+
+    fn update_manager_profile(&self, manager_id: &str, username: &str, full_name: &str) -> Result<()> {
+        validate_profile(username, full_name)?;
+        if self.state.borrow().managers.iter().any(|m| m.username == username && m.id != manager_id) {
+            return Err(Error::conflict(format!("Username '{}' already exists", username)));
+        }
+        self.with_manager(manager_id, |manager| {
+            manager.username = username.to_string();
+            manager.full_name = full_name.to_string();
+        })
+    }
+
+    fn reset_manager_password(&self, manager_id: &str, temporary_password: &str) -> Result<()> {
+        self.with_manager(manager_id, |manager| {
+            manager.password = auth::hash_password(temporary_password);
+            manager.must_change_password = true;
+            manager.failed_attempts = 0;
+            manager.locked_until = None;
+        })
+    }
+
+    fn set_product_unit(&self, product_unit: &ProductUnit) -> Result<()> {
+        let product = self
+            .get_product(&product_unit.product_id)?
+            .ok_or_else(|| Error::not_found("Product", &product_unit.product_id))?;
+        product_unit.validate(&product)?;
+
+        let mut state = self.state.borrow_mut();
+        match state
+            .product_units
+            .iter_mut()
+            .find(|u| u.product_id == product_unit.product_id && u.unit == product_unit.unit)
+        {
+            Some(existing) => existing.factor = product_unit.factor,
+            None => state.product_units.push(product_unit.clone()),
+        }
+        Ok(())
+    }
+
+    fn remove_product_unit(&self, product_id: &str, unit: &str) -> Result<()> {
+        let unit = unit::normalize(unit);
+        let mut state = self.state.borrow_mut();
+        let before = state.product_units.len();
+        state.product_units.retain(|u| !(u.product_id == product_id && u.unit == unit));
+        if state.product_units.len() == before {
+            return Err(Error::not_found("Unit", unit));
+        }
+        Ok(())
+    }
+
+    fn get_product_units(&self, product_id: &str) -> Result<Vec<ProductUnit>> {
+        let mut units: Vec<ProductUnit> = self
+            .state
+            .borrow()
+            .product_units
+            .iter()
+            .filter(|u| u.product_id == product_id)
+            .cloned()
+            .collect();
+        units.sort_by(|a, b| a.factor.total_cmp(&b.factor));
+        Ok(units)
+    }
+
+    fn set_bundle_component(&self, component: &BundleComponent) -> Result<()> {
+        if self.get_product(&component.bundle_id)?.is_none() {
+            return Err(Error::not_found("Product", &component.bundle_id));
+        }
+        let product = self
+            .get_product(&component.component_id)?
+            .ok_or_else(|| Error::not_found("Product", &component.component_id))?;
+        let mut state = self.state.borrow_mut();
+        let component_is_bundle = state.bundle_components.iter().any(|c| c.bundle_id == component.component_id);
+        let bundle_is_component = state.bundle_components.iter().any(|c| c.component_id == component.bundle_id);
+        component.validate(&product, component_is_bundle, bundle_is_component)?;
+
+        match state
+            .bundle_components
+            .iter_mut()
+            .find(|c| c.bundle_id == component.bundle_id && c.component_id == component.component_id)
+        {
+            Some(existing) => existing.quantity = component.quantity,
+            None => state.bundle_components.push(component.clone()),
+        }
+        Ok(())
+    }
+
+    fn remove_bundle_component(&self, bundle_id: &str, component_id: &str) -> Result<()> {
+        let mut state = self.state.borrow_mut();
+        let before = state.bundle_components.len();
+        state
+            .bundle_components
+            .retain(|c| !(c.bundle_id == bundle_id && c.component_id == component_id));
+        if state.bundle_components.len() == before {
+            return Err(Error::not_found("Bundle component", component_id));
+        }
+        Ok(())
+    }
+
+    fn get_bundle_components(&self, bundle_id: &str) -> Result<Vec<BundleComponent>> {
+        Ok(self
+            .state
+            .borrow()
+            .bundle_components
+            .iter()
+            .filter(|c| c.bundle_id == bundle_id)
+            .cloned()
+            .collect())
+    }
+
+    fn assemble_bundle(&mut self, bundle_id: &str, count: f64) -> Result<()> {
+        if !count.is_finite() || count <= 0.0 || count.fract() != 0.0 {
+            return Err(Error::validation("Bundles are assembled in whole, positive numbers"));
+        }
+        let components = self.get_bundle_components(bundle_id)?;
+        if components.is_empty() {
+            return Err(Error::validation("Product has no bill of materials"));
+        }
+        let state = self.state.get_mut();
+        for component in &components {
+            let needed = unit::round_quantity(component.quantity * count);
+            let available = state
+                .products
+                .iter()
+                .find(|p| p.id == component.component_id)
+                .map_or(0.0, |p| p.quantity);
+            if available < needed {
+                return Err(Error::InsufficientStock {
+                    product_id: component.component_id.clone(),
+                    available,
+                    requested: needed,
+                });
+            }
+        }
+
+        let now = Utc::now().timestamp();
+        for product in state.products.iter_mut() {
+            if let Some(component) = components.iter().find(|c| c.component_id == product.id) {
+                product.quantity = unit::round_quantity(product.quantity - component.quantity * count);
+                product.version += 1;
+            } else if product.id == bundle_id {
+                product.quantity = unit::round_quantity(product.quantity + count);
+                product.version += 1;
+                product.updated_at = now;
+            }
+        }
+        Ok(())
+    }
+
+    fn set_exchange_rate(&self, rate: &ExchangeRate) -> Result<()> {
+        rate.validate()?;
+        let mut state = self.state.borrow_mut();
+        state
+            .exchange_rates
+            .retain(|r| !(r.currency == rate.currency && r.effective_from == rate.effective_from));
+        state.exchange_rates.push(rate.clone());
+        Ok(())
+    }
+
+    fn get_exchange_rates(&self) -> Result<Vec<ExchangeRate>> {
+        let mut rates = self.state.borrow().exchange_rates.clone();
+        rates.sort_by(|a, b| b.effective_from.cmp(&a.effective_from).then_with(|| a.currency.cmp(&b.currency)));
+        Ok(rates)
+    }
+
+    fn save_login_state(&self, manager_id: &str, failed_attempts: u32, locked_until: Option<i64>) -> Result<()> {
+        self.with_manager(manager_id, |manager| {
+            manager.failed_attempts = failed_attempts;
+            manager.locked_until = locked_until;
+        })
+    }
+}
+
+fn ensure_unique_sku(products: &[Product], product: &Product) -> Result<()> {
+    let Some(sku) = &product.sku else {
+        return Ok(());
+    };
+    match products
+        .iter()
+        .find(|p| p.id != product.id && p.sku.as_deref().is_some_and(|other| other.eq_ignore_ascii_case(sku)))
+    {
+        Some(owner) => Err(Error::conflict(format!("SKU '{}' is already used by {}", sku, owner.name))),
+        None => Ok(()),
+    }
+}
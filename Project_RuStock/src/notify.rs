@@ -0,0 +1,353 @@
+//! Notifications about stock and account events, delivered to configurable sinks.
+//!
+//! Sinks are configured with `RUSTOCK_NOTIFY_SINKS`, a `;`-separated list of
+//! `log:<path>`, `command:<shell command>` or an `http://` / `https://` URL.
+//! Every event is sent to every sink as a JSON document; failed deliveries are
+//! retried and each outcome is returned so it can be written to the delivery log.
+//! The console hands events to a `Dispatcher`, which delivers them on a background
+//! thread so that an unreachable sink never holds up a sale.
+
+use crate::auth::Manager;
+use crate::config::env_or;
+use crate::currency;
+use crate::error::{Error, Result};
+use crate::product::Product;
+use crate::settings;
+use chrono::Utc;
+use serde::Serialize;
+use std::fs::OpenOptions;
+use std::io::Write;
+use std::path::PathBuf;
+use std::process::{Command, Stdio};
+use std::sync::mpsc::{self, Sender};
+use std::sync::Mutex;
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+
+const HTTP_TIMEOUT_SECS: u64 = 10;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum EventKind {
+    LowStock,
+    OutOfStock,
+    LargeSale,
+    ManagerDeactivated,
+}
+
+impl EventKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            EventKind::LowStock => "low_stock",
+            EventKind::OutOfStock => "out_of_stock",
+            EventKind::LargeSale => "large_sale",
+            EventKind::ManagerDeactivated => "manager_deactivated",
+        }
+    }
+
+    pub fn parse(value: &str) -> Option<Self> {
+        match value {
+            "low_stock" => Some(EventKind::LowStock),
+            "out_of_stock" => Some(EventKind::OutOfStock),
+            "large_sale" => Some(EventKind::LargeSale),
+            "manager_deactivated" => Some(EventKind::ManagerDeactivated),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Event {
+    pub kind: EventKind,
+    pub message: String,
+    /// Product, sale number or manager the event is about.
+    pub subject: String,
+    pub occurred_at: i64,
+}
+
+impl Event {
+    pub fn new(kind: EventKind, subject: impl Into<String>, message: impl Into<String>) -> Self {
+        Event {
+            kind,
+            message: message.into(),
+            subject: subject.into(),
+            occurred_at: Utc::now().timestamp(),
+        }
+    }
+
+    pub fn to_json(&self) -> String {
+        serde_json::to_string(self).unwrap_or_default()
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Sink {
+    /// Appends one JSON line per event to a file.
+    Log(PathBuf),
+    /// Runs a command through `sh -c` with the event as JSON on stdin; a non-zero exit is a failure.
+    Command(String),
+    /// POSTs the event as JSON; any non-2xx response is a failure.
+    Http(String),
+}
+
+impl Sink {
+    pub fn parse(spec: &str) -> Result<Self> {
+        let spec = spec.trim();
+        if let Some(path) = spec.strip_prefix("log:") {
+            return Ok(Sink::Log(PathBuf::from(path.trim())));
+        }
+        if let Some(command) = spec.strip_prefix("command:") {
+            return Ok(Sink::Command(command.trim().to_string()));
+        }
+        if spec.starts_with("http://") || spec.starts_with("https://") {
+            return Ok(Sink::Http(spec.to_string()));
+        }
+        Err(Error::validation(format!(
+            "Unknown notification sink '{}', expected log:<path>, command:<cmd> or an http(s) URL",
+            spec
+        )))
+    }
+
+    pub fn describe(&self) -> String {
+        match self {
+            Sink::Log(path) => format!("log:{}", path.display()),
+            Sink::Command(command) => format!("command:{}", command),
+            Sink::Http(url) => url.clone(),
+        }
+    }
+
+    fn send(&self, event: &Event) -> std::result::Result<(), String> {
+        let payload = event.to_json();
+        match self {
+            Sink::Log(path) => {
+                if let Some(dir) = path.parent().filter(|dir| !dir.as_os_str().is_empty()) {
+                    std::fs::create_dir_all(dir).map_err(|e| e.to_string())?;
+                }
+                let mut file = OpenOptions::new().create(true).append(true).open(path).map_err(|e| e.to_string())?;
+                writeln!(file, "{}", payload).map_err(|e| e.to_string())
+            }
+            Sink::Command(command) => {
+                let mut child = Command::new("sh")
+                    .arg("-c")
+                    .arg(command)
+                    .env("RUSTOCK_EVENT", event.kind.as_str())
+                    .env("RUSTOCK_EVENT_MESSAGE", &event.message)
+                    .stdin(Stdio::piped())
+                    .stdout(Stdio::null())
+                    .stderr(Stdio::null())
+                    .spawn()
+                    .map_err(|e| e.to_string())?;
+                if let Some(mut stdin) = child.stdin.take() {
+                    // A command that ignores its input may close the pipe early
+                    let _ = stdin.write_all(payload.as_bytes());
+                }
+                let status = child.wait().map_err(|e| e.to_string())?;
+                if status.success() {
+                    Ok(())
+                } else {
+                    Err(format!("command exited with {}", status))
+                }
+            }
+            Sink::Http(url) => ureq::post(url)
+                .timeout(Duration::from_secs(HTTP_TIMEOUT_SECS))
+                .set("Content-Type", "application/json")
+                .send_string(&payload)
+                .map(|_| ())
+                .map_err(|e| e.to_string()),
+        }
+    }
+}
+
+/// Outcome of sending one event to one sink.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Delivery {
+    pub event: EventKind,
+    pub subject: String,
+    pub sink: String,
+    pub attempts: u32,
+    pub delivered: bool,
+    /// Error of the last failed attempt.
+    pub error: Option<String>,
+    pub created_at: i64,
+}
+
+/// Thresholds and sinks, read from `RUSTOCK_*` environment variables; the
+/// low-stock threshold comes from the store settings.
+#[derive(Debug, Clone)]
+pub struct NotifyConfig {
+    pub sinks: Vec<Sink>,
+    /// Stock at or below this level, but above zero, raises a low-stock event.
+    pub low_stock_threshold: f64,
+    /// Sales totalling at least this much raise a large-sale event.
+    pub large_sale_amount: f64,
+    pub max_attempts: u32,
+    pub retry_delay: Duration,
+}
+
+impl Default for NotifyConfig {
+    fn default() -> Self {
+        NotifyConfig {
+            sinks: Vec::new(),
+            low_stock_threshold: 5.0,
+            large_sale_amount: 1000.0,
+            max_attempts: 3,
+            retry_delay: Duration::from_millis(500),
+        }
+    }
+}
+
+impl NotifyConfig {
+    /// Reads the configuration; sinks that cannot be parsed are returned as errors alongside it.
+    pub fn from_env() -> (Self, Vec<Error>) {
+        let defaults = NotifyConfig::default();
+        let mut errors = Vec::new();
+        let sinks = env_or("RUSTOCK_NOTIFY_SINKS", String::new())
+            .split(';')
+            .filter(|spec| !spec.trim().is_empty())
+            .filter_map(|spec| Sink::parse(spec).map_err(|e| errors.push(e)).ok())
+            .collect();
+
+        let config = NotifyConfig {
+            sinks,
+            low_stock_threshold: settings::current().low_stock_threshold,
+            large_sale_amount: env_or("RUSTOCK_LARGE_SALE_AMOUNT", defaults.large_sale_amount),
+            max_attempts: env_or("RUSTOCK_NOTIFY_ATTEMPTS", defaults.max_attempts).max(1),
+            retry_delay: Duration::from_millis(env_or("RUSTOCK_NOTIFY_RETRY_MS", 500)),
+        };
+        (config, errors)
+    }
+}
+
+pub struct Notifier {
+    config: NotifyConfig,
+}
+
+impl Notifier {
+    pub fn new(config: NotifyConfig) -> Self {
+        Notifier { config }
+    }
+
+    pub fn config(&self) -> &NotifyConfig {
+        &self.config
+    }
+
+    /// Sends `event` to every sink, retrying each up to `max_attempts` times.
+    pub fn deliver(&self, event: &Event) -> Vec<Delivery> {
+        self.config
+            .sinks
+            .iter()
+            .map(|sink| {
+                let mut attempts = 0;
+                let mut error = None;
+                while attempts < self.config.max_attempts {
+                    if attempts > 0 {
+                        thread::sleep(self.config.retry_delay);
+                    }
+                    attempts += 1;
+                    match sink.send(event) {
+                        Ok(()) => {
+                            error = None;
+                            break;
+                        }
+                        Err(e) => error = Some(e),
+                    }
+                }
+                Delivery {
+                    event: event.kind,
+                    subject: event.subject.clone(),
+                    sink: sink.describe(),
+                    attempts,
+                    delivered: error.is_none(),
+                    error,
+                    created_at: Utc::now().timestamp(),
+                }
+            })
+            .collect()
+    }
+
+    /// Low- or out-of-stock event when `product` crossed a threshold since it held `previous_quantity`.
+    pub fn stock_event(&self, product: &Product, previous_quantity: f64) -> Option<Event> {
+        let threshold = self.config.low_stock_threshold;
+        if product.quantity <= 0.0 && previous_quantity > 0.0 {
+            return Some(Event::new(
+                EventKind::OutOfStock,
+                &product.id,
+                format!("{} is out of stock", product.name),
+            ));
+        }
+        if product.quantity > 0.0 && product.quantity <= threshold && previous_quantity > threshold {
+            return Some(Event::new(
+                EventKind::LowStock,
+                &product.id,
+                format!("{} is low on stock: {} {} left", product.name, product.quantity, product.unit),
+            ));
+        }
+        None
+    }
+
+    pub fn sale_event(&self, sale_number: i64, total_amount: f64) -> Option<Event> {
+        (total_amount >= self.config.large_sale_amount).then(|| {
+            Event::new(
+                EventKind::LargeSale,
+                sale_number.to_string(),
+                format!("Sale #{} totalled {}", sale_number, currency::format_base(total_amount)),
+            )
+        })
+    }
+
+    pub fn manager_event(&self, manager: &Manager) -> Event {
+        Event::new(
+            EventKind::ManagerDeactivated,
+            &manager.id,
+            format!("Manager '{}' was deactivated", manager.username),
+        )
+    }
+}
+
+/// Delivers events on a background thread, one at a time in the order they were
+/// dispatched. The deliveries of each event are handed to `on_delivered` once done.
+pub struct Dispatcher {
+    sender: Mutex<Option<Sender<Event>>>,
+    worker: Mutex<Option<JoinHandle<()>>>,
+}
+
+impl Dispatcher {
+    pub fn spawn<F>(notifier: Notifier, mut on_delivered: F) -> Self
+    where
+        F: FnMut(&Event, Vec<Delivery>) + Send + 'static,
+    {
+        let (sender, receiver) = mpsc::channel::<Event>();
+        let worker = thread::spawn(move || {
+            for event in receiver {
+                let deliveries = notifier.deliver(&event);
+                on_delivered(&event, deliveries);
+            }
+        });
+        Dispatcher {
+            sender: Mutex::new(Some(sender)),
+            worker: Mutex::new(Some(worker)),
+        }
+    }
+
+    /// Queues `event` for delivery and returns without waiting for it.
+    pub fn dispatch(&self, event: Event) {
+        if let Some(sender) = self.sender.lock().unwrap().as_ref() {
+            // Only fails once the worker has stopped, when there is no one left to deliver it
+            let _ = sender.send(event);
+        }
+    }
+
+    /// Stops accepting events and waits until those already queued have been delivered.
+    pub fn finish(&self) {
+        self.sender.lock().unwrap().take();
+        if let Some(worker) = self.worker.lock().unwrap().take() {
+            let _ = worker.join();
+        }
+    }
+}
+
+impl Drop for Dispatcher {
+    fn drop(&mut self) {
+        self.finish();
+    }
+}
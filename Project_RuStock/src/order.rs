@@ -0,0 +1,206 @@
+//! Customer orders that hold stock for later collection.
+//!
+//! Every open order has stock allocated to it, which stays on the shelf but is
+//! no longer available to sell. Reservations and layaways are fully allocated
+//! when placed; a layaway is only collected once deposits cover its total.
+//! Backorders take what is free and are topped up, oldest first, as purchases
+//! arrive. Orders are placed in the product's stock unit.
+
+use crate::currency;
+use crate::error::{Error, Result};
+use crate::export::{Cell, Table};
+use crate::timezone;
+use chrono::Utc;
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum OrderKind {
+    Reservation,
+    Layaway,
+    Backorder,
+}
+
+impl OrderKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            OrderKind::Reservation => "reservation",
+            OrderKind::Layaway => "layaway",
+            OrderKind::Backorder => "backorder",
+        }
+    }
+
+    pub fn parse(value: &str) -> Option<Self> {
+        match value {
+            "reservation" => Some(OrderKind::Reservation),
+            "layaway" => Some(OrderKind::Layaway),
+            "backorder" => Some(OrderKind::Backorder),
+            _ => None,
+        }
+    }
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            OrderKind::Reservation => "Reservation",
+            OrderKind::Layaway => "Layaway",
+            OrderKind::Backorder => "Backorder",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum OrderStatus {
+    Open,
+    /// Collected by the customer and recorded as a sale.
+    Fulfilled,
+    Cancelled,
+}
+
+impl OrderStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            OrderStatus::Open => "open",
+            OrderStatus::Fulfilled => "fulfilled",
+            OrderStatus::Cancelled => "cancelled",
+        }
+    }
+
+    pub fn parse(value: &str) -> Option<Self> {
+        match value {
+            "open" => Some(OrderStatus::Open),
+            "fulfilled" => Some(OrderStatus::Fulfilled),
+            "cancelled" => Some(OrderStatus::Cancelled),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CustomerOrder {
+    pub id: String,
+    /// Sequential number given to the customer; assigned when the order is placed.
+    pub order_number: Option<i64>,
+    pub kind: OrderKind,
+    pub customer: String,
+    pub product_id: String,
+    pub quantity: f64,
+    /// Stock held for the order.
+    pub allocated: f64,
+    /// Price agreed when the order was placed.
+    pub unit_price: f64,
+    /// Deposits taken so far.
+    pub paid: f64,
+    pub status: OrderStatus,
+    pub created_at: i64,
+    pub closed_at: Option<i64>,
+    /// Sale recorded when the order was collected.
+    pub sale_number: Option<i64>,
+}
+
+impl CustomerOrder {
+    pub fn new(kind: OrderKind, customer: String, product_id: String, quantity: f64, unit_price: f64) -> Self {
+        CustomerOrder {
+            id: Uuid::new_v4().to_string(),
+            order_number: None,
+            kind,
+            customer,
+            product_id,
+            quantity,
+            allocated: 0.0,
+            unit_price,
+            paid: 0.0,
+            status: OrderStatus::Open,
+            created_at: Utc::now().timestamp(),
+            closed_at: None,
+            sale_number: None,
+        }
+    }
+
+    /// Takes `deposit` up front, as is usual for a layaway.
+    pub fn with_deposit(mut self, deposit: f64) -> Self {
+        self.paid = deposit;
+        self
+    }
+
+    pub fn total(&self) -> f64 {
+        round_cents(self.quantity * self.unit_price)
+    }
+
+    /// Amount still to pay.
+    pub fn balance(&self) -> f64 {
+        round_cents(self.total() - self.paid).max(0.0)
+    }
+
+    /// Quantity still waiting for stock.
+    pub fn outstanding(&self) -> f64 {
+        (self.quantity - self.allocated).max(0.0)
+    }
+
+    pub fn is_open(&self) -> bool {
+        self.status == OrderStatus::Open
+    }
+
+    /// Why the order cannot be collected yet, if anything.
+    pub fn collect_blocker(&self) -> Option<String> {
+        if !self.is_open() {
+            return Some(format!("Order is {}", self.status.as_str()));
+        }
+        if self.outstanding() > 0.0 {
+            return Some(format!("{} still awaiting stock", self.outstanding()));
+        }
+        if self.kind == OrderKind::Layaway && self.balance() > 0.0 {
+            return Some(format!("{} still to pay on the layaway", currency::format_base(self.balance())));
+        }
+        None
+    }
+
+    pub fn validate(&self) -> Result<()> {
+        if self.customer.trim().is_empty() {
+            return Err(Error::validation("Customer name is required"));
+        }
+        if !self.quantity.is_finite() || self.quantity <= 0.0 {
+            return Err(Error::validation("Order quantity must be positive"));
+        }
+        if !self.unit_price.is_finite() || self.unit_price <= 0.0 {
+            return Err(Error::validation("Unit price must be positive"));
+        }
+        if !self.paid.is_finite() || self.paid < 0.0 {
+            return Err(Error::validation("Deposit cannot be negative"));
+        }
+        if round_cents(self.paid - self.total()) > 0.0 {
+            return Err(Error::validation("Deposit cannot exceed the order total"));
+        }
+        Ok(())
+    }
+}
+
+fn round_cents(amount: f64) -> f64 {
+    (amount * 100.0).round() / 100.0
+}
+
+/// Open orders with their product names, for the open-orders report.
+pub fn open_orders_table(orders: &[(CustomerOrder, String)]) -> Table {
+    let mut table = Table::new(
+        "Open customer orders",
+        &[
+            "Order", "Kind", "Customer", "Product", "Quantity", "Allocated", "Awaiting", "Total", "Paid", "Balance",
+            "Placed",
+        ],
+    );
+    for (order, product) in orders {
+        table.push(vec![
+            Cell::Number(order.order_number.unwrap_or_default() as f64),
+            Cell::text(order.kind.label()),
+            Cell::text(&order.customer),
+            Cell::text(product),
+            Cell::Number(order.quantity),
+            Cell::Number(order.allocated),
+            Cell::Number(order.outstanding()),
+            Cell::Number(order.total()),
+            Cell::Number(order.paid),
+            Cell::Number(order.balance()),
+            Cell::text(timezone::format_date(order.created_at)),
+        ]);
+    }
+    table
+}
@@ -0,0 +1,106 @@
+//! Minimal PDF writer for monospaced text documents such as receipts.
+//! Uses the built-in Courier font so no font data has to be embedded.
+
+const PAGE_WIDTH: u32 = 595;
+const PAGE_HEIGHT: u32 = 842;
+const MARGIN: u32 = 50;
+const FONT_SIZE: u32 = 10;
+const LINE_HEIGHT: u32 = 12;
+
+/// Lays out `text` one line per row in Courier, starting a new A4 page when a page fills up.
+pub fn text_to_pdf(text: &str) -> Vec<u8> {
+    let lines_per_page = ((PAGE_HEIGHT - 2 * MARGIN) / LINE_HEIGHT) as usize;
+    let lines: Vec<&str> = text.lines().collect();
+    let pages: Vec<&[&str]> = if lines.is_empty() {
+        vec![&[]]
+    } else {
+        lines.chunks(lines_per_page).collect()
+    };
+
+    // Objects 1-3 are the catalog, page tree and font; each page then takes a page and a content object
+    let page_ids: Vec<usize> = (0..pages.len()).map(|i| 4 + i * 2).collect();
+    let mut objects: Vec<Vec<u8>> = Vec::new();
+
+    objects.push(b"<< /Type /Catalog /Pages 2 0 R >>".to_vec());
+    let kids: Vec<String> = page_ids.iter().map(|id| format!("{} 0 R", id)).collect();
+    objects.push(format!("<< /Type /Pages /Kids [{}] /Count {} >>", kids.join(" "), pages.len()).into_bytes());
+    objects.push(b"<< /Type /Font /Subtype /Type1 /BaseFont /Courier /Encoding /WinAnsiEncoding >>".to_vec());
+
+    for (page, page_id) in pages.iter().zip(&page_ids) {
+        objects.push(
+            format!(
+                "<< /Type /Page /Parent 2 0 R /MediaBox [0 0 {} {}] /Resources << /Font << /F1 3 0 R >> >> /Contents {} 0 R >>",
+                PAGE_WIDTH,
+                PAGE_HEIGHT,
+                page_id + 1
+            )
+            .into_bytes(),
+        );
+
+        let mut content = format!(
+            "BT\n/F1 {} Tf\n{} TL\n{} {} Td\n",
+            FONT_SIZE,
+            LINE_HEIGHT,
+            MARGIN,
+            PAGE_HEIGHT - MARGIN - FONT_SIZE
+        )
+        .into_bytes();
+        for line in page.iter() {
+            content.push(b'(');
+            content.extend(encode_line(line));
+            content.extend_from_slice(b") Tj T*\n");
+        }
+        content.extend_from_slice(b"ET");
+
+        let mut stream = format!("<< /Length {} >>\nstream\n", content.len()).into_bytes();
+        stream.extend(content);
+        stream.extend_from_slice(b"\nendstream");
+        objects.push(stream);
+    }
+
+    let mut out = b"%PDF-1.4\n".to_vec();
+    let mut offsets = Vec::with_capacity(objects.len());
+    for (idx, object) in objects.iter().enumerate() {
+        offsets.push(out.len());
+        out.extend(format!("{} 0 obj\n", idx + 1).into_bytes());
+        out.extend(object);
+        out.extend_from_slice(b"\nendobj\n");
+    }
+
+    let xref_offset = out.len();
+    out.extend(format!("xref\n0 {}\n0000000000 65535 f \n", objects.len() + 1).into_bytes());
+    for offset in offsets {
+        out.extend(format!("{:010} 00000 n \n", offset).into_bytes());
+    }
+    out.extend(
+        format!(
+            "trailer\n<< /Size {} /Root 1 0 R >>\nstartxref\n{}\n%%EOF\n",
+            objects.len() + 1,
+            xref_offset
+        )
+        .into_bytes(),
+    );
+    out
+}
+
+/// Encodes a line as a WinAnsi PDF string body, escaping delimiters and
+/// substituting characters the standard fonts cannot show.
+fn encode_line(line: &str) -> Vec<u8> {
+    let mut bytes = Vec::with_capacity(line.len());
+    for c in line.chars() {
+        match c {
+            '(' | ')' | '\\' => {
+                bytes.push(b'\\');
+                bytes.push(c as u8);
+            }
+            '€' => bytes.push(0x80),
+            '─' | '━' => bytes.push(b'-'),
+            '═' => bytes.push(b'='),
+            '│' | '║' => bytes.push(b'|'),
+            c if (c as u32) < 0x20 => bytes.push(b' '),
+            c if (c as u32) <= 0xFF => bytes.push(c as u32 as u8),
+            _ => bytes.push(b'?'),
+        }
+    }
+    bytes
+}
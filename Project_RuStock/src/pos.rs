@@ -0,0 +1,233 @@
+//! Point-of-sale shifts.
+//!
+//! A manager opens a shift by putting a float in the cash drawer. Sales rung up
+//! during the shift record how they were paid, and the X (mid-shift) and Z
+//! (closing) reports compare what each tender should hold with what was counted.
+//! A Z report closes the shift and is stored for later audit.
+
+use crate::currency;
+use crate::error::{Error, Result};
+use crate::timezone;
+use chrono::Utc;
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum PaymentMethod {
+    Cash,
+    Card,
+}
+
+impl PaymentMethod {
+    pub const ALL: [PaymentMethod; 2] = [PaymentMethod::Cash, PaymentMethod::Card];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            PaymentMethod::Cash => "cash",
+            PaymentMethod::Card => "card",
+        }
+    }
+
+    pub fn parse(value: &str) -> Option<Self> {
+        match value {
+            "cash" => Some(PaymentMethod::Cash),
+            "card" => Some(PaymentMethod::Card),
+            _ => None,
+        }
+    }
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            PaymentMethod::Cash => "Cash",
+            PaymentMethod::Card => "Card",
+        }
+    }
+}
+
+/// Money handed over for a sale; cash may exceed what is owed and earn change.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Payment {
+    pub method: PaymentMethod,
+    pub tendered: f64,
+}
+
+impl Payment {
+    pub fn cash(tendered: f64) -> Self {
+        Payment { method: PaymentMethod::Cash, tendered }
+    }
+
+    pub fn card(tendered: f64) -> Self {
+        Payment { method: PaymentMethod::Card, tendered }
+    }
+}
+
+/// How a set of payments covers a sale.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Settlement {
+    /// Each payment with the part of it kept against the sale.
+    pub payments: Vec<(Payment, f64)>,
+    /// Cash handed back to the customer.
+    pub change: f64,
+}
+
+/// Applies `payments` to a sale of `total`. Card payments must not exceed the
+/// total; any overpayment comes out of the cash tendered as change.
+pub fn settle(total: f64, payments: &[Payment]) -> Result<Settlement> {
+    if payments.is_empty() {
+        return Err(Error::validation("A sale needs at least one payment"));
+    }
+    if payments.iter().any(|p| !p.tendered.is_finite() || p.tendered <= 0.0) {
+        return Err(Error::validation("Payment amounts must be positive"));
+    }
+
+    let card: f64 = tendered(payments, PaymentMethod::Card);
+    let cash: f64 = tendered(payments, PaymentMethod::Cash);
+    if round_cents(card - total) > 0.0 {
+        return Err(Error::validation(format!(
+            "Card payments of {} exceed the sale total of {}",
+            currency::format_base(card),
+            currency::format_base(total)
+        )));
+    }
+    let change = round_cents(card + cash - total);
+    if change < 0.0 {
+        return Err(Error::validation(format!(
+            "Payments of {} do not cover the sale total of {}",
+            currency::format_base(card + cash),
+            currency::format_base(total)
+        )));
+    }
+
+    // Change is taken from the last cash payments first
+    let mut remaining_change = change;
+    let mut applied: Vec<(Payment, f64)> = payments.iter().map(|p| (*p, p.tendered)).collect();
+    for (payment, kept) in applied.iter_mut().rev().filter(|(p, _)| p.method == PaymentMethod::Cash) {
+        let returned = remaining_change.min(payment.tendered);
+        *kept = round_cents(payment.tendered - returned);
+        remaining_change = round_cents(remaining_change - returned);
+    }
+
+    Ok(Settlement { payments: applied, change })
+}
+
+fn tendered(payments: &[Payment], method: PaymentMethod) -> f64 {
+    payments.iter().filter(|p| p.method == method).map(|p| p.tendered).sum()
+}
+
+fn round_cents(amount: f64) -> f64 {
+    (amount * 100.0).round() / 100.0
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Shift {
+    pub id: String,
+    pub manager_id: String,
+    pub opened_at: i64,
+    /// Cash put in the drawer when the shift opened.
+    pub opening_float: f64,
+    pub closed_at: Option<i64>,
+}
+
+impl Shift {
+    pub fn new(manager_id: String, opening_float: f64) -> Self {
+        Shift {
+            id: Uuid::new_v4().to_string(),
+            manager_id,
+            opened_at: Utc::now().timestamp(),
+            opening_float,
+            closed_at: None,
+        }
+    }
+
+    pub fn is_open(&self) -> bool {
+        self.closed_at.is_none()
+    }
+
+    pub fn validate(&self) -> Result<()> {
+        if !self.opening_float.is_finite() || self.opening_float < 0.0 {
+            return Err(Error::validation("Opening float cannot be negative"));
+        }
+        Ok(())
+    }
+}
+
+/// Expected and counted takings of one payment method.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TenderLine {
+    pub method: PaymentMethod,
+    /// For cash, the opening float plus cash kept from sales.
+    pub expected: f64,
+    pub counted: Option<f64>,
+}
+
+impl TenderLine {
+    /// Counted minus expected; negative when the drawer is short.
+    pub fn difference(&self) -> Option<f64> {
+        self.counted.map(|counted| round_cents(counted - self.expected))
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ReportKind {
+    /// Read of an open shift; changes nothing.
+    X,
+    /// Closing report of a shift.
+    Z,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ShiftReport {
+    pub kind: ReportKind,
+    pub shift: Shift,
+    pub manager: String,
+    pub sales: i64,
+    pub total_sales: f64,
+    pub change_given: f64,
+    /// Refunds paid out of the shift's tenders.
+    pub refunds: f64,
+    pub lines: Vec<TenderLine>,
+}
+
+impl ShiftReport {
+    pub fn line(&self, method: PaymentMethod) -> Option<&TenderLine> {
+        self.lines.iter().find(|line| line.method == method)
+    }
+
+    /// Total counted minus total expected over every tender that was counted.
+    pub fn difference(&self) -> Option<f64> {
+        let differences: Vec<f64> = self.lines.iter().filter_map(TenderLine::difference).collect();
+        (!differences.is_empty()).then(|| round_cents(differences.iter().sum()))
+    }
+
+    pub fn to_text(&self) -> String {
+        let title = match self.kind {
+            ReportKind::X => "X REPORT (shift still open)",
+            ReportKind::Z => "Z REPORT",
+        };
+        let mut out = format!("{}\n", title);
+        out.push_str(&format!("Manager:       {}\n", self.manager));
+        out.push_str(&format!("Opened:        {}\n", timezone::format_datetime(self.shift.opened_at)));
+        if let Some(closed_at) = self.shift.closed_at {
+            out.push_str(&format!("Closed:        {}\n", timezone::format_datetime(closed_at)));
+        }
+        out.push_str(&format!("Sales:         {}\n", self.sales));
+        out.push_str(&format!("Total sales:   {}\n", currency::format_base(self.total_sales)));
+        out.push_str(&format!("Opening float: {}\n", currency::format_base(self.shift.opening_float)));
+        out.push_str(&format!("Change given:  {}\n", currency::format_base(self.change_given)));
+        out.push_str(&format!("Refunds:       {}\n\n", currency::format_base(self.refunds)));
+        out.push_str(&format!("{:<8} {:>12} {:>12} {:>12}\n", "Tender", "Expected", "Counted", "Over/Short"));
+        for line in &self.lines {
+            out.push_str(&format!(
+                "{:<8} {:>12} {:>12} {:>12}\n",
+                line.method.label(),
+                currency::format_base(line.expected),
+                line.counted.map_or_else(|| "-".to_string(), currency::format_base),
+                line.difference().map_or_else(|| "-".to_string(), currency::format_base),
+            ));
+        }
+        if let Some(difference) = self.difference() {
+            out.push_str(&format!("\nDrawer over/short: {}\n", currency::format_base(difference)));
+        }
+        out
+    }
+}
@@ -0,0 +1,161 @@
+use chrono::Utc;
+use serde::{Deserialize, Serialize};
+use crate::error::{Error, Result};
+use crate::timezone;
+
+pub const REASON_INITIAL: &str = "initial";
+pub const REASON_MANUAL: &str = "manual";
+pub const REASON_SCHEDULED: &str = "scheduled";
+pub const REASON_MARKDOWN: &str = "markdown";
+pub const REASON_MARKDOWN_END: &str = "markdown ended";
+
+/// One entry in a product's price history.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct PriceChange {
+    pub product_id: String,
+    pub old_price: Option<f64>,
+    pub new_price: f64,
+    pub changed_at: i64,
+    pub reason: String,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+pub enum ScheduleKind {
+    /// The new price stays in effect once it starts.
+    Permanent,
+    /// The price reverts to its previous value when the markdown ends.
+    Markdown,
+}
+
+impl ScheduleKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ScheduleKind::Permanent => "permanent",
+            ScheduleKind::Markdown => "markdown",
+        }
+    }
+
+    pub fn parse(value: &str) -> Option<Self> {
+        match value {
+            "permanent" => Some(ScheduleKind::Permanent),
+            "markdown" => Some(ScheduleKind::Markdown),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+pub enum ScheduleStatus {
+    Pending,
+    Active,
+    Completed,
+    Cancelled,
+}
+
+impl ScheduleStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ScheduleStatus::Pending => "pending",
+            ScheduleStatus::Active => "active",
+            ScheduleStatus::Completed => "completed",
+            ScheduleStatus::Cancelled => "cancelled",
+        }
+    }
+
+    pub fn parse(value: &str) -> Option<Self> {
+        match value {
+            "pending" => Some(ScheduleStatus::Pending),
+            "active" => Some(ScheduleStatus::Active),
+            "completed" => Some(ScheduleStatus::Completed),
+            "cancelled" => Some(ScheduleStatus::Cancelled),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct ScheduledPrice {
+    pub id: String,
+    pub product_id: String,
+    pub kind: ScheduleKind,
+    pub price: f64,
+    pub starts_at: i64,
+    pub ends_at: Option<i64>,
+    /// Price in effect when the schedule started; restored when a markdown ends.
+    pub previous_price: Option<f64>,
+    pub status: ScheduleStatus,
+    pub created_at: i64,
+}
+
+impl ScheduledPrice {
+    pub fn new(product_id: String, kind: ScheduleKind, price: f64, starts_at: i64, ends_at: Option<i64>) -> Self {
+        Self {
+            id: uuid::Uuid::new_v4().to_string(),
+            product_id,
+            kind,
+            price,
+            starts_at,
+            ends_at,
+            previous_price: None,
+            status: ScheduleStatus::Pending,
+            created_at: Utc::now().timestamp(),
+        }
+    }
+
+    pub fn validate(&self) -> Result<()> {
+        if self.price <= 0.0 {
+            return Err(Error::validation("Scheduled price must be positive"));
+        }
+        match (self.kind, self.ends_at) {
+            (ScheduleKind::Markdown, None) => {
+                return Err(Error::validation("A markdown needs an end date"));
+            }
+            (_, Some(end)) if end <= self.starts_at => {
+                return Err(Error::validation("End date must be after the start date"));
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+}
+
+/// Sales performance while one price was in effect.
+#[derive(Debug, Clone)]
+pub struct PricePeriod {
+    pub price: f64,
+    pub starts_at: i64,
+    pub ends_at: Option<i64>,
+    pub reason: String,
+    pub units_sold: f64,
+    pub revenue: f64,
+}
+
+impl PricePeriod {
+    /// Average units sold per day over the period, counting partial days as a full day.
+    pub fn units_per_day(&self, now: i64) -> f64 {
+        let end = self.ends_at.unwrap_or(now);
+        let days = ((end - self.starts_at) as f64 / 86_400.0).max(1.0);
+        self.units_sold / days
+    }
+}
+
+/// Parses `YYYY-MM-DD` or `YYYY-MM-DD HH:MM` in the store timezone into a Unix timestamp.
+pub fn parse_date(input: &str) -> Result<i64> {
+    timezone::parse_local(input, timezone::store_timezone())
+}
+
+/// Splits a price history (oldest first) into the periods each price was in effect.
+pub fn price_periods(history: &[PriceChange]) -> Vec<PricePeriod> {
+    history
+        .iter()
+        .enumerate()
+        .map(|(idx, change)| PricePeriod {
+            price: change.new_price,
+            starts_at: change.changed_at,
+            ends_at: history.get(idx + 1).map(|next| next.changed_at),
+            reason: change.reason.clone(),
+            units_sold: 0.0,
+            revenue: 0.0,
+        })
+        .collect()
+}
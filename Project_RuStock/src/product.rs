@@ -1,20 +1,41 @@
 use serde::{Deserialize, Serialize};
 use uuid::Uuid;
+use crate::error::{Error, Result};
+use crate::unit;
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct Product {
     pub id: String,
     pub name: String,
     pub description: String,
+    /// Price of one stock unit.
     pub price: f64,
-    pub quantity: i32,
+    /// Stock on hand, in `unit`.
+    pub quantity: f64,
+    /// Unit stock is kept in, e.g. "each" or "kg".
+    #[serde(default = "default_unit")]
+    pub unit: String,
+    /// Stock keeping unit code; unique across products when set.
+    #[serde(default)]
+    pub sku: Option<String>,
+    /// Lower-case keywords the product can be searched by.
+    #[serde(default)]
+    pub tags: Vec<String>,
     pub created_at: i64,
     pub updated_at: i64,
+    /// Set when the product is archived: hidden from sale screens and the active
+    /// inventory while its sales and purchase history is kept.
+    #[serde(default)]
+    pub archived_at: Option<i64>,
+    /// Incremented on every stored change. An update carrying an older version
+    /// than the stored row is rejected so concurrent edits cannot overwrite each other.
+    #[serde(default)]
+    pub version: i64,
 }
 
 impl Product {
     #[allow(dead_code)]
-    pub fn new(name: String, description: String, price: f64, quantity: i32) -> Self {
+    pub fn new(name: String, description: String, price: f64, quantity: f64) -> Self {
         let now = chrono::Utc::now().timestamp();
         Self {
             id: Uuid::new_v4().to_string(),
@@ -22,12 +43,21 @@ impl Product {
             description,
             price,
             quantity,
+            unit: default_unit(),
+            sku: None,
+            tags: Vec::new(),
             created_at: now,
             updated_at: now,
+            archived_at: None,
+            version: 0,
         }
     }
 
-    pub fn update(&mut self, name: Option<String>, description: Option<String>, price: Option<f64>, quantity: Option<i32>) {
+    pub fn is_archived(&self) -> bool {
+        self.archived_at.is_some()
+    }
+
+    pub fn update(&mut self, name: Option<String>, description: Option<String>, price: Option<f64>, quantity: Option<f64>) {
         if let Some(name) = name {
             self.name = name;
         }
@@ -43,16 +73,40 @@ impl Product {
         self.updated_at = chrono::Utc::now().timestamp();
     }
 
-    pub fn validate(&self) -> Result<(), String> {
+    pub fn validate(&self) -> Result<()> {
         if self.name.trim().is_empty() {
-            return Err("Product name cannot be empty".to_string());
+            return Err(Error::validation("Product name cannot be empty"));
         }
         if self.price < 0.0 {
-            return Err("Product price cannot be negative".to_string());
+            return Err(Error::validation("Product price cannot be negative"));
+        }
+        if self.quantity < 0.0 {
+            return Err(Error::validation("Product quantity cannot be negative"));
         }
-        if self.quantity < 0 {
-            return Err("Product quantity cannot be negative".to_string());
+        if self.unit.trim().is_empty() {
+            return Err(Error::validation("Product unit cannot be empty"));
         }
-        Ok(())
+        if let Some(sku) = &self.sku {
+            if sku.is_empty() || sku.chars().any(char::is_whitespace) {
+                return Err(Error::validation("SKU cannot be empty or contain spaces"));
+            }
+        }
+        unit::check_quantity(&self.unit, self.quantity)
     }
-} 
\ No newline at end of file
+}
+
+fn default_unit() -> String {
+    unit::DEFAULT_UNIT.to_string()
+}
+
+/// Splits comma-separated tags, trimming and lower-casing them and dropping
+/// blanks and duplicates.
+pub fn parse_tags(input: &str) -> Vec<String> {
+    let mut tags: Vec<String> = Vec::new();
+    for tag in input.split(',').map(|tag| tag.trim().to_lowercase()) {
+        if !tag.is_empty() && !tags.contains(&tag) {
+            tags.push(tag);
+        }
+    }
+    tags
+}
@@ -1,25 +1,61 @@
+use crate::currency;
 use serde::{Deserialize, Serialize};
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct Purchase {
     pub id: String,
     pub product_id: String,
-    pub quantity: i32,
+    /// Quantity in `unit`, which is converted into the product's stock unit when recorded.
+    pub quantity: f64,
+    /// Unit the stock was bought in; `None` means the product's stock unit.
+    #[serde(default)]
+    pub unit: Option<String>,
+    /// Price of one `unit`, in `currency`.
     pub purchase_price: f64,
     pub total_cost: f64,
     pub purchase_date: i64,
+    /// ISO code of the supplier's currency.
+    #[serde(default = "currency::base_currency")]
+    pub currency: String,
+    /// Base-currency value of one unit of `currency`, fixed when the purchase is recorded.
+    #[serde(default = "default_exchange_rate")]
+    pub exchange_rate: f64,
+}
+
+fn default_exchange_rate() -> f64 {
+    1.0
 }
 
 impl Purchase {
-    pub fn new(product_id: String, quantity: i32, purchase_price: f64) -> Self {
-        let total_cost = purchase_price * quantity as f64;
+    pub fn new(product_id: String, quantity: f64, purchase_price: f64) -> Self {
+        let total_cost = purchase_price * quantity;
         Self {
             id: uuid::Uuid::new_v4().to_string(),
             product_id,
             quantity,
+            unit: None,
             purchase_price,
             total_cost,
             purchase_date: chrono::Utc::now().timestamp(),
+            currency: currency::base_currency(),
+            exchange_rate: 1.0,
         }
     }
+
+    /// The same purchase billed in a foreign currency; the rate is looked up when it is recorded.
+    pub fn in_currency(mut self, code: &str) -> Self {
+        self.currency = code.trim().to_uppercase();
+        self
+    }
+
+    /// Total cost converted to the base currency.
+    pub fn base_total_cost(&self) -> f64 {
+        self.total_cost * self.exchange_rate
+    }
+
+    /// The same purchase counted in `unit`, e.g. cases instead of single items.
+    pub fn in_unit(mut self, unit: &str) -> Self {
+        self.unit = Some(crate::unit::normalize(unit));
+        self
+    }
 } 
\ No newline at end of file
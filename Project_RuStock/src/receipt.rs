@@ -0,0 +1,355 @@
+use crate::config::env_or;
+use crate::currency;
+use crate::error::Result;
+use crate::pdf;
+use crate::repository::Repository;
+use crate::sale::Sale;
+use crate::settings::{self, Settings};
+use crate::timezone;
+use crate::unit;
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+pub const TEXT_TEMPLATE: &str = "receipt.txt";
+pub const HTML_TEMPLATE: &str = "invoice.html";
+
+const DEFAULT_TEXT_TEMPLATE: &str = "\
+{{store_name:^40}}
+{{store_address:^40}}
+========================================
+Sale #{{sale_number}}
+{{date}}
+----------------------------------------
+{{#items}}
+{{name:<40}}
+  {{quantity:>6}} {{unit:<5}} x {{unit_price:>9}}{{line_total:>14}}
+{{/items}}
+----------------------------------------
+Loyalty discount       {{discount:>17}}
+Subtotal (net)         {{subtotal:>17}}
+Tax {{tax_rate:>5}}%             {{tax:>17}}
+TOTAL                  {{total:>17}}
+========================================
+{{footer:^40}}
+";
+
+const DEFAULT_HTML_TEMPLATE: &str = "\
+<!DOCTYPE html>
+<html>
+<head>
+<meta charset=\"utf-8\">
+<title>Invoice {{sale_number}} - {{store_name}}</title>
+<style>
+body { font-family: sans-serif; margin: 2em; }
+table { border-collapse: collapse; width: 100%; }
+th, td { border-bottom: 1px solid #ccc; padding: 0.4em; text-align: left; }
+td.num, th.num { text-align: right; }
+</style>
+</head>
+<body>
+<h1>{{store_name}}</h1>
+<p>{{store_address}}</p>
+<h2>Invoice #{{sale_number}}</h2>
+<p>Date: {{date}}</p>
+<table>
+<tr><th>Item</th><th class=\"num\">Qty</th><th>Unit</th><th class=\"num\">Unit Price</th><th class=\"num\">Total</th></tr>
+{{#items}}
+<tr><td>{{name}}</td><td class=\"num\">{{quantity}}</td><td>{{unit}}</td><td class=\"num\">{{unit_price}}</td><td class=\"num\">{{line_total}}</td></tr>
+{{/items}}
+</table>
+<p>Loyalty discount: {{discount}}<br>
+Subtotal (net): {{subtotal}}<br>
+Tax ({{tax_rate}}%): {{tax}}<br>
+<strong>Total: {{total}}</strong></p>
+<p>{{footer}}</p>
+</body>
+</html>
+";
+
+/// Store details and template locations used when rendering receipts.
+/// Store details come from the store settings; the currency symbol and the
+/// directories can be overridden with `RUSTOCK_*` environment variables.
+#[derive(Debug, Clone)]
+pub struct ReceiptConfig {
+    pub store_name: String,
+    pub store_address: String,
+    /// Tax rate in percent. Prices are tax-inclusive; the receipt shows the tax portion.
+    pub tax_rate: f64,
+    pub footer: String,
+    pub currency_symbol: String,
+    pub decimal_places: u32,
+    pub template_dir: PathBuf,
+    pub output_dir: PathBuf,
+}
+
+impl Default for ReceiptConfig {
+    fn default() -> Self {
+        ReceiptConfig::from_settings(&Settings::default())
+    }
+}
+
+impl ReceiptConfig {
+    pub fn from_settings(settings: &Settings) -> Self {
+        ReceiptConfig {
+            store_name: settings.store_name.clone(),
+            store_address: settings.store_address.clone(),
+            tax_rate: settings.tax_rate,
+            footer: settings.receipt_footer.clone(),
+            currency_symbol: currency::symbol(&settings.currency),
+            decimal_places: settings.decimal_places,
+            template_dir: PathBuf::from("templates"),
+            output_dir: PathBuf::from("receipts"),
+        }
+    }
+
+    pub fn from_env() -> Self {
+        let defaults = ReceiptConfig::from_settings(&settings::current());
+        ReceiptConfig {
+            currency_symbol: env_or("RUSTOCK_CURRENCY_SYMBOL", defaults.currency_symbol.clone()),
+            template_dir: env_or("RUSTOCK_TEMPLATE_DIR", defaults.template_dir.clone()),
+            output_dir: env_or("RUSTOCK_RECEIPT_DIR", defaults.output_dir.clone()),
+            ..defaults
+        }
+    }
+
+    fn money(&self, amount: f64) -> String {
+        format!("{}{:.*}", self.currency_symbol, self.decimal_places as usize, amount)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ReceiptFormat {
+    /// Plain text sized for 40-column thermal printers.
+    Text,
+    Html,
+    Pdf,
+}
+
+impl ReceiptFormat {
+    pub fn extension(&self) -> &'static str {
+        match self {
+            ReceiptFormat::Text => "txt",
+            ReceiptFormat::Html => "html",
+            ReceiptFormat::Pdf => "pdf",
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct ReceiptLine {
+    pub name: String,
+    pub quantity: f64,
+    pub unit: String,
+    pub unit_price: f64,
+    pub line_total: f64,
+}
+
+/// A stored sale resolved into everything a receipt prints.
+#[derive(Debug, Clone)]
+pub struct Receipt {
+    pub sale_number: i64,
+    pub timestamp: i64,
+    pub lines: Vec<ReceiptLine>,
+    /// Taken off the line totals for redeemed loyalty points.
+    pub discount: f64,
+    pub total: f64,
+}
+
+impl Receipt {
+    pub fn from_sale<R: Repository + ?Sized>(repo: &R, sale: &Sale) -> Result<Receipt> {
+        let mut lines = Vec::with_capacity(sale.items.len());
+        for item in &sale.items {
+            let (name, stock_unit) = match repo.get_product(&item.product_id)? {
+                Some(product) => (product.name, product.unit),
+                None => (item.product_id.clone(), unit::DEFAULT_UNIT.to_string()),
+            };
+            lines.push(ReceiptLine {
+                name,
+                quantity: item.quantity,
+                unit: item.unit.clone().unwrap_or(stock_unit),
+                unit_price: item.unit_price,
+                line_total: item.total_price,
+            });
+        }
+
+        Ok(Receipt {
+            sale_number: sale.sale_number.unwrap_or_default(),
+            timestamp: sale.timestamp,
+            lines,
+            discount: sale.discount,
+            total: sale.total_amount,
+        })
+    }
+
+    /// Tax contained in the (tax-inclusive) total at `rate` percent.
+    pub fn tax(&self, rate: f64) -> f64 {
+        self.total - self.total / (1.0 + rate / 100.0)
+    }
+
+    pub fn file_name(&self, format: ReceiptFormat) -> String {
+        format!("receipt-{:06}.{}", self.sale_number, format.extension())
+    }
+}
+
+pub fn render(receipt: &Receipt, config: &ReceiptConfig, format: ReceiptFormat) -> Result<Vec<u8>> {
+    match format {
+        ReceiptFormat::Text => Ok(render_text(receipt, config)?.into_bytes()),
+        ReceiptFormat::Pdf => Ok(pdf::text_to_pdf(&render_text(receipt, config)?)),
+        ReceiptFormat::Html => {
+            let template = load_template(&config.template_dir, HTML_TEMPLATE, DEFAULT_HTML_TEMPLATE)?;
+            let (vars, items) = template_values(receipt, config);
+            Ok(render_template(&template, &vars, &items, escape_html).into_bytes())
+        }
+    }
+}
+
+pub fn render_text(receipt: &Receipt, config: &ReceiptConfig) -> Result<String> {
+    let template = load_template(&config.template_dir, TEXT_TEMPLATE, DEFAULT_TEXT_TEMPLATE)?;
+    let (vars, items) = template_values(receipt, config);
+    Ok(render_template(&template, &vars, &items, str::to_string))
+}
+
+/// Renders the receipt into `config.output_dir` and returns the written path.
+pub fn save(receipt: &Receipt, config: &ReceiptConfig, format: ReceiptFormat) -> Result<PathBuf> {
+    let contents = render(receipt, config, format)?;
+    fs::create_dir_all(&config.output_dir)?;
+    let path = config.output_dir.join(receipt.file_name(format));
+    fs::write(&path, contents)?;
+    Ok(path)
+}
+
+/// Reads a template from `dir`, first writing the built-in default there so it can be edited.
+fn load_template(dir: &Path, name: &str, default: &str) -> Result<String> {
+    let path = dir.join(name);
+    if !path.exists() {
+        fs::create_dir_all(dir)?;
+        fs::write(&path, default)?;
+    }
+    Ok(fs::read_to_string(path)?)
+}
+
+type Values = HashMap<&'static str, String>;
+
+fn template_values(receipt: &Receipt, config: &ReceiptConfig) -> (Values, Vec<Values>) {
+    let tax = receipt.tax(config.tax_rate);
+    let date = timezone::format_in(receipt.timestamp, timezone::store_timezone(), "%Y-%m-%d %H:%M:%S %Z");
+
+    let vars = HashMap::from([
+        ("store_name", config.store_name.clone()),
+        ("store_address", config.store_address.clone()),
+        ("footer", config.footer.clone()),
+        ("sale_number", format!("{:06}", receipt.sale_number)),
+        ("date", date),
+        ("discount", if receipt.discount > 0.0 { format!("-{}", config.money(receipt.discount)) } else { config.money(0.0) }),
+        ("subtotal", config.money(receipt.total - tax)),
+        ("tax_rate", format!("{:.2}", config.tax_rate)),
+        ("tax", config.money(tax)),
+        ("total", config.money(receipt.total)),
+    ]);
+
+    let items = receipt
+        .lines
+        .iter()
+        .map(|line| {
+            HashMap::from([
+                ("name", line.name.clone()),
+                ("quantity", line.quantity.to_string()),
+                ("unit", line.unit.clone()),
+                ("unit_price", config.money(line.unit_price)),
+                ("line_total", config.money(line.line_total)),
+            ])
+        })
+        .collect();
+
+    (vars, items)
+}
+
+/// Expands `{{key}}` placeholders and one `{{#items}}...{{/items}}` block per line item.
+/// A placeholder may carry an alignment and width, e.g. `{{total:>10}}`, `{{name:<20}}` or `{{store_name:^40}}`.
+pub fn render_template(template: &str, vars: &Values, items: &[Values], escape: fn(&str) -> String) -> String {
+    const OPEN: &str = "{{#items}}";
+    const CLOSE: &str = "{{/items}}";
+
+    if let (Some(start), Some(end)) = (template.find(OPEN), template.find(CLOSE)) {
+        if start < end {
+            let before = &template[..start];
+            let body = skip_newline(&template[start + OPEN.len()..end]);
+            let after = skip_newline(&template[end + CLOSE.len()..]);
+
+            let mut out = substitute(before, vars, escape);
+            for item in items {
+                out.push_str(&substitute(body, item, escape));
+            }
+            out.push_str(&render_template(after, vars, items, escape));
+            return out;
+        }
+    }
+
+    substitute(template, vars, escape)
+}
+
+fn skip_newline(s: &str) -> &str {
+    s.strip_prefix("\r\n").or_else(|| s.strip_prefix('\n')).unwrap_or(s)
+}
+
+fn substitute(text: &str, vars: &Values, escape: fn(&str) -> String) -> String {
+    let mut out = String::with_capacity(text.len());
+    let mut rest = text;
+
+    while let Some(start) = rest.find("{{") {
+        out.push_str(&rest[..start]);
+        let after = &rest[start + 2..];
+        let end = match after.find("}}") {
+            Some(end) => end,
+            None => {
+                out.push_str(&rest[start..]);
+                return out;
+            }
+        };
+
+        let spec = after[..end].trim();
+        let (key, format) = match spec.split_once(':') {
+            Some((key, format)) => (key.trim(), Some(format)),
+            None => (spec, None),
+        };
+        let value = vars.get(key).map(String::as_str).unwrap_or("");
+        out.push_str(&escape(&align(value, format)));
+        rest = &after[end + 2..];
+    }
+
+    out.push_str(rest);
+    out
+}
+
+fn align(value: &str, format: Option<&str>) -> String {
+    let format = match format {
+        Some(format) if !format.is_empty() => format,
+        _ => return value.to_string(),
+    };
+
+    let (alignment, width) = match format.chars().next() {
+        Some(c @ ('<' | '>' | '^')) => (c, &format[1..]),
+        _ => ('<', format),
+    };
+    let width: usize = match width.parse() {
+        Ok(width) => width,
+        Err(_) => return value.to_string(),
+    };
+
+    let value: String = value.chars().take(width).collect();
+    match alignment {
+        '>' => format!("{:>width$}", value, width = width),
+        '^' => format!("{:^width$}", value, width = width),
+        _ => format!("{:<width$}", value, width = width),
+    }
+}
+
+pub fn escape_html(value: &str) -> String {
+    value
+        .replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&#39;")
+}
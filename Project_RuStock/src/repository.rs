@@ -0,0 +1,164 @@
+use crate::auth::{self, LoginOutcome, Manager, SecurityPolicy};
+use crate::bundle::{self, BundleComponent};
+use crate::currency::{self, ExchangeRate};
+use crate::error::{Error, Result};
+use crate::pricing::PriceChange;
+use crate::product::Product;
+use crate::purchase::Purchase;
+use crate::sale::Sale;
+use crate::timezone;
+use crate::unit::{self, ProductUnit};
+use chrono::Utc;
+
+/// Storage operations shared by the SQLite `Database` and the in-memory `MemoryRepository`.
+pub trait Repository {
+    // Products
+    fn add_product(&self, product: &Product) -> Result<()>;
+    fn update_product(&self, product: &Product) -> Result<()>;
+    /// Permanently removes a product. Products with sales or purchases cannot be
+    /// deleted (`Error::Conflict`); archive them instead so their history is kept.
+    fn delete_product(&mut self, id: &str) -> Result<()>;
+    /// Hides a product from sale screens and the active inventory.
+    fn archive_product(&self, id: &str) -> Result<()>;
+    fn restore_product(&self, id: &str) -> Result<()>;
+    /// Any product, archived or not.
+    fn get_product(&self, id: &str) -> Result<Option<Product>>;
+    /// Active (non-archived) products.
+    fn get_all_products(&self) -> Result<Vec<Product>>;
+    /// Archived products, most recently archived first.
+    fn get_archived_products(&self) -> Result<Vec<Product>>;
+    /// Every recorded list price of a product, oldest first.
+    fn get_price_history(&self, product_id: &str) -> Result<Vec<PriceChange>>;
+
+    // Units of measure
+    /// Adds an alternative unit for a product, or changes its conversion factor.
+    fn set_product_unit(&self, unit: &ProductUnit) -> Result<()>;
+    fn remove_product_unit(&self, product_id: &str, unit: &str) -> Result<()>;
+    fn get_product_units(&self, product_id: &str) -> Result<Vec<ProductUnit>>;
+
+    /// Converts `quantity` in `unit` (the stock unit when `None`) into the product's stock unit.
+    fn to_base_quantity(&self, product_id: &str, unit: Option<&str>, quantity: f64) -> Result<f64> {
+        let product = self
+            .get_product(product_id)?
+            .ok_or_else(|| Error::not_found("Product", product_id))?;
+        let units = self.get_product_units(product_id)?;
+        unit::to_base(&product, &units, unit, quantity)
+    }
+
+    // Bundles
+    /// Adds a component to a bundle's bill of materials, or changes its quantity.
+    fn set_bundle_component(&self, component: &BundleComponent) -> Result<()>;
+    fn remove_bundle_component(&self, bundle_id: &str, component_id: &str) -> Result<()>;
+    /// The bill of materials of a bundle; empty for ordinary products.
+    fn get_bundle_components(&self, bundle_id: &str) -> Result<Vec<BundleComponent>>;
+    /// Builds `count` bundles into the bundle's own stock, taking the components
+    /// from theirs. Fails without changing anything if a component is short.
+    fn assemble_bundle(&mut self, bundle_id: &str, count: f64) -> Result<()>;
+
+    /// How much of `product` can be sold now: its own stock not held for customer
+    /// orders plus, for a bundle, what its components' free stock can still build.
+    fn available_quantity(&self, product: &Product) -> Result<f64> {
+        let free = product.quantity - self.reserved_quantity(&product.id)?;
+        let components = self.get_bundle_components(&product.id)?;
+        if components.is_empty() {
+            return Ok(free);
+        }
+        let stock = components
+            .iter()
+            .map(|c| {
+                let quantity = self.get_product(&c.component_id)?.map_or(0.0, |p| p.quantity);
+                Ok(quantity - self.reserved_quantity(&c.component_id)?)
+            })
+            .collect::<Result<Vec<f64>>>()?;
+        Ok(free + bundle::buildable(&components, &stock))
+    }
+
+    /// Stock of `product_id` held for open customer orders. Only the SQLite
+    /// backend takes orders, so nothing is reserved by default.
+    fn reserved_quantity(&self, _product_id: &str) -> Result<f64> {
+        Ok(0.0)
+    }
+
+    // Exchange rates
+    /// Adds a dated rate, replacing one for the same currency and date.
+    fn set_exchange_rate(&self, rate: &ExchangeRate) -> Result<()>;
+    /// All maintained rates, newest first.
+    fn get_exchange_rates(&self) -> Result<Vec<ExchangeRate>>;
+
+    /// Base-currency value of one unit of `currency` at `at`; 1 for the base currency itself.
+    fn exchange_rate(&self, currency: &str, at: i64) -> Result<f64> {
+        let currency = currency::normalize(currency)?;
+        if currency == currency::base_currency() {
+            return Ok(1.0);
+        }
+        currency::rate_on(&self.get_exchange_rates()?, &currency, at).ok_or_else(|| {
+            Error::validation(format!("No {} exchange rate in effect on {}", currency, timezone::format_date(at)))
+        })
+    }
+
+    // Sales and purchases
+    /// Records every item of `sale` and decrements stock. Bundles are taken from
+    /// assembled stock first and built from their components for the rest.
+    /// Either all items are recorded or, if any product lacks stock or is archived,
+    /// none are. Returns the sale number. Sales to loyalty customers are refused;
+    /// the SQLite store records them with `Database::record_customer_sale`.
+    fn record_sale(&mut self, sale: &Sale) -> Result<i64>;
+    /// A stored sale with its line items, looked up by the number printed on its receipt.
+    fn get_sale_by_number(&self, sale_number: i64) -> Result<Option<Sale>>;
+    /// Records a purchase and adds its quantity, converted to the stock unit, to the
+    /// product's stock. The exchange rate is fixed from the rate table as of the purchase date.
+    fn record_purchase(&mut self, purchase: &Purchase) -> Result<()>;
+    /// Sales as (product name, quantity in stock units, total price, sale timestamp), newest first.
+    /// Quantities and totals are net of returns; rows returned in full are left out.
+    fn get_all_sales(&self) -> Result<Vec<(String, f64, f64, i64)>>;
+    fn get_all_purchases(&self) -> Result<Vec<Purchase>>;
+
+    // Managers
+    fn add_manager(&self, manager: &Manager) -> Result<()>;
+    fn get_manager_by_username(&self, username: &str) -> Result<Option<Manager>>;
+    fn get_manager(&self, manager_id: &str) -> Result<Option<Manager>>;
+    fn get_all_managers(&self) -> Result<Vec<Manager>>;
+    fn update_manager_status(&self, manager_id: &str, is_active: bool) -> Result<()>;
+    fn update_manager_password(&self, manager_id: &str, password: &str) -> Result<()>;
+    /// Renames a manager. Usernames stay unique; shifts and voids follow the manager's ID.
+    fn update_manager_profile(&self, manager_id: &str, username: &str, full_name: &str) -> Result<()>;
+    /// Replaces a forgotten password with a temporary one that must be changed at the
+    /// next login, and lifts any lockout.
+    fn reset_manager_password(&self, manager_id: &str, temporary_password: &str) -> Result<()>;
+    fn save_login_state(&self, manager_id: &str, failed_attempts: u32, locked_until: Option<i64>) -> Result<()>;
+
+    /// Checks credentials and maintains the persistent failed-attempt counter.
+    /// After `max_failed_attempts` consecutive failures the account is locked
+    /// for `lockout_minutes`, surviving restarts of the application.
+    fn attempt_login(&self, username: &str, password: &str, policy: &SecurityPolicy) -> Result<LoginOutcome> {
+        let now = Utc::now().timestamp();
+
+        let manager = match self.get_manager_by_username(username)? {
+            Some(manager) => manager,
+            None => return Ok(LoginOutcome::InvalidCredentials),
+        };
+
+        if manager.is_locked(now) {
+            return Ok(LoginOutcome::LockedOut { until: manager.locked_until.unwrap_or(now) });
+        }
+
+        if auth::verify_password(&manager.password, password) && manager.is_active {
+            self.save_login_state(&manager.id, 0, None)?;
+            return Ok(LoginOutcome::Success(Manager {
+                failed_attempts: 0,
+                locked_until: None,
+                ..manager
+            }));
+        }
+
+        let failed_attempts = manager.failed_attempts + 1;
+        if failed_attempts >= policy.max_failed_attempts {
+            let until = now + policy.lockout_minutes * 60;
+            self.save_login_state(&manager.id, 0, Some(until))?;
+            return Ok(LoginOutcome::LockedOut { until });
+        }
+
+        self.save_login_state(&manager.id, failed_attempts, None)?;
+        Ok(LoginOutcome::InvalidCredentials)
+    }
+}
@@ -1,11 +1,17 @@
 use chrono::Utc;
 use serde::{Deserialize, Serialize};
 use uuid::Uuid;
+use crate::error::{Error, Result};
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct SaleItem {
     pub product_id: String,
-    pub quantity: i32,
+    /// Quantity in `unit`, which is converted into the product's stock unit when recorded.
+    pub quantity: f64,
+    /// Unit the item was sold in; `None` means the product's stock unit.
+    #[serde(default)]
+    pub unit: Option<String>,
+    /// Price of one `unit`.
     pub unit_price: f64,
     pub total_price: f64,
 }
@@ -13,10 +19,21 @@ pub struct SaleItem {
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct Sale {
     pub id: String,
+    /// Sequential number printed on receipts; assigned when the sale is recorded.
+    pub sale_number: Option<i64>,
     pub items: Vec<SaleItem>,
+    /// What the customer pays: the item totals less `discount`.
     pub total_amount: f64,
     pub total_profit: f64,
     pub timestamp: i64,
+    /// Loyalty customer the sale is credited to.
+    #[serde(default)]
+    pub customer_id: Option<String>,
+    /// Loyalty points spent on `discount`.
+    #[serde(default)]
+    pub points_redeemed: i64,
+    #[serde(default)]
+    pub discount: f64,
 }
 
 impl Sale {
@@ -24,28 +41,52 @@ impl Sale {
         let total_amount = items.iter().map(|item| item.total_price).sum();
         Self {
             id: Uuid::new_v4().to_string(),
+            sale_number: None,
             items,
             total_amount,
             total_profit: 0.0,
             timestamp: Utc::now().timestamp(),
+            customer_id: None,
+            points_redeemed: 0,
+            discount: 0.0,
         }
     }
 
-    pub fn validate(&self) -> Result<(), String> {
+    /// Sum of the item totals before any discount.
+    pub fn subtotal(&self) -> f64 {
+        self.items.iter().map(|item| item.total_price).sum()
+    }
+
+    /// Credits the sale to a loyalty customer, spending `points` on a discount of `discount`.
+    pub fn for_customer(mut self, customer_id: String, points: i64, discount: f64) -> Self {
+        self.customer_id = Some(customer_id);
+        self.points_redeemed = points;
+        self.discount = discount;
+        self.total_amount = ((self.subtotal() - discount) * 100.0).round() / 100.0;
+        self
+    }
+
+    pub fn validate(&self) -> Result<()> {
         if self.items.is_empty() {
-            return Err("Sale must have at least one item".to_string());
+            return Err(Error::validation("Sale must have at least one item"));
         }
         for item in &self.items {
-            if item.quantity <= 0 {
-                return Err("Item quantity must be positive".to_string());
+            if item.quantity <= 0.0 {
+                return Err(Error::validation("Item quantity must be positive"));
             }
             if item.unit_price <= 0.0 {
-                return Err("Item unit price must be positive".to_string());
+                return Err(Error::validation("Item unit price must be positive"));
             }
-            if (item.unit_price * item.quantity as f64 - item.total_price).abs() > 0.01 {
-                return Err("Item total price calculation mismatch".to_string());
+            if (item.unit_price * item.quantity - item.total_price).abs() > 0.01 {
+                return Err(Error::validation("Item total price calculation mismatch"));
             }
         }
+        if !self.discount.is_finite() || self.discount < 0.0 || self.discount > self.subtotal() + 0.005 {
+            return Err(Error::validation("Discount must be between zero and the sale subtotal"));
+        }
+        if self.points_redeemed < 0 || (self.points_redeemed > 0 && self.customer_id.is_none()) {
+            return Err(Error::validation("Points can only be redeemed by a loyalty customer"));
+        }
         Ok(())
     }
 } 
\ No newline at end of file
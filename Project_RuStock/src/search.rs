@@ -0,0 +1,87 @@
+//! Full-text product search.
+//!
+//! `Database` keeps an FTS5 index over each product's name, description, SKU and
+//! tags. A query is split into terms the same way the index splits text; every
+//! term matches words it is a prefix of, and longer terms also match indexed
+//! words a few typos away, so "chedar" still finds "Cheddar". Products that
+//! match without any correction rank first, then by relevance.
+
+/// Results shown when a screen searches for a product.
+pub const DEFAULT_LIMIT: usize = 10;
+
+/// Lower-case runs of letters and digits, matching FTS5's default tokenizer.
+pub fn tokenize(text: &str) -> Vec<String> {
+    text.split(|c: char| !c.is_alphanumeric())
+        .filter(|term| !term.is_empty())
+        .map(str::to_lowercase)
+        .collect()
+}
+
+/// Typos tolerated in a term: none for short terms, where a single edit would
+/// match almost anything, one up to seven characters and two beyond.
+pub fn max_typos(term: &str) -> usize {
+    match term.chars().count() {
+        0..=3 => 0,
+        4..=7 => 1,
+        _ => 2,
+    }
+}
+
+/// Levenshtein distance between two strings, counted in characters.
+pub fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+    for (i, ca) in a.chars().enumerate() {
+        let mut current = vec![i + 1; b.len() + 1];
+        for (j, cb) in b.iter().enumerate() {
+            let substitution = previous[j] + usize::from(ca != *cb);
+            current[j + 1] = substitution.min(previous[j + 1] + 1).min(current[j] + 1);
+        }
+        previous = current;
+    }
+    previous[b.len()]
+}
+
+/// Indexed words within `term`'s typo allowance, excluding `term` itself.
+pub fn corrections<'a>(term: &str, vocabulary: &'a [String]) -> Vec<&'a str> {
+    let allowed = max_typos(term);
+    if allowed == 0 {
+        return Vec::new();
+    }
+    let length = term.chars().count();
+    vocabulary
+        .iter()
+        .filter(|word| word.as_str() != term && word.chars().count().abs_diff(length) <= allowed)
+        .filter(|word| edit_distance(term, word) <= allowed)
+        .map(String::as_str)
+        .collect()
+}
+
+/// Pieces of `term` of which any indexed word within its typo allowance must
+/// contain at least one unchanged. The term is cut into one more piece than the
+/// typos allowed, and each typo can change at most one piece.
+pub fn anchors(term: &str) -> Vec<String> {
+    let chars: Vec<char> = term.chars().collect();
+    let pieces = max_typos(term) + 1;
+    (0..pieces)
+        .map(|i| chars[i * chars.len() / pieces..(i + 1) * chars.len() / pieces].iter().collect())
+        .collect()
+}
+
+/// An FTS5 MATCH expression requiring every term, each as a prefix or as one of
+/// its corrections.
+pub fn match_expression(terms: &[(String, Vec<&str>)]) -> String {
+    terms
+        .iter()
+        .map(|(term, alternatives)| {
+            let mut options = vec![format!("{}*", quote(term))];
+            options.extend(alternatives.iter().map(|word| quote(word)));
+            format!("({})", options.join(" OR "))
+        })
+        .collect::<Vec<_>>()
+        .join(" AND ")
+}
+
+fn quote(term: &str) -> String {
+    format!("\"{}\"", term.replace('"', "\"\""))
+}
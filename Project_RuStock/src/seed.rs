@@ -0,0 +1,373 @@
+//! Synthetic demo data for evaluating reports and search on a realistic store.
+//!
+//! `generate` builds a catalogue of products in a handful of categories and
+//! simulates a store trading over the given number of days: busier weekends and
+//! a December peak, summer, winter and holiday categories that rise and fall
+//! with the calendar, a few best sellers and a long tail, and stock replenished
+//! by purchases when it runs low. The simulation is driven by a seeded generator,
+//! so the same options always produce the same store apart from record IDs.
+
+use crate::product::Product;
+use crate::purchase::Purchase;
+use crate::sale::{Sale, SaleItem};
+use crate::timezone::{self, Tz};
+use chrono::{Datelike, Days, NaiveDate};
+use std::collections::HashMap;
+use std::f64::consts::PI;
+
+pub const DEFAULT_PRODUCTS: usize = 2000;
+pub const DEFAULT_DAYS: u32 = 365;
+/// Sales on an average day; a year at this rate is well over 100,000 sales.
+pub const DEFAULT_SALES_PER_DAY: f64 = 300.0;
+pub const DEFAULT_SEED: u64 = 42;
+
+/// Prefix of every generated SKU.
+pub const SKU_PREFIX: &str = "DEMO-";
+
+// Days between a purchase being triggered by low stock and the goods arriving
+const LEAD_TIME_DAYS: usize = 3;
+// Cover, in days of expected demand, of the reorder point and of each order
+const REORDER_COVER_DAYS: f64 = 7.0;
+const ORDER_COVER_DAYS: f64 = 21.0;
+// Trading hours in the store's timezone
+const OPENING_HOUR: i64 = 9;
+const CLOSING_HOUR: i64 = 20;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SeedOptions {
+    pub products: usize,
+    /// Days of trading, ending yesterday.
+    pub days: u32,
+    pub sales_per_day: f64,
+    pub seed: u64,
+}
+
+impl Default for SeedOptions {
+    fn default() -> Self {
+        SeedOptions {
+            products: DEFAULT_PRODUCTS,
+            days: DEFAULT_DAYS,
+            sales_per_day: DEFAULT_SALES_PER_DAY,
+            seed: DEFAULT_SEED,
+        }
+    }
+}
+
+/// A generated store. Products carry their closing stock; purchases and sales
+/// are in time order.
+#[derive(Debug, Clone, Default)]
+pub struct DemoData {
+    pub products: Vec<Product>,
+    pub purchases: Vec<Purchase>,
+    pub sales: Vec<Sale>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Season {
+    AllYear,
+    Summer,
+    Winter,
+    /// The weeks before Christmas.
+    Holiday,
+}
+
+impl Season {
+    /// Demand relative to the category's average on day `day_of_year`.
+    fn factor(&self, day_of_year: u32) -> f64 {
+        let day = day_of_year as f64;
+        match self {
+            Season::AllYear => 1.0,
+            Season::Summer => 1.0 + 0.8 * (2.0 * PI * (day - 196.0) / 365.0).cos(),
+            Season::Winter => 1.0 + 0.8 * (2.0 * PI * (day - 15.0) / 365.0).cos(),
+            Season::Holiday => 0.6 + 3.0 * (-((day - 352.0) / 18.0).powi(2)).exp(),
+        }
+    }
+}
+
+struct Category {
+    tag: &'static str,
+    season: Season,
+    unit: &'static str,
+    nouns: &'static [&'static str],
+    /// Price range of one stock unit.
+    prices: (f64, f64),
+}
+
+const CATEGORIES: [Category; 8] = [
+    Category {
+        tag: "kitchen",
+        season: Season::AllYear,
+        unit: "each",
+        nouns: &["Kettle", "Mug", "Frying Pan", "Knife Set", "Cutting Board", "Teapot", "Colander"],
+        prices: (4.0, 80.0),
+    },
+    Category {
+        tag: "pantry",
+        season: Season::AllYear,
+        unit: "kg",
+        nouns: &["Coffee Beans", "Basmati Rice", "Bread Flour", "Cane Sugar", "Rolled Oats", "Lentils"],
+        prices: (2.0, 25.0),
+    },
+    Category {
+        tag: "cleaning",
+        season: Season::AllYear,
+        unit: "each",
+        nouns: &["Sponge Pack", "Detergent", "Mop", "Glass Cleaner", "Scrub Brush", "Dish Soap"],
+        prices: (1.5, 30.0),
+    },
+    Category {
+        tag: "stationery",
+        season: Season::AllYear,
+        unit: "each",
+        nouns: &["Notebook", "Pen Set", "Stapler", "Ring Binder", "Desk Organiser", "Sticky Notes"],
+        prices: (1.0, 35.0),
+    },
+    Category {
+        tag: "garden",
+        season: Season::Summer,
+        unit: "each",
+        nouns: &["Garden Hose", "Planter", "Rake", "Sprinkler", "Seed Mix", "Pruning Shears"],
+        prices: (3.0, 60.0),
+    },
+    Category {
+        tag: "outdoor",
+        season: Season::Summer,
+        unit: "each",
+        nouns: &["Cool Box", "Tent", "Hammock", "Sunscreen", "Picnic Blanket", "Camping Chair"],
+        prices: (6.0, 150.0),
+    },
+    Category {
+        tag: "heating",
+        season: Season::Winter,
+        unit: "each",
+        nouns: &["Fan Heater", "Wool Blanket", "Thermal Socks", "Hot Water Bottle", "Draught Excluder"],
+        prices: (5.0, 90.0),
+    },
+    Category {
+        tag: "toys",
+        season: Season::Holiday,
+        unit: "each",
+        nouns: &["Jigsaw Puzzle", "Board Game", "Plush Bear", "Building Blocks", "Kite", "Toy Train"],
+        prices: (5.0, 70.0),
+    },
+];
+
+const ADJECTIVES: [&str; 12] = [
+    "Classic", "Deluxe", "Eco", "Compact", "Premium", "Family", "Pro", "Mini", "Everyday", "Heritage", "Nordic", "Urban",
+];
+
+/// SplitMix64: small, fast and good enough for synthetic data.
+struct Rng(u64);
+
+impl Rng {
+    fn next_u64(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^ (z >> 31)
+    }
+
+    /// Uniform in [0, 1).
+    fn next_f64(&mut self) -> f64 {
+        (self.next_u64() >> 11) as f64 / (1u64 << 53) as f64
+    }
+
+    fn range(&mut self, low: f64, high: f64) -> f64 {
+        low + (high - low) * self.next_f64()
+    }
+
+    fn below(&mut self, n: usize) -> usize {
+        (self.next_u64() % n as u64) as usize
+    }
+
+    /// Index into `cumulative` weights, chosen in proportion to each weight.
+    fn weighted(&mut self, cumulative: &[f64]) -> usize {
+        let target = self.next_f64() * cumulative.last().copied().unwrap_or(0.0);
+        cumulative.partition_point(|&total| total <= target).min(cumulative.len() - 1)
+    }
+}
+
+fn cumulative(weights: impl Iterator<Item = f64>) -> Vec<f64> {
+    weights
+        .scan(0.0, |total, weight| {
+            *total += weight;
+            Some(*total)
+        })
+        .collect()
+}
+
+fn round_cents(amount: f64) -> f64 {
+    (amount * 100.0).round() / 100.0
+}
+
+/// Store traffic on `date` relative to an average day: busier towards the
+/// weekend, a December peak and modest growth over the period.
+fn traffic(date: NaiveDate, progress: f64) -> f64 {
+    const WEEKDAY: [f64; 7] = [0.85, 0.8, 0.9, 1.0, 1.2, 1.4, 0.85];
+    let day = date.ordinal() as f64;
+    let december = 1.0 + 0.4 * (-((day - 350.0) / 20.0).powi(2)).exp();
+    WEEKDAY[date.weekday().num_days_from_monday() as usize] * december * (0.9 + 0.2 * progress)
+}
+
+struct Stock {
+    on_hand: f64,
+    reorder_point: f64,
+    order_quantity: f64,
+    unit_cost: f64,
+    /// Day and quantity of a purchase on its way.
+    incoming: Option<(usize, f64)>,
+}
+
+/// Generates a store trading for `options.days` days up to the day before `today`.
+pub fn generate(options: &SeedOptions, today: NaiveDate, tz: Tz) -> DemoData {
+    let mut rng = Rng(options.seed);
+    let days = options.days.max(1) as usize;
+    let first_date = today - Days::new(days as u64);
+    let day_start = |day: usize| timezone::start_of_day(first_date + Days::new(day as u64), tz);
+    let opened_at = day_start(0);
+
+    let mut products = Vec::with_capacity(options.products);
+    let mut category_of = Vec::with_capacity(options.products);
+    let mut popularity = Vec::with_capacity(options.products);
+    let mut names: HashMap<String, usize> = HashMap::new();
+    for i in 0..options.products {
+        let category_index = rng.below(CATEGORIES.len());
+        let category = &CATEGORIES[category_index];
+        let noun = category.nouns[rng.below(category.nouns.len())];
+        let base = format!("{} {}", ADJECTIVES[rng.below(ADJECTIVES.len())], noun);
+        let seen = names.entry(base.clone()).or_default();
+        *seen += 1;
+        let name = if *seen == 1 { base } else { format!("{} Mk {}", base, seen) };
+
+        let price = (rng.range(category.prices.0, category.prices.1).round() - 0.01).max(0.99);
+        let mut product = Product::new(
+            name,
+            format!("{} {} for everyday use", category.tag, noun.to_lowercase()),
+            price,
+            0.0,
+        );
+        product.unit = category.unit.to_string();
+        product.sku = Some(format!("{}{:06}", SKU_PREFIX, i + 1));
+        product.tags = vec![category.tag.to_string(), "demo".to_string()];
+        product.created_at = opened_at;
+        product.updated_at = opened_at;
+
+        // A long tail behind a few best sellers, with cheaper products selling more often
+        popularity.push((rng.next_f64() * 50.0 + 1.0).powf(-1.2) * 100.0 / price.sqrt());
+        category_of.push(category_index);
+        products.push(product);
+    }
+    let members: Vec<Vec<usize>> = (0..CATEGORIES.len())
+        .map(|c| (0..products.len()).filter(|&p| category_of[p] == c).collect())
+        .collect();
+    let member_weights: Vec<Vec<f64>> = members
+        .iter()
+        .map(|ids| cumulative(ids.iter().map(|&p| popularity[p])))
+        .collect();
+    let category_weights: Vec<f64> = members.iter().map(|ids| ids.iter().map(|&p| popularity[p]).sum()).collect();
+    let total_weight: f64 = category_weights.iter().sum();
+
+    // Opening stock covers the first few weeks of expected demand
+    let items_per_sale = 1.8;
+    let mut stock: Vec<Stock> = products
+        .iter()
+        .zip(&popularity)
+        .map(|(product, weight)| {
+            let daily = options.sales_per_day * items_per_sale * weight / total_weight.max(f64::MIN_POSITIVE);
+            let order_quantity = (daily * ORDER_COVER_DAYS).ceil().max(10.0);
+            Stock {
+                on_hand: 0.0,
+                reorder_point: (daily * REORDER_COVER_DAYS).ceil().max(3.0),
+                order_quantity,
+                unit_cost: round_cents(product.price * rng.range(0.45, 0.7)),
+                incoming: Some((0, order_quantity)),
+            }
+        })
+        .collect();
+
+    let mut purchases = Vec::new();
+    let mut sales = Vec::new();
+    for day in 0..days {
+        let date = first_date + Days::new(day as u64);
+        let start = day_start(day);
+
+        // Deliveries arrive before opening
+        for (product, stock) in products.iter().zip(stock.iter_mut()) {
+            if let Some((arrival, quantity)) = stock.incoming {
+                if arrival <= day {
+                    let cost = round_cents(stock.unit_cost * rng.range(0.95, 1.05));
+                    let mut purchase = Purchase::new(product.id.clone(), quantity, cost);
+                    purchase.purchase_date = start + 8 * 3600 + rng.below(3600) as i64;
+                    purchases.push(purchase);
+                    stock.on_hand += quantity;
+                    stock.incoming = None;
+                }
+            }
+        }
+
+        let seasonal: Vec<f64> = CATEGORIES
+            .iter()
+            .zip(&category_weights)
+            .map(|(category, weight)| category.season.factor(date.ordinal()).max(0.1) * weight)
+            .collect();
+        let category_picker = cumulative(seasonal.into_iter());
+
+        let expected = options.sales_per_day * traffic(date, day as f64 / days as f64);
+        let count = (expected * rng.range(0.85, 1.15)).round() as usize;
+        let mut times: Vec<i64> = (0..count)
+            .map(|_| start + OPENING_HOUR * 3600 + rng.below(((CLOSING_HOUR - OPENING_HOUR) * 3600) as usize) as i64)
+            .collect();
+        times.sort_unstable();
+
+        for timestamp in times {
+            let mut items: Vec<SaleItem> = Vec::new();
+            loop {
+                let category = rng.weighted(&category_picker);
+                if members[category].is_empty() {
+                    break;
+                }
+                let p = members[category][rng.weighted(&member_weights[category])];
+                let product = &products[p];
+                let quantity = if product.unit == "kg" {
+                    (rng.range(0.25, 3.0) * 10.0).round() / 10.0
+                } else if rng.next_f64() < 0.8 {
+                    1.0
+                } else {
+                    (2 + rng.below(3)) as f64
+                };
+                // Shoppers leave without an item that is sold out
+                if stock[p].on_hand >= quantity && !items.iter().any(|item| item.product_id == product.id) {
+                    stock[p].on_hand = (stock[p].on_hand - quantity).max(0.0);
+                    items.push(SaleItem {
+                        product_id: product.id.clone(),
+                        quantity,
+                        unit: None,
+                        unit_price: product.price,
+                        total_price: round_cents(product.price * quantity),
+                    });
+                    if stock[p].on_hand <= stock[p].reorder_point && stock[p].incoming.is_none() {
+                        stock[p].incoming = Some((day + LEAD_TIME_DAYS, stock[p].order_quantity));
+                    }
+                }
+                if items.len() >= 5 || rng.next_f64() < 0.55 {
+                    break;
+                }
+            }
+            if items.is_empty() {
+                continue;
+            }
+            let mut sale = Sale::new(items);
+            sale.total_amount = round_cents(sale.total_amount);
+            sale.timestamp = timestamp;
+            sales.push(sale);
+        }
+    }
+
+    let closed_at = day_start(days);
+    for (product, stock) in products.iter_mut().zip(&stock) {
+        product.quantity = (stock.on_hand * 1000.0).round() / 1000.0;
+        product.updated_at = closed_at;
+    }
+    DemoData { products, purchases, sales }
+}
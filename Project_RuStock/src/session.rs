@@ -0,0 +1,34 @@
+use chrono::Utc;
+use std::sync::atomic::{AtomicBool, AtomicI64, Ordering};
+
+// The console is single-user and every screen reads input through `prompt`,
+// so idle tracking is kept in process-wide state rather than threaded through each menu.
+static LAST_ACTIVITY: AtomicI64 = AtomicI64::new(0);
+static TIMEOUT_SECS: AtomicI64 = AtomicI64::new(0);
+static EXPIRED: AtomicBool = AtomicBool::new(false);
+
+/// Starts a new session that expires after `timeout_minutes` without input. Zero disables the timeout.
+pub fn start(timeout_minutes: i64) {
+    TIMEOUT_SECS.store(timeout_minutes.max(0) * 60, Ordering::SeqCst);
+    LAST_ACTIVITY.store(Utc::now().timestamp(), Ordering::SeqCst);
+    EXPIRED.store(false, Ordering::SeqCst);
+}
+
+/// Returned for the input that expired the session, so the screen waiting for
+/// it is left without acting on it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Expired;
+
+/// Records user input. If the gap since the previous input exceeded the timeout the session is
+/// marked expired and `true` is returned, only for the input that expired it, so that the caller
+/// can discard whatever that input was meant for.
+pub fn touch() -> bool {
+    let now = Utc::now().timestamp();
+    let last = LAST_ACTIVITY.swap(now, Ordering::SeqCst);
+    let timeout = TIMEOUT_SECS.load(Ordering::SeqCst);
+    timeout > 0 && last > 0 && now - last > timeout && !EXPIRED.swap(true, Ordering::SeqCst)
+}
+
+pub fn is_expired() -> bool {
+    EXPIRED.load(Ordering::SeqCst)
+}
@@ -0,0 +1,205 @@
+//! Store settings: the store's identity, money formatting, tax and stock defaults.
+//!
+//! Values are layered. Built-in defaults are overridden by the settings file
+//! (`rustock.toml`, or the path in `RUSTOCK_SETTINGS`), then by the matching
+//! `RUSTOCK_*` environment variables, then by values saved from the console,
+//! which `Database` keeps in its `settings` table. The merged settings are
+//! installed once the database is open and read through `current()` by
+//! everything that prints store details or formats money.
+
+use crate::config::env_or;
+use crate::currency;
+use crate::error::{Error, Result};
+use crate::journal::AccountMap;
+use crate::loyalty::LoyaltyProgram;
+use crate::void;
+use serde::Deserialize;
+use std::path::{Path, PathBuf};
+use std::sync::RwLock;
+
+pub const DEFAULT_PATH: &str = "rustock.toml";
+
+/// Most decimal places an amount can be shown with.
+pub const MAX_DECIMAL_PLACES: u32 = 4;
+
+static CURRENT: RwLock<Option<Settings>> = RwLock::new(None);
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Settings {
+    pub store_name: String,
+    pub store_address: String,
+    /// ISO 4217 code of the base currency: sales are made in it and purchase
+    /// costs are converted to it. The console refuses to change it once sales
+    /// or purchases are recorded.
+    pub currency: String,
+    /// Decimal places amounts in the base currency are shown with.
+    pub decimal_places: u32,
+    /// Tax rate in percent. Prices are tax-inclusive; receipts show the tax portion.
+    pub tax_rate: f64,
+    /// Stock at or below this level, but above zero, counts as low.
+    pub low_stock_threshold: f64,
+    pub receipt_footer: String,
+    /// Minutes after a sale, purchase or stock adjustment during which a manager
+    /// can still void it; 0 turns voiding off.
+    pub void_window_minutes: u32,
+    /// Ledger accounts the accounting journal posts to; set in the file only.
+    pub accounts: AccountMap,
+    /// Points earning, redemption and tiers; set in the file only.
+    pub loyalty: LoyaltyProgram,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Settings {
+            store_name: "RuStock Store".to_string(),
+            store_address: String::new(),
+            currency: currency::DEFAULT_BASE_CURRENCY.to_string(),
+            decimal_places: 2,
+            tax_rate: 0.0,
+            low_stock_threshold: 5.0,
+            receipt_footer: "Thank you for your purchase!".to_string(),
+            void_window_minutes: void::DEFAULT_WINDOW_MINUTES,
+            accounts: AccountMap::default(),
+            loyalty: LoyaltyProgram::default(),
+        }
+    }
+}
+
+/// A setting that can be overridden from the environment or the console.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Key {
+    /// Name used in the settings file and the settings table.
+    pub name: &'static str,
+    pub label: &'static str,
+    pub env: &'static str,
+}
+
+pub const KEYS: [Key; 8] = [
+    Key { name: "store_name", label: "Store name", env: "RUSTOCK_STORE_NAME" },
+    Key { name: "store_address", label: "Store address", env: "RUSTOCK_STORE_ADDRESS" },
+    Key { name: "currency", label: "Base currency", env: "RUSTOCK_CURRENCY" },
+    Key { name: "decimal_places", label: "Decimal places", env: "RUSTOCK_DECIMAL_PLACES" },
+    Key { name: "tax_rate", label: "Default tax rate (%)", env: "RUSTOCK_TAX_RATE" },
+    Key { name: "low_stock_threshold", label: "Low-stock threshold", env: "RUSTOCK_LOW_STOCK_THRESHOLD" },
+    Key { name: "receipt_footer", label: "Receipt footer", env: "RUSTOCK_RECEIPT_FOOTER" },
+    Key { name: "void_window_minutes", label: "Void window (minutes)", env: "RUSTOCK_VOID_WINDOW_MINUTES" },
+];
+
+impl Settings {
+    /// Reads the settings file and environment. Values that cannot be used are
+    /// returned as errors alongside the settings, which keep the earlier layer's value.
+    pub fn load() -> (Self, Vec<Error>) {
+        let mut errors = Vec::new();
+        let mut settings = Settings::from_file(&file_path()).unwrap_or_else(|e| {
+            errors.push(e);
+            Settings::default()
+        });
+        let from_env: Vec<(String, String)> = KEYS
+            .iter()
+            .filter_map(|key| std::env::var(key.env).ok().map(|value| (key.name.to_string(), value)))
+            .collect();
+        errors.extend(settings.apply(&from_env));
+        (settings, errors)
+    }
+
+    /// Parses a TOML settings file; a missing file gives the defaults.
+    pub fn from_file(path: &Path) -> Result<Self> {
+        let text = match std::fs::read_to_string(path) {
+            Ok(text) => text,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Settings::default()),
+            Err(e) => return Err(e.into()),
+        };
+        let mut settings: Settings = toml::from_str(&text)
+            .map_err(|e| Error::validation(format!("{}: {}", path.display(), e.message())))?;
+        settings.currency = currency::normalize(&settings.currency)
+            .map_err(|e| Error::validation(format!("{}: {}", path.display(), e)))?;
+        settings.validate().map_err(|e| Error::validation(format!("{}: {}", path.display(), e)))?;
+        Ok(settings)
+    }
+
+    /// Sets each `(key, value)` pair in turn, returning the ones that were rejected.
+    pub fn apply(&mut self, values: &[(String, String)]) -> Vec<Error> {
+        values.iter().filter_map(|(key, value)| self.set(key, value).err()).collect()
+    }
+
+    /// The value of `key` as it is shown and stored.
+    pub fn get(&self, key: &str) -> Option<String> {
+        Some(match key {
+            "store_name" => self.store_name.clone(),
+            "store_address" => self.store_address.clone(),
+            "currency" => self.currency.clone(),
+            "decimal_places" => self.decimal_places.to_string(),
+            "tax_rate" => self.tax_rate.to_string(),
+            "low_stock_threshold" => self.low_stock_threshold.to_string(),
+            "receipt_footer" => self.receipt_footer.clone(),
+            "void_window_minutes" => self.void_window_minutes.to_string(),
+            _ => return None,
+        })
+    }
+
+    /// Parses and validates `value` for `key`; nothing changes when it is rejected.
+    pub fn set(&mut self, key: &str, value: &str) -> Result<()> {
+        let mut updated = self.clone();
+        let value = value.trim();
+        match key {
+            "store_name" => updated.store_name = value.to_string(),
+            "store_address" => updated.store_address = value.to_string(),
+            "currency" => updated.currency = currency::normalize(value)?,
+            "decimal_places" => updated.decimal_places = parse_number(key, value)?,
+            "tax_rate" => updated.tax_rate = parse_number(key, value)?,
+            "low_stock_threshold" => updated.low_stock_threshold = parse_number(key, value)?,
+            "receipt_footer" => updated.receipt_footer = value.to_string(),
+            "void_window_minutes" => updated.void_window_minutes = parse_number(key, value)?,
+            _ => return Err(Error::validation(format!("Unknown setting '{}'", key))),
+        }
+        updated.validate()?;
+        *self = updated;
+        Ok(())
+    }
+
+    pub fn validate(&self) -> Result<()> {
+        if self.store_name.trim().is_empty() {
+            return Err(Error::validation("Store name cannot be empty"));
+        }
+        currency::normalize(&self.currency)?;
+        if self.decimal_places > MAX_DECIMAL_PLACES {
+            return Err(Error::validation(format!("Decimal places must be between 0 and {}", MAX_DECIMAL_PLACES)));
+        }
+        if !self.tax_rate.is_finite() || !(0.0..100.0).contains(&self.tax_rate) {
+            return Err(Error::validation("Tax rate must be at least 0% and below 100%"));
+        }
+        if !self.low_stock_threshold.is_finite() || self.low_stock_threshold < 0.0 {
+            return Err(Error::validation("Low-stock threshold cannot be negative"));
+        }
+        self.accounts.validate()?;
+        self.loyalty.validate()
+    }
+}
+
+fn parse_number<T: std::str::FromStr>(key: &str, value: &str) -> Result<T> {
+    value
+        .parse()
+        .map_err(|_| Error::validation(format!("'{}' is not a valid number for {}", value, key)))
+}
+
+/// The settings file: `RUSTOCK_SETTINGS`, or `rustock.toml` in the working directory.
+pub fn file_path() -> PathBuf {
+    env_or("RUSTOCK_SETTINGS", PathBuf::from(DEFAULT_PATH))
+}
+
+/// The settings in effect. Until `install` is called they are read from the
+/// settings file and environment.
+pub fn current() -> Settings {
+    if let Some(settings) = CURRENT.read().unwrap_or_else(|e| e.into_inner()).as_ref() {
+        return settings.clone();
+    }
+    let settings = Settings::load().0;
+    install(settings.clone());
+    settings
+}
+
+/// Makes `settings` the ones in effect for the rest of the process.
+pub fn install(settings: Settings) {
+    *CURRENT.write().unwrap_or_else(|e| e.into_inner()) = Some(settings);
+}
@@ -0,0 +1,90 @@
+//! The store's timezone, used to display timestamps and to decide where days begin.
+//!
+//! Every table stores timestamps as Unix seconds. Reports convert them to local
+//! dates in the zone named by `RUSTOCK_TIMEZONE` (an IANA name such as
+//! `Europe/Berlin`), so "today" and a report's day boundaries follow the store's
+//! clock rather than UTC. The zone defaults to UTC.
+
+use crate::config::env_or;
+use crate::error::{Error, Result};
+use chrono::{DateTime, Days, NaiveDate, NaiveDateTime, TimeZone, Utc};
+pub use chrono_tz::Tz;
+
+/// The configured store timezone; UTC when unset or not a known zone.
+pub fn store_timezone() -> Tz {
+    env_or("RUSTOCK_TIMEZONE", Tz::UTC)
+}
+
+pub fn parse(name: &str) -> Result<Tz> {
+    name.trim()
+        .parse()
+        .map_err(|_| Error::validation(format!("Unknown timezone '{}', expected a name like Europe/London", name.trim())))
+}
+
+/// The local calendar date of `timestamp` in `tz`.
+pub fn local_date(timestamp: i64, tz: Tz) -> NaiveDate {
+    DateTime::<Utc>::from_timestamp(timestamp, 0)
+        .unwrap_or_default()
+        .with_timezone(&tz)
+        .date_naive()
+}
+
+/// The first instant of `date` in `tz`. Where a clock change skips midnight the
+/// day starts at the first local time that exists.
+pub fn start_of_day(date: NaiveDate, tz: Tz) -> i64 {
+    (0..24)
+        .filter_map(|hour| date.and_hms_opt(hour, 0, 0))
+        .find_map(|local| tz.from_local_datetime(&local).earliest())
+        .map_or_else(|| date.and_hms_opt(0, 0, 0).unwrap_or_default().and_utc().timestamp(), |dt| dt.timestamp())
+}
+
+/// The start of the day after the one containing `timestamp`, i.e. the exclusive
+/// end of that day.
+pub fn end_of_day(timestamp: i64, tz: Tz) -> i64 {
+    let date = local_date(timestamp, tz);
+    start_of_day(date.checked_add_days(Days::new(1)).unwrap_or(date), tz)
+}
+
+/// Days since 1970-01-01, numbering local dates so consecutive days differ by one.
+pub fn day_number(date: NaiveDate) -> i64 {
+    date.signed_duration_since(NaiveDate::default()).num_days()
+}
+
+/// Parses `YYYY-MM-DD` or `YYYY-MM-DD HH:MM` as a local time in `tz`.
+pub fn parse_local(input: &str, tz: Tz) -> Result<i64> {
+    let input = input.trim();
+    if let Ok(local) = NaiveDateTime::parse_from_str(input, "%Y-%m-%d %H:%M") {
+        return tz
+            .from_local_datetime(&local)
+            .earliest()
+            .map(|dt| dt.timestamp())
+            .ok_or_else(|| Error::validation(format!("{} does not exist in {} (clocks changed)", input, tz)));
+    }
+    if let Ok(date) = NaiveDate::parse_from_str(input, "%Y-%m-%d") {
+        return Ok(start_of_day(date, tz));
+    }
+    Err(Error::validation(format!("Invalid date '{}', expected YYYY-MM-DD [HH:MM]", input)))
+}
+
+/// Formats `timestamp` as a local time in `tz` with a chrono format string.
+pub fn format_in(timestamp: i64, tz: Tz, format: &str) -> String {
+    DateTime::<Utc>::from_timestamp(timestamp, 0)
+        .map(|dt| dt.with_timezone(&tz).format(format).to_string())
+        .unwrap_or_default()
+}
+
+/// `YYYY-MM-DD` in the store timezone.
+pub fn format_date(timestamp: i64) -> String {
+    format_in(timestamp, store_timezone(), "%Y-%m-%d")
+}
+
+/// `YYYY-MM-DD HH:MM` in the store timezone.
+pub fn format_datetime(timestamp: i64) -> String {
+    format_in(timestamp, store_timezone(), "%Y-%m-%d %H:%M")
+}
+
+/// The start of today in the store timezone.
+pub fn today_start() -> i64 {
+    let tz = store_timezone();
+    start_of_day(local_date(Utc::now().timestamp(), tz), tz)
+}
@@ -0,0 +1,89 @@
+use crate::error::{Error, Result};
+use crate::product::Product;
+use serde::{Deserialize, Serialize};
+
+/// Stock unit given to products that do not name one.
+pub const DEFAULT_UNIT: &str = "each";
+
+/// Units measured on a continuous scale; quantities in any other unit must be whole.
+const MEASURED_UNITS: &[&str] = &["kg", "g", "lb", "oz", "l", "litre", "liter", "ml", "m", "cm"];
+
+/// Stored quantities are rounded to this many decimal places to keep repeated
+/// fractional sales from drifting.
+const QUANTITY_DECIMALS: i32 = 6;
+
+/// An alternative unit a product can be bought or sold in, such as a case of 24.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct ProductUnit {
+    pub product_id: String,
+    pub unit: String,
+    /// How many of the product's stock unit one `unit` holds.
+    pub factor: f64,
+}
+
+impl ProductUnit {
+    pub fn new(product_id: String, unit: &str, factor: f64) -> Self {
+        Self {
+            product_id,
+            unit: normalize(unit),
+            factor,
+        }
+    }
+
+    pub fn validate(&self, product: &Product) -> Result<()> {
+        if self.unit.is_empty() {
+            return Err(Error::validation("Unit name cannot be empty"));
+        }
+        if self.unit == product.unit {
+            return Err(Error::validation(format!("'{}' is already the stock unit", self.unit)));
+        }
+        if !self.factor.is_finite() || self.factor <= 0.0 {
+            return Err(Error::validation("Conversion factor must be positive"));
+        }
+        Ok(())
+    }
+}
+
+pub fn normalize(unit: &str) -> String {
+    unit.trim().to_lowercase()
+}
+
+pub fn allows_fraction(unit: &str) -> bool {
+    MEASURED_UNITS.contains(&normalize(unit).as_str())
+}
+
+pub fn round_quantity(quantity: f64) -> f64 {
+    let scale = 10f64.powi(QUANTITY_DECIMALS);
+    (quantity * scale).round() / scale
+}
+
+/// Rejects fractional quantities of units that are counted rather than measured.
+pub fn check_quantity(unit: &str, quantity: f64) -> Result<()> {
+    if !quantity.is_finite() {
+        return Err(Error::validation("Quantity must be a number"));
+    }
+    if quantity.fract() != 0.0 && !allows_fraction(unit) {
+        return Err(Error::validation(format!("Quantities in '{}' must be whole numbers", unit)));
+    }
+    Ok(())
+}
+
+/// Converts `quantity` given in `unit` (the stock unit when `None`) into the product's stock unit.
+pub fn to_base(product: &Product, units: &[ProductUnit], unit: Option<&str>, quantity: f64) -> Result<f64> {
+    let unit = match unit.map(normalize) {
+        Some(unit) if unit != product.unit => unit,
+        _ => {
+            check_quantity(&product.unit, quantity)?;
+            return Ok(round_quantity(quantity));
+        }
+    };
+
+    let conversion = units
+        .iter()
+        .find(|u| u.unit == unit)
+        .ok_or_else(|| Error::validation(format!("'{}' is not sold in '{}'", product.name, unit)))?;
+    check_quantity(&unit, quantity)?;
+    let base = round_quantity(quantity * conversion.factor);
+    check_quantity(&product.unit, base)?;
+    Ok(base)
+}
@@ -0,0 +1,93 @@
+//! Voiding the most recent sale, purchase or stock adjustment.
+//!
+//! A mistake is undone by writing a compensating record, never by deleting
+//! history: a voided sale has its remaining items returned and refunded, a
+//! voided purchase is offset by a negative purchase that takes the stock back
+//! out, and a voided adjustment by the opposite adjustment. Only the most recent
+//! operation of each kind can be voided, within the store's void window, and
+//! every void records the manager who approved it.
+
+use crate::error::{Error, Result};
+
+/// Minutes after an operation during which it can still be voided.
+pub const DEFAULT_WINDOW_MINUTES: u32 = 30;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OperationKind {
+    Sale,
+    Purchase,
+    Adjustment,
+}
+
+impl OperationKind {
+    pub const ALL: [OperationKind; 3] = [OperationKind::Sale, OperationKind::Purchase, OperationKind::Adjustment];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            OperationKind::Sale => "sale",
+            OperationKind::Purchase => "purchase",
+            OperationKind::Adjustment => "adjustment",
+        }
+    }
+
+    pub fn parse(value: &str) -> Option<Self> {
+        match value {
+            "sale" => Some(OperationKind::Sale),
+            "purchase" => Some(OperationKind::Purchase),
+            "adjustment" => Some(OperationKind::Adjustment),
+            _ => None,
+        }
+    }
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            OperationKind::Sale => "Sale",
+            OperationKind::Purchase => "Purchase",
+            OperationKind::Adjustment => "Stock adjustment",
+        }
+    }
+}
+
+/// A sale, purchase or adjustment that could be voided.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Operation {
+    pub kind: OperationKind,
+    /// Sale number, purchase ID or adjustment ID.
+    pub reference: String,
+    pub description: String,
+    pub timestamp: i64,
+}
+
+impl Operation {
+    /// Whether the operation is still inside a window of `minutes` at `now`; a
+    /// window of 0 admits nothing.
+    pub fn within_window(&self, minutes: u32, now: i64) -> bool {
+        minutes > 0 && now - self.timestamp <= i64::from(minutes) * 60
+    }
+
+    pub fn ensure_voidable(&self, minutes: u32, now: i64) -> Result<()> {
+        if !self.within_window(minutes, now) {
+            return Err(Error::conflict(format!(
+                "{} is more than {} minutes old and can no longer be voided",
+                self.description, minutes
+            )));
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct VoidRecord {
+    pub id: i64,
+    pub kind: OperationKind,
+    pub reference: String,
+    pub description: String,
+    /// ID of the compensating purchase or adjustment; sales are compensated by returns.
+    pub compensation: Option<String>,
+    /// Refunded to the customer for a voided sale.
+    pub refund: f64,
+    /// Manager who approved the void.
+    pub manager_id: String,
+    pub reason: String,
+    pub voided_at: i64,
+}
@@ -0,0 +1,193 @@
+mod common;
+
+use rustock::analytics::{self, AbcClass, DateRange, ProductActivity};
+use rustock::currency::{self, ExchangeRate};
+use rustock::db::Database;
+use rustock::export::{self, Cell, ExportFormat, Table};
+use rustock::product::Product;
+use rustock::purchase::Purchase;
+use rustock::repository::Repository;
+use rustock::sale::{Sale, SaleItem};
+
+const DAY: i64 = 86_400;
+
+fn activity(name: &str, revenue: f64) -> ProductActivity {
+    ProductActivity {
+        product_id: name.to_lowercase(),
+        name: name.to_string(),
+        revenue,
+        ..ProductActivity::default()
+    }
+}
+
+#[test]
+fn abc_classes_follow_cumulative_revenue() {
+    let products = [
+        activity("Small", 50.0),
+        activity("Top", 700.0),
+        activity("Middle", 150.0),
+        activity("Minor", 100.0),
+        activity("Unsold", 0.0),
+    ];
+
+    let rows = analytics::abc_classification(&products);
+    let classes: Vec<(&str, AbcClass)> = rows.iter().map(|r| (r.name.as_str(), r.class)).collect();
+
+    assert_eq!(
+        classes,
+        vec![
+            ("Top", AbcClass::A),
+            ("Middle", AbcClass::A),
+            ("Minor", AbcClass::B),
+            ("Small", AbcClass::C),
+            ("Unsold", AbcClass::C),
+        ]
+    );
+    assert!((rows[1].cumulative_share - 0.85).abs() < 1e-9);
+}
+
+#[test]
+fn turnover_rebuilds_stock_held_during_the_range() {
+    let now = 100 * DAY;
+    let range = DateRange::new(Some(now - 30 * DAY), Some(now - 10 * DAY));
+    let product = ProductActivity {
+        name: "Coffee".to_string(),
+        quantity: 20.0,
+        units_sold: 40.0,
+        units_purchased: 30.0,
+        units_sold_after: 10.0,
+        units_purchased_after: 0.0,
+        ..ProductActivity::default()
+    };
+
+    let row = &analytics::turnover(std::slice::from_ref(&product), &range, now)[0];
+
+    // 30 on hand at the end of the range, 40 at its start
+    assert_eq!(product.closing_stock(), 30.0);
+    assert_eq!(product.opening_stock(), 40.0);
+    assert_eq!(row.average_stock, 35.0);
+    assert!((row.turnover.unwrap() - 40.0 / 35.0).abs() < 1e-9);
+    // 2 units a day over the 20-day range
+    assert!((row.days_of_supply.unwrap() - 10.0).abs() < 1e-9);
+}
+
+#[test]
+fn dead_stock_lists_unsold_items_by_tied_up_value() {
+    let now = 365 * DAY;
+    let recent = ProductActivity { quantity: 5.0, last_sale_at: Some(now - 10 * DAY), ..activity("Recent", 0.0) };
+    let stale = ProductActivity {
+        quantity: 4.0,
+        unit_cost: Some(2.5),
+        last_sale_at: Some(now - 200 * DAY),
+        ..activity("Stale", 0.0)
+    };
+    let never_sold = ProductActivity { quantity: 3.0, price: 9.0, ..activity("Never", 0.0) };
+    let sold_out = ProductActivity { quantity: 0.0, ..activity("Empty", 0.0) };
+
+    let rows = analytics::dead_stock(&[recent, stale, never_sold, sold_out], now, 90);
+
+    let names: Vec<&str> = rows.iter().map(|r| r.name.as_str()).collect();
+    assert_eq!(names, vec!["Never", "Stale"]);
+    assert_eq!(rows[0].tied_up_value, 27.0);
+    assert_eq!(rows[1].tied_up_value, 10.0);
+}
+
+#[test]
+fn product_activity_respects_the_date_range() {
+    let mut db = Database::open(":memory:", None).unwrap();
+    let product = Product::new("Tea".to_string(), String::new(), 4.0, 10.0);
+    db.add_product(&product).unwrap();
+    let mut old_purchase = Purchase::new(product.id.clone(), 10.0, 1.0);
+    old_purchase.purchase_date -= 60 * DAY;
+    db.record_purchase(&old_purchase).unwrap();
+    db.record_purchase(&Purchase::new(product.id.clone(), 10.0, 2.0)).unwrap();
+    db.record_sale(&Sale::new(vec![SaleItem {
+        product_id: product.id.clone(),
+        quantity: 3.0,
+        unit: None,
+        unit_price: 4.0,
+        total_price: 12.0,
+    }]))
+    .unwrap();
+
+    let all_time = &db.product_activity(&DateRange::default()).unwrap()[0];
+    assert_eq!(all_time.units_sold, 3.0);
+    assert_eq!(all_time.revenue, 12.0);
+    assert_eq!(all_time.units_purchased, 20.0);
+    assert_eq!(all_time.unit_cost, Some(1.5));
+    assert!(all_time.last_sale_at.is_some());
+
+    let before_today = DateRange::new(None, Some(old_purchase.purchase_date + DAY));
+    let earlier = &db.product_activity(&before_today).unwrap()[0];
+    assert_eq!(earlier.units_sold, 0.0);
+    assert_eq!(earlier.units_purchased, 10.0);
+    assert_eq!(earlier.units_sold_after, 3.0);
+    assert_eq!(earlier.units_purchased_after, 10.0);
+    assert!(earlier.last_sale_at.is_none());
+}
+
+#[test]
+fn returns_are_netted_out_of_sales_and_activity() {
+    let (mut db, product) = common::store_with("Tea", 4.0, 10.0);
+    let mut sale = common::sale_of(&product, 3.0);
+    sale.timestamp -= 60 * DAY;
+    let number = db.record_sale(&sale).unwrap();
+    assert_eq!(db.record_return(number, &product.id, 1.0, "Damaged").unwrap(), 4.0);
+
+    assert_eq!(db.get_all_sales().unwrap(), vec![("Tea".to_string(), 2.0, 8.0, sale.timestamp)]);
+    assert_eq!(db.sales_page(0, 10).unwrap(), db.get_all_sales().unwrap());
+    let all_time = &db.product_activity(&DateRange::default()).unwrap()[0];
+    assert_eq!((all_time.units_sold, all_time.revenue), (2.0, 8.0));
+    assert_eq!(all_time.last_sale_at, Some(sale.timestamp));
+
+    // The return came after the range, so the stock held then is rebuilt from it
+    let at_the_time = &db.product_activity(&DateRange::new(None, Some(sale.timestamp + DAY))).unwrap()[0];
+    assert_eq!((at_the_time.units_sold, at_the_time.revenue), (3.0, 12.0));
+    assert_eq!(at_the_time.units_sold_after, -1.0);
+    assert_eq!(at_the_time.closing_stock(), 7.0);
+    assert_eq!(at_the_time.opening_stock(), 10.0);
+
+    db.record_return(number, &product.id, 2.0, "Damaged").unwrap();
+    assert!(db.get_all_sales().unwrap().is_empty());
+    assert_eq!(db.sales_count().unwrap(), 0);
+    let all_time = &db.product_activity(&DateRange::default()).unwrap()[0];
+    assert_eq!((all_time.units_sold, all_time.revenue), (0.0, 0.0));
+    assert!(all_time.last_sale_at.is_none());
+}
+
+#[test]
+fn costs_and_profit_are_in_the_base_currency() {
+    let mut db = Database::open(":memory:", None).unwrap();
+    let product = Product::new("Cheddar".to_string(), String::new(), 10.0, 0.0);
+    db.add_product(&product).unwrap();
+    db.set_exchange_rate(&ExchangeRate::new("GBP", 1.25, 0)).unwrap();
+    db.record_purchase(&Purchase::new(product.id.clone(), 4.0, 4.0).in_currency("GBP")).unwrap();
+    db.record_purchase(&Purchase::new(product.id.clone(), 4.0, 6.0)).unwrap();
+
+    let activity = db.product_activity(&DateRange::default()).unwrap();
+    // (4 x £4 at 1.25 + 4 x $6) / 8 units
+    assert_eq!(activity[0].unit_cost, Some(5.5));
+
+    let sold = ProductActivity { units_sold: 2.0, revenue: 20.0, ..activity[0].clone() };
+    assert_eq!(analytics::abc_classification(&[sold])[0].gross_profit, Some(9.0));
+    assert_eq!(currency::format_amount(-9.5, "GBP"), "-£9.50");
+    assert_eq!(currency::format_amount(1234.5, "CHF"), "CHF 1234.50");
+}
+
+#[test]
+fn tables_export_to_csv_and_xlsx() {
+    let mut table = Table::new("ABC analysis all time", &["Product", "Revenue"]);
+    table.push(vec![Cell::text("Tea, \"green\""), Cell::Number(12.5)]);
+    table.push(vec![Cell::text("Cups"), Cell::Empty]);
+
+    assert_eq!(table.to_csv(), "Product,Revenue\n\"Tea, \"\"green\"\"\",12.5\nCups,\n");
+
+    let dir = std::env::temp_dir().join(format!("rustock-test-export-{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    let path = export::save(&table, &dir, ExportFormat::Xlsx).unwrap();
+
+    let name = path.file_name().unwrap().to_string_lossy().into_owned();
+    assert!(name.starts_with("abc-analysis-all-time-") && name.ends_with(".xlsx"));
+    // xlsx files are zip archives
+    assert!(std::fs::read(&path).unwrap().starts_with(b"PK"));
+}
@@ -0,0 +1,104 @@
+use rustock::attachment::{self, AttachmentConfig, OwnerKind, StorageKind};
+use rustock::db::Database;
+use rustock::error::Error;
+use rustock::product::Product;
+use rustock::purchase::Purchase;
+use rustock::repository::Repository;
+use std::path::PathBuf;
+
+fn sqlite() -> Database {
+    Database::open(":memory:", None).expect("open in-memory SQLite database")
+}
+
+fn product(db: &Database, name: &str) -> Product {
+    let product = Product::new(name.to_string(), String::new(), 2.0, 0.0);
+    db.add_product(&product).unwrap();
+    product
+}
+
+fn file_storage(name: &str) -> AttachmentConfig {
+    let dir: PathBuf = std::env::temp_dir().join(format!("rustock-attachments-{}-{}", name, std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    AttachmentConfig { storage: StorageKind::Files, dir, ..AttachmentConfig::default() }
+}
+
+fn attachment_count(db: &Database, owner: &str) -> usize {
+    db.get_attachments(OwnerKind::Product, owner).unwrap().len()
+}
+
+#[test]
+fn identical_content_is_stored_once_and_verified() {
+    let mut db = sqlite();
+    let config = AttachmentConfig::default();
+    let kettle = product(&db, "Kettle");
+    let toaster = product(&db, "Toaster");
+    let spec = b"%PDF-1.4 kettle specification".to_vec();
+
+    let first = db.add_attachment(OwnerKind::Product, &kettle.id, "/tmp/docs/spec.pdf", &spec, &config).unwrap();
+    assert_eq!(first.file_name, "spec.pdf");
+    assert_eq!(first.content_type, "application/pdf");
+    assert_eq!(first.hash, attachment::content_hash(&spec));
+    let shared = db.add_attachment(OwnerKind::Product, &toaster.id, "range.pdf", &spec, &config).unwrap();
+    assert_eq!(shared.hash, first.hash);
+
+    // The same content twice on one record is refused, whatever it is called
+    assert!(matches!(
+        db.add_attachment(OwnerKind::Product, &kettle.id, "copy.pdf", &spec, &config),
+        Err(Error::Conflict(_))
+    ));
+    assert!(matches!(
+        db.add_attachment(OwnerKind::Product, "missing", "spec.pdf", &spec, &config),
+        Err(Error::NotFound { .. })
+    ));
+
+    db.remove_attachment(&first.id).unwrap();
+    assert_eq!(db.attachment_data(&shared.id).unwrap(), spec);
+    assert_eq!(attachment_count(&db, &kettle.id), 0);
+}
+
+#[test]
+fn size_limits_are_enforced() {
+    let mut db = sqlite();
+    let lamp = product(&db, "Lamp");
+    let config = AttachmentConfig { max_bytes: 16, ..AttachmentConfig::default() };
+
+    assert!(matches!(
+        db.add_attachment(OwnerKind::Product, &lamp.id, "photo.png", &[7u8; 17], &config),
+        Err(Error::Validation(_))
+    ));
+    assert!(matches!(
+        db.add_attachment(OwnerKind::Product, &lamp.id, "empty.txt", &[], &config),
+        Err(Error::Validation(_))
+    ));
+    let photo = db.add_attachment(OwnerKind::Product, &lamp.id, "photo.png", &[7u8; 16], &config).unwrap();
+    assert!(photo.is_image());
+}
+
+#[test]
+fn file_storage_writes_content_and_cleans_up() {
+    let mut db = sqlite();
+    let config = file_storage("files");
+    let chair = product(&db, "Chair");
+    let purchase = Purchase::new(chair.id.clone(), 4.0, 30.0);
+    db.record_purchase(&purchase).unwrap();
+    let invoice = b"Invoice 1042: 4 chairs".to_vec();
+
+    let attached = db.add_attachment(OwnerKind::Purchase, &purchase.id, "invoice-1042.txt", &invoice, &config).unwrap();
+    let path = config.path_for(&attached.hash);
+    assert_eq!(std::fs::read(&path).unwrap(), invoice);
+    assert_eq!(db.attachment_data(&attached.id).unwrap(), invoice);
+
+    // A file changed behind the database's back is reported, not returned
+    std::fs::write(&path, b"tampered").unwrap();
+    assert!(matches!(db.attachment_data(&attached.id), Err(Error::Io(_))));
+
+    // Deleting a product removes its attachments and any content left unreferenced
+    let photo = db.add_attachment(OwnerKind::Product, &product(&db, "Stool").id, "stool.jpg", b"jpeg", &config).unwrap();
+    db.delete_product(&photo.owner_id).unwrap();
+    assert!(db.get_attachment(&photo.id).unwrap().is_none());
+    assert!(!config.path_for(&photo.hash).exists());
+
+    db.remove_attachment(&attached.id).unwrap();
+    assert!(!path.exists());
+    std::fs::remove_dir_all(&config.dir).unwrap();
+}
@@ -0,0 +1,100 @@
+mod common;
+
+use rustock::backup;
+use rustock::db::Database;
+use rustock::product::Product;
+use rustock::repository::Repository;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+fn product_names(db: &Database) -> Vec<String> {
+    let mut names: Vec<String> = db.get_all_products().unwrap().into_iter().map(|p| p.name).collect();
+    names.sort();
+    names
+}
+
+fn leftover_scratch_files(dir: &Path) -> Vec<PathBuf> {
+    fs::read_dir(dir)
+        .unwrap()
+        .map(|entry| entry.unwrap().path())
+        .filter(|path| path.to_string_lossy().ends_with(".tmp"))
+        .collect()
+}
+
+#[test]
+fn backups_round_trip_with_and_without_compression() {
+    let dir = common::scratch_dir("backup-round-trip");
+    let mut db = common::sqlite();
+    db.add_product(&Product::new("Kettle".to_string(), String::new(), 50.0, 4.0)).unwrap();
+
+    let plain = backup::create_backup(&db, &dir, false).unwrap();
+    let compressed = backup::create_backup(&db, &dir, true).unwrap();
+    assert_eq!(backup::list_backups(&dir).unwrap().len(), 2);
+
+    for snapshot in [plain, compressed] {
+        db.add_product(&Product::new("Toaster".to_string(), String::new(), 30.0, 1.0)).unwrap();
+        assert_eq!(product_names(&db), vec!["Kettle", "Toaster"]);
+
+        backup::restore_backup(&mut db, &snapshot).unwrap();
+        assert_eq!(product_names(&db), vec!["Kettle"]);
+    }
+    assert!(leftover_scratch_files(&dir).is_empty());
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn a_failed_restore_cleans_up_and_keeps_the_data() {
+    let dir = common::scratch_dir("backup-corrupt");
+    let corrupt = dir.join("rustock-20240101-000000.db.gz");
+    fs::write(&corrupt, b"not gzip data").unwrap();
+    let mut db = common::sqlite();
+    db.add_product(&Product::new("Kettle".to_string(), String::new(), 50.0, 4.0)).unwrap();
+
+    assert!(backup::restore_backup(&mut db, &corrupt).is_err());
+    assert!(backup::restore_backup(&mut db, &dir.join("missing.db")).is_err());
+
+    assert_eq!(product_names(&db), vec!["Kettle"]);
+    assert!(leftover_scratch_files(&dir).is_empty());
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn replacing_the_database_file_leaves_no_stale_write_ahead_log() {
+    let dir = common::scratch_dir("replace-file");
+    let path = dir.join("rustock.db");
+    let replacement = dir.join("rustock.db.enc.tmp");
+    let db = Database::open(&path, None).unwrap();
+    db.add_product(&Product::new("Kettle".to_string(), String::new(), 50.0, 4.0)).unwrap();
+    db.backup_to(&replacement).unwrap();
+    {
+        let copy = Database::open(&replacement, None).unwrap();
+        copy.add_product(&Product::new("Toaster".to_string(), String::new(), 30.0, 1.0)).unwrap();
+    }
+    // Another terminal keeps the log alive past this connection's close
+    let other = rusqlite::Connection::open(&path).unwrap();
+    other.query_row("SELECT COUNT(*) FROM products", [], |row| row.get::<_, i64>(0)).unwrap();
+    db.add_product(&Product::new("Grill".to_string(), String::new(), 80.0, 2.0)).unwrap();
+    assert!(dir.join("rustock.db-wal").exists());
+
+    db.replace_with(&replacement).unwrap();
+
+    assert!(!replacement.exists());
+    assert!(!dir.join("rustock.db-wal").exists());
+    assert!(!dir.join("rustock.db-shm").exists());
+    drop(other);
+    let reopened = Database::open(&path, None).unwrap();
+    assert_eq!(product_names(&reopened), vec!["Kettle", "Toaster"]);
+    drop(reopened);
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn an_in_memory_database_cannot_be_replaced() {
+    let dir = common::scratch_dir("replace-memory");
+    let replacement = dir.join("rustock.db.enc.tmp");
+    fs::write(&replacement, b"").unwrap();
+
+    assert!(common::sqlite().replace_with(&replacement).is_err());
+    assert!(replacement.exists());
+    fs::remove_dir_all(&dir).unwrap();
+}
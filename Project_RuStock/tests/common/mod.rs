@@ -0,0 +1,44 @@
+//! Fixtures shared by the integration tests. Each test crate uses only some of them.
+#![allow(dead_code)]
+
+use rustock::db::Database;
+use rustock::product::Product;
+use rustock::repository::Repository;
+use rustock::sale::{Sale, SaleItem};
+use std::path::PathBuf;
+
+pub fn sqlite() -> Database {
+    Database::open(":memory:", None).expect("open in-memory SQLite database")
+}
+
+pub fn stocked_product<R: Repository>(repo: &R, name: &str, price: f64, quantity: f64) -> Product {
+    let product = Product::new(name.to_string(), String::new(), price, quantity);
+    repo.add_product(&product).unwrap();
+    product
+}
+
+/// A fresh in-memory store holding one product.
+pub fn store_with(name: &str, price: f64, quantity: f64) -> (Database, Product) {
+    let db = sqlite();
+    let product = stocked_product(&db, name, price, quantity);
+    (db, product)
+}
+
+/// A sale of `quantity` base units of `product` at its current price.
+pub fn sale_of(product: &Product, quantity: f64) -> Sale {
+    Sale::new(vec![SaleItem {
+        product_id: product.id.clone(),
+        quantity,
+        unit: None,
+        unit_price: product.price,
+        total_price: product.price * quantity,
+    }])
+}
+
+/// An empty directory under the system temp dir, unique to this test run.
+pub fn scratch_dir(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("rustock-test-{}-{}", name, std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(&dir).unwrap();
+    dir
+}
@@ -0,0 +1,48 @@
+mod common;
+
+use common::sale_of;
+use rustock::db::Database;
+use rustock::error::Error;
+use rustock::product::Product;
+use rustock::repository::Repository;
+use std::path::PathBuf;
+
+fn shared_db_path(name: &str) -> PathBuf {
+    common::scratch_dir(name).join("rustock.db")
+}
+
+#[test]
+fn file_databases_use_wal() {
+    let path = shared_db_path("wal");
+    let _db = Database::open(&path, None).unwrap();
+
+    let conn = rusqlite::Connection::open(&path).unwrap();
+    let mode: String = conn.query_row("PRAGMA journal_mode", [], |row| row.get(0)).unwrap();
+    assert_eq!(mode, "wal");
+}
+
+#[test]
+fn terminals_cannot_oversell_or_clobber_each_other() {
+    let path = shared_db_path("terminals");
+    let mut first = Database::open(&path, None).unwrap();
+    let mut second = Database::open(&path, None).unwrap();
+    let product = Product::new("Last Units".to_string(), String::new(), 2.0, 3.0);
+    first.add_product(&product).unwrap();
+
+    // Both clerks saw 3 in stock; only the first sale of 2 can succeed
+    first.record_sale(&sale_of(&product, 2.0)).unwrap();
+    assert!(matches!(
+        second.record_sale(&sale_of(&product, 2.0)),
+        Err(Error::InsufficientStock { available, .. }) if available == 1.0
+    ));
+
+    // An edit screen opened before the sale still holds quantity 3
+    let mut stale = second.get_product(&product.id).unwrap().unwrap();
+    first.record_sale(&sale_of(&product, 1.0)).unwrap();
+    stale.update(Some("Renamed".to_string()), None, None, None);
+
+    assert!(matches!(second.update_product(&stale), Err(Error::Conflict(_))));
+    let stored = first.get_product(&product.id).unwrap().unwrap();
+    assert_eq!(stored.quantity, 0.0);
+    assert_eq!(stored.name, "Last Units");
+}
@@ -0,0 +1,141 @@
+use rustock::db::Database;
+use rustock::forecast::{self, DemandSeries, Method, PlanSettings, SECONDS_PER_DAY};
+use rustock::product::Product;
+use rustock::repository::Repository;
+use rustock::sale::{Sale, SaleItem};
+use rustock::timezone::{self, Tz};
+
+fn series(name: &str, on_hand: f64, daily: Vec<f64>) -> DemandSeries {
+    DemandSeries {
+        product_id: name.to_lowercase(),
+        name: name.to_string(),
+        unit: "each".to_string(),
+        on_hand,
+        first_day: 0,
+        daily,
+    }
+}
+
+/// Eight weeks of busy weekends: 10 a day on two days of the week, 2 on the rest.
+fn weekly_pattern() -> Vec<f64> {
+    (0..56).map(|day| if day % 7 >= 5 { 10.0 } else { 2.0 }).collect()
+}
+
+#[test]
+fn every_method_forecasts_steady_demand_exactly() {
+    let steady = series("Bread", 0.0, vec![4.0; 30]);
+
+    for method in Method::CANDIDATES {
+        assert!(method.forecast(&steady.daily, 0, 3).iter().all(|d| (d - 4.0).abs() < 1e-9));
+        let metrics = forecast::backtest(&steady, method).unwrap();
+        assert!(metrics.mae < 1e-9 && metrics.rmse < 1e-9);
+        assert_eq!(metrics.mape, Some(0.0));
+    }
+    assert!(forecast::backtest(&series("New", 0.0, vec![1.0; 7]), Method::CANDIDATES[0]).is_none());
+}
+
+#[test]
+fn seasonal_method_wins_on_weekly_patterns() {
+    let weekends = series("Beer", 0.0, weekly_pattern());
+
+    let (method, metrics) = forecast::best_method(&weekends);
+    assert_eq!(method, Method::Seasonal { alpha: 0.3 });
+    assert!(metrics.unwrap().mae < 1e-9);
+
+    // Day 56 starts a new week: five weekdays, then the weekend
+    let next_week = method.forecast(&weekends.daily, weekends.first_day, 7);
+    let expected = [2.0, 2.0, 2.0, 2.0, 2.0, 10.0, 10.0];
+    assert!(next_week.iter().zip(expected).all(|(f, e)| (f - e).abs() < 1e-9));
+}
+
+#[test]
+fn purchase_plan_orders_up_to_lead_time_and_cover() {
+    let settings = PlanSettings { lead_time_days: 7, review_days: 14 };
+    let plan = forecast::purchase_plan(
+        &[
+            series("Low", 10.0, vec![2.0; 28]),
+            series("Plenty", 100.0, vec![2.0; 28]),
+            series("Unsold", 0.0, vec![0.0; 28]),
+        ],
+        &settings,
+    );
+
+    assert_eq!(plan.len(), 2);
+    let low = &plan[0];
+    assert_eq!(low.name, "Low");
+    assert!((low.lead_time_demand - 14.0).abs() < 1e-9);
+    assert!(low.safety_stock.abs() < 1e-9);
+    // 21 days of demand less the 10 on hand
+    assert_eq!(low.suggested_order, 32.0);
+    assert_eq!(plan[1].suggested_order, 0.0);
+
+    let table = forecast::plan_table(&plan, &settings);
+    assert_eq!(table.rows.len(), 2);
+    assert_eq!(table.headers.last().unwrap(), "Suggested Order");
+}
+
+#[test]
+fn daily_demand_buckets_sales_by_day() {
+    let mut db = Database::open(":memory:", None).unwrap();
+    let product = Product::new("Coffee".to_string(), String::new(), 3.0, 20.0);
+    db.add_product(&product).unwrap();
+    for quantity in [2.0, 3.0] {
+        db.record_sale(&Sale::new(vec![SaleItem {
+            product_id: product.id.clone(),
+            quantity,
+            unit: None,
+            unit_price: 3.0,
+            total_price: 3.0 * quantity,
+        }]))
+        .unwrap();
+    }
+
+    // Two days from now, the last three full days are yesterday, today and tomorrow
+    let now = chrono::Utc::now().timestamp() + 2 * SECONDS_PER_DAY;
+    let demand = db.daily_demand(3, now, Tz::UTC).unwrap();
+
+    assert_eq!(demand.len(), 1);
+    assert_eq!(demand[0].daily, vec![0.0, 5.0, 0.0]);
+    assert_eq!(demand[0].on_hand, 15.0);
+}
+
+#[test]
+fn daily_demand_uses_the_store_timezone_for_day_boundaries() {
+    let mut db = Database::open(":memory:", None).unwrap();
+    let product = Product::new("Bagel".to_string(), String::new(), 2.0, 20.0);
+    db.add_product(&product).unwrap();
+    let mut sale = Sale::new(vec![SaleItem {
+        product_id: product.id.clone(),
+        quantity: 4.0,
+        unit: None,
+        unit_price: 2.0,
+        total_price: 8.0,
+    }]);
+    // 02:00 UTC on 10 March is still the evening of 9 March in New York
+    sale.timestamp = timezone::parse_local("2025-03-10 02:00", Tz::UTC).unwrap();
+    db.record_sale(&sale).unwrap();
+
+    let now = timezone::parse_local("2025-03-11 12:00", Tz::UTC).unwrap();
+    assert_eq!(db.daily_demand(3, now, Tz::UTC).unwrap()[0].daily, vec![0.0, 0.0, 4.0]);
+    assert_eq!(db.daily_demand(3, now, Tz::America__New_York).unwrap()[0].daily, vec![0.0, 4.0, 0.0]);
+}
+
+#[test]
+fn returned_units_come_off_the_day_they_were_sold() {
+    let mut db = Database::open(":memory:", None).unwrap();
+    let product = Product::new("Bagel".to_string(), String::new(), 2.0, 20.0);
+    db.add_product(&product).unwrap();
+    let mut sale = Sale::new(vec![SaleItem {
+        product_id: product.id.clone(),
+        quantity: 4.0,
+        unit: None,
+        unit_price: 2.0,
+        total_price: 8.0,
+    }]);
+    sale.timestamp -= 2 * SECONDS_PER_DAY;
+    let number = db.record_sale(&sale).unwrap();
+    db.record_return(number, &product.id, 3.0, "Stale").unwrap();
+
+    let now = chrono::Utc::now().timestamp();
+    assert_eq!(db.daily_demand(3, now, Tz::UTC).unwrap()[0].daily, vec![0.0, 1.0, 0.0]);
+}
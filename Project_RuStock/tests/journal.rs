@@ -0,0 +1,148 @@
+use rustock::analytics::DateRange;
+use rustock::db::Database;
+use rustock::error::Error;
+use rustock::journal::{self, AccountMap, JournalEntry, Posting, Transaction};
+use rustock::product::Product;
+use rustock::purchase::Purchase;
+use rustock::repository::Repository;
+use rustock::sale::{Sale, SaleItem};
+use rustock::void::OperationKind;
+
+/// A product with 10 units bought at 4.00 each.
+fn stocked(price: f64) -> (Database, Product) {
+    let mut db = Database::open(":memory:", None).unwrap();
+    let product = Product::new("Lamp".to_string(), String::new(), price, 0.0);
+    db.add_product(&product).unwrap();
+    db.record_purchase(&Purchase::new(product.id.clone(), 10.0, 4.0)).unwrap();
+    (db, product)
+}
+
+fn sell(db: &mut Database, product: &Product, quantity: f64) -> i64 {
+    db.record_sale(&Sale::new(vec![SaleItem {
+        product_id: product.id.clone(),
+        quantity,
+        unit: None,
+        unit_price: product.price,
+        total_price: product.price * quantity,
+    }]))
+    .unwrap()
+}
+
+fn postings(entry: &JournalEntry) -> Vec<(&str, f64)> {
+    entry.postings.iter().map(|p| (p.account.as_str(), p.amount)).collect()
+}
+
+#[test]
+fn sales_split_tax_and_post_cost_of_goods_sold() {
+    let (mut db, product) = stocked(11.0);
+    sell(&mut db, &product, 2.0);
+
+    let transactions = db.journal_transactions(&DateRange::default()).unwrap();
+    let entries = journal::journal(&transactions, &AccountMap::default(), 10.0);
+    assert_eq!(entries.len(), 2);
+    assert!(entries.iter().all(JournalEntry::is_balanced));
+
+    let purchase = entries.iter().find(|e| e.description.starts_with("Purchase")).unwrap();
+    assert_eq!(postings(purchase), vec![("Assets:Inventory", 40.0), ("Liabilities:Accounts Payable", -40.0)]);
+
+    let sale = entries.iter().find(|e| e.description.starts_with("Sale")).unwrap();
+    assert_eq!(
+        postings(sale),
+        vec![
+            ("Assets:Cash", 22.0),
+            ("Income:Sales", -20.0),
+            ("Liabilities:Sales Tax", -2.0),
+            ("Expenses:Cost of Goods Sold", 8.0),
+            ("Assets:Inventory", -8.0),
+        ]
+    );
+
+    let balances = journal::trial_balance(&entries);
+    assert!(balances.contains(&("Assets:Inventory".to_string(), 32.0)));
+}
+
+#[test]
+fn returns_restock_and_are_limited_to_what_was_sold() {
+    let (mut db, product) = stocked(10.0);
+    let sale_number = sell(&mut db, &product, 3.0);
+
+    assert_eq!(db.record_return(sale_number, &product.id, 2.0, "Faulty").unwrap(), 20.0);
+    assert_eq!(db.get_product(&product.id).unwrap().unwrap().quantity, 9.0);
+    assert_eq!(db.returned_quantity(sale_number, &product.id).unwrap(), 2.0);
+    assert!(matches!(db.record_return(sale_number, &product.id, 2.0, ""), Err(Error::Validation(_))));
+    assert!(matches!(db.record_return(sale_number + 1, &product.id, 1.0, ""), Err(Error::NotFound { .. })));
+
+    let transactions = db.journal_transactions(&DateRange::default()).unwrap();
+    let entries = journal::journal(&transactions, &AccountMap::default(), 0.0);
+    let refund = entries.iter().find(|e| e.description.starts_with("Return")).unwrap();
+    assert_eq!(
+        postings(refund),
+        vec![
+            ("Income:Sales Returns", 20.0),
+            ("Assets:Cash", -20.0),
+            ("Assets:Inventory", 8.0),
+            ("Expenses:Cost of Goods Sold", -8.0),
+        ]
+    );
+}
+
+#[test]
+fn stock_adjustments_are_recorded_and_valued_at_average_cost() {
+    let (mut db, product) = stocked(10.0);
+
+    assert_eq!(db.adjust_stock(&product.id, -1.5, "Broken in storage").unwrap(), 8.5);
+    assert!(matches!(db.adjust_stock(&product.id, -20.0, "Lost"), Err(Error::Validation(_))));
+    assert!(matches!(db.adjust_stock(&product.id, 1.0, " "), Err(Error::Validation(_))));
+    assert!(matches!(db.delete_product(&product.id), Err(Error::Conflict(_))));
+
+    let transactions = db.journal_transactions(&DateRange::default()).unwrap();
+    let accounts = AccountMap { adjustments: "Expenses:Shrinkage".to_string(), ..AccountMap::default() };
+    let entries = journal::journal(&transactions, &accounts, 0.0);
+    let adjustment = entries.iter().find(|e| e.description.starts_with("Stock adjustment")).unwrap();
+    assert_eq!(postings(adjustment), vec![("Assets:Inventory", -6.0), ("Expenses:Shrinkage", 6.0)]);
+}
+
+#[test]
+fn card_takings_and_exports() {
+    let transactions = [Transaction::Sale { sale_number: 7, timestamp: 0, total: 30.0, card: 12.5, cost: None }];
+    let entries = journal::journal(&transactions, &AccountMap::default(), 0.0);
+    assert_eq!(
+        entries[0].postings,
+        vec![
+            Posting { account: "Assets:Card Receivable".to_string(), amount: 12.5 },
+            Posting { account: "Assets:Cash".to_string(), amount: 17.5 },
+            Posting { account: "Income:Sales".to_string(), amount: -30.0 },
+        ]
+    );
+
+    let csv = journal::journal_table(&entries).to_csv();
+    assert_eq!(csv.lines().count(), 4);
+    assert!(csv.lines().nth(3).unwrap().ends_with(",Income:Sales,,30"));
+
+    let ledger = journal::to_ledger(&entries, "USD");
+    assert!(ledger.contains("* Sale #7\n"));
+    assert!(ledger.contains("    Assets:Card Receivable"));
+    assert!(ledger.contains("12.50 USD\n"));
+    assert!(ledger.contains("-30.00 USD\n"));
+
+    let invalid = AccountMap { cash: "Assets:  Cash".to_string(), ..AccountMap::default() };
+    assert!(invalid.validate().is_err());
+}
+
+#[test]
+fn sales_after_every_purchase_is_voided_carry_no_cost() {
+    let mut db = Database::open(":memory:", None).unwrap();
+    let product = Product::new("Lamp".to_string(), String::new(), 11.0, 5.0);
+    db.add_product(&product).unwrap();
+    db.record_purchase(&Purchase::new(product.id.clone(), 10.0, 4.0)).unwrap();
+    let admin = db.get_manager_by_username("admin").unwrap().unwrap();
+    let purchase = db.last_operation(OperationKind::Purchase).unwrap().unwrap();
+    db.void_operation(OperationKind::Purchase, &purchase.reference, &admin.id, "Never arrived", 30).unwrap();
+    sell(&mut db, &product, 1.0);
+
+    let transactions = db.journal_transactions(&DateRange::default()).unwrap();
+    let sale = transactions.iter().find(|t| matches!(t, Transaction::Sale { .. })).unwrap();
+    assert!(matches!(sale, Transaction::Sale { cost: None, .. }));
+    let entries = journal::journal(&transactions, &AccountMap::default(), 0.0);
+    assert!(entries.iter().all(|e| e.is_balanced() && e.postings.iter().all(|p| p.amount.is_finite())));
+}
@@ -0,0 +1,119 @@
+mod common;
+
+use common::{sale_of, store_with};
+use rustock::db::Database;
+use rustock::error::Error;
+use rustock::loyalty::{Customer, LoyaltyProgram};
+use rustock::product::Product;
+use rustock::repository::Repository;
+use rustock::settings::Settings;
+use rustock::void::OperationKind;
+use std::path::Path;
+
+fn setup() -> (Database, Product, Customer) {
+    let (db, product) = store_with("Kettle", 50.0, 100.0);
+    let customer = Customer::new("Ada Lovelace".to_string(), Some("555-0100".to_string()), None);
+    db.add_customer(&customer).unwrap();
+    (db, product, customer)
+}
+
+#[test]
+fn tiers_multiply_points_and_points_buy_discounts() {
+    let program = LoyaltyProgram::default();
+    assert_eq!(program.tier(0.0).unwrap().name, "Bronze");
+    assert_eq!(program.tier(500.0).unwrap().name, "Silver");
+    assert_eq!(program.next_tier(600.0).unwrap().name, "Gold");
+    assert!(program.next_tier(5000.0).is_none());
+
+    assert_eq!(program.points_earned(19.99, 0.0), 19);
+    assert_eq!(program.points_earned(100.0, 750.0), 125);
+    assert_eq!(program.discount(250, 100.0), 2.5);
+    assert_eq!(program.discount(50_000, 100.0), 100.0);
+    assert_eq!(program.max_redeemable(50_000, 12.34), 1234);
+    assert_eq!(LoyaltyProgram { point_value: 0.0, ..program.clone() }.max_redeemable(500, 10.0), 0);
+
+    let negative = LoyaltyProgram { points_per_unit: -1.0, ..program };
+    assert!(negative.validate().is_err());
+    let example = Settings::from_file(Path::new("rustock.example.toml")).unwrap();
+    assert_eq!(example.loyalty, LoyaltyProgram::default());
+}
+
+#[test]
+fn customers_are_found_by_phone_or_name() {
+    let (db, _, customer) = setup();
+
+    assert_eq!(db.find_customer_by_phone(" 555-0100 ").unwrap(), Some(customer.clone()));
+    assert_eq!(db.search_customers("lovel", 5).unwrap(), vec![customer.clone()]);
+    assert!(db.search_customers("babbage", 5).unwrap().is_empty());
+
+    let same_phone = Customer::new("Impostor".to_string(), Some("555-0100".to_string()), None);
+    assert!(matches!(db.add_customer(&same_phone), Err(Error::Conflict(_))));
+    let no_name = Customer::new(" ".to_string(), None, None);
+    assert!(matches!(db.add_customer(&no_name), Err(Error::Validation(_))));
+    let bad_email = Customer::new("Charles".to_string(), None, Some("charles at home".to_string()));
+    assert!(matches!(db.add_customer(&bad_email), Err(Error::Validation(_))));
+}
+
+#[test]
+fn sales_earn_points_that_are_redeemed_at_checkout() {
+    let (mut db, product, customer) = setup();
+    let program = LoyaltyProgram::default();
+
+    db.record_customer_sale(&sale_of(&product, 12.0).for_customer(customer.id.clone(), 0, 0.0)).unwrap();
+    assert_eq!(db.points_balance(&customer.id).unwrap(), 600);
+    // Now Silver, so points are earned at 1.25x
+    db.record_customer_sale(&sale_of(&product, 2.0).for_customer(customer.id.clone(), 0, 0.0)).unwrap();
+    assert_eq!(db.points_balance(&customer.id).unwrap(), 725);
+
+    let too_many = sale_of(&product, 1.0).for_customer(customer.id.clone(), 800, 8.0);
+    assert!(matches!(db.record_customer_sale(&too_many), Err(Error::Validation(_))));
+    let wrong_discount = sale_of(&product, 1.0).for_customer(customer.id.clone(), 500, 20.0);
+    assert!(matches!(db.record_customer_sale(&wrong_discount), Err(Error::Validation(_))));
+    let mut anonymous = sale_of(&product, 1.0);
+    anonymous.discount = 5.0;
+    assert!(matches!(db.record_sale(&anonymous), Err(Error::Validation(_))));
+    assert!(matches!(db.record_customer_sale(&sale_of(&product, 1.0)), Err(Error::Validation(_))));
+    let stranger = sale_of(&product, 1.0).for_customer("missing".to_string(), 0, 0.0);
+    assert!(matches!(db.record_customer_sale(&stranger), Err(Error::NotFound { .. })));
+
+    let redeemed = sale_of(&product, 1.0).for_customer(customer.id.clone(), 500, program.discount(500, 50.0));
+    assert_eq!(redeemed.total_amount, 45.0);
+    let number = db.record_customer_sale(&redeemed).unwrap();
+    let stored = db.get_sale_by_number(number).unwrap().unwrap();
+    assert_eq!((stored.discount, stored.points_redeemed), (5.0, 500));
+    // 725 - 500 + 45 * 1.25
+    assert_eq!(db.points_balance(&customer.id).unwrap(), 281);
+
+    let summary = db.customer_summary(&customer.id, &program).unwrap();
+    assert_eq!(summary.sales, 3);
+    assert_eq!(summary.lifetime_value, 745.0);
+    assert_eq!(summary.tier.as_deref(), Some("Silver"));
+    let history = db.customer_history(&customer.id).unwrap();
+    assert_eq!(history.iter().map(|s| s.sale_number).collect::<Vec<_>>(), vec![number, number - 1, number - 2]);
+    assert_eq!((history[0].total, history[0].discount, history[0].points), (45.0, 5.0, -444));
+}
+
+#[test]
+fn returns_and_voids_reverse_points_and_refund_what_was_paid() {
+    let (mut db, product, customer) = setup();
+    let admin = db.get_manager_by_username("admin").unwrap().unwrap();
+    db.record_customer_sale(&sale_of(&product, 1.0).for_customer(customer.id.clone(), 0, 0.0)).unwrap();
+    assert_eq!(db.points_balance(&customer.id).unwrap(), 50);
+
+    // Two kettles for 100.00, 40 points off, 99.60 paid earning 99 points
+    let number = db.record_customer_sale(&sale_of(&product, 2.0).for_customer(customer.id.clone(), 40, 0.4)).unwrap();
+    assert_eq!(db.points_balance(&customer.id).unwrap(), 109);
+
+    assert_eq!(db.record_return(number, &product.id, 1.0, "Leaks").unwrap(), 49.8);
+    // Half of the 99 earned points are taken back and half of the 40 spent given back
+    assert_eq!(db.points_balance(&customer.id).unwrap(), 109 - 50 + 20);
+
+    let sale = db.last_operation(OperationKind::Sale).unwrap().unwrap();
+    let void = db.void_operation(OperationKind::Sale, &sale.reference, &admin.id, "Wrong customer", 30).unwrap();
+    assert_eq!(void.refund, 49.8);
+    assert_eq!(db.points_balance(&customer.id).unwrap(), 50);
+
+    let summary = db.customer_summary(&customer.id, &LoyaltyProgram::default()).unwrap();
+    assert_eq!(summary.lifetime_value, 50.0);
+    assert_eq!(db.customer_history(&customer.id).unwrap()[0].refunded, 99.6);
+}
@@ -0,0 +1,98 @@
+mod common;
+
+use common::{sale_of, sqlite, stocked_product};
+use rustock::auth::{self, LoginOutcome, Manager, ManagerRemoval, PasswordPolicy, SecurityPolicy};
+use rustock::db::Database;
+use rustock::error::Error;
+use rustock::pos::Shift;
+use rustock::repository::Repository;
+use rustock::void::OperationKind;
+
+fn setup() -> (Database, Manager, Manager) {
+    let db = sqlite();
+    let admin = db.get_manager_by_username("admin").unwrap().unwrap();
+    let clerk = Manager::new("clerk".to_string(), "Secret123".to_string(), "Clerk".to_string());
+    db.add_manager(&clerk).unwrap();
+    (db, admin, clerk)
+}
+
+#[test]
+fn temporary_passwords_follow_the_policy() {
+    let strict = PasswordPolicy { min_length: 16, require_symbol: true, ..PasswordPolicy::default() };
+    for policy in [PasswordPolicy::default(), strict] {
+        let first = auth::temporary_password(&policy);
+        assert!(policy.check(&first).is_ok(), "{} breaks the policy", first);
+        assert!(first.chars().count() >= policy.min_length.max(12));
+        assert!(!first.contains(['0', 'O', '1', 'l', 'I']));
+        assert_ne!(first, auth::temporary_password(&policy));
+    }
+}
+
+#[test]
+fn managers_without_history_are_deleted() {
+    let (mut db, admin, clerk) = setup();
+
+    assert!(matches!(db.remove_manager(&admin.id, &admin.id, None), Err(Error::Conflict(_))));
+    assert_eq!(db.remove_manager(&clerk.id, &admin.id, None).unwrap(), ManagerRemoval::Deleted);
+    assert!(db.get_manager(&clerk.id).unwrap().is_none());
+    assert!(matches!(db.remove_manager(&clerk.id, &admin.id, None), Err(Error::NotFound { .. })));
+
+    // The last active manager always stays
+    let spare = Manager::new("spare".to_string(), "Secret123".to_string(), "Spare".to_string());
+    db.add_manager(&spare).unwrap();
+    db.update_manager_status(&spare.id, false).unwrap();
+    assert!(matches!(db.remove_manager(&admin.id, &spare.id, None), Err(Error::Conflict(_))));
+}
+
+#[test]
+fn managers_with_history_are_retired_and_hand_over_open_shifts() {
+    let (mut db, admin, clerk) = setup();
+    let product = stocked_product(&db, "Saw", 20.0, 5.0);
+    db.record_sale(&sale_of(&product, 1.0)).unwrap();
+    let sale = db.last_operation(OperationKind::Sale).unwrap().unwrap();
+    db.void_operation(OperationKind::Sale, &sale.reference, &clerk.id, "Wrong saw", 30).unwrap();
+    let shift = Shift::new(clerk.id.clone(), 50.0);
+    db.open_shift(&shift).unwrap();
+
+    assert!(matches!(db.remove_manager(&clerk.id, &admin.id, None), Err(Error::Conflict(_))));
+    assert!(matches!(db.remove_manager(&clerk.id, &admin.id, Some(&clerk.id)), Err(Error::Validation(_))));
+
+    let removal = db.remove_manager(&clerk.id, &admin.id, Some(&admin.id)).unwrap();
+    assert_eq!(removal, ManagerRemoval::Retired { reassigned_shifts: 1 });
+    assert_eq!(db.current_shift(&admin.id).unwrap().unwrap().id, shift.id);
+    assert_eq!(db.get_voids(10).unwrap()[0].manager_id, clerk.id);
+
+    // A retired account keeps its row and username but is closed for good
+    let retired = db.get_manager(&clerk.id).unwrap().unwrap();
+    assert!(retired.is_removed() && !retired.is_active);
+    assert!(matches!(
+        db.attempt_login("clerk", "Secret123", &SecurityPolicy::default()).unwrap(),
+        LoginOutcome::InvalidCredentials
+    ));
+    assert!(matches!(db.update_manager_status(&clerk.id, true), Err(Error::NotFound { .. })));
+    assert!(matches!(db.reset_manager_password(&clerk.id, "Temporary99"), Err(Error::NotFound { .. })));
+    let impostor = Manager::new("clerk".to_string(), "Secret123".to_string(), "Impostor".to_string());
+    assert!(matches!(db.add_manager(&impostor), Err(Error::Conflict(_))));
+    assert!(matches!(db.remove_manager(&clerk.id, &admin.id, None), Err(Error::Conflict(_))));
+}
+
+#[test]
+fn plaintext_passwords_are_hashed_when_an_old_database_opens() {
+    let dir = common::scratch_dir("plaintext-passwords");
+    let path = dir.join("plaintext.db");
+    drop(Database::open(&path, None).unwrap());
+    {
+        let conn = rusqlite::Connection::open(&path).unwrap();
+        conn.execute("UPDATE managers SET password = 'admin123'", []).unwrap();
+    }
+
+    let db = Database::open(&path, None).unwrap();
+    let admin = db.get_manager_by_username("admin").unwrap().unwrap();
+    assert!(auth::is_password_hash(&admin.password));
+    assert!(matches!(
+        db.attempt_login("admin", "admin123", &SecurityPolicy::default()).unwrap(),
+        LoginOutcome::Success(_)
+    ));
+    drop(db);
+    let _ = std::fs::remove_dir_all(&dir);
+}
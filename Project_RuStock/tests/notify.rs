@@ -0,0 +1,132 @@
+mod common;
+
+use rustock::db::Database;
+use rustock::notify::{Dispatcher, Event, EventKind, Notifier, NotifyConfig, Sink};
+use rustock::product::Product;
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::TcpListener;
+use std::time::{Duration, Instant};
+
+fn notifier(sinks: Vec<Sink>) -> Notifier {
+    Notifier::new(NotifyConfig {
+        sinks,
+        retry_delay: Duration::ZERO,
+        ..NotifyConfig::default()
+    })
+}
+
+#[test]
+fn stock_events_fire_when_a_threshold_is_crossed() {
+    let notifier = notifier(Vec::new());
+    let mut product = Product::new("Milk".to_string(), String::new(), 1.0, 4.0);
+
+    let low = notifier.stock_event(&product, 8.0).unwrap();
+    assert_eq!(low.kind, EventKind::LowStock);
+    assert_eq!(low.subject, product.id);
+    // Already below the threshold before the sale
+    assert!(notifier.stock_event(&product, 5.0).is_none());
+
+    product.quantity = 0.0;
+    assert_eq!(notifier.stock_event(&product, 4.0).unwrap().kind, EventKind::OutOfStock);
+    assert!(notifier.stock_event(&product, 0.0).is_none());
+
+    assert!(notifier.sale_event(7, 999.99).is_none());
+    assert_eq!(notifier.sale_event(8, 1000.0).unwrap().subject, "8");
+}
+
+#[test]
+fn log_and_command_sinks_receive_the_event_as_json() {
+    let dir = common::scratch_dir("notify-local");
+    let log = dir.join("events").join("rustock.log");
+    let captured = dir.join("captured.json");
+    let notifier = notifier(vec![
+        Sink::parse(&format!("log:{}", log.display())).unwrap(),
+        Sink::parse(&format!("command:cat > {} && test \"$RUSTOCK_EVENT\" = large_sale", captured.display())).unwrap(),
+    ]);
+
+    let deliveries = notifier.deliver(&Event::new(EventKind::LargeSale, "12", "Sale #12 totalled $1500.00"));
+
+    assert!(deliveries.iter().all(|d| d.delivered && d.attempts == 1));
+    let line = std::fs::read_to_string(&log).unwrap();
+    assert!(line.contains("\"kind\":\"large_sale\"") && line.ends_with('\n'));
+    assert_eq!(std::fs::read_to_string(&captured).unwrap(), line.trim_end());
+}
+
+#[test]
+fn failed_deliveries_are_retried_and_logged() {
+    let db = Database::open(":memory:", None).unwrap();
+    let notifier = notifier(vec![Sink::Command("exit 3".to_string())]);
+    let event = Event::new(EventKind::OutOfStock, "p-1", "Milk is out of stock");
+
+    let deliveries = notifier.deliver(&event);
+    db.log_deliveries(&deliveries).unwrap();
+
+    let log = db.get_delivery_log(10).unwrap();
+    assert_eq!(log.len(), 1);
+    assert_eq!(log[0].event, EventKind::OutOfStock);
+    assert_eq!(log[0].attempts, 3);
+    assert!(!log[0].delivered);
+    assert!(log[0].error.as_deref().unwrap().contains("3"));
+    assert!(Sink::parse("ftp://example.com").is_err());
+}
+
+#[test]
+fn dispatched_events_are_delivered_and_logged_in_the_background() {
+    let db = Database::open(common::scratch_dir("notify-dispatch").join("rustock.db"), None).unwrap();
+    assert!(Database::open(":memory:", None).unwrap().reopen().is_err());
+    let log = db.reopen().unwrap();
+    let slow = Notifier::new(NotifyConfig {
+        sinks: vec![Sink::Command("sleep 0.2; exit 1".to_string())],
+        retry_delay: Duration::from_millis(200),
+        ..NotifyConfig::default()
+    });
+    let dispatcher = Dispatcher::spawn(slow, move |_, deliveries| log.log_deliveries(&deliveries).unwrap());
+
+    let started = Instant::now();
+    dispatcher.dispatch(Event::new(EventKind::LargeSale, "12", "Sale #12 totalled $1500.00"));
+    dispatcher.dispatch(Event::new(EventKind::OutOfStock, "p-1", "Milk is out of stock"));
+    // Three attempts at each of two events take over two seconds
+    assert!(started.elapsed() < Duration::from_millis(200));
+
+    dispatcher.finish();
+    let logged: Vec<_> = db.get_delivery_log(10).unwrap().iter().map(|d| (d.event, d.attempts)).collect();
+    assert_eq!(logged, vec![(EventKind::OutOfStock, 3), (EventKind::LargeSale, 3)]);
+    // Nothing is delivered once the dispatcher has finished
+    dispatcher.dispatch(Event::new(EventKind::LowStock, "p-2", "Bread is low on stock"));
+    assert_eq!(db.get_delivery_log(10).unwrap().len(), 2);
+}
+
+#[test]
+fn http_sink_posts_json() {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let url = format!("http://{}/hooks/stock", listener.local_addr().unwrap());
+    let server = std::thread::spawn(move || {
+        let (stream, _) = listener.accept().unwrap();
+        let mut reader = BufReader::new(stream);
+        let mut request_line = String::new();
+        reader.read_line(&mut request_line).unwrap();
+        let mut length = 0;
+        loop {
+            let mut header = String::new();
+            reader.read_line(&mut header).unwrap();
+            if header.trim().is_empty() {
+                break;
+            }
+            if let Some(value) = header.to_lowercase().strip_prefix("content-length:") {
+                length = value.trim().parse().unwrap();
+            }
+        }
+        let mut body = vec![0; length];
+        reader.read_exact(&mut body).unwrap();
+        reader.get_mut().write_all(b"HTTP/1.1 204 No Content\r\nContent-Length: 0\r\n\r\n").unwrap();
+        (request_line, String::from_utf8(body).unwrap())
+    });
+
+    let deliveries = notifier(vec![Sink::parse(&url).unwrap()])
+        .deliver(&Event::new(EventKind::LowStock, "p-1", "Milk is low on stock"));
+
+    let (request_line, body) = server.join().unwrap();
+    assert!(deliveries[0].delivered);
+    assert!(request_line.starts_with("POST /hooks/stock"));
+    assert!(body.contains("\"message\":\"Milk is low on stock\""));
+}
@@ -0,0 +1,86 @@
+mod common;
+
+use common::{sale_of, store_with};
+use rustock::db::Database;
+use rustock::error::Error;
+use rustock::order::{self, CustomerOrder, OrderKind, OrderStatus};
+use rustock::product::Product;
+use rustock::purchase::Purchase;
+use rustock::repository::Repository;
+
+fn order(kind: OrderKind, customer: &str, product: &Product, quantity: f64) -> CustomerOrder {
+    CustomerOrder::new(kind, customer.to_string(), product.id.clone(), quantity, product.price)
+}
+
+fn receive(db: &mut Database, product: &Product, quantity: f64) {
+    db.record_purchase(&Purchase::new(product.id.clone(), quantity, 50.0)).unwrap();
+}
+
+#[test]
+fn reservations_hold_stock_back_from_sales() {
+    let (mut db, product) = store_with("Bike", 10.0, 5.0);
+
+    let reserved = db.place_order(&order(OrderKind::Reservation, "Ann", &product, 3.0)).unwrap();
+    assert_eq!(reserved.allocated, 3.0);
+    let stored = db.get_product(&product.id).unwrap().unwrap();
+    assert_eq!(stored.quantity, 5.0);
+    assert_eq!(db.available_quantity(&stored).unwrap(), 2.0);
+
+    assert!(matches!(db.record_sale(&sale_of(&product, 3.0)), Err(Error::InsufficientStock { .. })));
+    db.record_sale(&sale_of(&product, 2.0)).unwrap();
+    assert!(matches!(
+        db.place_order(&order(OrderKind::Reservation, "Ben", &product, 1.0)),
+        Err(Error::InsufficientStock { .. })
+    ));
+
+    let number = reserved.order_number.unwrap();
+    let sale_number = db.fulfil_order(number).unwrap();
+    let collected = db.get_order(number).unwrap().unwrap();
+    assert_eq!(collected.status, OrderStatus::Fulfilled);
+    assert_eq!(collected.sale_number, Some(sale_number));
+    assert_eq!(db.get_sale_by_number(sale_number).unwrap().unwrap().total_amount, 30.0);
+    assert_eq!(db.get_product(&product.id).unwrap().unwrap().quantity, 0.0);
+    assert!(matches!(db.fulfil_order(number), Err(Error::Conflict(_))));
+}
+
+#[test]
+fn layaway_is_collected_once_paid_in_full() {
+    let (mut db, product) = store_with("Bike", 100.0, 2.0);
+    let layaway = db
+        .place_order(&order(OrderKind::Layaway, "Cleo", &product, 1.0).with_deposit(30.0))
+        .unwrap();
+    let number = layaway.order_number.unwrap();
+
+    assert!(matches!(db.fulfil_order(number), Err(Error::Conflict(_))));
+    assert!(matches!(db.add_deposit(number, 80.0), Err(Error::Validation(_))));
+    assert_eq!(db.add_deposit(number, 70.0).unwrap().balance(), 0.0);
+    assert_eq!(db.get_deposits(number).unwrap().iter().map(|(amount, _)| *amount).collect::<Vec<_>>(), vec![30.0, 70.0]);
+
+    db.fulfil_order(number).unwrap();
+    assert_eq!(db.get_product(&product.id).unwrap().unwrap().quantity, 1.0);
+}
+
+#[test]
+fn backorders_are_filled_oldest_first_as_stock_arrives() {
+    let (mut db, product) = store_with("Bike", 10.0, 1.0);
+    let first = db.place_order(&order(OrderKind::Backorder, "Dev", &product, 3.0)).unwrap();
+    let second = db.place_order(&order(OrderKind::Backorder, "Eve", &product, 2.0)).unwrap();
+    assert_eq!((first.allocated, second.allocated), (1.0, 0.0));
+    assert!(matches!(db.fulfil_order(first.order_number.unwrap()), Err(Error::Conflict(_))));
+
+    receive(&mut db, &product, 3.0);
+    let first = db.get_order(first.order_number.unwrap()).unwrap().unwrap();
+    let second = db.get_order(second.order_number.unwrap()).unwrap().unwrap();
+    assert_eq!((first.allocated, second.allocated), (3.0, 1.0));
+
+    // Stock freed by a cancellation goes to the next backorder in line
+    db.cancel_order(first.order_number.unwrap()).unwrap();
+    let second = db.get_order(second.order_number.unwrap()).unwrap().unwrap();
+    assert_eq!(second.outstanding(), 0.0);
+
+    let open = db.open_orders().unwrap();
+    assert_eq!(open.len(), 1);
+    let table = order::open_orders_table(&[(open[0].clone(), product.name.clone())]);
+    assert_eq!(table.rows.len(), 1);
+    assert!(matches!(db.delete_product(&product.id), Err(Error::Conflict(_))));
+}
@@ -0,0 +1,92 @@
+mod common;
+
+use common::{sale_of, sqlite, stocked_product};
+use rustock::error::Error;
+use rustock::pos::{self, Payment, PaymentMethod, ReportKind, Shift};
+use rustock::repository::Repository;
+use rustock::void::OperationKind;
+
+#[test]
+fn change_comes_out_of_cash_and_cards_cannot_overpay() {
+    let settlement = pos::settle(16.5, &[Payment::cash(20.0)]).unwrap();
+    assert_eq!(settlement.change, 3.5);
+    assert_eq!(settlement.payments[0].1, 16.5);
+
+    let split = pos::settle(30.0, &[Payment::card(25.0), Payment::cash(10.0)]).unwrap();
+    assert_eq!(split.change, 5.0);
+    assert_eq!(split.payments.iter().map(|(_, kept)| *kept).collect::<Vec<_>>(), vec![25.0, 5.0]);
+
+    assert!(matches!(pos::settle(30.0, &[Payment::card(31.0)]), Err(Error::Validation(_))));
+    assert!(matches!(pos::settle(30.0, &[Payment::cash(29.99)]), Err(Error::Validation(_))));
+    assert!(pos::settle(30.0, &[]).is_err());
+}
+
+#[test]
+fn shift_reconciles_counted_takings_per_tender() {
+    let mut db = sqlite();
+    let manager = db.get_manager_by_username("admin").unwrap().unwrap();
+    let product = stocked_product(&db, "Candle", 12.0, 20.0);
+
+    let shift = Shift::new(manager.id.clone(), 100.0);
+    db.open_shift(&shift).unwrap();
+    assert!(matches!(db.open_shift(&Shift::new(manager.id.clone(), 50.0)), Err(Error::Conflict(_))));
+
+    let (number, settlement) = db.record_pos_sale(&sale_of(&product, 1.0), &shift.id, &[Payment::cash(20.0)]).unwrap();
+    assert_eq!(settlement.change, 8.0);
+    assert_eq!(db.get_sale_payments(number).unwrap(), vec![(Payment::cash(20.0), 12.0)]);
+    db.record_pos_sale(&sale_of(&product, 3.0), &shift.id, &[Payment::card(30.0), Payment::cash(10.0)])
+        .unwrap();
+    // Sales outside the POS flow are not part of the drawer
+    db.record_sale(&sale_of(&product, 1.0)).unwrap();
+
+    let x = db.shift_report(&shift.id).unwrap();
+    assert_eq!(x.kind, ReportKind::X);
+    assert_eq!((x.sales, x.total_sales, x.change_given), (2, 48.0, 12.0));
+    assert_eq!(x.line(PaymentMethod::Cash).unwrap().expected, 118.0);
+    assert_eq!(x.line(PaymentMethod::Card).unwrap().expected, 30.0);
+
+    let z = db.close_shift(&shift.id, &[(PaymentMethod::Cash, 115.0), (PaymentMethod::Card, 30.0)]).unwrap();
+    assert_eq!(z.kind, ReportKind::Z);
+    assert_eq!(z.line(PaymentMethod::Cash).unwrap().difference(), Some(-3.0));
+    assert_eq!(z.difference(), Some(-3.0));
+    assert!(z.to_text().contains("Z REPORT"));
+
+    // The Z report is kept for audit and the closed shift takes no more sales
+    assert_eq!(db.shift_report(&shift.id).unwrap(), z);
+    assert!(db.current_shift(&manager.id).unwrap().is_none());
+    assert!(matches!(
+        db.record_pos_sale(&sale_of(&product, 1.0), &shift.id, &[Payment::card(12.0)]),
+        Err(Error::Conflict(_))
+    ));
+    assert!(matches!(db.close_shift(&shift.id, &[]), Err(Error::Conflict(_))));
+}
+
+#[test]
+fn refunds_come_out_of_the_shift_and_tender_that_paid_them() {
+    let mut db = sqlite();
+    let manager = db.get_manager_by_username("admin").unwrap().unwrap();
+    let product = stocked_product(&db, "Candle", 12.0, 20.0);
+    let shift = Shift::new(manager.id.clone(), 100.0);
+    db.open_shift(&shift).unwrap();
+
+    let (cash_sale, _) = db.record_pos_sale(&sale_of(&product, 3.0), &shift.id, &[Payment::cash(40.0)]).unwrap();
+    let (card_sale, _) = db.record_pos_sale(&sale_of(&product, 2.0), &shift.id, &[Payment::card(24.0)]).unwrap();
+    assert_eq!(db.record_pos_return(cash_sale, &product.id, 1.0, "Chipped", &shift.id, PaymentMethod::Cash).unwrap(), 12.0);
+    assert_eq!(db.record_pos_return(card_sale, &product.id, 1.0, "Chipped", &shift.id, PaymentMethod::Card).unwrap(), 12.0);
+    // A refund given before the sale is voided drops out with the sale
+    let (voided, _) = db.record_pos_sale(&sale_of(&product, 2.0), &shift.id, &[Payment::cash(24.0)]).unwrap();
+    db.record_pos_return(voided, &product.id, 1.0, "Chipped", &shift.id, PaymentMethod::Cash).unwrap();
+    db.void_operation(OperationKind::Sale, &voided.to_string(), &manager.id, "Rung up twice", 30).unwrap();
+
+    let x = db.shift_report(&shift.id).unwrap();
+    assert_eq!(x.refunds, 24.0);
+    assert_eq!(x.line(PaymentMethod::Cash).unwrap().expected, 124.0);
+    assert_eq!(x.line(PaymentMethod::Card).unwrap().expected, 12.0);
+
+    let z = db.close_shift(&shift.id, &[(PaymentMethod::Cash, 124.0), (PaymentMethod::Card, 12.0)]).unwrap();
+    assert_eq!(z.difference(), Some(0.0));
+    assert!(matches!(
+        db.record_pos_return(cash_sale, &product.id, 1.0, "Late", &shift.id, PaymentMethod::Cash),
+        Err(Error::Conflict(_))
+    ));
+}
@@ -0,0 +1,110 @@
+mod common;
+
+use common::{sale_of, store_with};
+use rustock::db::Database;
+use rustock::error::Error;
+use rustock::pricing::{self, ScheduleKind, ScheduledPrice};
+use rustock::product::Product;
+use rustock::repository::Repository;
+use rustock::void::OperationKind;
+
+const DAY: i64 = 86_400;
+
+fn price_of(db: &Database, product: &Product) -> f64 {
+    db.get_product(&product.id).unwrap().unwrap().price
+}
+
+#[test]
+fn permanent_change_applies_once_started() {
+    let (db, product) = store_with("Kettle", 20.0, 10.0);
+    let start = product.created_at + DAY;
+    db.schedule_price_change(&ScheduledPrice::new(product.id.clone(), ScheduleKind::Permanent, 18.0, start, None))
+        .unwrap();
+
+    assert_eq!(db.apply_scheduled_prices(start - 1).unwrap(), 0);
+    assert_eq!(price_of(&db, &product), 20.0);
+
+    assert_eq!(db.apply_scheduled_prices(start).unwrap(), 1);
+    assert_eq!(price_of(&db, &product), 18.0);
+    assert!(db.get_open_price_schedules(Some(&product.id)).unwrap().is_empty());
+
+    let history = db.get_price_history(&product.id).unwrap();
+    assert_eq!(history.last().unwrap().reason, pricing::REASON_SCHEDULED);
+    assert_eq!(history.last().unwrap().changed_at, start);
+}
+
+#[test]
+fn markdown_reverts_when_it_ends() {
+    let (db, product) = store_with("Kettle", 20.0, 10.0);
+    let start = product.created_at + DAY;
+    let end = start + 3 * DAY;
+    db.schedule_price_change(&ScheduledPrice::new(product.id.clone(), ScheduleKind::Markdown, 15.0, start, Some(end)))
+        .unwrap();
+
+    db.apply_scheduled_prices(start + DAY).unwrap();
+    assert_eq!(price_of(&db, &product), 15.0);
+
+    db.apply_scheduled_prices(end).unwrap();
+    assert_eq!(price_of(&db, &product), 20.0);
+
+    let reasons: Vec<String> = db.get_price_history(&product.id).unwrap().into_iter().map(|c| c.reason).collect();
+    assert_eq!(reasons, vec![pricing::REASON_INITIAL, pricing::REASON_MARKDOWN, pricing::REASON_MARKDOWN_END]);
+}
+
+#[test]
+fn markdown_that_already_ended_applies_and_reverts_in_one_pass() {
+    let (db, product) = store_with("Kettle", 20.0, 10.0);
+    let start = product.created_at + DAY;
+    db.schedule_price_change(&ScheduledPrice::new(product.id.clone(), ScheduleKind::Markdown, 15.0, start, Some(start + DAY)))
+        .unwrap();
+
+    db.apply_scheduled_prices(start + 10 * DAY).unwrap();
+
+    assert_eq!(price_of(&db, &product), 20.0);
+    assert_eq!(db.get_price_history(&product.id).unwrap().len(), 3);
+}
+
+#[test]
+fn markdown_requires_an_end_after_its_start() {
+    let (db, product) = store_with("Kettle", 20.0, 10.0);
+    let start = product.created_at + DAY;
+
+    let open_ended = ScheduledPrice::new(product.id.clone(), ScheduleKind::Markdown, 15.0, start, None);
+    assert!(matches!(db.schedule_price_change(&open_ended), Err(Error::Validation(_))));
+
+    let backwards = ScheduledPrice::new(product.id.clone(), ScheduleKind::Markdown, 15.0, start, Some(start - 1));
+    assert!(matches!(db.schedule_price_change(&backwards), Err(Error::Validation(_))));
+}
+
+#[test]
+fn cancelled_schedule_is_never_applied() {
+    let (db, product) = store_with("Kettle", 20.0, 10.0);
+    let schedule = ScheduledPrice::new(product.id.clone(), ScheduleKind::Permanent, 25.0, product.created_at + DAY, None);
+    db.schedule_price_change(&schedule).unwrap();
+
+    db.cancel_price_schedule(&schedule.id).unwrap();
+    db.apply_scheduled_prices(product.created_at + 2 * DAY).unwrap();
+
+    assert_eq!(price_of(&db, &product), 20.0);
+}
+
+#[test]
+fn price_effect_counts_sales_net_of_returns_and_voids() {
+    let (mut db, product) = store_with("Kettle", 20.0, 10.0);
+    let admin = db.get_manager_by_username("admin").unwrap().unwrap();
+    let kept = db.record_sale(&sale_of(&product, 3.0)).unwrap();
+    db.record_return(kept, &product.id, 1.0, "Dented").unwrap();
+    let voided = db.record_sale(&sale_of(&product, 2.0)).unwrap();
+    db.void_operation(OperationKind::Sale, &voided.to_string(), &admin.id, "Rung up twice", 30).unwrap();
+
+    let periods = db.price_effect_report(&product.id).unwrap();
+    assert_eq!(periods.len(), 1);
+    assert_eq!((periods[0].units_sold, periods[0].revenue), (2.0, 40.0));
+}
+
+#[test]
+fn parse_date_accepts_date_and_date_time() {
+    assert_eq!(pricing::parse_date("1970-01-02").unwrap(), DAY);
+    assert_eq!(pricing::parse_date("1970-01-01 01:30").unwrap(), 5400);
+    assert!(pricing::parse_date("02/01/1970").is_err());
+}
@@ -0,0 +1,114 @@
+mod common;
+
+use rusqlite::Connection;
+use rustock::db::Database;
+use rustock::memory::MemoryRepository;
+use rustock::product::Product;
+use rustock::receipt::{self, Receipt, ReceiptConfig, ReceiptFormat};
+use rustock::repository::Repository;
+use rustock::sale::{Sale, SaleItem};
+use std::collections::HashMap;
+use std::path::Path;
+
+fn config_in(dir: &Path) -> ReceiptConfig {
+    ReceiptConfig {
+        store_name: "Corner Shop".to_string(),
+        tax_rate: 20.0,
+        template_dir: dir.join("templates"),
+        output_dir: dir.join("receipts"),
+        ..ReceiptConfig::default()
+    }
+}
+
+fn recorded_receipt() -> Receipt {
+    let mut repo = MemoryRepository::new();
+    let product = Product::new("Tea <Earl Grey>".to_string(), String::new(), 6.0, 10.0);
+    repo.add_product(&product).unwrap();
+    let sale = Sale::new(vec![SaleItem {
+        product_id: product.id.clone(),
+        quantity: 2.0,
+        unit: None,
+        unit_price: 6.0,
+        total_price: 12.0,
+    }]);
+    let number = repo.record_sale(&sale).unwrap();
+    let stored = repo.get_sale_by_number(number).unwrap().unwrap();
+    Receipt::from_sale(&repo, &stored).unwrap()
+}
+
+#[test]
+fn text_receipt_fits_a_thermal_printer_and_breaks_out_tax() {
+    let dir = common::scratch_dir("text");
+    let text = receipt::render_text(&recorded_receipt(), &config_in(&dir)).unwrap();
+
+    assert!(text.lines().all(|line| line.chars().count() <= 40), "{}", text);
+    assert!(text.contains("Sale #000001"));
+    assert!(text.contains("Tea <Earl Grey>"));
+    assert!(text.contains("$10.00"), "net subtotal missing:\n{}", text);
+    assert!(text.contains("$2.00"), "tax missing:\n{}", text);
+    assert!(text.contains("$12.00"));
+}
+
+#[test]
+fn edited_template_is_used_and_html_is_escaped() {
+    let dir = common::scratch_dir("html");
+    let config = config_in(&dir);
+    std::fs::create_dir_all(&config.template_dir).unwrap();
+    std::fs::write(
+        config.template_dir.join(receipt::HTML_TEMPLATE),
+        "<h1>{{store_name}}</h1>{{#items}}<li>{{name}}</li>{{/items}}",
+    )
+    .unwrap();
+
+    let html = String::from_utf8(receipt::render(&recorded_receipt(), &config, ReceiptFormat::Html).unwrap()).unwrap();
+
+    assert_eq!(html, "<h1>Corner Shop</h1><li>Tea &lt;Earl Grey&gt;</li>");
+}
+
+#[test]
+fn pdf_receipt_is_written_to_the_output_dir() {
+    let dir = common::scratch_dir("pdf");
+    let path = receipt::save(&recorded_receipt(), &config_in(&dir), ReceiptFormat::Pdf).unwrap();
+
+    let bytes = std::fs::read(&path).unwrap();
+    assert!(path.ends_with("receipt-000001.pdf"));
+    assert!(bytes.starts_with(b"%PDF-1.4"));
+    assert!(bytes.ends_with(b"%%EOF\n"));
+}
+
+#[test]
+fn placeholders_support_alignment() {
+    let vars = HashMap::from([("a", "xy".to_string()), ("b", "toolong".to_string())]);
+
+    let out = receipt::render_template("[{{a:>4}}][{{a:^6}}][{{b:<3}}][{{missing}}]", &vars, &[], str::to_string);
+
+    assert_eq!(out, "[  xy][  xy  ][too][]");
+}
+
+#[test]
+fn legacy_sale_rows_become_numbered_sales() {
+    let dir = common::scratch_dir("legacy");
+    let path = dir.join("legacy.db");
+    {
+        let conn = Connection::open(&path).unwrap();
+        conn.execute_batch(
+            "CREATE TABLE products (id TEXT PRIMARY KEY, name TEXT NOT NULL, description TEXT,
+                                    price REAL NOT NULL, quantity INTEGER NOT NULL,
+                                    created_at INTEGER NOT NULL, updated_at INTEGER NOT NULL);
+             CREATE TABLE sales (id INTEGER PRIMARY KEY AUTOINCREMENT, product_id TEXT NOT NULL,
+                                 quantity INTEGER NOT NULL, total_price REAL NOT NULL, sale_date TEXT NOT NULL);
+             INSERT INTO products VALUES ('p1', 'Mug', '', 4.0, 3, 0, 0);
+             INSERT INTO sales (product_id, quantity, total_price, sale_date) VALUES ('p1', 2, 8.0, '2025-07-01 10:00:00');",
+        )
+        .unwrap();
+    }
+
+    let db = Database::open(&path, None).unwrap();
+    let sale = db.get_sale_by_number(1).unwrap().unwrap();
+
+    assert_eq!(sale.items.len(), 1);
+    assert_eq!(sale.items[0].unit_price, 4.0);
+    assert_eq!(sale.timestamp, 1_751_364_000);
+    // Text sale dates become Unix timestamps like every other table
+    assert_eq!(db.get_all_sales().unwrap()[0].3, 1_751_364_000);
+}
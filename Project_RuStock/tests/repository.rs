@@ -0,0 +1,521 @@
+mod common;
+
+use common::{sale_of, sqlite, stocked_product};
+use rustock::auth::{self, LoginOutcome, Manager, SecurityPolicy};
+use rustock::bundle::BundleComponent;
+use rustock::currency::ExchangeRate;
+use rustock::error::Error;
+use rustock::memory::MemoryRepository;
+use rustock::product::Product;
+use rustock::purchase::Purchase;
+use rustock::repository::Repository;
+use rustock::sale::{Sale, SaleItem};
+use rustock::unit::ProductUnit;
+
+fn memory() -> MemoryRepository {
+    MemoryRepository::new()
+}
+
+fn sale_decrements_stock<R: Repository>(mut repo: R) {
+    let product = stocked_product(&repo, "Widget", 2.5, 10.0);
+
+    repo.record_sale(&sale_of(&product, 4.0)).unwrap();
+
+    let stored = repo.get_product(&product.id).unwrap().unwrap();
+    assert_eq!(stored.quantity, 6.0);
+}
+
+fn sale_rejects_insufficient_stock<R: Repository>(mut repo: R) {
+    let plenty = stocked_product(&repo, "Plenty", 1.0, 50.0);
+    let scarce = stocked_product(&repo, "Scarce", 1.0, 2.0);
+    let sale = Sale::new(vec![sale_of(&plenty, 5.0).items[0].clone(), sale_of(&scarce, 3.0).items[0].clone()]);
+
+    match repo.record_sale(&sale) {
+        Err(Error::InsufficientStock { product_id, available, requested }) => {
+            assert_eq!(product_id, scarce.id);
+            assert_eq!(available, 2.0);
+            assert_eq!(requested, 3.0);
+        }
+        other => panic!("expected InsufficientStock, got {:?}", other),
+    }
+
+    // The whole sale is rolled back, including the item that had stock
+    assert_eq!(repo.get_product(&plenty.id).unwrap().unwrap().quantity, 50.0);
+    assert!(repo.get_all_sales().unwrap().is_empty());
+}
+
+fn sale_of_unknown_product_is_not_found<R: Repository>(mut repo: R) {
+    let ghost = Product::new("Ghost".to_string(), String::new(), 1.0, 1.0);
+
+    assert!(matches!(repo.record_sale(&sale_of(&ghost, 1.0)), Err(Error::NotFound { .. })));
+}
+
+fn loyalty_sales_are_left_to_the_customer_ledger<R: Repository>(mut repo: R) {
+    let product = stocked_product(&repo, "Widget", 2.5, 10.0);
+    let loyal = sale_of(&product, 4.0).for_customer("customer-1".to_string(), 0, 0.0);
+
+    assert!(matches!(repo.record_sale(&loyal), Err(Error::Validation(_))));
+    assert_eq!(repo.get_product(&product.id).unwrap().unwrap().quantity, 10.0);
+    assert!(repo.get_all_sales().unwrap().is_empty());
+}
+
+fn purchases_are_recorded_and_listed<R: Repository>(mut repo: R) {
+    let product = stocked_product(&repo, "Bolt", 0.2, 0.0);
+    let mut older = Purchase::new(product.id.clone(), 100.0, 0.1);
+    older.purchase_date -= 3600;
+    let newer = Purchase::new(product.id.clone(), 50.0, 0.12);
+
+    repo.record_purchase(&older).unwrap();
+    repo.record_purchase(&newer).unwrap();
+
+    let purchases = repo.get_all_purchases().unwrap();
+    assert_eq!(purchases.len(), 2);
+    assert_eq!(purchases[0].id, newer.id);
+    assert!((purchases[1].total_cost - 10.0).abs() < 1e-9);
+}
+
+fn purchase_of_unknown_product_is_not_found<R: Repository>(mut repo: R) {
+    let purchase = Purchase::new("missing".to_string(), 1.0, 1.0);
+
+    assert!(matches!(repo.record_purchase(&purchase), Err(Error::NotFound { .. })));
+}
+
+fn update_and_delete_missing_product_is_not_found<R: Repository>(mut repo: R) {
+    let product = Product::new("Nowhere".to_string(), String::new(), 1.0, 1.0);
+
+    assert!(matches!(repo.update_product(&product), Err(Error::NotFound { .. })));
+    assert!(matches!(repo.delete_product(&product.id), Err(Error::NotFound { .. })));
+}
+
+fn archived_product_keeps_history_and_can_be_restored<R: Repository>(mut repo: R) {
+    let product = stocked_product(&repo, "Retro", 5.0, 10.0);
+    repo.record_sale(&sale_of(&product, 2.0)).unwrap();
+
+    repo.archive_product(&product.id).unwrap();
+
+    assert!(repo.get_all_products().unwrap().is_empty());
+    assert_eq!(repo.get_archived_products().unwrap()[0].id, product.id);
+    assert!(repo.get_product(&product.id).unwrap().unwrap().is_archived());
+    assert_eq!(repo.get_all_sales().unwrap().len(), 1);
+    assert!(matches!(repo.record_sale(&sale_of(&product, 1.0)), Err(Error::Conflict(_))));
+    let restock = Purchase::new(product.id.clone(), 5.0, 2.0);
+    assert!(matches!(repo.record_purchase(&restock), Err(Error::Conflict(_))));
+
+    repo.restore_product(&product.id).unwrap();
+
+    assert!(repo.get_archived_products().unwrap().is_empty());
+    assert_eq!(repo.get_all_products().unwrap()[0].quantity, 8.0);
+    repo.record_sale(&sale_of(&product, 1.0)).unwrap();
+}
+
+fn only_products_without_history_can_be_deleted<R: Repository>(mut repo: R) {
+    let sold = stocked_product(&repo, "Sold", 1.0, 5.0);
+    let restocked = stocked_product(&repo, "Restocked", 1.0, 0.0);
+    let unused = stocked_product(&repo, "Unused", 1.0, 5.0);
+    repo.record_sale(&sale_of(&sold, 1.0)).unwrap();
+    repo.record_purchase(&Purchase::new(restocked.id.clone(), 3.0, 0.5)).unwrap();
+
+    assert!(matches!(repo.delete_product(&sold.id), Err(Error::Conflict(_))));
+    assert!(matches!(repo.delete_product(&restocked.id), Err(Error::Conflict(_))));
+    repo.delete_product(&unused.id).unwrap();
+
+    assert!(repo.get_product(&unused.id).unwrap().is_none());
+    assert_eq!(repo.get_all_products().unwrap().len(), 2);
+    assert_eq!(repo.get_all_sales().unwrap().len(), 1);
+    assert_eq!(repo.get_all_purchases().unwrap().len(), 1);
+}
+
+fn sales_report_lists_product_names<R: Repository>(mut repo: R) {
+    let product = stocked_product(&repo, "Gadget", 3.0, 10.0);
+    repo.record_sale(&sale_of(&product, 2.0)).unwrap();
+    repo.record_sale(&sale_of(&product, 1.0)).unwrap();
+
+    let sales = repo.get_all_sales().unwrap();
+    assert_eq!(sales.len(), 2);
+    assert!(sales.iter().all(|(name, ..)| name == "Gadget"));
+    let revenue: f64 = sales.iter().map(|(_, _, total, _)| total).sum();
+    assert!((revenue - 9.0).abs() < 1e-9);
+
+    let inventory = repo.get_all_products().unwrap();
+    assert_eq!(inventory.len(), 1);
+    assert_eq!(inventory[0].quantity, 7.0);
+}
+
+fn sales_are_numbered_and_retrievable<R: Repository>(mut repo: R) {
+    let cheap = stocked_product(&repo, "Pen", 1.5, 10.0);
+    let dear = stocked_product(&repo, "Notebook", 4.0, 10.0);
+    let first = sale_of(&cheap, 2.0);
+    let second = Sale::new(vec![sale_of(&cheap, 1.0).items[0].clone(), sale_of(&dear, 3.0).items[0].clone()]);
+
+    assert_eq!(repo.record_sale(&first).unwrap(), 1);
+    assert_eq!(repo.record_sale(&second).unwrap(), 2);
+
+    let stored = repo.get_sale_by_number(2).unwrap().unwrap();
+    assert_eq!(stored.id, second.id);
+    assert_eq!(stored.sale_number, Some(2));
+    assert_eq!(stored.items.len(), 2);
+    assert_eq!(stored.items[1].product_id, dear.id);
+    assert_eq!(stored.items[1].unit_price, 4.0);
+    assert!((stored.total_amount - 13.5).abs() < 1e-9);
+    assert!(repo.get_sale_by_number(3).unwrap().is_none());
+}
+
+fn stale_product_update_is_a_conflict<R: Repository>(mut repo: R) {
+    let product = stocked_product(&repo, "Cable", 3.0, 10.0);
+    let mut first_terminal = repo.get_product(&product.id).unwrap().unwrap();
+    let mut second_terminal = repo.get_product(&product.id).unwrap().unwrap();
+
+    first_terminal.update(None, None, None, Some(25.0));
+    repo.update_product(&first_terminal).unwrap();
+    second_terminal.update(None, None, None, Some(12.0));
+
+    assert!(matches!(repo.update_product(&second_terminal), Err(Error::Conflict(_))));
+    assert_eq!(repo.get_product(&product.id).unwrap().unwrap().quantity, 25.0);
+
+    // A sale also changes the row, so an edit loaded before it is stale too
+    let mut reloaded = repo.get_product(&product.id).unwrap().unwrap();
+    repo.record_sale(&sale_of(&product, 5.0)).unwrap();
+    reloaded.update(None, None, None, Some(30.0));
+
+    assert!(matches!(repo.update_product(&reloaded), Err(Error::Conflict(_))));
+    assert_eq!(repo.get_product(&product.id).unwrap().unwrap().quantity, 20.0);
+}
+
+fn duplicate_sku_is_a_conflict<R: Repository>(repo: R) {
+    let mut cola = Product::new("Cola".to_string(), String::new(), 1.25, 10.0);
+    cola.sku = Some("DRK-001".to_string());
+    cola.tags = vec!["drinks".to_string()];
+    repo.add_product(&cola).unwrap();
+    let mut lemonade = Product::new("Lemonade".to_string(), String::new(), 1.5, 10.0);
+    lemonade.sku = Some("drk-001".to_string());
+
+    assert!(matches!(repo.add_product(&lemonade), Err(Error::Conflict(_))));
+
+    lemonade.sku = Some("DRK-002".to_string());
+    repo.add_product(&lemonade).unwrap();
+    let mut renamed = repo.get_product(&lemonade.id).unwrap().unwrap();
+    renamed.sku = Some("DRK-001".to_string());
+    assert!(matches!(repo.update_product(&renamed), Err(Error::Conflict(_))));
+
+    let stored = repo.get_product(&cola.id).unwrap().unwrap();
+    assert_eq!(stored.sku.as_deref(), Some("DRK-001"));
+    assert_eq!(stored.tags, vec!["drinks"]);
+}
+
+fn cases_and_weights_convert_into_stock_units<R: Repository>(mut repo: R) {
+    let cola = stocked_product(&repo, "Cola", 1.25, 10.0);
+    let mut cheese = Product::new("Cheese".to_string(), String::new(), 12.0, 2.0);
+    cheese.unit = "kg".to_string();
+    repo.add_product(&cheese).unwrap();
+    repo.set_product_unit(&ProductUnit::new(cola.id.clone(), "Case", 24.0)).unwrap();
+
+    repo.record_purchase(&Purchase::new(cola.id.clone(), 2.0, 20.0).in_unit("case")).unwrap();
+    assert_eq!(repo.to_base_quantity(&cola.id, Some("case"), 2.0).unwrap(), 48.0);
+    // Two cases of 24 arrive on top of the 10 in stock
+    let restocked = repo.get_product(&cola.id).unwrap().unwrap();
+    assert_eq!((restocked.quantity, restocked.version), (58.0, cola.version + 1));
+
+    let case_sale = Sale::new(vec![SaleItem {
+        product_id: cola.id.clone(),
+        quantity: 1.0,
+        unit: Some("case".to_string()),
+        unit_price: 30.0,
+        total_price: 30.0,
+    }]);
+    let number = repo.record_sale(&case_sale).unwrap();
+    repo.record_sale(&sale_of(&cheese, 0.25)).unwrap();
+
+    assert_eq!(repo.get_product(&cola.id).unwrap().unwrap().quantity, 34.0);
+    assert_eq!(repo.get_product(&cheese.id).unwrap().unwrap().quantity, 1.75);
+    let stored = repo.get_sale_by_number(number).unwrap().unwrap();
+    assert_eq!(stored.items[0].quantity, 1.0);
+    assert_eq!(stored.items[0].unit.as_deref(), Some("case"));
+    let purchase = &repo.get_all_purchases().unwrap()[0];
+    assert_eq!((purchase.quantity, purchase.unit.as_deref()), (2.0, Some("case")));
+
+    // Counted units cannot be split and unknown units are refused
+    assert!(matches!(repo.record_sale(&sale_of(&cola, 0.5)), Err(Error::Validation(_))));
+    let mut by_pallet = sale_of(&cola, 1.0);
+    by_pallet.items[0].unit = Some("pallet".to_string());
+    assert!(matches!(repo.record_sale(&by_pallet), Err(Error::Validation(_))));
+    assert!(matches!(
+        repo.set_product_unit(&ProductUnit::new(cola.id.clone(), "each", 2.0)),
+        Err(Error::Validation(_))
+    ));
+}
+
+fn foreign_purchases_use_the_rate_in_effect<R: Repository>(mut repo: R) {
+    const DAY: i64 = 86_400;
+    let wine = stocked_product(&repo, "Wine", 15.0, 0.0);
+    let mut purchase = Purchase::new(wine.id.clone(), 10.0, 5.0).in_currency("eur");
+    purchase.purchase_date = 100 * DAY;
+
+    // No EUR rate maintained yet
+    assert!(matches!(repo.record_purchase(&purchase), Err(Error::Validation(_))));
+
+    repo.set_exchange_rate(&ExchangeRate::new("EUR", 1.10, 90 * DAY)).unwrap();
+    repo.set_exchange_rate(&ExchangeRate::new("EUR", 1.20, 110 * DAY)).unwrap();
+    repo.set_exchange_rate(&ExchangeRate::new("eur", 1.05, 90 * DAY)).unwrap();
+    assert_eq!(repo.get_exchange_rates().unwrap().len(), 2);
+    assert_eq!(repo.exchange_rate("USD", 0).unwrap(), 1.0);
+    assert!(matches!(repo.set_exchange_rate(&ExchangeRate::new("GBP", 0.0, 0)), Err(Error::Validation(_))));
+    assert!(matches!(repo.set_exchange_rate(&ExchangeRate::new("USD", 2.0, 0)), Err(Error::Validation(_))));
+
+    repo.record_purchase(&purchase).unwrap();
+    repo.record_purchase(&Purchase::new(wine.id.clone(), 1.0, 4.0)).unwrap();
+
+    let purchases = repo.get_all_purchases().unwrap();
+    let foreign = purchases.iter().find(|p| p.id == purchase.id).unwrap();
+    assert_eq!(foreign.currency, "EUR");
+    assert_eq!(foreign.exchange_rate, 1.05);
+    assert!((foreign.base_total_cost() - 52.5).abs() < 1e-9);
+    let local = purchases.iter().find(|p| p.id != purchase.id).unwrap();
+    assert_eq!((local.currency.as_str(), local.exchange_rate), ("USD", 1.0));
+}
+
+fn bundles_sell_from_assembled_stock_then_components<R: Repository>(mut repo: R) {
+    let chocolate = stocked_product(&repo, "Chocolate", 3.0, 10.0);
+    let wine = stocked_product(&repo, "Wine", 12.0, 3.0);
+    let gift_box = stocked_product(&repo, "Gift Box", 25.0, 0.0);
+    repo.set_bundle_component(&BundleComponent::new(gift_box.id.clone(), chocolate.id.clone(), 2.0)).unwrap();
+    repo.set_bundle_component(&BundleComponent::new(gift_box.id.clone(), wine.id.clone(), 1.0)).unwrap();
+    let quantity = |repo: &R, product: &Product| repo.get_product(&product.id).unwrap().unwrap().quantity;
+
+    assert_eq!(repo.available_quantity(&gift_box).unwrap(), 3.0);
+    repo.assemble_bundle(&gift_box.id, 1.0).unwrap();
+    assert_eq!((quantity(&repo, &gift_box), quantity(&repo, &chocolate), quantity(&repo, &wine)), (1.0, 8.0, 2.0));
+
+    // One box comes from assembled stock, the other is built from components
+    repo.record_sale(&sale_of(&gift_box, 2.0)).unwrap();
+    assert_eq!((quantity(&repo, &gift_box), quantity(&repo, &chocolate), quantity(&repo, &wine)), (0.0, 6.0, 1.0));
+
+    assert!(matches!(
+        repo.record_sale(&sale_of(&gift_box, 2.0)),
+        Err(Error::InsufficientStock { available, .. }) if available == 1.0
+    ));
+    assert!(matches!(repo.assemble_bundle(&gift_box.id, 2.0), Err(Error::InsufficientStock { .. })));
+    assert_eq!(quantity(&repo, &wine), 1.0);
+
+    // Bundles are one level deep and their components cannot be deleted
+    let hamper = stocked_product(&repo, "Hamper", 60.0, 0.0);
+    assert!(matches!(
+        repo.set_bundle_component(&BundleComponent::new(hamper.id.clone(), gift_box.id.clone(), 1.0)),
+        Err(Error::Validation(_))
+    ));
+    assert!(matches!(
+        repo.set_bundle_component(&BundleComponent::new(hamper.id.clone(), hamper.id.clone(), 1.0)),
+        Err(Error::Validation(_))
+    ));
+    assert!(matches!(repo.delete_product(&chocolate.id), Err(Error::Conflict(_))));
+    repo.remove_bundle_component(&gift_box.id, &wine.id).unwrap();
+    assert_eq!(repo.get_bundle_components(&gift_box.id).unwrap().len(), 1);
+}
+
+fn stored_timestamps_round_trip<R: Repository>(mut repo: R) {
+    let mut product = Product::new("Lamp".to_string(), String::new(), 20.0, 5.0);
+    product.created_at = 1_700_000_000;
+    product.updated_at = 1_700_003_600;
+    repo.add_product(&product).unwrap();
+    let mut sale = sale_of(&product, 1.0);
+    sale.timestamp = 1_700_007_200;
+    let number = repo.record_sale(&sale).unwrap();
+
+    let stored = repo.get_product(&product.id).unwrap().unwrap();
+    assert_eq!((stored.created_at, stored.updated_at), (1_700_000_000, 1_700_003_600));
+    let listed = &repo.get_all_products().unwrap()[0];
+    assert_eq!((listed.created_at, listed.updated_at), (1_700_000_000, 1_700_003_600));
+    assert_eq!(repo.get_sale_by_number(number).unwrap().unwrap().timestamp, 1_700_007_200);
+    assert_eq!(repo.get_all_sales().unwrap()[0].3, 1_700_007_200);
+}
+
+fn price_changes_are_recorded_in_history<R: Repository>(repo: R) {
+    let mut product = stocked_product(&repo, "Lamp", 10.0, 5.0);
+
+    product.update(None, Some("Desk lamp".to_string()), None, None);
+    repo.update_product(&product).unwrap();
+    product = repo.get_product(&product.id).unwrap().unwrap();
+    product.update(None, None, Some(12.5), None);
+    repo.update_product(&product).unwrap();
+
+    let history = repo.get_price_history(&product.id).unwrap();
+    assert_eq!(history.len(), 2);
+    assert_eq!(history[0].old_price, None);
+    assert_eq!(history[0].new_price, 10.0);
+    assert_eq!(history[1].old_price, Some(10.0));
+    assert_eq!(history[1].new_price, 12.5);
+}
+
+fn default_admin_must_change_password<R: Repository>(repo: R) {
+    let policy = SecurityPolicy::default();
+
+    match repo.attempt_login("admin", "admin123", &policy).unwrap() {
+        LoginOutcome::Success(manager) => assert!(manager.must_change_password),
+        other => panic!("expected Success, got {:?}", other),
+    }
+}
+
+fn repeated_failures_lock_the_account<R: Repository>(repo: R) {
+    let policy = SecurityPolicy { max_failed_attempts: 3, ..SecurityPolicy::default() };
+    let manager = Manager::new("clerk".to_string(), "Secret123".to_string(), "Clerk".to_string());
+    repo.add_manager(&manager).unwrap();
+
+    for _ in 0..2 {
+        match repo.attempt_login("clerk", "wrong", &policy).unwrap() {
+            LoginOutcome::InvalidCredentials => {}
+            other => panic!("expected InvalidCredentials, got {:?}", other),
+        }
+    }
+    assert!(matches!(
+        repo.attempt_login("clerk", "wrong", &policy).unwrap(),
+        LoginOutcome::LockedOut { .. }
+    ));
+
+    // Correct credentials are refused while the lock holds
+    assert!(matches!(
+        repo.attempt_login("clerk", "Secret123", &policy).unwrap(),
+        LoginOutcome::LockedOut { .. }
+    ));
+}
+
+fn inactive_manager_cannot_log_in<R: Repository>(repo: R) {
+    let policy = SecurityPolicy::default();
+    let manager = Manager::new("temp".to_string(), "Secret123".to_string(), "Temp".to_string());
+    repo.add_manager(&manager).unwrap();
+    repo.update_manager_status(&manager.id, false).unwrap();
+
+    assert!(matches!(
+        repo.attempt_login("temp", "Secret123", &policy).unwrap(),
+        LoginOutcome::InvalidCredentials
+    ));
+}
+
+fn unknown_username_looks_like_a_wrong_password<R: Repository>(repo: R) {
+    let policy = SecurityPolicy::default();
+
+    for username in ["admin", "nobody"] {
+        match repo.attempt_login(username, "wrong", &policy).unwrap() {
+            LoginOutcome::InvalidCredentials => {}
+            other => panic!("expected InvalidCredentials for {}, got {:?}", username, other),
+        }
+    }
+}
+
+fn password_change_clears_forced_rotation<R: Repository>(repo: R) {
+    let policy = SecurityPolicy::default();
+    let admin = repo.get_manager_by_username("admin").unwrap().unwrap();
+
+    repo.update_manager_password(&admin.id, "NewPass123").unwrap();
+
+    match repo.attempt_login("admin", "NewPass123", &policy).unwrap() {
+        LoginOutcome::Success(manager) => assert!(!manager.must_change_password),
+        other => panic!("expected Success, got {:?}", other),
+    }
+}
+
+fn duplicate_username_is_a_conflict<R: Repository>(repo: R) {
+    let manager = Manager::new("admin".to_string(), "Secret123".to_string(), "Impostor".to_string());
+
+    assert!(matches!(repo.add_manager(&manager), Err(Error::Conflict(_))));
+    assert_eq!(repo.get_all_managers().unwrap().len(), 1);
+}
+
+fn managers_can_be_renamed_but_usernames_stay_unique<R: Repository>(repo: R) {
+    let manager = Manager::new("clerk".to_string(), "Secret123".to_string(), "Clerk".to_string());
+    repo.add_manager(&manager).unwrap();
+
+    repo.update_manager_profile(&manager.id, "cashier", "Head Cashier").unwrap();
+    let renamed = repo.get_manager(&manager.id).unwrap().unwrap();
+    assert_eq!((renamed.username.as_str(), renamed.full_name.as_str()), ("cashier", "Head Cashier"));
+    assert!(repo.get_manager_by_username("clerk").unwrap().is_none());
+
+    assert!(matches!(repo.update_manager_profile(&manager.id, "admin", "Clerk"), Err(Error::Conflict(_))));
+    assert!(matches!(repo.update_manager_profile(&manager.id, "cashier", " "), Err(Error::Validation(_))));
+    assert!(matches!(repo.update_manager_profile("missing", "nobody", "Nobody"), Err(Error::NotFound { .. })));
+}
+
+fn password_reset_unlocks_and_forces_a_change<R: Repository>(repo: R) {
+    let policy = SecurityPolicy { max_failed_attempts: 1, ..SecurityPolicy::default() };
+    let manager = Manager::new("clerk".to_string(), "Secret123".to_string(), "Clerk".to_string());
+    repo.add_manager(&manager).unwrap();
+    repo.attempt_login("clerk", "forgotten", &policy).unwrap();
+
+    repo.reset_manager_password(&manager.id, "Temporary99").unwrap();
+    match repo.attempt_login("clerk", "Temporary99", &policy).unwrap() {
+        LoginOutcome::Success(manager) => assert!(manager.must_change_password),
+        other => panic!("expected Success, got {:?}", other),
+    }
+    assert!(matches!(
+        repo.attempt_login("clerk", "Secret123", &policy).unwrap(),
+        LoginOutcome::LockedOut { .. }
+    ));
+}
+
+fn passwords_are_stored_as_salted_hashes<R: Repository>(repo: R) {
+    let first = Manager::new("clerk".to_string(), "Secret123".to_string(), "Clerk".to_string());
+    let second = Manager::new("cashier".to_string(), "Secret123".to_string(), "Cashier".to_string());
+    repo.add_manager(&first).unwrap();
+    repo.add_manager(&second).unwrap();
+
+    let first = repo.get_manager(&first.id).unwrap().unwrap();
+    let second = repo.get_manager(&second.id).unwrap().unwrap();
+    assert!(!first.password.contains("Secret123"));
+    assert_ne!(first.password, second.password);
+    assert!(auth::verify_password(&first.password, "Secret123"));
+    assert!(!auth::verify_password(&first.password, "secret123"));
+
+    repo.update_manager_password(&first.id, "Changed123").unwrap();
+    let changed = repo.get_manager(&first.id).unwrap().unwrap();
+    assert!(auth::is_password_hash(&changed.password));
+    assert!(auth::verify_password(&changed.password, "Changed123"));
+}
+
+macro_rules! backend_tests {
+    ($backend:ident: $($name:ident),* $(,)?) => {
+        mod $backend {
+            $(
+                #[test]
+                fn $name() {
+                    super::$name(super::$backend());
+                }
+            )*
+        }
+    };
+}
+
+macro_rules! repository_tests {
+    ($($backend:ident),*) => {
+        $(
+            backend_tests!($backend:
+                sale_decrements_stock,
+                sale_rejects_insufficient_stock,
+                sale_of_unknown_product_is_not_found,
+                loyalty_sales_are_left_to_the_customer_ledger,
+                purchases_are_recorded_and_listed,
+                purchase_of_unknown_product_is_not_found,
+                update_and_delete_missing_product_is_not_found,
+                archived_product_keeps_history_and_can_be_restored,
+                only_products_without_history_can_be_deleted,
+                sales_report_lists_product_names,
+                sales_are_numbered_and_retrievable,
+                stale_product_update_is_a_conflict,
+                duplicate_sku_is_a_conflict,
+                cases_and_weights_convert_into_stock_units,
+                foreign_purchases_use_the_rate_in_effect,
+                bundles_sell_from_assembled_stock_then_components,
+                stored_timestamps_round_trip,
+                price_changes_are_recorded_in_history,
+                default_admin_must_change_password,
+                repeated_failures_lock_the_account,
+                inactive_manager_cannot_log_in,
+                unknown_username_looks_like_a_wrong_password,
+                password_change_clears_forced_rotation,
+                duplicate_username_is_a_conflict,
+                managers_can_be_renamed_but_usernames_stay_unique,
+                password_reset_unlocks_and_forces_a_change,
+                passwords_are_stored_as_salted_hashes,
+            );
+        )*
+    };
+}
+
+repository_tests!(sqlite, memory);
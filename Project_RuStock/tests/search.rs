@@ -0,0 +1,91 @@
+use rustock::db::Database;
+use rustock::product::{self, Product};
+use rustock::repository::Repository;
+use rustock::search;
+
+fn sqlite() -> Database {
+    Database::open(":memory:", None).expect("open in-memory SQLite database")
+}
+
+fn add(db: &Database, name: &str, description: &str, sku: &str, tags: &str) -> Product {
+    let mut product = Product::new(name.to_string(), description.to_string(), 1.0, 5.0);
+    product.sku = Some(sku.to_string());
+    product.tags = product::parse_tags(tags);
+    db.add_product(&product).unwrap();
+    product
+}
+
+fn names(results: &[Product]) -> Vec<&str> {
+    results.iter().map(|p| p.name.as_str()).collect()
+}
+
+#[test]
+fn typo_tolerance_grows_with_term_length() {
+    assert_eq!(search::edit_distance("chedar", "cheddar"), 1);
+    assert_eq!(search::edit_distance("kitten", "sitting"), 3);
+    assert_eq!(search::max_typos("tea"), 0);
+    assert_eq!(search::max_typos("chedar"), 1);
+    assert_eq!(search::max_typos("parmigiano"), 2);
+
+    let vocabulary = vec!["cheddar".to_string(), "cheese".to_string(), "tea".to_string()];
+    assert_eq!(search::corrections("chedar", &vocabulary), vec!["cheddar"]);
+    assert!(search::corrections("tee", &vocabulary).is_empty());
+    assert_eq!(search::anchors("chedar"), vec!["che", "dar"]);
+    assert_eq!(search::anchors("parmigiano"), vec!["par", "mig", "iano"]);
+    assert_eq!(product::parse_tags(" Dairy, cheese,,dairy "), vec!["dairy", "cheese"]);
+}
+
+#[test]
+fn results_are_ranked_and_match_prefixes_and_typos() {
+    let db = sqlite();
+    add(&db, "Cheese Grater", "Stainless steel", "KIT-010", "kitchen");
+    add(&db, "Mature Cheddar", "Strong cheese from Somerset", "CHS-001", "dairy, cheese");
+    add(&db, "Oat Crackers", "Goes well with cheddar", "BAK-004", "bakery");
+
+    // A name hit outranks a mention in the description
+    assert_eq!(names(&db.search_products("cheddar", 10).unwrap()), vec!["Mature Cheddar", "Oat Crackers"]);
+    assert_eq!(names(&db.search_products("ched", 10).unwrap()), vec!["Mature Cheddar", "Oat Crackers"]);
+    assert_eq!(names(&db.search_products("chs-001", 10).unwrap()), vec!["Mature Cheddar"]);
+    assert_eq!(names(&db.search_products("dairy ched", 10).unwrap()), vec!["Mature Cheddar"]);
+    assert_eq!(names(&db.search_products("somerst chedar", 10).unwrap()), vec!["Mature Cheddar"]);
+    assert_eq!(names(&db.search_products("grater", 1).unwrap()), vec!["Cheese Grater"]);
+    assert!(db.search_products("caviar", 10).unwrap().is_empty());
+    assert!(db.search_products("  ", 10).unwrap().is_empty());
+}
+
+#[test]
+fn typos_anywhere_in_a_term_are_corrected() {
+    let db = sqlite();
+    add(&db, "Mature Cheddar", "", "CHS-001", "");
+    add(&db, "Parmigiano Reggiano", "", "CHS-002", "");
+
+    for query in ["xheddar", "chexdar", "cheddax", "chedda", "cheddarr"] {
+        assert_eq!(names(&db.search_products(query, 10).unwrap()), vec!["Mature Cheddar"], "{}", query);
+    }
+    for query in ["barmigiamo", "parmigxxno", "pamigiano", "parmigianooo"] {
+        assert_eq!(names(&db.search_products(query, 10).unwrap()), vec!["Parmigiano Reggiano"], "{}", query);
+    }
+    assert!(db.search_products("parmxgixnx", 10).unwrap().is_empty());
+}
+
+#[test]
+fn index_follows_updates_and_deletes() {
+    let mut db = sqlite();
+    let tea = add(&db, "Green Tea", "", "TEA-001", "drinks");
+
+    let mut renamed = db.get_product(&tea.id).unwrap().unwrap();
+    renamed.update(Some("Jasmine Tea".to_string()), None, None, None);
+    renamed.tags = product::parse_tags("drinks, loose leaf");
+    db.update_product(&renamed).unwrap();
+
+    assert!(db.search_products("green", 10).unwrap().is_empty());
+    assert_eq!(names(&db.search_products("jasmine", 10).unwrap()), vec!["Jasmine Tea"]);
+    assert_eq!(names(&db.search_products("leaf", 10).unwrap()), vec!["Jasmine Tea"]);
+
+    // Archived products stay searchable so they can be found and restored
+    db.archive_product(&tea.id).unwrap();
+    assert_eq!(db.search_products("jasmine", 10).unwrap().len(), 1);
+
+    db.delete_product(&tea.id).unwrap();
+    assert!(db.search_products("jasmine", 10).unwrap().is_empty());
+}
@@ -0,0 +1,103 @@
+use chrono::{Datelike, NaiveDate};
+use rustock::analytics::DateRange;
+use rustock::db::Database;
+use rustock::error::Error;
+use rustock::product::Product;
+use rustock::repository::Repository;
+use rustock::seed::{self, SeedOptions};
+use rustock::timezone::{self, Tz};
+use std::collections::HashMap;
+
+fn today() -> NaiveDate {
+    NaiveDate::from_ymd_opt(2026, 1, 1).unwrap()
+}
+
+fn small() -> SeedOptions {
+    SeedOptions { products: 60, days: 30, sales_per_day: 25.0, seed: 7 }
+}
+
+#[test]
+fn generated_stores_are_reproducible_and_consistent() {
+    let data = seed::generate(&small(), today(), Tz::UTC);
+    let again = seed::generate(&small(), today(), Tz::UTC);
+    assert_eq!(data.sales.len(), again.sales.len());
+    assert_eq!(
+        data.sales.iter().map(|s| s.total_amount).sum::<f64>(),
+        again.sales.iter().map(|s| s.total_amount).sum::<f64>()
+    );
+
+    assert_eq!(data.products.len(), 60);
+    assert!(data.products.iter().all(|p| p.validate().is_ok() && p.quantity >= 0.0));
+    assert!(data.sales.iter().all(|s| s.validate().is_ok()));
+    assert!(data.sales.windows(2).all(|w| w[0].timestamp <= w[1].timestamp));
+
+    // Nothing is sold that was not bought first
+    let mut stock: HashMap<&str, f64> = HashMap::new();
+    for purchase in &data.purchases {
+        *stock.entry(&purchase.product_id).or_default() += purchase.quantity;
+    }
+    for item in data.sales.iter().flat_map(|s| &s.items) {
+        *stock.entry(&item.product_id).or_default() -= item.quantity;
+    }
+    for product in &data.products {
+        assert!((stock.get(product.id.as_str()).copied().unwrap_or(0.0) - product.quantity).abs() < 1e-3);
+    }
+
+    let first = timezone::start_of_day(NaiveDate::from_ymd_opt(2025, 12, 2).unwrap(), Tz::UTC);
+    assert!(data.sales.iter().all(|s| s.timestamp >= first && s.timestamp < timezone::start_of_day(today(), Tz::UTC)));
+}
+
+#[test]
+fn demand_follows_the_seasons() {
+    let options = SeedOptions { products: 200, days: 365, sales_per_day: 60.0, seed: 3 };
+    let data = seed::generate(&options, today(), Tz::UTC);
+    let garden: Vec<&str> = data
+        .products
+        .iter()
+        .filter(|p| p.tags.contains(&"garden".to_string()))
+        .map(|p| p.id.as_str())
+        .collect();
+    let units_in = |month: u32| -> f64 {
+        data.sales
+            .iter()
+            .filter(|s| chrono::DateTime::from_timestamp(s.timestamp, 0).unwrap().month() == month)
+            .flat_map(|s| &s.items)
+            .filter(|item| garden.contains(&item.product_id.as_str()))
+            .map(|item| item.quantity)
+            .sum()
+    };
+    assert!(units_in(7) > 2.0 * units_in(1));
+
+    let sales_on = |weekday: chrono::Weekday| {
+        data.sales
+            .iter()
+            .filter(|s| chrono::DateTime::from_timestamp(s.timestamp, 0).unwrap().weekday() == weekday)
+            .count()
+    };
+    assert!(sales_on(chrono::Weekday::Sat) > sales_on(chrono::Weekday::Tue));
+}
+
+#[test]
+fn demo_data_only_seeds_an_empty_database() {
+    let mut db = Database::open(":memory:", None).unwrap();
+    let data = seed::generate(&small(), today(), Tz::UTC);
+    db.load_demo_data(&data).unwrap();
+
+    assert_eq!(db.get_products().unwrap().len(), 60);
+    assert_eq!(db.sales_count().unwrap(), data.sales.iter().map(|s| s.items.len()).sum::<usize>());
+    assert_eq!(db.get_recent_sales(1).unwrap()[0].0, data.sales.len() as i64);
+    let sku = data.products[0].sku.clone().unwrap();
+    assert!(sku.starts_with(seed::SKU_PREFIX));
+    assert_eq!(db.search_products(&sku, 5).unwrap()[0].id, data.products[0].id);
+    let activity = db.product_activity(&DateRange::default()).unwrap();
+    assert_eq!(
+        activity.iter().map(|a| a.units_sold).sum::<f64>().round(),
+        data.sales.iter().flat_map(|s| &s.items).map(|i| i.quantity).sum::<f64>().round()
+    );
+
+    assert!(matches!(db.load_demo_data(&data), Err(Error::Conflict(_))));
+
+    let mut used = Database::open(":memory:", None).unwrap();
+    used.add_product(&Product::new("Real".to_string(), String::new(), 1.0, 1.0)).unwrap();
+    assert!(matches!(used.load_demo_data(&data), Err(Error::Conflict(_))));
+}
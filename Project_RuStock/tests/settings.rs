@@ -0,0 +1,97 @@
+mod common;
+
+use rustock::db::Database;
+use rustock::error::Error;
+use rustock::receipt::ReceiptConfig;
+use rustock::repository::Repository;
+use rustock::settings::{Settings, KEYS};
+use std::path::PathBuf;
+
+fn settings_file(name: &str, contents: &str) -> PathBuf {
+    let path = std::env::temp_dir().join(format!("rustock-settings-{}-{}.toml", name, std::process::id()));
+    std::fs::write(&path, contents).unwrap();
+    path
+}
+
+#[test]
+fn file_values_override_defaults() {
+    let path = settings_file(
+        "file",
+        "store_name = \"Corner Shop\"\ncurrency = \"eur\"\ndecimal_places = 0\ntax_rate = 20.0\n",
+    );
+
+    let settings = Settings::from_file(&path).unwrap();
+    assert_eq!(settings.store_name, "Corner Shop");
+    assert_eq!(settings.currency, "EUR");
+    assert_eq!(settings.decimal_places, 0);
+    assert_eq!(settings.tax_rate, 20.0);
+    assert_eq!(settings.receipt_footer, Settings::default().receipt_footer);
+
+    let invalid = settings_file("invalid", "currency = \"euro\"\n");
+    assert!(matches!(Settings::from_file(&invalid), Err(Error::Validation(_))));
+    let unknown = settings_file("unknown", "store_nmae = \"Typo\"\n");
+    assert!(matches!(Settings::from_file(&unknown), Err(Error::Validation(_))));
+    assert_eq!(Settings::from_file(&path.with_extension("missing")).unwrap(), Settings::default());
+
+    for file in [path, invalid, unknown] {
+        std::fs::remove_file(file).unwrap();
+    }
+}
+
+#[test]
+fn rejected_values_leave_settings_unchanged() {
+    let mut settings = Settings::default();
+
+    settings.set("currency", " gbp ").unwrap();
+    assert_eq!(settings.currency, "GBP");
+    assert!(settings.set("decimal_places", "7").is_err());
+    assert!(settings.set("tax_rate", "-5").is_err());
+    assert!(settings.set("store_name", "  ").is_err());
+    assert!(settings.set("colour", "red").is_err());
+    assert_eq!(settings.decimal_places, 2);
+    assert_eq!(settings.store_name, Settings::default().store_name);
+
+    let errors = settings.apply(&[
+        ("low_stock_threshold".to_string(), "12".to_string()),
+        ("tax_rate".to_string(), "lots".to_string()),
+    ]);
+    assert_eq!(errors.len(), 1);
+    assert_eq!(settings.low_stock_threshold, 12.0);
+    assert!(KEYS.iter().all(|key| settings.get(key.name).is_some()));
+}
+
+#[test]
+fn saved_settings_override_and_reset() {
+    let db = Database::open(":memory:", None).unwrap();
+
+    let updated = db.save_setting("receipt_footer", "See you soon").unwrap();
+    assert_eq!(updated.receipt_footer, "See you soon");
+    db.save_setting("tax_rate", "8.5").unwrap();
+    assert!(db.save_setting("decimal_places", "ten").is_err());
+    assert_eq!(db.saved_settings().unwrap().len(), 2);
+
+    let (settings, errors) = db.store_settings().unwrap();
+    assert!(errors.is_empty());
+    let receipt = ReceiptConfig::from_settings(&settings);
+    assert_eq!(receipt.footer, "See you soon");
+    assert_eq!(receipt.tax_rate, 8.5);
+
+    let reset = db.reset_setting("receipt_footer").unwrap();
+    assert_eq!(reset.receipt_footer, Settings::default().receipt_footer);
+    assert_eq!(reset.tax_rate, 8.5);
+}
+
+#[test]
+fn base_currency_is_fixed_once_money_is_recorded() {
+    let (mut db, product) = common::store_with("Kettle", 20.0, 5.0);
+
+    assert_eq!(db.save_setting("currency", "eur").unwrap().currency, "EUR");
+    db.record_sale(&common::sale_of(&product, 1.0)).unwrap();
+
+    assert!(matches!(db.save_setting("currency", "GBP"), Err(Error::Conflict(_))));
+    assert!(matches!(db.reset_setting("currency"), Err(Error::Conflict(_))));
+    assert_eq!(db.store_settings().unwrap().0.currency, "EUR");
+    // Saving the same currency again and other settings still work
+    db.save_setting("currency", "EUR").unwrap();
+    db.save_setting("tax_rate", "5").unwrap();
+}
@@ -0,0 +1,30 @@
+use rustock::timezone::{self, Tz};
+
+#[test]
+fn local_dates_and_times_follow_the_zone() {
+    let berlin = timezone::parse("Europe/Berlin").unwrap();
+    assert!(timezone::parse("Mars/Olympus").is_err());
+
+    let midnight = timezone::parse_local("2025-07-01", berlin).unwrap();
+    assert_eq!(midnight, timezone::parse_local("2025-06-30 22:00", Tz::UTC).unwrap());
+    assert_eq!(timezone::format_in(midnight, berlin, "%Y-%m-%d %H:%M %Z"), "2025-07-01 00:00 CEST");
+    assert_eq!(timezone::format_in(midnight, Tz::UTC, "%Y-%m-%d"), "2025-06-30");
+    assert_eq!(timezone::local_date(midnight, berlin).to_string(), "2025-07-01");
+}
+
+#[test]
+fn days_follow_clock_changes() {
+    let london = timezone::parse("Europe/London").unwrap();
+
+    // Clocks go forward on 30 March 2025, so that day is 23 hours long
+    let start = timezone::parse_local("2025-03-30", london).unwrap();
+    assert_eq!(timezone::end_of_day(start, london) - start, 23 * 3600);
+    // 01:30 does not exist that night
+    assert!(timezone::parse_local("2025-03-30 01:30", london).is_err());
+
+    // Santiago skipped midnight on 7 September 2025; the day starts at 01:00
+    let santiago = timezone::parse("America/Santiago").unwrap();
+    let start = timezone::parse_local("2025-09-07", santiago).unwrap();
+    assert_eq!(timezone::format_in(start, santiago, "%H:%M"), "01:00");
+    assert_eq!(timezone::day_number(timezone::local_date(start, santiago)), 20_338);
+}
@@ -0,0 +1,121 @@
+mod common;
+
+use chrono::Utc;
+use common::{sale_of, store_with};
+use rustock::analytics::DateRange;
+use rustock::db::Database;
+use rustock::error::Error;
+use rustock::journal::{self, AccountMap};
+use rustock::product::Product;
+use rustock::purchase::Purchase;
+use rustock::repository::Repository;
+use rustock::void::OperationKind;
+
+const WINDOW: u32 = 30;
+
+fn setup() -> (Database, Product, String) {
+    let (db, product) = store_with("Drill", 50.0, 10.0);
+    let admin = db.get_manager_by_username("admin").unwrap().unwrap();
+    (db, product, admin.id)
+}
+
+fn stock(db: &Database, product: &Product) -> f64 {
+    db.get_product(&product.id).unwrap().unwrap().quantity
+}
+
+#[test]
+fn the_latest_sale_is_voided_by_returning_what_is_left() {
+    let (mut db, product, manager) = setup();
+    let first = db.record_sale(&sale_of(&product, 1.0)).unwrap();
+    let second = db.record_sale(&sale_of(&product, 3.0)).unwrap();
+    db.record_return(second, &product.id, 1.0, "Scratched").unwrap();
+
+    let latest = db.last_operation(OperationKind::Sale).unwrap().unwrap();
+    assert_eq!(latest.reference, second.to_string());
+    assert!(matches!(
+        db.void_operation(OperationKind::Sale, &first.to_string(), &manager, "Wrong item", WINDOW),
+        Err(Error::Conflict(_))
+    ));
+    assert!(matches!(
+        db.void_operation(OperationKind::Sale, &latest.reference, &manager, " ", WINDOW),
+        Err(Error::Validation(_))
+    ));
+
+    let void = db.void_operation(OperationKind::Sale, &latest.reference, &manager, "Wrong item", WINDOW).unwrap();
+    assert_eq!(void.refund, 100.0);
+    assert_eq!(void.manager_id, manager);
+    assert_eq!(stock(&db, &product), 9.0);
+    assert_eq!(db.returned_quantity(second, &product.id).unwrap(), 3.0);
+    // History is kept and the previous sale is now the latest
+    assert!(db.get_sale_by_number(second).unwrap().is_some());
+    assert_eq!(db.last_operation(OperationKind::Sale).unwrap().unwrap().reference, first.to_string());
+    assert_eq!(db.get_voids(10).unwrap(), vec![void]);
+}
+
+#[test]
+fn voided_sales_drop_out_of_sales_figures() {
+    let (mut db, product, manager) = setup();
+    db.record_sale(&sale_of(&product, 2.0)).unwrap();
+    let mistake = db.record_sale(&sale_of(&product, 3.0)).unwrap();
+    db.void_operation(OperationKind::Sale, &mistake.to_string(), &manager, "Rung up twice", WINDOW).unwrap();
+
+    let activity = &db.product_activity(&DateRange::default()).unwrap()[0];
+    assert_eq!((activity.units_sold, activity.revenue), (2.0, 100.0));
+    assert_eq!(activity.closing_stock(), 8.0);
+    assert_eq!(db.get_all_sales().unwrap().len(), 1);
+}
+
+#[test]
+fn operations_outside_the_window_cannot_be_voided() {
+    let (mut db, product, manager) = setup();
+    let mut old = sale_of(&product, 1.0);
+    old.timestamp = Utc::now().timestamp() - 3600;
+    let number = db.record_sale(&old).unwrap().to_string();
+
+    assert!(matches!(
+        db.void_operation(OperationKind::Sale, &number, &manager, "Too late", WINDOW),
+        Err(Error::Conflict(_))
+    ));
+    assert!(db.void_operation(OperationKind::Sale, &number, &manager, "Later", 90).is_ok());
+
+    db.record_sale(&sale_of(&product, 1.0)).unwrap();
+    let latest = db.last_operation(OperationKind::Sale).unwrap().unwrap();
+    assert!(matches!(
+        db.void_operation(OperationKind::Sale, &latest.reference, &manager, "Disabled", 0),
+        Err(Error::Conflict(_))
+    ));
+}
+
+#[test]
+fn purchases_and_adjustments_are_offset_by_compensating_records() {
+    let (mut db, product, manager) = setup();
+    db.record_purchase(&Purchase::new(product.id.clone(), 40.0, 20.0)).unwrap();
+    db.adjust_stock(&product.id, -2.0, "Damaged").unwrap();
+    assert_eq!(stock(&db, &product), 48.0);
+
+    let adjustment = db.last_operation(OperationKind::Adjustment).unwrap().unwrap();
+    let void = db.void_operation(OperationKind::Adjustment, &adjustment.reference, &manager, "Found them", WINDOW).unwrap();
+    assert!(void.compensation.is_some());
+    assert_eq!(stock(&db, &product), 50.0);
+    assert!(db.last_operation(OperationKind::Adjustment).unwrap().is_none());
+
+    // Stock from the purchase that has been sold cannot be taken back out
+    db.record_sale(&sale_of(&product, 15.0)).unwrap();
+    let purchase = db.last_operation(OperationKind::Purchase).unwrap().unwrap();
+    assert!(matches!(
+        db.void_operation(OperationKind::Purchase, &purchase.reference, &manager, "Typo", WINDOW),
+        Err(Error::Conflict(_))
+    ));
+    let sale = db.last_operation(OperationKind::Sale).unwrap().unwrap();
+    db.void_operation(OperationKind::Sale, &sale.reference, &manager, "Typo", WINDOW).unwrap();
+    db.void_operation(OperationKind::Purchase, &purchase.reference, &manager, "Typo", WINDOW).unwrap();
+    assert_eq!(stock(&db, &product), 10.0);
+    assert!(db.last_operation(OperationKind::Purchase).unwrap().is_none());
+    assert_eq!(db.get_all_purchases().unwrap().len(), 2);
+
+    let transactions = db.journal_transactions(&DateRange::default()).unwrap();
+    let balances = journal::trial_balance(&journal::journal(&transactions, &AccountMap::default(), 0.0));
+    assert!(balances.contains(&("Liabilities:Accounts Payable".to_string(), 0.0)));
+    assert!(balances.contains(&("Income:Sales".to_string(), -750.0)));
+    assert!(balances.contains(&("Income:Sales Returns".to_string(), 750.0)));
+}